@@ -4,38 +4,175 @@
 //
 // Distributed under the terms of the MIT license.
 
+mod manifest;
 mod verify;
 
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::env::current_dir;
+use std::ffi::OsStr;
+use std::future::Future;
 use std::io::{stdout, Write};
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::process;
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 
 use anyhow::{anyhow, bail, Result};
-use clap::{crate_authors, crate_version, Arg, ArgAction, Command};
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use clap::builder::{PossibleValue, PossibleValuesParser, StringValueParser, TypedValueParser};
+use clap::{crate_authors, crate_version, value_parser, Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use clap_complete::{generate, Shell};
 use lazy_static::lazy_static;
+use manifest::{download_manifest, Manifest, ManifestTarget};
 use regex::Regex;
 use reqwest::Client;
+use serde::Serialize;
 use tabwriter::TabWriter;
 use termcolor::ColorChoice;
-use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::task::spawn_blocking;
 use tokio::{pin, select, time};
+use wdgetlib::backup_index::{get_dump_run_sizes_from, get_latest_dump_runs_from};
+use wdgetlib::cache::{CachedWikiList, DumpCache};
+use wdgetlib::datasets::{
+    download_dataset, find_dataset, get_dataset_file_url, get_dataset_files, DatasetSelection, DirectoryPeriod,
+    DATASETS,
+};
+use wdgetlib::store::{create_dump_store, find_multistream_files, DumpStore};
 use wdgetlib::*;
 
-fn create_client() -> Result<Client> {
-    Ok(reqwest::Client::builder()
-        .user_agent(concat!(
-            "wdget/",
-            crate_version!(),
-            " (https://github.com/Count-Count/wikidumptools)"
-        ))
-        .build()?)
+const MIRROR_SHORTCUTS: [(&str, &str); 3] = [
+    ("acc.umu.se", "https://ftp.acc.umu.se/mirror/wikimedia.org/dumps"),
+    ("your.org", "http://dumps.wikimedia.your.org/"),
+    ("bringyour.com", "https://wikimedia.bringyour.com/"),
+];
+const MIRROR_SHORTCUT_NAMES: [&str; 3] = [MIRROR_SHORTCUTS[0].0, MIRROR_SHORTCUTS[1].0, MIRROR_SHORTCUTS[2].0];
+
+/// Returns the root URL of the mirror given by URL or shortcut.
+fn resolve_mirror(mirror: &str) -> &str {
+    MIRROR_SHORTCUTS
+        .iter()
+        .find(|(shortcut, _)| *shortcut == mirror)
+        .map_or(mirror, |(_, url)| url)
+}
+
+/// Accepts any string but offers the given values for shell completion.
+#[derive(Clone)]
+struct SuggestedValues(&'static [&'static str]);
+
+impl TypedValueParser for SuggestedValues {
+    type Value = String;
+
+    fn parse_ref(&self, cmd: &Command, arg: Option<&Arg>, value: &OsStr) -> Result<String, clap::Error> {
+        StringValueParser::new().parse_ref(cmd, arg, value)
+    }
+
+    fn possible_values(&self) -> Option<Box<dyn Iterator<Item = PossibleValue> + '_>> {
+        Some(Box::new(self.0.iter().map(PossibleValue::new)))
+    }
+}
+
+const USER_AGENT: &str = concat!(
+    "wdget/",
+    crate_version!(),
+    " (https://github.com/Count-Count/wikidumptools)"
+);
+
+/// Wikimedia asks bulk downloaders to include contact information like an email address in the user agent, it is
+/// appended to the default user agent. With HTTP/2 all requests to a host share a single multiplexed connection,
+/// otherwise each parallel download uses its own connection.
+fn create_client(user_agent_suffix: Option<&str>, http2: bool) -> Result<Client> {
+    let user_agent = match user_agent_suffix.map(str::trim).filter(|suffix| !suffix.is_empty()) {
+        Some(suffix) => format!("{USER_AGENT} {suffix}"),
+        None => USER_AGENT.to_owned(),
+    };
+    // idle connections are kept for the next files instead of connecting again for each of many small files
+    let builder = reqwest::Client::builder()
+        .user_agent(user_agent)
+        .pool_idle_timeout(time::Duration::from_secs(300))
+        .tcp_keepalive(time::Duration::from_secs(60));
+    let builder = if http2 {
+        builder
+            .http2_prior_knowledge()
+            .http2_keep_alive_interval(time::Duration::from_secs(30))
+            .http2_keep_alive_while_idle(true)
+    } else {
+        // HTTP/2 would be negotiated with servers supporting it otherwise
+        builder.http1_only()
+    };
+    Ok(builder.build()?)
+}
+
+/// Connects to the dump website or mirror before the downloads start, so that with HTTP/2 they share the connection
+/// instead of all connecting at the same time. Prints the protocol used in verbose mode.
+async fn warm_up_connection(
+    client: &Client,
+    download_options: &DownloadOptions<'_>,
+    http2: bool,
+    verbose: bool,
+) -> Result<()> {
+    if !http2 && !verbose {
+        return Ok(());
+    }
+    let root_url = download_options
+        .mirror
+        .or(download_options.base_url)
+        .unwrap_or(DEFAULT_BASE_URL);
+    let response = client.head(format!("{root_url}/")).send().await?;
+    if verbose {
+        eprintln!("Connected to {root_url} using {:?}.", response.version());
+    }
+    Ok(())
+}
+
+/// The cached list of wikis is used for this long before it is retrieved again.
+const WIKI_LIST_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Returns the wikis of the dump website from the cache if the cached list is recent enough. Otherwise the list is
+/// retrieved and cached, if that fails because of a network error or in offline mode an older cached list is used.
+async fn get_wikis(client: &Client, base_url: &str, refresh: bool, offline: bool) -> Result<Vec<Wiki>> {
+    // wikis can be listed without a cache directory
+    let cache = DumpCache::open_default().ok();
+    let cached = match &cache {
+        Some(cache) => cache.read_wiki_list(base_url).unwrap_or_else(|e| {
+            eprintln!("Ignoring the cached list of wikis: {e}");
+            None
+        }),
+        None => None,
+    };
+    if let Some(cached) = cached
+        .as_ref()
+        .filter(|cached| !refresh && cached.age() < WIKI_LIST_MAX_AGE)
+    {
+        return Ok(cached.wikis.clone());
+    }
+    let result = if offline {
+        Err(Error::Offline())
+    } else {
+        get_available_wikis_from(client, base_url).await
+    };
+    match (result, cached) {
+        (Ok(wikis), _) => {
+            let wiki_list = CachedWikiList::new(base_url, wikis);
+            if let Some(cache) = &cache {
+                if let Err(e) = cache.write_wiki_list(&wiki_list) {
+                    eprintln!("Could not cache the list of wikis: {e}");
+                }
+            }
+            Ok(wiki_list.wikis)
+        }
+        (Err(e), Some(cached)) if matches!(e.class(), ErrorClass::Network) || matches!(e, Error::Offline()) => {
+            let age = Duration::from_std(cached.age()).unwrap_or_else(|_| Duration::zero());
+            eprintln!("{e}, using the list of wikis retrieved {}.", get_human_age(age));
+            Ok(cached.wikis)
+        }
+        (Err(e), _) => Err(e.into()),
+    }
 }
 
-async fn list_wikis(client: &Client) -> Result<()> {
-    let mut wikis = get_available_wikis_from_wikidata(client).await?;
+async fn list_wikis(client: &Client, base_url: &str, refresh: bool, offline: bool) -> Result<()> {
+    let mut wikis = get_wikis(client, base_url, refresh, offline).await?;
     wikis.sort_unstable_by(|e1, e2| e1.id.cmp(&e2.id));
     let mut tw = TabWriter::new(stdout());
     writeln!(tw, "Wiki\tDescription").unwrap();
@@ -46,38 +183,253 @@ async fn list_wikis(client: &Client) -> Result<()> {
     Ok(())
 }
 
-async fn list_dates(client: &Client, wiki: &str) -> Result<()> {
-    let dates = get_available_dates(client, wiki).await?;
+/// Lists the wikis with the date, status and size of their latest dump run from the index of the dump website, sorted
+/// by `sort` (name, size or date). The size is the total size of the completed jobs of the run.
+async fn list_wikis_with_dumps(
+    client: &Client,
+    base_url: &str,
+    refresh: bool,
+    offline: bool,
+    sort: &str,
+) -> Result<()> {
+    let mut wikis = get_wikis(client, base_url, refresh, offline).await?;
+    if offline {
+        return Err(Error::Offline().into());
+    }
+    let dump_runs = get_latest_dump_runs_from(client, base_url).await?;
+    let listed_runs = wikis
+        .iter()
+        .filter_map(|wiki| dump_runs.get(&wiki.id))
+        .collect::<Vec<_>>();
+    eprintln!("Retrieving the dump status of {} wikis...", listed_runs.len());
+    // the dump website allows two connections
+    let sizes = get_dump_run_sizes_from(client, base_url, &listed_runs, 2).await;
+    wikis.sort_unstable_by(|e1, e2| e1.id.cmp(&e2.id));
+    match sort {
+        // largest and newest first, wikis without dumps last
+        "size" => wikis.sort_by_key(|wiki| Reverse(sizes.get(&wiki.id))),
+        "date" => wikis.sort_by_key(|wiki| Reverse(dump_runs.get(&wiki.id).map(|dump_run| &dump_run.date))),
+        _ => {}
+    }
+    let mut tw = TabWriter::new(stdout());
+    writeln!(tw, "Wiki\tDescription\tLatest dump\tStatus\tSize").unwrap();
+    for wiki in &wikis {
+        let dump_run = dump_runs.get(&wiki.id);
+        writeln!(
+            tw,
+            "{}\t{}\t{}\t{}\t{:>10}",
+            wiki.id,
+            wiki.name,
+            dump_run.map_or("", |dump_run| &dump_run.date),
+            dump_run.map_or("", |dump_run| &dump_run.status),
+            sizes
+                .get(&wiki.id)
+                .map(|&size| get_human_size(size))
+                .unwrap_or_default()
+        )
+        .unwrap();
+    }
+    tw.flush().unwrap();
+    Ok(())
+}
+
+/// Lists the latest dump run of every wiki in the index of the dump website with its status and the time of its last
+/// update. Only the index is retrieved, so wikis missing from the wiki list are included.
+async fn list_latest(client: &Client, base_url: &str, only_done: bool, json: bool) -> Result<()> {
+    let dump_runs = get_latest_dump_runs_from(client, base_url).await?;
+    let dump_runs = dump_runs
+        .values()
+        .filter(|dump_run| !only_done || dump_run.is_complete())
+        .collect::<Vec<_>>();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&dump_runs)?);
+    } else {
+        let mut tw = TabWriter::new(stdout());
+        writeln!(tw, "Wiki\tLatest dump\tStatus\tUpdated").unwrap();
+        for dump_run in dump_runs {
+            writeln!(
+                tw,
+                "{}\t{}\t{}\t{}",
+                dump_run.wiki, dump_run.date, dump_run.status, dump_run.updated
+            )
+            .unwrap();
+        }
+        tw.flush().unwrap();
+    }
+    Ok(())
+}
+
+async fn list_dates(client: &Client, base_url: &str, wiki: &str) -> Result<()> {
+    let dates = get_available_dates_from(client, base_url, wiki).await?;
     for date in dates {
         println!("{date}");
     }
     Ok(())
 }
 
-async fn list_types(client: &Client, wiki: &str, date: &str) -> Result<()> {
-    let dump_status = get_dump_status(client, wiki, date).await?;
+async fn list_types(client: &Client, base_url: &str, wiki: &str, date: &str) -> Result<()> {
+    if is_other_dump_tree(wiki) {
+        return list_other_dump_files(client, base_url, wiki, date).await;
+    }
+    let dump_status = get_dump_status_from(client, base_url, wiki, date).await?;
+    let now = DateTime::<Utc>::from(SystemTime::now()).naive_utc();
     let mut tw = TabWriter::new(stdout());
-    writeln!(tw, "Dump\tStatus\tNo. of files\tCompressed size").unwrap();
+    writeln!(
+        tw,
+        "Dump\tStatus\tUpdated\tNo. of files\tCompressed size\tEst. decompressed size"
+    )
+    .unwrap();
     for (job_name, job_info) in &dump_status.jobs {
+        let updated = NaiveDateTime::parse_from_str(&job_info.updated, "%Y-%m-%d %H:%M:%S")
+            .map(|updated| get_human_age(now - updated))
+            .unwrap_or_default();
         if let Some(files) = &job_info.files {
             let sum = files.values().map(|info| info.size.unwrap_or(0)).sum::<u64>();
+            let decompressed_sum = files
+                .iter()
+                .map(|(file_name, info)| estimate_decompressed_size(job_name, file_name, info.size.unwrap_or(0)))
+                .sum::<u64>();
             writeln!(
                 tw,
-                "{}\t{}\t{:3} file(s)\t{:>10}",
+                "{}\t{}\t{}\t{:3} file(s)\t{:>10}\t{:>10}",
                 &job_name,
                 &job_info.status,
+                updated,
                 files.len(),
-                get_human_size(sum)
+                get_human_size(sum),
+                get_human_size(decompressed_sum)
             )
             .unwrap();
         } else {
-            writeln!(tw, "{}\t{}", &job_name, &job_info.status).unwrap();
+            writeln!(tw, "{}\t{}\t{}", &job_name, &job_info.status, updated).unwrap();
         }
     }
     tw.flush().unwrap();
     Ok(())
 }
 
+async fn list_other_dump_files(client: &Client, base_url: &str, tree: &str, date: &str) -> Result<()> {
+    let files = get_other_dump_files_from(client, base_url, tree, date).await?;
+    let mut tw = TabWriter::new(stdout());
+    writeln!(tw, "File\tSize").unwrap();
+    for (file_name, file_info) in &files {
+        match file_info.size {
+            Some(size) => writeln!(tw, "{}\t{:>10}", file_name, get_human_size(size)).unwrap(),
+            None => writeln!(tw, "{file_name}").unwrap(),
+        }
+    }
+    tw.flush().unwrap();
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct DumpFileListEntry<'a> {
+    name: &'a str,
+    size: Option<u64>,
+    sha1: Option<&'a str>,
+    url: String,
+}
+
+/// Prints the files of a dump with their download URLs below the root URL of the dump website or mirror.
+fn list_files(
+    files: &BTreeMap<String, DumpFileInfo>,
+    wiki: &str,
+    date: &str,
+    root_url: &str,
+    filter: Option<&Regex>,
+    json: bool,
+) -> Result<()> {
+    let entries = files
+        .iter()
+        .filter(|(file_name, _)| filter.is_none_or(|filter| filter.is_match(file_name)))
+        .map(|(file_name, file_info)| DumpFileListEntry {
+            name: file_name,
+            size: file_info.size,
+            sha1: file_info.sha1.as_deref(),
+            url: get_dump_file_url(Some(root_url), wiki, date, file_name),
+        })
+        .collect::<Vec<_>>();
+    print_file_list(entries, json)
+}
+
+async fn list_dataset_files(
+    client: &Client,
+    selection: &DatasetSelection<'_>,
+    mirror: Option<&str>,
+    json: bool,
+) -> Result<()> {
+    let files = get_dataset_files(client, selection).await?;
+    let entries = files
+        .iter()
+        .map(|(file_name, file_info)| DumpFileListEntry {
+            name: file_name,
+            size: file_info.size,
+            sha1: file_info.sha1.as_deref(),
+            url: get_dataset_file_url(mirror, file_info),
+        })
+        .collect::<Vec<_>>();
+    print_file_list(entries, json)
+}
+
+fn print_file_list(entries: Vec<DumpFileListEntry>, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else {
+        let mut out = stdout().lock();
+        for entry in entries {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                entry.name,
+                entry.size.map(|size| size.to_string()).unwrap_or_default(),
+                entry.sha1.unwrap_or_default(),
+                entry.url
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn list_datasets() {
+    let mut tw = TabWriter::new(stdout());
+    writeln!(tw, "Dataset\tFiles per directory\tDescription").unwrap();
+    for dataset in DATASETS {
+        let period = match dataset.directory_period {
+            DirectoryPeriod::Month => "month",
+            DirectoryPeriod::Year => "year",
+        };
+        writeln!(tw, "{}\t{}\t{}", dataset.name, period, dataset.description).unwrap();
+    }
+    tw.flush().unwrap();
+}
+
+/// Estimates the size of a dump file after decompression using typical compression ratios, history dumps compress
+/// much better than others since consecutive revisions are similar.
+fn estimate_decompressed_size(job_name: &str, file_name: &str, size: u64) -> u64 {
+    let ratio = match file_name.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("7z") => 50.0,
+        Some("bz2") if job_name.contains("history") => 11.0,
+        Some("bz2") => 4.5,
+        Some("gz") => 4.0,
+        _ => 1.0,
+    };
+    (size as f64 * ratio) as u64
+}
+
+fn get_human_age(age: Duration) -> String {
+    if age.num_minutes() < 1 {
+        "just now".to_owned()
+    } else if age.num_hours() < 1 {
+        std::format!("{} min ago", age.num_minutes())
+    } else if age.num_days() < 1 {
+        std::format!("{} h ago", age.num_hours())
+    } else if age.num_days() == 1 {
+        "1 day ago".to_owned()
+    } else {
+        std::format!("{} days ago", age.num_days())
+    }
+}
+
 fn get_human_size(byte_len: u64) -> String {
     let mut len = byte_len as f64;
     let units = ["KiB", "MiB", "GiB", "TiB", "PiB"];
@@ -106,41 +458,30 @@ fn check_date_valid(date_spec: &str) -> Result<()> {
 
 async fn check_date_may_retrieve_latest(
     client: &Client,
+    base_url: &str,
     wiki: &str,
     date_spec: &str,
     dump_type: Option<&str>,
 ) -> Result<String> {
     if date_spec == "latest" {
-        Ok(get_latest_available_date(client, wiki, dump_type).await?)
+        Ok(get_latest_available_date_from(client, base_url, wiki, dump_type).await?)
     } else {
         check_date_valid(date_spec).map(|_| date_spec.to_owned())
     }
 }
 
-async fn download<T>(
-    client: &Client,
-    wiki: &str,
-    date: &str,
-    dump_type: &str,
-    target_directory: T,
-    download_options: &DownloadOptions<'_>,
+/// Runs the download and prints its progress, returns the result of the download.
+async fn report_download_progress<F, T>(
+    download_fut: F,
+    mut progress_receive: UnboundedReceiver<DownloadProgress>,
+    decompress: bool,
     show_progress: bool,
     show_warnings: bool,
-) -> Result<()>
+) -> Result<T>
 where
-    T: AsRef<Path> + Send,
+    F: Future<Output = std::result::Result<T, wdgetlib::Error>>,
 {
     use DownloadProgress::*;
-    let (progress_send, mut progress_receive) = unbounded_channel::<DownloadProgress>();
-    let download_fut = download_dump(
-        client,
-        wiki,
-        date,
-        dump_type,
-        target_directory,
-        download_options,
-        Some(progress_send),
-    );
     pin!(download_fut);
 
     let progress_update_period = time::Duration::from_secs(1);
@@ -155,13 +496,14 @@ where
     let mut bytes_received = 0_u64;
     let mut decompressed_bytes_written = 0_u64;
     let mut total_data_size: Option<u64> = None;
+    let mut download_output = None;
     let mut download_finished = false;
     let mut progress_reporting_finished = false;
     let mut downloaded_file_count = 0;
     while !download_finished || !progress_reporting_finished {
         select! {
             download_res = &mut download_fut, if !download_finished => {
-                download_res?;
+                download_output = Some(download_res?);
                 download_finished = true;
             }
             _ = tokio::signal::ctrl_c() => {
@@ -176,13 +518,25 @@ where
                         decompressed_bytes_written += count;
                     },
                     Some(TotalDownloadSize(size)) => {
-                        total_data_size.replace(size);
+                        // several dumps may be downloaded at the same time
+                        *total_data_size.get_or_insert(0) += size;
                     },
+                    Some(TotalFileCount(_count)) => {},
                     Some(ExistingFileIgnored(_path, file_name)) => {
                         if show_warnings {
                             eprintln!("{file_name} exists, skipping.");
                         }
                     },
+                    Some(ExistingFileVerified(_path, file_name)) => {
+                        if show_progress {
+                            eprintln!("{file_name} exists and is valid, skipping.");
+                        }
+                    },
+                    Some(ExistingFileInvalid(_path, file_name)) => {
+                        if show_warnings {
+                            eprintln!("{file_name} exists but does not match the size or checksum, downloading again.");
+                        }
+                    },
                     Some(FileFinished(_path, file_name)) => {
                         if show_progress {
                             eprint!("\r{:1$}\r","",last_printed_progress_len);
@@ -190,6 +544,32 @@ where
                             downloaded_file_count += 1;
                         }
                     },
+                    Some(PartFileResumed(_path, file_name, len)) => {
+                        // counted as received but not for the speed
+                        bytes_received += len;
+                        prev_bytes_received += len;
+                        if show_progress {
+                            eprint!("\r{:1$}\r","",last_printed_progress_len);
+                            eprintln!("Resuming download of {} after {}.", file_name, get_human_size(len));
+                        }
+                    },
+                    Some(ChecksumNotVerified(_path, file_name)) => {
+                        if show_warnings {
+                            eprint!("\r{:1$}\r","",last_printed_progress_len);
+                            eprintln!("Checksum of {file_name} cannot be verified since its decompression was resumed.");
+                        }
+                    },
+                    Some(FileLinked(_path, file_name)) => {
+                        if show_progress {
+                            eprint!("\r{:1$}\r","",last_printed_progress_len);
+                            eprintln!("Linked {file_name} from the older dump.");
+                        }
+                    },
+                    Some(StalePartFileRemoved(_path, file_name)) => {
+                        if show_warnings {
+                            eprintln!("Removed stale part file {file_name}.");
+                        }
+                    },
                     Some(CouldNotRemoveTempFile(_path, file_name, error)) => {
                         if show_warnings {
                             eprintln!("Could not remove temporary file {}: {}", file_name, &error);
@@ -213,7 +593,7 @@ where
                         if let Some(total_data_size) = total_data_size {
                             std::format!(
                                 "\rDownloading {}- {} ({} %) of {} downloaded {}.",
-                                if download_options.decompress {"and decompressing "} else {""},
+                                if decompress {"and decompressing "} else {""},
                                 get_human_size(bytes_received),
                                 bytes_received * 100 / total_data_size,
                                 get_human_size(total_data_size),
@@ -221,7 +601,7 @@ where
                         } else {
                             std::format!(
                                 "\rDownloading {}- {} downloaded {}.",
-                                if download_options.decompress {"and decompressing "} else {""},
+                                if decompress {"and decompressing "} else {""},
                                 get_human_size(bytes_received),
                                 speed)
                         };
@@ -243,7 +623,7 @@ where
         if downloaded_file_count > 0 {
             let total_mib = bytes_received as f64 / 1024.0 / 1024.0;
             let mib_per_sec = total_mib / start_time.elapsed().as_secs_f64();
-            if download_options.decompress {
+            if decompress {
                 eprintln!(
                     "\rDownloaded {:.2} MiB ({:.2} MiB/s) and decompressed to {:.2} MiB.",
                     total_mib,
@@ -258,98 +638,617 @@ where
         }
     }
 
+    // UNWRAP: the loop only ends after the download finished
+    Ok(download_output.unwrap())
+}
+
+async fn download<T>(
+    client: &Client,
+    wiki: &str,
+    date: &str,
+    dump_type: &str,
+    target_directory: T,
+    download_options: &DownloadOptions<'_>,
+    show_progress: bool,
+    show_warnings: bool,
+) -> Result<()>
+where
+    T: AsRef<Path> + Send,
+{
+    let (progress_send, progress_receive) = unbounded_channel::<DownloadProgress>();
+    let download_fut = download_dump(
+        client,
+        wiki,
+        date,
+        dump_type,
+        target_directory,
+        download_options,
+        Some(progress_send),
+    );
+    report_download_progress(
+        download_fut,
+        progress_receive,
+        download_options.decompress,
+        show_progress,
+        show_warnings,
+    )
+    .await
+}
+
+async fn make_page_store(source_directory: &Path, file_prefix: &str, store_directory: PathBuf) -> Result<()> {
+    let sources = find_multistream_files(source_directory, file_prefix)?;
+    if sources.is_empty() {
+        bail!("No multistream dump files found to create the page store from.");
+    }
+    let start_time = Instant::now();
+    let page_count = spawn_blocking(move || create_dump_store(&sources, &store_directory)).await??;
+    eprintln!(
+        "Stored {} pages in {:.2} seconds.",
+        page_count,
+        start_time.elapsed().as_secs_f64()
+    );
     Ok(())
 }
 
-async fn run() -> Result<()> {
-    let wiki_name_arg = Arg::new("wiki name").help("Name of the wiki").required(true);
+fn get_page(store_directory: &Path, title: Option<&String>, page_id: Option<&String>) -> Result<()> {
+    let store = DumpStore::open(store_directory)?;
+    let page = match (title, page_id) {
+        (Some(title), _) => store.get_page_by_title(title)?,
+        (None, Some(page_id)) => {
+            let page_id = page_id.parse().map_err(|_| anyhow!("Invalid page id."))?;
+            store.get_page_by_id(page_id)?
+        }
+        (None, None) => unreachable!("Either title or id required by arg matching."),
+    };
+    match page {
+        Some(page) => {
+            print!("{page}");
+            Ok(())
+        }
+        None => bail!("Page not found."),
+    }
+}
+
+/// Arguments shared by the download commands.
+fn download_args() -> [Arg; 11] {
+    [
+        Arg::new("quiet")
+            .short('q')
+            .long("quiet")
+            .help("Don't print progress updates")
+            .action(ArgAction::SetTrue),
+        Arg::new("decompress")
+            .short('d')
+            .long("decompress")
+            .help("Decompress .bz2 files during download")
+            .action(ArgAction::SetTrue),
+        Arg::new("target-dir")
+            .short('t')
+            .long("target-dir")
+            .help("Target directory"),
+        Arg::new("mirror")
+            .short('m')
+            .long("mirror")
+            .help("Mirror root URL or one of the shortcuts 'acc.umu.se', 'your.org' and 'bringyour.com'")
+            .value_parser(SuggestedValues(&MIRROR_SHORTCUT_NAMES))
+            .hide_possible_values(true),
+        Arg::new("concurrency")
+            .short('j')
+            .long("concurrency")
+            .help("Number of parallel connections, defaults to 1 if no mirror, determined heuristically otherwise."),
+        Arg::new("order")
+            .long("order")
+            .value_parser(["name", "largest-first", "smallest-first", "interleaved"])
+            .default_value("name")
+            .value_name("order")
+            .help("Order in which files are downloaded, interleaved alternates between large and small files"),
+        Arg::new("all-or-nothing")
+            .long("all-or-nothing")
+            .help(
+                "Download into a hidden subdirectory of the target directory and only move the files into place once \
+                 all files have been downloaded and verified",
+            )
+            .action(ArgAction::SetTrue),
+        Arg::new("resume")
+            .long("resume")
+            .help(
+                "Continue downloads from the .part files of interrupted runs and keep the .part files of failed \
+                 downloads, otherwise stale .part files are removed. Decompressing downloads of multistream dumps \
+                 are continued after the last complete stream, without verifying their checksum",
+            )
+            .action(ArgAction::SetTrue),
+        Arg::new("no-verify")
+            .long("no-verify")
+            .help("Don't check the SHA1 checksums of downloaded files")
+            .action(ArgAction::SetTrue),
+        Arg::new("verify-existing")
+            .long("verify-existing")
+            .help(
+                "Check the size and SHA1 checksum of existing files instead of skipping them and download them \
+                 again if they don't match",
+            )
+            .action(ArgAction::SetTrue),
+        Arg::new("link-from").long("link-from").value_name("older-dir").help(
+            "Hard link files of an older run of the dump in the directory which have the same size and checksum \
+                 instead of downloading them again (copied if they are on another file system)",
+        ),
+    ]
+}
+
+/// Arguments selecting files of an analytics dataset.
+fn dataset_args() -> [Arg; 4] {
+    [
+        Arg::new("dataset")
+            .help("Name of the dataset, see list-datasets")
+            .value_parser(PossibleValuesParser::new(DATASETS.iter().map(|dataset| dataset.name)))
+            .required(true),
+        Arg::new("from")
+            .help("First date of the range (YYYYMMDD)")
+            .required(true),
+        Arg::new("to").help("Last date of the range (YYYYMMDD), defaults to the first date"),
+        Arg::new("filter")
+            .long("filter")
+            .value_name("regex")
+            .help("Only select files with names matching the regular expression, e.g. a wiki for clickstream"),
+    ]
+}
+
+fn get_layout(matches: &ArgMatches) -> DirectoryLayout {
+    match matches.get_one::<String>("layout").unwrap().as_str() {
+        "flat" => DirectoryLayout::Flat,
+        "wiki-date" => DirectoryLayout::WikiDate,
+        _ => unreachable!(),
+    }
+}
+
+fn get_download_options<'a>(matches: &'a ArgMatches, base_url: &'a str) -> Result<DownloadOptions<'a>> {
+    let mirror = matches.get_one::<String>("mirror").map(|mirror| resolve_mirror(mirror));
+    let concurrency = matches
+        .get_one::<String>("concurrency")
+        .map(|s| str::parse::<NonZeroUsize>(s))
+        .transpose()
+        .map_err(|_| anyhow!("Invalid number for concurrency option."))?;
+    match concurrency {
+        Some(concurrency) if mirror.is_none() && base_url == DEFAULT_BASE_URL && concurrency.get() > 2 => {
+            bail!("A maximum of two concurrent connections are allowed for main Wikimedia dump website")
+        }
+        _ => {}
+    }
+    Ok(DownloadOptions {
+        base_url: Some(base_url),
+        mirror,
+        decompress: matches.get_flag("decompress"),
+        concurrency,
+        order: match matches.get_one::<String>("order").unwrap().as_str() {
+            "name" => DownloadOrder::Name,
+            "largest-first" => DownloadOrder::LargestFirst,
+            "smallest-first" => DownloadOrder::SmallestFirst,
+            "interleaved" => DownloadOrder::Interleaved,
+            _ => unreachable!(),
+        },
+        all_or_nothing: matches.get_flag("all-or-nothing"),
+        skip_verification: matches.get_flag("no-verify"),
+        verify_existing: matches.get_flag("verify-existing"),
+        connection_limit: None,
+        resume: matches.get_flag("resume"),
+        link_from: matches.get_one::<String>("link-from").map(Path::new),
+    })
+}
+
+fn get_filter(matches: &ArgMatches) -> Result<Option<Regex>> {
+    matches
+        .get_one::<String>("filter")
+        .map(|filter| Regex::new(filter))
+        .transpose()
+        .map_err(|e| anyhow!("Invalid filter regex: {}", e))
+}
+
+fn get_dataset_selection<'a>(matches: &'a ArgMatches, filter: Option<&'a Regex>) -> Result<DatasetSelection<'a>> {
+    // UNWRAP: required arguments
+    let from = matches.get_one::<String>("from").unwrap();
+    Ok(DatasetSelection {
+        dataset: find_dataset(matches.get_one::<String>("dataset").unwrap())?,
+        from,
+        to: matches.get_one::<String>("to").unwrap_or(from),
+        filter,
+    })
+}
+
+fn build_cli() -> Command {
+    let wiki_name_arg = Arg::new("wiki name")
+        .help("Name of the wiki or a dump tree below other/, e.g. other/wikibase/wikidatawiki or other/cirrussearch")
+        .required(true);
+    let dump_type_arg = Arg::new("dump type")
+        .help(
+            "Type of the dump, for dump trees below other/ a part of the file name without the date \
+             (cirrussearch-content and cirrussearch-general are looked up in other/cirrussearch)",
+        )
+        .value_parser(SuggestedValues(KNOWN_DUMP_TYPES))
+        .hide_possible_values(true)
+        .required(true);
     let dump_date_arg = Arg::new("dump date")
         .help("Date of the dump (YYYYMMDD or 'latest')")
         .required(true);
+    let layout_arg = Arg::new("layout")
+        .long("layout")
+        .value_parser(["flat", "wiki-date"])
+        .default_value("flat")
+        .value_name("layout")
+        .help("Arrangement of the dump files in the directory, wiki-date stores them in <dir>/<wiki>/<date>/");
 
-    let matches = Command::new("WikiDumpGet")
+    Command::new("WikiDumpGet")
         .version(crate_version!())
         .author(crate_authors!())
         .about("Download Wikipedia and other Wikimedia wiki dumps from the internet.")
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(
+            Arg::new("quiet-errors")
+                .long("quiet-errors")
+                .global(true)
+                .help("Print only the error class (e.g. dump-not-ready) instead of the error message on failure")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("offline")
+                .long("offline")
+                .global(true)
+                .help("Fail instead of accessing the network, only subcommands working on local files succeed")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("http2")
+                .long("http2")
+                .global(true)
+                .help(
+                    "Use HTTP/2 with a single connection per host shared by all downloads, which is faster for many \
+                     small files",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .global(true)
+                .help("Print the protocol used to connect to the dump website or mirror")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("base-url")
+                .long("base-url")
+                .value_name("url")
+                .global(true)
+                .help(
+                    "Root URL of another dump website with the layout of dumps.wikimedia.org \
+                     (<url>/<wiki>/<date>/dumpstatus.json), e.g. of a third-party wiki farm, defaults to \
+                     $WIKIDUMPTOOLS_BASE_URL. The wikis are listed from its root instead of Wikidata, analytics \
+                     datasets are always downloaded from Wikimedia",
+                ),
+        )
+        .arg(
+            Arg::new("user-agent-suffix")
+                .long("user-agent-suffix")
+                .value_name("contact")
+                .global(true)
+                .help(
+                    "Contact information like an email address appended to the user agent as requested by Wikimedia \
+                     for bulk downloads, defaults to $WIKIDUMPTOOLS_USER_AGENT_SUFFIX",
+                ),
+        )
+        .after_help(
+            "Exit codes: 0 success, 1 other error, 2 invalid arguments, 65 verification failure, 69 network error, \
+             74 disk error, 75 dump not ready (retry later), 130 aborted by the user",
+        )
         .subcommand(
             Command::new("download")
                 .about("Download a wiki dump")
-                .arg(wiki_name_arg.clone())
-                .arg(dump_date_arg.clone())
-                .arg(Arg::new("dump type").help("Type of the dump").required(true))
+                .arg(
+                    wiki_name_arg
+                        .clone()
+                        .required(false)
+                        .required_unless_present("manifest"),
+                )
+                .arg(
+                    dump_date_arg
+                        .clone()
+                        .required(false)
+                        .required_unless_present("manifest"),
+                )
+                .arg(
+                    dump_type_arg
+                        .clone()
+                        .required(false)
+                        .required_unless_present("manifest"),
+                )
+                .args(download_args())
+                .arg(
+                    Arg::new("manifest")
+                        .long("manifest")
+                        .value_name("file")
+                        .conflicts_with_all(["wiki name", "dump date", "dump type", "page-store"])
+                        .help(
+                            "Download the dumps listed in a TOML manifest ([[dump]] tables with wiki, type, optional \
+                             date and target-dir), parallel-dumps of them at the same time sharing the connections \
+                             given by --concurrency",
+                        ),
+                )
+                .arg(layout_arg.clone().conflicts_with("cache"))
+                .arg(
+                    Arg::new("cache")
+                        .long("cache")
+                        .conflicts_with("target-dir")
+                        .help(
+                            "Download to the dump cache shared with wdgrep (<cache dir>/<wiki>/<date>/<type>/), \
+                             the cache directory can be set with WIKIDUMPTOOLS_CACHE",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("page-store")
+                        .long("page-store")
+                        .conflicts_with("decompress")
+                        .help("Unpack downloaded multistream dumps into a page store for random access by title or id")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("get-page")
+                .about("Print the XML of a page from a page store created with download --page-store")
+                .arg(Arg::new("store dir").help("Page store directory").required(true))
+                .arg(Arg::new("title").long("title").help("Title of the page"))
+                .arg(Arg::new("id").long("id").help("Id of the page"))
+                .group(ArgGroup::new("page").args(["title", "id"]).required(true)),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about(
+                    "Verify an already downloaded wiki dump, without wiki name, date and type the newest dump the \
+                     files in the directory belong to is verified",
+                )
+                .arg(wiki_name_arg.clone().required(false))
+                .arg(dump_date_arg.clone().required(false))
+                .arg(dump_type_arg.clone().required(false))
+                .arg(
+                    Arg::new("dir")
+                        .short('d')
+                        .long("dir")
+                        .help("Directory with the dump files"),
+                )
+                .arg(layout_arg)
+                .arg(
+                    Arg::new("concurrency")
+                        .short('j')
+                        .long("concurrency")
+                        .help("Number of files hashed in parallel, defaults to the number of logical cpus."),
+                )
                 .arg(
                     Arg::new("quiet")
                         .short('q')
                         .long("quiet")
                         .help("Don't print progress updates")
                         .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("clean")
+                .about(
+                    "Remove the .part files and staging directories left by interrupted downloads, the files of \
+                     completely downloaded staging directories are moved into the download directory first",
                 )
+                .arg(Arg::new("dir").help("Download directory").required(true)),
+        )
+        .subcommand(
+            Command::new("list-files")
+                .about("List the files of a dump with size, SHA-1 checksum and download URL")
+                .arg(wiki_name_arg.clone())
+                .arg(dump_date_arg.clone())
+                .arg(dump_type_arg.clone())
                 .arg(
-                    Arg::new("decompress")
-                        .short('d')
-                        .long("decompress")
-                        .help("Decompress .bz2 files during download")
-                        .action(ArgAction::SetTrue),
+                    Arg::new("mirror")
+                        .short('m')
+                        .long("mirror")
+                        .help("Mirror root URL or one of the shortcuts 'acc.umu.se', 'your.org' and 'bringyour.com'")
+                        .value_parser(SuggestedValues(&MIRROR_SHORTCUT_NAMES))
+                        .hide_possible_values(true),
                 )
                 .arg(
-                    Arg::new("target-dir")
-                        .short('t')
-                        .long("target-dir")
-                        .help("Target directory"),
+                    Arg::new("filter")
+                        .long("filter")
+                        .value_name("regex")
+                        .help("Only list files with names matching the regular expression"),
                 )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["tsv", "json"])
+                        .default_value("tsv")
+                        .value_name("format")
+                        .help("Output format, TSV columns are name, size in bytes, SHA-1 checksum and URL"),
+                ),
+        )
+        .subcommand(Command::new("list-datasets").about("List the analytics datasets which can be downloaded"))
+        .subcommand(
+            Command::new("list-dataset-files")
+                .about("List the files of an analytics dataset in a date range with size, SHA-1 checksum and URL")
+                .args(dataset_args())
                 .arg(
                     Arg::new("mirror")
                         .short('m')
                         .long("mirror")
-                        .help("Mirror root URL or one of the shortcuts 'acc.umu.se', 'your.org' and 'bringyour.com'"),
+                        .help("Mirror root URL or one of the shortcuts 'acc.umu.se', 'your.org' and 'bringyour.com'")
+                        .value_parser(SuggestedValues(&MIRROR_SHORTCUT_NAMES))
+                        .hide_possible_values(true),
                 )
-                .arg(Arg::new("concurrency").short('j').long("concurrency").help(
-                    "Number of parallel connections, defaults to 1 if no mirror, determined heuristically otherwise.",
-                )),
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["tsv", "json"])
+                        .default_value("tsv")
+                        .value_name("format")
+                        .help("Output format, TSV columns are name, size in bytes, SHA-1 checksum and URL"),
+                ),
         )
         .subcommand(
-            Command::new("verify")
-                .about("Verify an already downloaded wiki dump")
-                .arg(wiki_name_arg.clone())
-                .arg(dump_date_arg.clone())
-                .arg(Arg::new("dump type").help("Type of the dump").required(true))
+            Command::new("download-dataset")
+                .about("Download the files of an analytics dataset in a date range, e.g. pageview counts")
+                .args(dataset_args())
+                .args(download_args()),
+        )
+        .subcommand(
+            Command::new("list-wikis")
+                .about(
+                    "List all wikis for which dumps are available, the list is cached for a week and the cached list \
+                     is also used if the dump website or Wikidata cannot be reached",
+                )
                 .arg(
-                    Arg::new("dir")
-                        .short('d')
-                        .long("dir")
-                        .help("Directory with the dump files"),
+                    Arg::new("refresh")
+                        .long("refresh")
+                        .help("Retrieve the list again even if the cached list is recent")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("dumps")
+                        .long("dumps")
+                        .help(
+                            "Also list the date, status and size of the latest dump run of each wiki from the dump \
+                             index, the size is that of the completed jobs and needs the dump status of every wiki",
+                        )
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sort")
+                        .long("sort")
+                        .value_parser(["name", "size", "date"])
+                        .default_value("name")
+                        .value_name("key")
+                        .requires("dumps")
+                        .help("Order of the wikis listed with --dumps, largest or newest first"),
+                ),
+        )
+        .subcommand(
+            Command::new("list-latest")
+                .about(
+                    "List the latest dump run of every wiki with its status from the index of the dump website, \
+                     unlike list-wikis --dumps only the index is retrieved",
+                )
+                .arg(
+                    Arg::new("only-done")
+                        .long("only-done")
+                        .help("Only list dump runs which are complete")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(["table", "json"])
+                        .default_value("table")
+                        .value_name("format")
+                        .help("Output format"),
                 ),
         )
-        .subcommand(Command::new("list-wikis").about("List all wikis for which dumps are available"))
         .subcommand(
             Command::new("list-dates")
                 .about("List all dump dates available for this wiki")
                 .arg(wiki_name_arg.clone())
-                .arg(Arg::new("dump type").help("Type of the dump").required(false)),
+                .arg(dump_type_arg.required(false)),
         )
         .subcommand(
             Command::new("list-dumps")
-                .about("List all dumps available for this wiki at this date")
+                .about(
+                    "List all dumps available for this wiki at this date, decompressed sizes are estimated from \
+                     typical compression ratios",
+                )
                 .arg(wiki_name_arg.clone())
                 .arg(dump_date_arg),
         )
-        .get_matches();
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .hide(true)
+                .arg(
+                    Arg::new("shell")
+                        .help("Shell to generate the completion script for")
+                        .value_parser(value_parser!(Shell))
+                        .required(true),
+                ),
+        )
+}
 
+/// Exit codes follow sysexits.h, 130 is the conventional exit code after SIGINT.
+fn get_exit_code_and_class_name(error: &anyhow::Error) -> (i32, &'static str) {
+    match error.downcast_ref::<Error>().map(Error::class) {
+        Some(ErrorClass::Network) => (69, "network-error"),
+        Some(ErrorClass::Verification) => (65, "verification-failure"),
+        Some(ErrorClass::DumpNotReady) => (75, "dump-not-ready"),
+        Some(ErrorClass::UserAbort) => (130, "user-abort"),
+        Some(ErrorClass::Disk) => (74, "disk-error"),
+        Some(ErrorClass::Other) | None => (1, "error"),
+    }
+}
+
+async fn run(matches: &ArgMatches) -> Result<()> {
     let _color_choice = if atty::is(atty::Stream::Stdout) {
         ColorChoice::Auto
     } else {
         ColorChoice::Never
     };
-    let client = create_client()?;
-    match matches.subcommand_name().unwrap() {
-        "list-wikis" => list_wikis(&client).await?,
+    if let Some(subcommand_matches) = matches.subcommand_matches("completions") {
+        // UNWRAP: required argument
+        let shell = *subcommand_matches.get_one::<Shell>("shell").unwrap();
+        generate(shell, &mut build_cli(), "wdget", &mut stdout());
+        return Ok(());
+    }
+    // UNWRAP: subcommand is required
+    let subcommand = matches.subcommand_name().unwrap();
+    let offline = matches.get_flag("offline");
+    // the wikis are listed from the cache in offline mode
+    if offline && !matches!(subcommand, "list-datasets" | "clean" | "get-page" | "list-wikis") {
+        return Err(Error::Offline().into());
+    }
+    let user_agent_suffix = matches
+        .get_one::<String>("user-agent-suffix")
+        .cloned()
+        .or_else(|| std::env::var("WIKIDUMPTOOLS_USER_AGENT_SUFFIX").ok());
+    let base_url = matches
+        .get_one::<String>("base-url")
+        .cloned()
+        .or_else(|| std::env::var("WIKIDUMPTOOLS_BASE_URL").ok());
+    let base_url = base_url
+        .as_deref()
+        .map_or(DEFAULT_BASE_URL, |base_url| base_url.trim_end_matches('/'));
+    let http2 = matches.get_flag("http2");
+    let verbose = matches.get_flag("verbose");
+    let client = create_client(user_agent_suffix.as_deref(), http2)?;
+    match subcommand {
+        "list-wikis" => {
+            let subcommand_matches = matches.subcommand_matches("list-wikis").unwrap();
+            let refresh = subcommand_matches.get_flag("refresh");
+            if subcommand_matches.get_flag("dumps") {
+                // UNWRAP: has a default value
+                let sort = subcommand_matches.get_one::<String>("sort").unwrap();
+                list_wikis_with_dumps(&client, base_url, refresh, offline, sort).await?;
+            } else {
+                list_wikis(&client, base_url, refresh, offline).await?;
+            }
+        }
+
+        "list-latest" => {
+            let subcommand_matches = matches.subcommand_matches("list-latest").unwrap();
+            // UNWRAP: has a default value
+            let json = subcommand_matches.get_one::<String>("format").unwrap() == "json";
+            list_latest(&client, base_url, subcommand_matches.get_flag("only-done"), json).await?;
+        }
 
         "list-dates" => {
             // todo: check args: wiki name, handle optional type, handle no dump found condition
             let subcommand_matches = matches.subcommand_matches("list-dates").unwrap();
-            list_dates(&client, subcommand_matches.get_one::<String>("wiki name").unwrap()).await?;
+            list_dates(
+                &client,
+                base_url,
+                subcommand_matches.get_one::<String>("wiki name").unwrap(),
+            )
+            .await?;
         }
 
         "list-dumps" => {
@@ -357,50 +1256,81 @@ async fn run() -> Result<()> {
             let subcommand_matches = matches.subcommand_matches("list-dumps").unwrap();
             let wiki = subcommand_matches.get_one::<String>("wiki name").unwrap();
             let date_spec = subcommand_matches.get_one::<String>("dump date").unwrap();
-            let date = check_date_may_retrieve_latest(&client, wiki, date_spec, None).await?;
+            let date = check_date_may_retrieve_latest(&client, base_url, wiki, date_spec, None).await?;
             eprintln!("Listing dumps for {wiki}, dump run from {date}");
-            list_types(&client, wiki, &date).await?;
+            list_types(&client, base_url, wiki, &date).await?;
+        }
+
+        "list-files" => {
+            let subcommand_matches = matches.subcommand_matches("list-files").unwrap();
+            let (wiki, dump_type) = resolve_dump_tree(
+                subcommand_matches.get_one::<String>("wiki name").unwrap(),
+                subcommand_matches.get_one::<String>("dump type").unwrap(),
+            );
+            let (wiki, dump_type) = (wiki.as_str(), dump_type.as_str());
+            let date_spec = subcommand_matches.get_one::<String>("dump date").unwrap();
+            let date = check_date_may_retrieve_latest(&client, base_url, wiki, date_spec, Some(dump_type)).await?;
+            let mirror = subcommand_matches
+                .get_one::<String>("mirror")
+                .map(|mirror| resolve_mirror(mirror));
+            let filter = get_filter(subcommand_matches)?;
+            let json = subcommand_matches.get_one::<String>("format").unwrap() == "json";
+            let files = get_dump_files_from(&client, base_url, wiki, &date, dump_type).await?;
+            list_files(&files, wiki, &date, mirror.unwrap_or(base_url), filter.as_ref(), json)?;
         }
 
         "download" => {
             // todo: check args
             let subcommand_matches = matches.subcommand_matches("download").unwrap();
-            let wiki = subcommand_matches.get_one::<String>("wiki name").unwrap();
+            if let Some(manifest_file) = subcommand_matches.get_one::<String>("manifest") {
+                let manifest = Manifest::read(Path::new(manifest_file))?;
+                let target = if subcommand_matches.get_flag("cache") {
+                    ManifestTarget::Cache(DumpCache::open_default()?)
+                } else {
+                    let target_dir = match subcommand_matches.get_one::<String>("target-dir") {
+                        None => current_dir().map_err(|e| anyhow!("Current directory not accessible: {}", e))?,
+                        Some(dir) => PathBuf::from(dir),
+                    };
+                    ManifestTarget::Directory(target_dir, get_layout(subcommand_matches))
+                };
+                let download_options = get_download_options(subcommand_matches, base_url)?;
+                warm_up_connection(&client, &download_options, http2, verbose).await?;
+                let show_progress = !subcommand_matches.get_flag("quiet") && atty::is(atty::Stream::Stderr);
+                let show_warnings = !subcommand_matches.get_flag("quiet");
+                return download_manifest(
+                    &client,
+                    &manifest,
+                    &target,
+                    &download_options,
+                    show_progress,
+                    show_warnings,
+                )
+                .await;
+            }
+            let (wiki, dump_type) = resolve_dump_tree(
+                subcommand_matches.get_one::<String>("wiki name").unwrap(),
+                subcommand_matches.get_one::<String>("dump type").unwrap(),
+            );
+            let (wiki, dump_type) = (wiki.as_str(), dump_type.as_str());
             let date_spec = subcommand_matches.get_one::<String>("dump date").unwrap();
-            let dump_type = subcommand_matches.get_one::<String>("dump type").unwrap();
-            let date = check_date_may_retrieve_latest(&client, wiki, date_spec, Some(dump_type)).await?;
-            let target_dir = match subcommand_matches.get_one::<String>("target-dir") {
-                None => current_dir().map_err(|e| anyhow!("Current directory not accessible: {}", e))?,
-                Some(dir) => PathBuf::from(dir),
+            let date = check_date_may_retrieve_latest(&client, base_url, wiki, date_spec, Some(dump_type)).await?;
+            let cache = if subcommand_matches.get_flag("cache") {
+                Some(DumpCache::open_default()?)
+            } else {
+                None
+            };
+            let target_dir = match (subcommand_matches.get_one::<String>("target-dir"), &cache) {
+                (_, Some(cache)) => cache.create_dump_directory(wiki, &date, dump_type)?,
+                (None, None) => current_dir().map_err(|e| anyhow!("Current directory not accessible: {}", e))?,
+                (Some(dir), None) => PathBuf::from(dir),
             };
             if !target_dir.is_dir() {
                 bail!("Target directory does not exist or is not accessible.")
             };
-            let mirror = match subcommand_matches.get_one::<String>("mirror").map(String::as_str) {
-                Some("acc.umu.se") => Some("https://ftp.acc.umu.se/mirror/wikimedia.org/dumps"),
-                Some("your.org") => Some("http://dumps.wikimedia.your.org/"),
-                Some("bringyour.com") => Some("https://wikimedia.bringyour.com/"),
-                Some(url) => Some(url),
-                None => None,
-            };
-
-            let concurrency = subcommand_matches
-                .get_one::<String>("concurrency")
-                .map(|s| str::parse::<NonZeroUsize>(s))
-                .transpose()
-                .map_err(|_| anyhow!("Invalid number for concurrency option."))?;
-            match concurrency {
-                Some(concurrency) if mirror.is_none() && concurrency.get() > 2 => {
-                    bail!("A maximum of two concurrent connections are allowed for main Wikimedia dump website")
-                }
-                _ => {}
-            }
-
-            let download_options = DownloadOptions {
-                mirror,
-                decompress: subcommand_matches.get_flag("decompress"),
-                concurrency,
-            };
+            // the cache has its own layout
+            let target_dir = get_layout(subcommand_matches).create_dump_directory(&target_dir, wiki, &date)?;
+            let download_options = get_download_options(subcommand_matches, base_url)?;
+            warm_up_connection(&client, &download_options, http2, verbose).await?;
             let show_progress = !subcommand_matches.get_flag("quiet") && atty::is(atty::Stream::Stderr);
             let show_warnings = !subcommand_matches.get_flag("quiet");
             download(
@@ -408,19 +1338,75 @@ async fn run() -> Result<()> {
                 wiki,
                 &date,
                 dump_type,
-                target_dir,
+                &target_dir,
                 &download_options,
                 show_progress,
                 show_warnings,
             )
             .await?;
+            if let Some(cache) = &cache {
+                cache.write_manifest(wiki, &date, dump_type)?;
+            }
+            if subcommand_matches.get_flag("page-store") {
+                let store_dir = target_dir.join(format!("{wiki}-{date}-{dump_type}-store"));
+                make_page_store(&target_dir, &format!("{wiki}-{date}-"), store_dir).await?;
+            }
+        }
+        "list-datasets" => list_datasets(),
+
+        "list-dataset-files" => {
+            let subcommand_matches = matches.subcommand_matches("list-dataset-files").unwrap();
+            let filter = get_filter(subcommand_matches)?;
+            let selection = get_dataset_selection(subcommand_matches, filter.as_ref())?;
+            let mirror = subcommand_matches
+                .get_one::<String>("mirror")
+                .map(|mirror| resolve_mirror(mirror));
+            let json = subcommand_matches.get_one::<String>("format").unwrap() == "json";
+            list_dataset_files(&client, &selection, mirror, json).await?;
+        }
+
+        "download-dataset" => {
+            let subcommand_matches = matches.subcommand_matches("download-dataset").unwrap();
+            let filter = get_filter(subcommand_matches)?;
+            let selection = get_dataset_selection(subcommand_matches, filter.as_ref())?;
+            let target_dir = match subcommand_matches.get_one::<String>("target-dir") {
+                None => current_dir().map_err(|e| anyhow!("Current directory not accessible: {}", e))?,
+                Some(dir) => PathBuf::from(dir),
+            };
+            if !target_dir.is_dir() {
+                bail!("Target directory does not exist or is not accessible.")
+            };
+            let download_options = get_download_options(subcommand_matches, base_url)?;
+            warm_up_connection(&client, &download_options, http2, verbose).await?;
+            let show_progress = !subcommand_matches.get_flag("quiet") && atty::is(atty::Stream::Stderr);
+            let show_warnings = !subcommand_matches.get_flag("quiet");
+            let (progress_send, progress_receive) = unbounded_channel::<DownloadProgress>();
+            let download_fut =
+                download_dataset(&client, &selection, &target_dir, &download_options, Some(progress_send));
+            report_download_progress(
+                download_fut,
+                progress_receive,
+                download_options.decompress,
+                show_progress,
+                show_warnings,
+            )
+            .await?;
         }
+
         "verify" => {
             let subcommand_matches = matches.subcommand_matches("verify").unwrap();
-            let wiki = subcommand_matches.get_one::<String>("wiki name").unwrap();
-            let date_spec = subcommand_matches.get_one::<String>("dump date").unwrap();
-            check_date_valid(date_spec)?;
-            let dump_type = subcommand_matches.get_one::<String>("dump type").unwrap();
+            let dump = match (
+                subcommand_matches.get_one::<String>("wiki name"),
+                subcommand_matches.get_one::<String>("dump date"),
+                subcommand_matches.get_one::<String>("dump type"),
+            ) {
+                (Some(wiki), Some(date_spec), Some(dump_type)) => {
+                    check_date_valid(date_spec)?;
+                    Some((resolve_dump_tree(wiki, dump_type), date_spec))
+                }
+                (None, None, None) => None,
+                _ => bail!("Wiki name, dump date and dump type need to be given together."),
+            };
             let dump_files_dir = match subcommand_matches.get_one::<String>("dir") {
                 None => current_dir().map_err(|e| anyhow!("Current directory not accessible: {}", e))?,
                 Some(dir) => PathBuf::from(dir),
@@ -428,7 +1414,45 @@ async fn run() -> Result<()> {
             if !dump_files_dir.is_dir() {
                 bail!("Dump files directory does not exist or is not accessible.")
             };
-            verify::verify_downloaded_dump(&client, wiki, date_spec, dump_type, dump_files_dir).await?;
+            let concurrency = subcommand_matches
+                .get_one::<String>("concurrency")
+                .map(|s| str::parse::<NonZeroUsize>(s))
+                .transpose()
+                .map_err(|_| anyhow!("Invalid number for concurrency option."))?;
+            let show_progress = !subcommand_matches.get_flag("quiet") && atty::is(atty::Stream::Stderr);
+            let layout = get_layout(subcommand_matches);
+            match dump {
+                Some(((wiki, dump_type), date_spec)) => {
+                    let files = get_dump_files_from(&client, base_url, &wiki, date_spec, &dump_type).await?;
+                    let dump_dir = layout.dump_directory(&dump_files_dir, &wiki, date_spec);
+                    verify::verify_downloaded_dump(&files, dump_dir, concurrency, show_progress).await?;
+                }
+                None => {
+                    let dump_dir = match layout {
+                        DirectoryLayout::Flat => dump_files_dir,
+                        DirectoryLayout::WikiDate => verify::find_latest_dump_directory(&dump_files_dir)?,
+                    };
+                    verify::verify_detected_dump(&client, base_url, dump_dir, concurrency, show_progress).await?;
+                }
+            }
+        }
+        "clean" => {
+            let subcommand_matches = matches.subcommand_matches("clean").unwrap();
+            let removed = clean_directory(Path::new(subcommand_matches.get_one::<String>("dir").unwrap()))?;
+            for path in &removed {
+                println!("Removed {}", path.display());
+            }
+            if removed.is_empty() {
+                eprintln!("Nothing to clean up.");
+            }
+        }
+        "get-page" => {
+            let subcommand_matches = matches.subcommand_matches("get-page").unwrap();
+            get_page(
+                Path::new(subcommand_matches.get_one::<String>("store dir").unwrap()),
+                subcommand_matches.get_one::<String>("title"),
+                subcommand_matches.get_one::<String>("id"),
+            )?;
         }
         _ => unreachable!("Unknown subcommand, should be caught by arg matching."),
     }
@@ -437,9 +1461,15 @@ async fn run() -> Result<()> {
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let res = run().await;
+    let matches = build_cli().get_matches();
+    let res = run(&matches).await;
     if let Err(e) = res {
-        eprintln!("{e}");
-        process::exit(1);
+        let (exit_code, class_name) = get_exit_code_and_class_name(&e);
+        if matches.get_flag("quiet-errors") {
+            eprintln!("{class_name}");
+        } else {
+            eprintln!("{e}");
+        }
+        process::exit(exit_code);
     }
 }
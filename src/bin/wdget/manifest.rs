@@ -0,0 +1,186 @@
+// wdget
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Manifests listing several dumps which are downloaded in one run, e.g. all wikis fetched by a mirror:
+//!
+//! ```toml
+//! parallel-dumps = 2
+//!
+//! [[dump]]
+//! wiki = "enwiki"
+//! type = "articlesmultistreamdump"
+//!
+//! [[dump]]
+//! wiki = "dewiki"
+//! date = "20240101"
+//! type = "metacurrentdump"
+//! target-dir = "/srv/dumps/dewiki"
+//! ```
+
+use std::fs;
+use std::io::{stdout, Write};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Result};
+use futures::stream::{self, StreamExt};
+use reqwest::Client;
+use serde::Deserialize;
+use tabwriter::TabWriter;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Semaphore;
+use wdgetlib::cache::DumpCache;
+use wdgetlib::{
+    download_dump, get_concurrency, resolve_dump_tree, DirectoryLayout, DownloadOptions, DownloadProgress,
+    DEFAULT_BASE_URL,
+};
+
+use crate::{check_date_may_retrieve_latest, report_download_progress};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Manifest {
+    /// Number of dumps downloaded at the same time, defaults to one after the other
+    parallel_dumps: Option<NonZeroUsize>,
+    #[serde(rename = "dump", default)]
+    dumps: Vec<ManifestEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct ManifestEntry {
+    wiki: String,
+    #[serde(default = "latest_date")]
+    date: String,
+    #[serde(rename = "type")]
+    dump_type: String,
+    /// Overrides the target directory given on the command line
+    target_dir: Option<PathBuf>,
+}
+
+fn latest_date() -> String {
+    "latest".to_owned()
+}
+
+/// Where the dumps of a manifest are downloaded to unless their entry gives a target directory.
+pub enum ManifestTarget {
+    /// Target directory given on the command line, the layout also applies to the target directories of entries
+    Directory(PathBuf, DirectoryLayout),
+    Cache(DumpCache),
+}
+
+impl Manifest {
+    pub fn read(file: &Path) -> Result<Manifest> {
+        let content =
+            fs::read_to_string(file).map_err(|e| anyhow!("Could not read manifest {}: {}", file.display(), e))?;
+        let manifest: Manifest =
+            toml::from_str(&content).map_err(|e| anyhow!("Invalid manifest {}: {}", file.display(), e))?;
+        if manifest.dumps.is_empty() {
+            bail!("The manifest does not list any dumps.");
+        }
+        Ok(manifest)
+    }
+}
+
+/// Downloads the dumps listed in the manifest and prints a summary. The connections of all dumps downloaded at the same
+/// time are limited to the concurrency of the download options. Dumps which fail do not stop the others.
+pub async fn download_manifest(
+    client: &Client,
+    manifest: &Manifest,
+    target: &ManifestTarget,
+    download_options: &DownloadOptions<'_>,
+    show_progress: bool,
+    show_warnings: bool,
+) -> Result<()> {
+    let connection_limit = Semaphore::new(get_concurrency(download_options));
+    let download_options = DownloadOptions {
+        connection_limit: Some(&connection_limit),
+        ..*download_options
+    };
+    let decompress = download_options.decompress;
+    let parallel_dumps = manifest.parallel_dumps.map_or(1, NonZeroUsize::get);
+    let (progress_send, progress_receive) = unbounded_channel::<DownloadProgress>();
+    let download_fut = async move {
+        let results = stream::iter(&manifest.dumps)
+            .map(|entry| {
+                let progress_send = progress_send.clone();
+                let download_options = &download_options;
+                async move {
+                    let result = download_entry(client, entry, target, download_options, progress_send).await;
+                    (entry, result)
+                }
+            })
+            .buffered(parallel_dumps)
+            .collect::<Vec<_>>()
+            .await;
+        Ok(results)
+    };
+    let results =
+        report_download_progress(download_fut, progress_receive, decompress, show_progress, show_warnings).await?;
+
+    let mut tw = TabWriter::new(stdout());
+    writeln!(tw, "Wiki\tDate\tType\tResult").unwrap();
+    for (entry, result) in &results {
+        match result {
+            Ok(date) => writeln!(tw, "{}\t{}\t{}\tdownloaded", entry.wiki, date, entry.dump_type).unwrap(),
+            Err(e) => writeln!(tw, "{}\t{}\t{}\tfailed: {}", entry.wiki, entry.date, entry.dump_type, e).unwrap(),
+        }
+    }
+    tw.flush().unwrap();
+    let failed_count = results.iter().filter(|(_, result)| result.is_err()).count();
+    if failed_count > 0 {
+        bail!("{} of {} dumps could not be downloaded.", failed_count, results.len());
+    }
+    Ok(())
+}
+
+/// Downloads a dump of the manifest and returns its date.
+async fn download_entry(
+    client: &Client,
+    entry: &ManifestEntry,
+    target: &ManifestTarget,
+    download_options: &DownloadOptions<'_>,
+    progress_send: UnboundedSender<DownloadProgress>,
+) -> Result<String> {
+    let (wiki, dump_type) = resolve_dump_tree(&entry.wiki, &entry.dump_type);
+    let (wiki, dump_type) = (wiki.as_str(), dump_type.as_str());
+    let base_url = download_options.base_url.unwrap_or(DEFAULT_BASE_URL);
+    let date = check_date_may_retrieve_latest(client, base_url, wiki, &entry.date, Some(dump_type)).await?;
+    let target_directory = match (&entry.target_dir, target) {
+        (Some(dir), ManifestTarget::Cache(_)) => dir.clone(),
+        (None, ManifestTarget::Cache(cache)) => cache.create_dump_directory(wiki, &date, dump_type)?,
+        (entry_dir, ManifestTarget::Directory(dir, layout)) => {
+            let dir = entry_dir.as_ref().unwrap_or(dir);
+            if !dir.is_dir() {
+                bail!(
+                    "Target directory {} does not exist or is not accessible.",
+                    dir.display()
+                );
+            }
+            layout.create_dump_directory(dir, wiki, &date)?
+        }
+    };
+    if !target_directory.is_dir() {
+        bail!(
+            "Target directory {} does not exist or is not accessible.",
+            target_directory.display()
+        );
+    }
+    download_dump(
+        client,
+        wiki,
+        &date,
+        dump_type,
+        &target_directory,
+        download_options,
+        Some(progress_send),
+    )
+    .await?;
+    if let ManifestTarget::Cache(cache) = target {
+        cache.write_manifest(wiki, &date, dump_type)?;
+    }
+    Ok(date)
+}
@@ -4,125 +4,314 @@
 //
 // Distributed under the terms of the MIT license.
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Write};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
+use anyhow::{bail, Result};
+use lazy_static::lazy_static;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use regex::Regex;
 use reqwest::Client;
-use sha1::{Digest, Sha1};
-use wdgetlib::{get_dump_status, DumpFileInfo, Error};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::task::spawn_blocking;
+use tokio::{pin, select, time};
+use wdgetlib::hashing::{HashAlgorithm, HashingReader};
+use wdgetlib::{get_dump_status_from, DumpFileInfo, Error};
 
-type Result<T> = std::result::Result<T, Error>;
+use crate::get_human_size;
 
+struct FileToVerify {
+    path: PathBuf,
+    name: String,
+    algorithm: HashAlgorithm,
+    expected_checksum: String,
+}
+
+enum VerifyProgress {
+    BytesHashed(u64),
+    FileVerified(String),
+}
+
+/// Verifies the files of a dump, which are listed by `get_dump_files()`.
 pub async fn verify_downloaded_dump<T>(
+    files: &BTreeMap<String, DumpFileInfo>,
+    dump_files_directory: T,
+    concurrency: Option<NonZeroUsize>,
+    show_progress: bool,
+) -> Result<()>
+where
+    T: AsRef<Path> + Send,
+{
+    let dump_files_directory = dump_files_directory.as_ref();
+    if !dump_files_directory.exists() {
+        return Err(Error::TargetDirectoryDoesNotExist(dump_files_directory.to_owned()).into());
+    }
+    verify_dump_files(files, dump_files_directory, concurrency, show_progress).await
+}
+
+/// Verifies the dump the files in the directory belong to. Wiki and date are taken from the file names
+/// (`<wiki>-<date>-...`), if files of several dumps of the wiki are found the newest one is verified. All completed
+/// jobs of the dump with files in the directory are verified.
+pub async fn verify_detected_dump<T>(
     client: &Client,
-    wiki: &str,
-    date: &str,
-    dump_type: &str,
+    base_url: &str,
     dump_files_directory: T,
+    concurrency: Option<NonZeroUsize>,
+    show_progress: bool,
 ) -> Result<()>
 where
     T: AsRef<Path> + Send,
 {
     let dump_files_directory = dump_files_directory.as_ref();
     if !dump_files_directory.exists() {
-        return Err(Error::TargetDirectoryDoesNotExist(dump_files_directory.to_owned()));
+        return Err(Error::TargetDirectoryDoesNotExist(dump_files_directory.to_owned()).into());
+    }
+    let file_names = fs::read_dir(dump_files_directory)?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().into_owned()))
+        .collect::<std::io::Result<BTreeSet<String>>>()?;
+    let (wiki, date) = detect_dump(&file_names)?;
+    let dump_status = get_dump_status_from(client, base_url, &wiki, &date).await?;
+    let mut files = BTreeMap::new();
+    let mut jobs = Vec::new();
+    for (job, job_info) in dump_status.jobs {
+        let job_files = match job_info.files {
+            Some(job_files) if job_info.status == "done" => job_files,
+            _ => continue,
+        };
+        if job_files.keys().any(|file_name| {
+            file_names.contains(file_name) || file_names.contains(get_decompressed_file_name(file_name))
+        }) {
+            files.extend(job_files);
+            jobs.push(job);
+        }
+    }
+    if jobs.is_empty() {
+        bail!("No files of completed jobs of the {wiki} dump of {date} found.");
+    }
+    eprintln!("Verifying the {wiki} dump of {date}: {}", jobs.join(", "));
+    verify_dump_files(&files, dump_files_directory, concurrency, show_progress).await
+}
+
+/// Returns the wiki and the newest date found in the dump file names.
+fn detect_dump(file_names: &BTreeSet<String>) -> Result<(String, String)> {
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new("^([a-z0-9_]+)-([1-9][0-9]{7})-").expect("Error parsing dump file name regex constant");
+    }
+    let dumps: BTreeSet<(&str, &str)> = file_names
+        .iter()
+        .filter_map(|file_name| {
+            let cap = RE.captures(file_name)?;
+            Some((cap.get(1)?.as_str(), cap.get(2)?.as_str()))
+        })
+        .collect();
+    let wikis: BTreeSet<&str> = dumps.iter().map(|&(wiki, _)| wiki).collect();
+    if wikis.len() > 1 {
+        bail!(
+            "Dump files of several wikis found ({}), please specify the dump to verify.",
+            wikis.into_iter().collect::<Vec<_>>().join(", ")
+        );
+    }
+    // sorted by date
+    match dumps.last() {
+        Some(&(wiki, date)) => Ok((wiki.to_owned(), date.to_owned())),
+        None => bail!("No dump files found, please specify the dump to verify."),
+    }
+}
+
+/// Returns the directory of the newest dump in a directory with the wiki-date layout (`<dir>/<wiki>/<date>/`).
+pub fn find_latest_dump_directory(directory: &Path) -> Result<PathBuf> {
+    lazy_static! {
+        static ref WIKI_RE: Regex = Regex::new("^[a-z0-9_]+$").expect("Error parsing wiki name regex constant");
+        static ref DATE_RE: Regex = Regex::new("^[1-9][0-9]{7}$").expect("Error parsing date regex constant");
+    }
+    let subdirectories = |directory: &Path, re: &Regex| -> Result<BTreeSet<String>> {
+        let mut names = BTreeSet::new();
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_dir() && re.is_match(&name) {
+                names.insert(name);
+            }
+        }
+        Ok(names)
+    };
+    let mut dumps = BTreeSet::new();
+    for wiki in subdirectories(directory, &WIKI_RE)? {
+        for date in subdirectories(&directory.join(&wiki), &DATE_RE)? {
+            dumps.insert((wiki.clone(), date));
+        }
+    }
+    let wikis: BTreeSet<&str> = dumps.iter().map(|(wiki, _)| wiki.as_str()).collect();
+    if wikis.len() > 1 {
+        bail!(
+            "Dumps of several wikis found ({}), please specify the dump to verify.",
+            wikis.into_iter().collect::<Vec<_>>().join(", ")
+        );
     }
-    let dump_status = get_dump_status(client, wiki, date).await?;
-    let job_info = dump_status.jobs.get(dump_type).ok_or(Error::DumpTypeNotFound())?;
-    if &job_info.status != "done" {
-        return Err(Error::DumpNotComplete());
+    // sorted by date
+    match dumps.last() {
+        Some((wiki, date)) => Ok(directory.join(wiki).join(date)),
+        None => bail!("No dump directories found, please specify the dump to verify."),
     }
-    let files = job_info.files.as_ref().ok_or(Error::DumpHasNoFiles())?;
+}
+
+async fn verify_dump_files(
+    files: &BTreeMap<String, DumpFileInfo>,
+    dump_files_directory: &Path,
+    concurrency: Option<NonZeroUsize>,
+    show_progress: bool,
+) -> Result<()> {
+    // check presence and sizes of all files first, these are cheap
+    let mut files_to_verify = Vec::with_capacity(files.len());
+    let mut total_size = 0_u64;
     for (file_name, file_data) in files {
-        let target_file_name = get_target_file_name(file_name, false);
-        let target_file_path = get_file_in_dir(dump_files_directory, target_file_name);
+        let target_file_path = get_file_in_dir(dump_files_directory, file_name);
         if !target_file_path.exists() {
-            let decompressed_target_file_name = get_target_file_name(file_name, true);
+            let decompressed_target_file_name = get_decompressed_file_name(file_name);
             let decompressed_target_file_path = get_file_in_dir(dump_files_directory, decompressed_target_file_name);
             return if decompressed_target_file_path.exists() {
-                Err(Error::DecompressedFileCannotBeVerified(
-                    decompressed_target_file_name.to_owned(),
-                ))
+                Err(Error::DecompressedFileCannotBeVerified(decompressed_target_file_name.to_owned()).into())
             } else {
-                Err(Error::FileToBeVerifiedNotFound(target_file_name.to_owned()))
+                Err(Error::FileToBeVerifiedNotFound(file_name.to_owned()).into())
             };
         }
-        verify_existing_file(&target_file_path, target_file_name, file_data, true)?;
-    }
-    Ok(())
-}
-
-fn verify_existing_file(file_path: &Path, file_name: &str, file_data: &DumpFileInfo, verbose: bool) -> Result<()> {
-    let file_metadata = fs::metadata(file_path).map_err(|e| {
-        Error::DumpFileAccessError(
-            file_path.to_owned(),
-            std::format!("Could not get file information: {e}"),
-        )
-    })?;
-    if let Some(expected_file_size) = &file_data.size {
-        if *expected_file_size != file_metadata.len() {
-            return Err(Error::DumpFileAccessError(
-                file_path.to_owned(),
-                std::format!(
-                    "Dump file size does not match the expected size. Expected: {}, actual: {}.",
-                    expected_file_size,
-                    file_metadata.len()
-                ),
-            ));
+        let file_len = fs::metadata(&target_file_path)
+            .map_err(|e| {
+                Error::DumpFileAccessError(
+                    target_file_path.clone(),
+                    std::format!("Could not get file information: {e}"),
+                )
+            })?
+            .len();
+        if let Some(expected_file_size) = file_data.size {
+            if expected_file_size != file_len {
+                return Err(Error::FileSizeMismatch(target_file_path, expected_file_size, file_len).into());
+            }
         }
-    }
-    match file_data.sha1.as_ref() {
-        Some(expected_sha1) => {
-            let mut file = fs::File::open(file_path).map_err(|e| {
-                Error::DumpFileAccessError(file_path.to_owned(), std::format!("Could not read mapping file: {e}"))
-            })?;
-            if verbose {
-                eprint!("Verifying {file_name}...");
-                std::io::stderr().flush().unwrap();
+        match file_data.checksum() {
+            Some((algorithm, expected_checksum)) => {
+                total_size += file_len;
+                files_to_verify.push(FileToVerify {
+                    path: target_file_path,
+                    name: file_name.to_owned(),
+                    algorithm,
+                    expected_checksum: expected_checksum.to_owned(),
+                });
             }
-            let start_time = Instant::now();
-            let mut hasher = Sha1::new();
-            let hashed_bytes = std::io::copy(&mut file, &mut hasher).map_err(|e| {
-                Error::DumpFileAccessError(file_path.to_owned(), std::format!("Could not read mapping file: {e}"))
-            })?;
-            let sha1_bytes = hasher.finalize();
-            let actual_sha1 = format!("{sha1_bytes:x}");
-            if expected_sha1 != &actual_sha1 {
-                return Err(Error::DumpFileAccessError(
-                    file_path.to_owned(),
-                    "SHA1 digest differs from the expected one.".to_owned(),
-                ));
-            };
-            if verbose {
-                eprintln!(
-                    "\rVerified {} - OK - {:.2} MiB in {:.2} seconds ({:.2} MiB/s)",
-                    file_name,
-                    hashed_bytes as f64 / 1024.0 / 1024.0,
-                    start_time.elapsed().as_secs_f64(),
-                    hashed_bytes as f64 / 1024.0 / 1024.0 / start_time.elapsed().as_secs_f64()
-                );
-            } else {
-                println!("Verified {} - OK.", &file_name);
+            None => {
+                eprintln!("WARNING: {file_name} cannot be checked due to missing checksum.");
             }
         }
-        None => {
-            eprintln!(
-                "WARNING: {} cannot be checked due to missing SHA1 checksum.",
-                &file_name
-            );
+    }
+
+    // hash files in parallel
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(concurrency.map_or(0, NonZeroUsize::get))
+        .build()?;
+    let (progress_send, mut progress_receive) = unbounded_channel::<VerifyProgress>();
+    let verification = spawn_blocking(move || {
+        pool.install(|| {
+            files_to_verify
+                .par_iter()
+                .try_for_each_with(progress_send, |progress_send, file| verify_file(file, progress_send))
+        })
+    });
+    pin!(verification);
+
+    let mut progress_update_interval = time::interval_at(
+        tokio::time::Instant::now() + tokio::time::Duration::from_secs(1),
+        time::Duration::from_secs(1),
+    );
+    let start_time = Instant::now();
+    let mut bytes_hashed = 0_u64;
+    let mut last_printed_progress_len = 0;
+    let mut verification_finished = false;
+    let mut progress_reporting_finished = false;
+    while !verification_finished || !progress_reporting_finished {
+        select! {
+            verification_res = &mut verification, if !verification_finished => {
+                verification_res??;
+                verification_finished = true;
+            }
+            _ = tokio::signal::ctrl_c() => {
+                return Err(Error::AbortedByUser().into());
+            }
+            verify_progress = progress_receive.recv(), if !progress_reporting_finished => {
+                match verify_progress {
+                    Some(VerifyProgress::BytesHashed(count)) => {
+                        bytes_hashed += count;
+                    }
+                    Some(VerifyProgress::FileVerified(file_name)) => {
+                        if show_progress {
+                            eprint!("\r{:1$}\r", "", last_printed_progress_len);
+                        }
+                        println!("Verified {file_name} - OK.");
+                    }
+                    None => {
+                        progress_reporting_finished = true;
+                    }
+                }
+            }
+            _ = progress_update_interval.tick() => {
+                if show_progress && total_size > 0 {
+                    let progress_string = std::format!(
+                        "\rVerifying - {} ({} %) of {} hashed ({}/s).",
+                        get_human_size(bytes_hashed),
+                        bytes_hashed * 100 / total_size,
+                        get_human_size(total_size),
+                        get_human_size((bytes_hashed as f64 / start_time.elapsed().as_secs_f64()) as u64)
+                    );
+                    let new_printed_progress_len = progress_string.chars().count();
+                    eprint!("{progress_string}{:1$}", "", last_printed_progress_len.saturating_sub(new_printed_progress_len));
+                    std::io::stderr().flush().unwrap();
+                    last_printed_progress_len = new_printed_progress_len;
+                }
+            }
         }
     }
+    if show_progress {
+        eprint!("\r{:1$}\r", "", last_printed_progress_len);
+        let total_mib = bytes_hashed as f64 / 1024.0 / 1024.0;
+        eprintln!(
+            "Verified {:.2} MiB in {:.2} seconds ({:.2} MiB/s).",
+            total_mib,
+            start_time.elapsed().as_secs_f64(),
+            total_mib / start_time.elapsed().as_secs_f64()
+        );
+    }
     Ok(())
 }
 
-fn get_target_file_name(file_name: &str, decompress: bool) -> &str {
-    if decompress {
-        file_name.strip_suffix(".bz2").unwrap_or(file_name)
-    } else {
-        file_name
+fn verify_file(file: &FileToVerify, progress_send: &mut UnboundedSender<VerifyProgress>) -> Result<()> {
+    let f = fs::File::open(&file.path)
+        .map_err(|e| Error::DumpFileAccessError(file.path.clone(), std::format!("Could not read dump file: {e}")))?;
+    let mut reader = HashingReader::new(f, file.algorithm);
+    let mut buf = vec![0; 4 * 1024 * 1024];
+    loop {
+        let read_len = reader.read(&mut buf).map_err(|e| {
+            Error::DumpFileAccessError(file.path.clone(), std::format!("Could not read dump file: {e}"))
+        })?;
+        if read_len == 0 {
+            break;
+        }
+        // receiver only goes away on abort
+        progress_send.send(VerifyProgress::BytesHashed(read_len as u64)).ok();
     }
+    if file.expected_checksum != reader.finalize_hex() {
+        return Err(Error::ChecksumMismatch(file.path.clone()).into());
+    };
+    progress_send.send(VerifyProgress::FileVerified(file.name.clone())).ok();
+    Ok(())
+}
+
+fn get_decompressed_file_name(file_name: &str) -> &str {
+    file_name.strip_suffix(".bz2").unwrap_or(file_name)
 }
 
 fn get_file_in_dir(directory: &Path, file_name: &str) -> PathBuf {
@@ -0,0 +1,103 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::crate_version;
+use wdgetlib::cache::{parse_dump_spec, DumpCache};
+use wdgetlib::{download_dump, get_latest_available_date, resolve_dump_tree, DownloadOptions};
+
+fn get_paths_and_total_size(paths: Vec<PathBuf>) -> Result<(Vec<String>, u64)> {
+    let mut dump_files = Vec::with_capacity(paths.len());
+    let mut total_size = 0;
+    for path in paths {
+        total_size += fs::metadata(&path)?.len();
+        dump_files.push(
+            path.into_os_string()
+                .into_string()
+                .map_err(|path| anyhow!("Cached dump file path {:?} is not valid UTF-8.", path))?,
+        );
+    }
+    Ok((dump_files, total_size))
+}
+
+/// Returns the files of a dump given as `wiki:date:type` if it has been completely downloaded to the cache,
+/// `latest` denotes the most recent dump in the cache.
+pub fn get_cached_dump(dump_spec: &str) -> Result<Option<(Vec<String>, u64)>> {
+    let (wiki, date_spec, dump_type) = match parse_dump_spec(dump_spec) {
+        Some(parts) => parts,
+        None => return Ok(None),
+    };
+    let (wiki, dump_type) = resolve_dump_tree(wiki, dump_type);
+    let cache = DumpCache::open_default()?;
+    let date = if date_spec == "latest" {
+        match cache.get_latest_date(&wiki, &dump_type)? {
+            Some(date) => date,
+            None => return Ok(None),
+        }
+    } else {
+        date_spec.to_owned()
+    };
+    cache
+        .get_dump_files(&wiki, &date, &dump_type)?
+        .map(get_paths_and_total_size)
+        .transpose()
+}
+
+/// Downloads the dump given as `wiki:date:type` into the cache unless it is already there and returns the paths
+/// of its files with their total size.
+pub fn fetch_dump(dump_spec: &str) -> Result<(Vec<String>, u64)> {
+    let (wiki, date_spec, dump_type) = parse_dump_spec(dump_spec)
+        .ok_or_else(|| anyhow!("Dump to fetch must be given as wiki:date:type, e.g. enwiki:latest:articlesdump."))?;
+    let (wiki, dump_type) = resolve_dump_tree(wiki, dump_type);
+    let cache = DumpCache::open_default()?;
+    if date_spec != "latest" {
+        if let Some(paths) = cache.get_dump_files(&wiki, date_spec, &dump_type)? {
+            return get_paths_and_total_size(paths);
+        }
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    runtime.block_on(async {
+        let client = reqwest::Client::builder()
+            .user_agent(concat!(
+                "wdgrep/",
+                crate_version!(),
+                " (https://github.com/Count-Count/wikidumptools)"
+            ))
+            .build()?;
+        let date = if date_spec == "latest" {
+            get_latest_available_date(&client, &wiki, Some(&dump_type)).await?
+        } else {
+            date_spec.to_owned()
+        };
+        if let Some(paths) = cache.get_dump_files(&wiki, &date, &dump_type)? {
+            return get_paths_and_total_size(paths);
+        }
+        let cache_directory = cache.create_dump_directory(&wiki, &date, &dump_type)?;
+        eprintln!(
+            "Fetching {wiki} {dump_type} dump from {date} into {}",
+            cache_directory.display()
+        );
+        // files left from an interrupted download are not downloaded again
+        download_dump(
+            &client,
+            &wiki,
+            &date,
+            &dump_type,
+            &cache_directory,
+            &DownloadOptions::default(),
+            None,
+        )
+        .await?;
+        cache.write_manifest(&wiki, &date, &dump_type)?;
+        let paths = cache
+            .get_dump_files(&wiki, &date, &dump_type)?
+            .ok_or_else(|| anyhow!("Fetched dump files changed while reading them."))?;
+        get_paths_and_total_size(paths)
+    })
+}
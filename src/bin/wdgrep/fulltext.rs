@@ -0,0 +1,138 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::path::Path;
+
+use simdutf8::basic::from_utf8;
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, INDEXED, STORED, STRING, TEXT};
+use tantivy::{Index, IndexWriter, TantivyDocument};
+use termcolor::Buffer;
+use wdgreplib::{Error, Result, RevisionInfo, RevisionProcessor};
+
+struct Fields {
+    page_id: Field,
+    namespace: Field,
+    title: Field,
+    revision_id: Field,
+    model: Field,
+    contributor: Field,
+    minor: Field,
+    text: Field,
+}
+
+impl Fields {
+    fn create_schema() -> Schema {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_u64_field("page_id", INDEXED | STORED);
+        schema_builder.add_i64_field("namespace", INDEXED | STORED);
+        schema_builder.add_text_field("title", TEXT | STORED);
+        schema_builder.add_u64_field("revision_id", INDEXED | STORED);
+        schema_builder.add_text_field("model", STRING | STORED);
+        // user id or IP address
+        schema_builder.add_text_field("contributor", STRING | STORED);
+        schema_builder.add_bool_field("minor", INDEXED | STORED);
+        // not stored to keep the index small, the text can be extracted from the dump
+        schema_builder.add_text_field("text", TEXT);
+        schema_builder.build()
+    }
+
+    fn from_schema(schema: &Schema) -> Result<Fields> {
+        Ok(Fields {
+            page_id: schema.get_field("page_id")?,
+            namespace: schema.get_field("namespace")?,
+            title: schema.get_field("title")?,
+            revision_id: schema.get_field("revision_id")?,
+            model: schema.get_field("model")?,
+            contributor: schema.get_field("contributor")?,
+            minor: schema.get_field("minor")?,
+            text: schema.get_field("text")?,
+        })
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(tag: &str, text: &str) -> Result<T> {
+    text.parse()
+        .map_err(|_| Error::InvalidNumberInTag(tag.to_owned(), text.to_owned()))
+}
+
+/// Adds each revision to a new tantivy full-text index.
+pub struct IndexBuilder {
+    writer: IndexWriter,
+    fields: Fields,
+}
+
+impl IndexBuilder {
+    /// Creates the index directory if necessary, it must not contain an index yet.
+    pub fn create<P: AsRef<Path>>(index_directory: P, memory_budget: usize) -> Result<IndexBuilder> {
+        let index_directory = index_directory.as_ref();
+        std::fs::create_dir_all(index_directory)?;
+        let index = Index::create_in_dir(index_directory, Fields::create_schema())?;
+        Ok(IndexBuilder {
+            writer: index.writer(memory_budget)?,
+            fields: Fields::from_schema(&index.schema())?,
+        })
+    }
+
+    /// Commits the documents and waits for the merges to finish.
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.commit()?;
+        self.writer.wait_merging_threads()?;
+        Ok(())
+    }
+}
+
+impl RevisionProcessor for IndexBuilder {
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], _output: &mut Buffer) -> Result<bool> {
+        let fields = &self.fields;
+        let mut document = TantivyDocument::default();
+        document.add_u64(fields.page_id, parse_number("id", revision.page_id)?);
+        document.add_i64(fields.namespace, parse_number("ns", revision.namespace)?);
+        document.add_text(fields.title, revision.title);
+        document.add_u64(fields.revision_id, parse_number("id", revision.revision_id)?);
+        document.add_text(fields.model, revision.model);
+        if let Some(contributor) = revision.contributor_id.or(revision.contributor_ip) {
+            document.add_text(fields.contributor, contributor);
+        }
+        document.add_bool(fields.minor, revision.minor);
+        document.add_text(fields.text, from_utf8(text)?);
+        self.writer.add_document(document)?;
+        Ok(false)
+    }
+}
+
+pub struct IndexHit {
+    pub score: f32,
+    pub title: String,
+    pub revision_id: u64,
+}
+
+/// Runs a query in the tantivy query syntax against the title and text of the revisions, other fields can be
+/// queried with their name, e.g. `namespace:0 AND text:"foo bar"`. Returns the best hits first.
+pub fn search_index<P: AsRef<Path>>(index_directory: P, query: &str, limit: usize) -> Result<Vec<IndexHit>> {
+    let index = Index::open_in_dir(index_directory)?;
+    let fields = Fields::from_schema(&index.schema())?;
+    let searcher = index.reader()?.searcher();
+    let query = QueryParser::for_index(&index, vec![fields.title, fields.text]).parse_query(query)?;
+    let mut hits = Vec::new();
+    for (score, address) in searcher.search(&query, &TopDocs::with_limit(limit))? {
+        let document: TantivyDocument = searcher.doc(address)?;
+        hits.push(IndexHit {
+            score,
+            title: document
+                .get_first(fields.title)
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_owned(),
+            revision_id: document
+                .get_first(fields.revision_id)
+                .and_then(|v| v.as_u64())
+                .unwrap_or_default(),
+        });
+    }
+    Ok(hits)
+}
@@ -4,16 +4,49 @@
 //
 // Distributed under the terms of the MIT license.
 
-mod lib;
+mod fetch;
+mod fulltext;
+mod parquet_export;
+mod remote;
+mod reverts;
+mod sqlite;
+mod stats;
+mod terms;
+mod wiki_export;
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::Write;
 use std::num::NonZeroUsize;
-use std::process;
-use std::time::Instant;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+use std::{process, thread};
 
-use clap::{crate_authors, crate_version, Arg, ArgAction, Command};
-use lib::{get_dump_files, search_dump, SearchDumpResult, SearchOptions};
+use clap::{crate_authors, crate_version, value_parser, Arg, ArgAction, ArgMatches, Command};
+use clap_complete::{generate, Shell};
+use fulltext::IndexBuilder;
+use parquet_export::ParquetExport;
+use regex::Regex;
+use reverts::{RevertDetector, REVERTS_HEADER};
+use sqlite::SqliteExport;
+use stats::{PageStatistics, PAGE_STATISTICS_HEADER};
+use tabwriter::TabWriter;
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use terms::{TermFrequencies, TERM_FREQUENCIES_HEADER};
+use wdgreplib::{
+    check_pattern, expand_shortcuts, get_dump_files, index, is_bzip2_decompression_slow, plan_search, process_dump,
+    search_dump, siteinfo, trigram, CirrusSearchField, Engine, FileSplit, HistogramKey, MatchHistogram, MatchSink,
+    Namespaces, OutputLimit, PageList, PageTimeLimitAction, ResultRecorder, ResultSet, SearchCheckpoint,
+    SearchDumpResult, SearchField, SearchOptions, SearchPlan, SearchProgress, Sha1Verifier,
+};
+use wiki_export::WikiTextExport;
+
+#[cfg(not(feature = "pcre2"))]
+const ENGINES: [&str; 3] = ["auto", "regex", "aho-corasick"];
+#[cfg(feature = "pcre2")]
+const ENGINES: [&str; 4] = ["auto", "regex", "aho-corasick", "pcre2"];
 
 #[global_allocator]
 static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
@@ -24,28 +57,1160 @@ fn exit_with_error(stderr: &mut StandardStream, msg: &str) -> ! {
     stderr.reset().unwrap();
     process::exit(1);
 }
-fn main() {
-    let matches = Command::new("WikiDumpGrep")
+
+/// Arguments shared by all commands reading dump files.
+fn dump_args() -> [Arg; 25] {
+    [
+        Arg::new("namespaces")
+            .long("ns")
+            .value_delimiter(',')
+            .help("Restrict search to those namespaces (comma-separated list of numeric namespaces)"),
+        Arg::new("models")
+            .long("model")
+            .value_delimiter(',')
+            .value_name("models")
+            .help("Restrict search to revisions with those content models (comma-separated list, e.g. wikitext)"),
+        Arg::new("content-formats")
+            .long("content-format")
+            .value_delimiter(',')
+            .value_name("formats")
+            .help("Restrict search to revisions with those content formats (comma-separated list, e.g. text/x-wiki)"),
+        Arg::new("skip-minor")
+            .long("skip-minor")
+            .help("Skip revisions marked as minor edits")
+            .action(ArgAction::SetTrue),
+        Arg::new("only-ip-edits")
+            .long("only-ip-edits")
+            .help("Only search revisions made by unregistered users")
+            .action(ArgAction::SetTrue),
+        Arg::new("verbose")
+            .short('v')
+            .long("verbose")
+            .help("Print performance statistics")
+            .action(ArgAction::SetTrue),
+        Arg::new("threads")
+            .short('j')
+            .long("threads")
+            .value_name("num")
+            .visible_alias("cpu-parallelism")
+            .help("Number of parallel threads to use. The default is the number of logical cpus."),
+        Arg::new("io-parallelism")
+            .long("io-parallelism")
+            .value_name("num")
+            .help(
+                "Number of dump files read at once, defaults to one uncompressed file per device for spinning disks, \
+             unlimited otherwise.",
+            ),
+        Arg::new("memory-budget")
+            .long("memory-budget")
+            .value_name("MiB")
+            .value_parser(value_parser!(u64).range(1..))
+            .help(
+                "Limit the memory used by all threads together for revision texts of at least 1 MiB, threads wait \
+                 while other threads read such texts and giant pages are read one at a time",
+            ),
+        Arg::new("chunk-size")
+            .long("chunk-size")
+            .value_name("MiB")
+            .value_parser(value_parser!(u64).range(1..))
+            .conflicts_with_all(["min-chunk-size", "max-chunk-size"])
+            .help(
+                "Size of the slices plain dump files are split into to search them in parallel, by default the total \
+                 size of the files divided by the number of threads within --min-chunk-size and --max-chunk-size",
+            ),
+        Arg::new("min-chunk-size")
+            .long("min-chunk-size")
+            .value_name("MiB")
+            .value_parser(value_parser!(u64).range(1..))
+            .default_value("16")
+            .help("Minimum size of the slices plain dump files are split into"),
+        Arg::new("max-chunk-size")
+            .long("max-chunk-size")
+            .value_name("MiB")
+            .value_parser(value_parser!(u64).range(1..))
+            .default_value("500")
+            .help("Maximum size of the slices plain dump files are split into"),
+        Arg::new("7z-binary")
+            .long("7z-binary")
+            .value_name("path")
+            .help("Binary for extracting text from .7z files, defaults to the first of 7z, 7za and 7zz found."),
+        Arg::new("7z-options")
+            .long("7z-options")
+            .value_name("options")
+            .help("Options passed to 7z binary for extracting text from .7z files to stdout, defaults to \"e -so\"."),
+        Arg::new("bzcat-binary").long("bzcat-binary").value_name("path").help(
+            "Binary for extracting text from .bz2 files, defaults to lbzip2/pbzip2, internal decompression or bzcat.",
+        ),
+        Arg::new("bzcat-options")
+            .long("bzcat-options")
+            .value_name("options")
+            .help(
+                "Options passed to bzcat binary for extracting text from .bz2 files, defaults to no options for bzcat.",
+            ),
+        Arg::new("cirrussearch-field")
+            .long("cirrussearch-field")
+            .value_parser(["text", "source_text"])
+            .default_value("text")
+            .value_name("field")
+            .help("Field of CirrusSearch (.json) dumps to read: rendered text or wikitext source"),
+        Arg::new("fetch").long("fetch").value_name("wiki:date:type").help(
+            "Download the dump (e.g. enwiki:latest:articlesdump) into the cache directory if the dump file or \
+                 prefix is not given or not found and search the downloaded files",
+        ),
+        Arg::new("dedup")
+            .long("dedup")
+            .help("Process each revision only once if it is found in several dump files (needs memory per revision)")
+            .action(ArgAction::SetTrue),
+        Arg::new("title-filter").long("title-filter").value_name("regex").help(
+            "Only read pages with titles matching the regular expression, for dumps with an index only the \
+                 parts of the dump containing matching pages are decompressed",
+        ),
+        Arg::new("titles-file").long("titles-file").value_name("file").help(
+            "Only read the pages listed in the file, one title per line or, if all lines are numbers, one page id \
+                 per line, e.g. to refine the results of an earlier search. Titles are normalized like on the wiki, \
+                 e.g. talk:Some_page matches Talk:Some page",
+        ),
+        Arg::new("and")
+            .long("and")
+            .value_name("result set")
+            .action(ArgAction::Append)
+            .help("Only read the pages in the result set saved by an earlier search with --save-results (repeatable)"),
+        Arg::new("not")
+            .long("not")
+            .value_name("result set")
+            .action(ArgAction::Append)
+            .help("Skip the pages in the result set saved by an earlier search with --save-results (repeatable)"),
+        Arg::new("pageid-range")
+            .long("pageid-range")
+            .value_name("first-last")
+            .help(
+                "Only read the pages whose id is in the range, e.g. 1000000-2000000, dump files named by other page \
+                 id ranges are skipped",
+            ),
+        Arg::new("ordered-output")
+            .long("ordered-output")
+            .help(
+                "Print the output in the order of the dump files instead of as soon as it is found \
+                 (needs memory for the output of parts searched in parallel)",
+            )
+            .action(ArgAction::SetTrue),
+    ]
+}
+
+/// Parses a page id range like `1000000-2000000`, both ids are included.
+fn parse_page_id_range(range: &str) -> Option<RangeInclusive<u64>> {
+    let (first, last) = range.split_once('-')?;
+    let (first, last) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+    (first <= last).then_some(first..=last)
+}
+
+fn read_result_set_page_ids(file: &str, stderr: &mut StandardStream) -> HashSet<u64> {
+    ResultSet::read(Path::new(file))
+        .unwrap_or_else(|err| {
+            exit_with_error(stderr, format!("Could not read result set {file}: {err}").as_str());
+        })
+        .page_ids()
+}
+
+/// Values of the shared dump arguments which need to outlive `SearchOptions`.
+struct DumpArgs<'a> {
+    namespaces: Option<Vec<&'a str>>,
+    models: Option<Vec<&'a str>>,
+    content_formats: Option<Vec<&'a str>>,
+    title_filter: Option<Regex>,
+    page_list: Option<PageList>,
+    restrict_page_ids: Option<HashSet<u64>>,
+    exclude_page_ids: Option<HashSet<u64>>,
+    options_7z: Option<Vec<&'a str>>,
+    options_bzcat: Option<Vec<&'a str>>,
+}
+
+impl<'a> DumpArgs<'a> {
+    fn from_matches(matches: &'a ArgMatches, dump_files: &[String], stderr: &mut StandardStream) -> DumpArgs<'a> {
+        DumpArgs {
+            namespaces: matches
+                .get_many::<String>("namespaces")
+                .map(|val| val.map(|s| str::trim(s)).filter(|x| !x.is_empty()).collect()),
+            models: matches
+                .get_many::<String>("models")
+                .map(|val| val.map(|s| str::trim(s)).filter(|x| !x.is_empty()).collect()),
+            content_formats: matches
+                .get_many::<String>("content-formats")
+                .map(|val| val.map(|s| str::trim(s)).filter(|x| !x.is_empty()).collect()),
+            title_filter: matches
+                .get_one::<String>("title-filter")
+                .map(|title_filter| Regex::new(title_filter))
+                .transpose()
+                .unwrap_or_else(|err| {
+                    exit_with_error(stderr, format!("Invalid title filter: {err}").as_str());
+                }),
+            page_list: matches
+                .get_one::<String>("titles-file")
+                .map(|titles_file| PageList::from_file(Path::new(titles_file), &get_namespaces(dump_files)))
+                .transpose()
+                .unwrap_or_else(|err| {
+                    exit_with_error(stderr, format!("Could not read titles file: {err}").as_str());
+                }),
+            restrict_page_ids: matches.get_many::<String>("and").map(|result_set_files| {
+                let mut page_id_sets = result_set_files.map(|file| read_result_set_page_ids(file, stderr));
+                // UNWRAP: the argument has at least one value
+                let first = page_id_sets.next().unwrap();
+                page_id_sets.fold(first, |intersection, page_ids| &intersection & &page_ids)
+            }),
+            exclude_page_ids: matches.get_many::<String>("not").map(|result_set_files| {
+                result_set_files
+                    .flat_map(|file| read_result_set_page_ids(file, stderr))
+                    .collect()
+            }),
+            options_7z: matches
+                .get_one::<String>("7z-options")
+                .map(|s| s.split(' ').collect::<Vec<_>>()),
+            options_bzcat: matches
+                .get_one::<String>("bzcat-options")
+                .map(|s| s.split(' ').collect::<Vec<_>>()),
+        }
+    }
+
+    fn configure(
+        &'a self,
+        matches: &'a ArgMatches,
+        dump_files: &[String],
+        search_options: &mut SearchOptions<'a>,
+        stderr: &mut StandardStream,
+    ) {
+        if let Some(namespaces) = self.namespaces.as_deref() {
+            check_namespaces_or_exit(dump_files, namespaces, stderr);
+            search_options.restrict_namespaces(namespaces);
+        }
+        self.models
+            .as_deref()
+            .map(|models| search_options.restrict_models(models));
+        self.content_formats
+            .as_deref()
+            .map(|formats| search_options.restrict_formats(formats));
+        if let Some(title_filter) = &self.title_filter {
+            search_options.with_title_filter(title_filter);
+        }
+        if let Some(page_list) = &self.page_list {
+            search_options.with_page_list(page_list);
+        }
+        if let Some(page_ids) = &self.restrict_page_ids {
+            search_options.with_restrict_page_ids(page_ids);
+        }
+        if let Some(page_ids) = &self.exclude_page_ids {
+            search_options.with_exclude_page_ids(page_ids);
+        }
+        if let Some(range) = matches.get_one::<String>("pageid-range") {
+            let range = parse_page_id_range(range).unwrap_or_else(|| {
+                exit_with_error(stderr, format!("Invalid page id range: {range}").as_str());
+            });
+            search_options.with_page_id_range(range);
+        }
+
+        matches
+            .get_one::<String>("threads")
+            .map(|s| str::parse::<NonZeroUsize>(s))
+            .transpose()
+            .unwrap_or_else(|_err| {
+                exit_with_error(stderr, "Invalid number specified for thread count");
+            })
+            .map(|thread_count| search_options.with_thread_count(thread_count));
+
+        matches
+            .get_one::<String>("io-parallelism")
+            .map(|s| str::parse::<NonZeroUsize>(s))
+            .transpose()
+            .unwrap_or_else(|_err| {
+                exit_with_error(stderr, "Invalid number specified for I/O parallelism");
+            })
+            .map(|io_parallelism| search_options.with_io_parallelism(io_parallelism));
+
+        if let Some(&memory_budget) = matches.get_one::<u64>("memory-budget") {
+            search_options.with_memory_budget(memory_budget * 1024 * 1024);
+        }
+
+        if let Some(&chunk_size) = matches.get_one::<u64>("chunk-size") {
+            search_options.with_chunk_size(chunk_size * 1024 * 1024);
+        }
+        let min_chunk_size = *matches.get_one::<u64>("min-chunk-size").unwrap();
+        let max_chunk_size = *matches.get_one::<u64>("max-chunk-size").unwrap();
+        if min_chunk_size > max_chunk_size {
+            exit_with_error(stderr, "The minimum chunk size is larger than the maximum chunk size.");
+        }
+        search_options.with_chunk_size_bounds(min_chunk_size * 1024 * 1024, max_chunk_size * 1024 * 1024);
+
+        matches
+            .get_one::<String>("7z-binary")
+            .map(|binary| search_options.with_binary_7z(binary));
+        if let Some(options) = self.options_7z.as_ref() {
+            search_options.with_options_7z(options);
+        }
+
+        matches
+            .get_one::<String>("bzcat-binary")
+            .map(|binary| search_options.with_binary_bzcat(binary));
+        if let Some(options) = self.options_bzcat.as_ref() {
+            search_options.with_options_bzcat(options);
+        }
+        if is_bzip2_decompression_slow(dump_files, search_options) {
+            stderr.set_color(ColorSpec::new().set_fg(Some(Color::Yellow))).unwrap();
+            writeln!(
+                stderr,
+                "Warning: Searching compressed .bz2 files is slow without lbzip2 or pbzip2, \
+                 use .7z files, seekable .zst files (see wdump recompress) or uncompressed files instead."
+            )
+            .unwrap();
+            stderr.reset().unwrap();
+        }
+
+        search_options.deduplicate(matches.get_flag("dedup"));
+        search_options.ordered_output(matches.get_flag("ordered-output"));
+        search_options.skip_minor(matches.get_flag("skip-minor"));
+        search_options.only_ip_edits(matches.get_flag("only-ip-edits"));
+
+        search_options.with_cirrussearch_field(
+            match matches.get_one::<String>("cirrussearch-field").unwrap().as_str() {
+                "text" => CirrusSearchField::Text,
+                "source_text" => CirrusSearchField::SourceText,
+                _ => unreachable!(),
+            },
+        );
+    }
+}
+
+/// Returns the namespaces from the siteinfo of the first dump file which has one, the canonical English namespaces
+/// otherwise.
+fn get_namespaces(dump_files: &[String]) -> Namespaces {
+    dump_files
+        .iter()
+        .find_map(|dump_file| siteinfo::parse_dump_header(dump_file).ok())
+        .map_or_else(Namespaces::default, |dump_header| {
+            Namespaces::from_dump_header(&dump_header)
+        })
+}
+
+/// Exits if a requested namespace is not listed in the siteinfo of any of the dump files, since nothing would
+/// match silently otherwise.
+fn check_namespaces_or_exit(dump_files: &[String], namespaces: &[&str], stderr: &mut StandardStream) {
+    let mut known_namespaces = BTreeMap::new();
+    for dump_file in dump_files {
+        // CirrusSearch dumps have no siteinfo, other errors are reported by the search
+        if let Ok(dump_header) = siteinfo::parse_dump_header(dump_file) {
+            known_namespaces.extend(dump_header.namespaces);
+        }
+    }
+    if known_namespaces.is_empty() {
+        return;
+    }
+    let unknown_namespaces: Vec<&str> = namespaces
+        .iter()
+        .copied()
+        .filter(|namespace| !known_namespaces.keys().any(|key| key.to_string() == *namespace))
+        .collect();
+    if !unknown_namespaces.is_empty() {
+        let valid_namespaces = known_namespaces
+            .iter()
+            .map(|(key, name)| match name.as_str() {
+                "" => format!("{key} (main)"),
+                name => format!("{key} ({name})"),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        exit_with_error(
+            stderr,
+            format!(
+                "Namespace(s) {} not found in dump, valid namespaces are: {valid_namespaces}",
+                unknown_namespaces.join(", ")
+            )
+            .as_str(),
+        );
+    }
+}
+
+fn get_dump_files_or_exit(matches: &ArgMatches, stderr: &mut StandardStream) -> (Vec<String>, u64) {
+    let dump_file_or_prefix = matches.get_one::<String>("dump file or prefix");
+    if dump_file_or_prefix.is_some_and(String::is_empty) {
+        exit_with_error(stderr, "Non-empty dump file (prefix) needs to be specified.");
+    }
+
+    let local_dump_files = dump_file_or_prefix.map(|dump_file_or_prefix| get_dump_files(dump_file_or_prefix));
+    let (dump_files, total_size) = match local_dump_files {
+        Some(Ok((dump_files, total_size))) if !dump_files.is_empty() => (dump_files, total_size),
+        local_dump_files => {
+            // the dump may also be given as wiki:date:type if it has been downloaded to the cache
+            let cached_dump_files = dump_file_or_prefix
+                .map(|dump_spec| fetch::get_cached_dump(dump_spec))
+                .transpose()
+                .unwrap_or_else(|err| {
+                    exit_with_error(stderr, format!("Error while reading dump cache: {err}").as_str());
+                })
+                .flatten();
+            match (cached_dump_files, matches.get_one::<String>("fetch"), local_dump_files) {
+                (Some(dump_files_and_size), _, _) => dump_files_and_size,
+                (None, Some(dump_spec), _) => fetch::fetch_dump(dump_spec).unwrap_or_else(|err| {
+                    exit_with_error(stderr, format!("Error while fetching dump: {err}").as_str());
+                }),
+                (None, None, Some(Ok(dump_files_and_size))) => dump_files_and_size,
+                (None, None, Some(Err(err))) => exit_with_error(stderr, format!("{err}").as_str()),
+                (None, None, None) => unreachable!("Dump file or prefix required by arg matching without --fetch."),
+            }
+        }
+    };
+    (dump_files, total_size)
+}
+
+/// Prints a line whenever another percent of a compressed dump file has been read, or another 100 MiB have been
+/// decompressed if the compressed size or the number of compressed bytes read is unknown.
+fn print_progress(progress_receive: Receiver<SearchProgress>, file_sizes: HashMap<String, u64>) {
+    let mut reported = HashMap::new();
+    for progress in progress_receive {
+        let (file_name, compressed_bytes_read, bytes_read, finished) = match progress {
+            SearchProgress::BytesRead(file_name, compressed, decompressed) => {
+                (file_name, compressed, decompressed, false)
+            }
+            SearchProgress::Finished(file_name, compressed, decompressed) => {
+                (file_name, compressed, decompressed, true)
+            }
+        };
+        let mib_read = bytes_read / 1024 / 1024;
+        let (step, description) = match (compressed_bytes_read, file_sizes.get(&file_name)) {
+            (Some(compressed_bytes_read), Some(&file_size)) if file_size > 0 => {
+                let percent = compressed_bytes_read * 100 / file_size;
+                (percent, format!("{percent}% ({mib_read} MiB decompressed)"))
+            }
+            _ => (mib_read / 100, format!("{mib_read} MiB decompressed")),
+        };
+        if finished {
+            eprintln!("{file_name}: finished, {mib_read} MiB decompressed");
+        } else if reported.insert(file_name.clone(), step) != Some(step) {
+            eprintln!("{file_name}: {description}");
+        }
+    }
+}
+
+fn print_match_histogram(stderr: &mut StandardStream, match_histogram: &MatchHistogram) {
+    const MAX_BAR_LEN: u64 = 50;
+    let buckets = match_histogram.buckets();
+    let max_count = buckets.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    let label_len = buckets.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let count_len = max_count.to_string().len();
+    let mut number_hl_color = ColorSpec::new();
+    number_hl_color.set_fg(Some(Color::Yellow));
+
+    match match_histogram.key() {
+        HistogramKey::TextSize => writeln!(stderr, "Matching revisions by text size:").unwrap(),
+        HistogramKey::Namespace => writeln!(stderr, "Matching revisions by namespace:").unwrap(),
+    }
+    for (label, count) in buckets {
+        write!(stderr, "{label:>label_len$}  ").unwrap();
+        stderr.set_color(&number_hl_color).unwrap();
+        write!(stderr, "{count:>count_len$}").unwrap();
+        stderr.reset().unwrap();
+        // rounded up so that buckets with matches are visible
+        let bar_len = (count * MAX_BAR_LEN).div_ceil(max_count.max(1));
+        if bar_len > 0 {
+            write!(stderr, "  {}", "#".repeat(bar_len as usize)).unwrap();
+        }
+        writeln!(stderr).unwrap();
+    }
+}
+
+fn print_performance_statistics(
+    stderr: &mut StandardStream,
+    search_dump_result: &SearchDumpResult,
+    total_size: u64,
+    start: Instant,
+) {
+    let elapsed_seconds = start.elapsed().as_secs_f64();
+    let mib_read = total_size as f64 / 1024.0 / 1024.0;
+    let mib_read_uncompressed = search_dump_result.bytes_processed as f64 / 1024.0 / 1024.0;
+    let mut number_hl_color = ColorSpec::new();
+    number_hl_color.set_fg(Some(Color::Yellow));
+
+    stderr.reset().unwrap();
+    if search_dump_result.compressed_files_found {
+        write!(stderr, "Searched ").unwrap();
+        stderr.set_color(&number_hl_color).unwrap();
+        write!(stderr, "{mib_read:.2}").unwrap();
+        stderr.reset().unwrap();
+        write!(stderr, " MiB compressed, ").unwrap();
+        stderr.set_color(&number_hl_color).unwrap();
+        write!(stderr, "{mib_read_uncompressed:.2}").unwrap();
+        stderr.reset().unwrap();
+        write!(stderr, " MiB uncompressed in ").unwrap();
+        stderr.set_color(&number_hl_color).unwrap();
+        write!(stderr, "{elapsed_seconds:.2}").unwrap();
+        stderr.reset().unwrap();
+        write!(stderr, " seconds (").unwrap();
+        stderr.set_color(&number_hl_color).unwrap();
+        write!(stderr, "{:.2}", mib_read / elapsed_seconds).unwrap();
+        stderr.reset().unwrap();
+        write!(stderr, " MiB/s compressed, ").unwrap();
+        stderr.set_color(&number_hl_color).unwrap();
+        write!(stderr, "{:.2}", mib_read_uncompressed / elapsed_seconds).unwrap();
+        stderr.reset().unwrap();
+        writeln!(stderr, " MiB/s uncompressed).").unwrap();
+    } else {
+        write!(stderr, "Searched ").unwrap();
+        stderr.set_color(&number_hl_color).unwrap();
+        write!(stderr, "{mib_read:.2}").unwrap();
+        stderr.reset().unwrap();
+        write!(stderr, " MiB in ").unwrap();
+        stderr.set_color(&number_hl_color).unwrap();
+        write!(stderr, "{elapsed_seconds:.2}").unwrap();
+        stderr.reset().unwrap();
+        write!(stderr, " seconds (").unwrap();
+        stderr.set_color(&number_hl_color).unwrap();
+        write!(stderr, "{:.2}", mib_read / elapsed_seconds).unwrap();
+        stderr.reset().unwrap();
+        writeln!(stderr, " MiB/s).").unwrap();
+    }
+    if search_dump_result.truncated {
+        writeln!(stderr, "Search was truncated at the timeout.").unwrap();
+    }
+    if search_dump_result.rotational_storage {
+        writeln!(
+            stderr,
+            "Dump files are on rotational storage, concurrent reads of uncompressed files were limited."
+        )
+        .unwrap();
+    }
+}
+
+fn print_search_plan(search_plan: &SearchPlan) {
+    let mib = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+    for file in &search_plan.files {
+        let split = match file.split {
+            FileSplit::Skipped => "skipped, outside of the page id range".to_owned(),
+            FileSplit::Whole => "decompressed and searched as a whole".to_owned(),
+            FileSplit::Slices { parts, slice_size } => format!("{parts} slices of {:.2} MiB", mib(slice_size)),
+            FileSplit::Ranges { parts } => format!("{parts} indexed ranges"),
+        };
+        println!("{} ({:.2} MiB): {split}", file.dump_file, mib(file.size));
+    }
+    for (extension, decompressor) in &search_plan.decompressors {
+        println!("Decompressor for {extension}: {decompressor}");
+    }
+    println!("Threads: {}", search_plan.thread_count);
+    match search_plan.chunk_size_bounds {
+        Some((min, max)) => println!(
+            "Chunk size: {:.2} MiB ({:.2} MiB of plain files / {} threads, bounded to {:.2}-{:.2} MiB)",
+            mib(search_plan.chunk_size),
+            mib(search_plan.sliced_size),
+            search_plan.thread_count,
+            mib(min),
+            mib(max)
+        ),
+        None => println!(
+            "Chunk size: {:.2} MiB (set with --chunk-size)",
+            mib(search_plan.chunk_size)
+        ),
+    }
+    if let Some(io_parallelism) = search_plan.io_parallelism {
+        if search_plan.io_parallelism_uncompressed_only {
+            println!("Uncompressed files read at the same time: {io_parallelism}");
+        } else {
+            println!("Files read at the same time: {io_parallelism}");
+        }
+    }
+    if search_plan.rotational_storage {
+        println!("Dump files are on rotational storage.");
+    }
+}
+
+fn search(matches: &ArgMatches, color_choice: ColorChoice, stderr: &mut StandardStream) {
+    let search_term = matches.get_one::<String>("search term").unwrap();
+    let search_term = if matches.get_flag("no-shortcuts") {
+        Cow::Borrowed(search_term.as_str())
+    } else {
+        expand_shortcuts(search_term).unwrap_or_else(|err| exit_with_error(stderr, &err.to_string()))
+    };
+    let search_term = search_term.as_ref();
+    let pattern_warnings = check_pattern(search_term);
+    if !pattern_warnings.is_empty() {
+        if matches.get_flag("strict") {
+            let msg = pattern_warnings
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n");
+            exit_with_error(stderr, &msg);
+        }
+        stderr.set_color(ColorSpec::new().set_fg(Some(Color::Yellow))).unwrap();
+        for warning in &pattern_warnings {
+            writeln!(stderr, "Warning: {warning}").unwrap();
+        }
+        stderr.reset().unwrap();
+    }
+    // URLs are always streamed, dumps given as wiki:date:type only with --stream
+    let remote_urls = match matches.get_one::<String>("dump file or prefix") {
+        Some(dump) if remote::is_url(dump) => Some(vec![dump.clone()]),
+        Some(dump) if matches.get_flag("stream") => Some(remote::get_dump_urls(dump).unwrap_or_else(|err| {
+            exit_with_error(stderr, format!("Error while retrieving dump files: {err}").as_str());
+        })),
+        _ => None,
+    };
+    let (dump_files, total_size) = match remote_urls {
+        Some(_) => (Vec::new(), 0),
+        None => get_dump_files_or_exit(matches, stderr),
+    };
+
+    let (progress_send, progress_receive) = channel();
+    let progress_printer = matches.get_flag("progress").then(|| {
+        let file_sizes = dump_files
+            .iter()
+            .filter_map(|dump_file| Some((dump_file.clone(), std::fs::metadata(dump_file).ok()?.len())))
+            .collect();
+        thread::spawn(move || print_progress(progress_receive, file_sizes))
+    });
+
+    let mut search_options = SearchOptions::new();
+    search_options.with_color_choice(color_choice);
+    let dump_args = DumpArgs::from_matches(matches, &dump_files, stderr);
+    dump_args.configure(matches, &dump_files, &mut search_options, stderr);
+    if progress_printer.is_some() {
+        search_options.with_progress_sender(&progress_send);
+    }
+
+    search_options.only_print_title(matches.get_flag("revisions-with-matches"));
+    if let Some(replacement) = matches.get_one::<String>("replace") {
+        search_options.with_replacement(replacement);
+    }
+    search_options.print_title_prefix(matches.get_flag("with-title"));
+    search_options.count_unique(matches.get_flag("count-unique"));
+    search_options.strip_markup(matches.get_flag("strip-markup"));
+    if matches.get_one::<String>("field").unwrap() == "comment" {
+        if dump_files
+            .iter()
+            .any(|dump_file| wdgreplib::is_cirrussearch_dump(dump_file))
+        {
+            exit_with_error(stderr, "CirrusSearch dumps do not contain edit summaries.");
+        }
+        if matches.get_flag("verify-sha1") {
+            exit_with_error(stderr, "Revision texts are not read when searching edit summaries.");
+        }
+        search_options.with_search_field(SearchField::Comment);
+    }
+    search_options.group_by_page(matches.get_one::<String>("group-by").is_some());
+    search_options.prefilter(!matches.get_flag("no-prefilter"));
+    search_options.count_deleted(matches.get_flag("count-deleted"));
+    search_options.report_deleted(matches.get_flag("report-deleted"));
+    search_options.keep_going(matches.get_flag("keep-going"));
+    search_options.with_engine(match matches.get_one::<String>("engine").unwrap().as_str() {
+        "auto" => Engine::Auto,
+        "regex" => Engine::Regex,
+        "aho-corasick" => Engine::AhoCorasick,
+        #[cfg(feature = "pcre2")]
+        "pcre2" => Engine::Pcre2,
+        _ => unreachable!(),
+    });
+
+    let format = matches.get_one::<String>("format").unwrap().as_str();
+    let output = matches.get_one::<String>("output");
+    if format != "text" && matches.contains_id("group-by") {
+        exit_with_error(stderr, "Matches can only be grouped with the text output format.");
+    }
+    if format != "text" && matches.contains_id("split-output-by-ns") {
+        exit_with_error(
+            stderr,
+            "The output can only be split by namespace with the text output format.",
+        );
+    }
+    if let Some(directory) = matches.get_one::<String>("split-output-by-ns") {
+        search_options.split_output_by_namespace(Path::new(directory));
+    }
+    if let Some(&count) = matches.get_one::<u64>("head") {
+        search_options.limit_output(OutputLimit::Head(count));
+    }
+    if let Some(&count) = matches.get_one::<u64>("tail") {
+        search_options.limit_output(OutputLimit::Tail(count));
+    }
+    // compressed output in a terminal is of no use
+    search_options.compress_output(matches.get_flag("compress-output") && !atty::is(atty::Stream::Stdout));
+    let dump_matches = matches.get_one::<String>("dump-matches");
+    if dump_matches.is_some() && (format != "text" || matches.contains_id("group-by")) {
+        exit_with_error(
+            stderr,
+            "Matching revisions can only be dumped with the text output format and without grouping.",
+        );
+    }
+    if format != "text" && output.is_none() {
+        exit_with_error(
+            stderr,
+            "An output file or directory needs to be specified with --output.",
+        );
+    }
+    let include_text = matches.get_flag("include-text");
+    let match_sink: Option<Box<dyn MatchSink>> = match format {
+        "text" => dump_matches.map(|directory| -> Box<dyn MatchSink> {
+            Box::new(WikiTextExport::create(directory).unwrap_or_else(|err| {
+                exit_with_error(stderr, format!("Could not create output directory: {err}").as_str());
+            }))
+        }),
+        "sqlite" => Some(Box::new(
+            SqliteExport::create(output.unwrap(), include_text).unwrap_or_else(|err| {
+                exit_with_error(stderr, format!("Could not create SQLite database: {err}").as_str());
+            }),
+        )),
+        "parquet" => Some(Box::new(
+            ParquetExport::create(output.unwrap(), include_text).unwrap_or_else(|err| {
+                exit_with_error(stderr, format!("Could not create output directory: {err}").as_str());
+            }),
+        )),
+        _ => unreachable!(),
+    };
+    let match_sink = match_sink.as_deref();
+    if let Some(match_sink) = match_sink {
+        search_options.with_match_sink(match_sink);
+    }
+
+    let result_recorder = matches.contains_id("save-results").then(ResultRecorder::new);
+    if let Some(result_recorder) = &result_recorder {
+        search_options.with_result_recorder(result_recorder);
+    }
+    let checkpoint_file = matches.get_one::<String>("checkpoint").map(Path::new);
+    if checkpoint_file.is_some() && (format != "text" || remote_urls.is_some()) {
+        exit_with_error(
+            stderr,
+            "Checkpoints can only be used with the text output format and local dump files.",
+        );
+    }
+    let checkpoint = checkpoint_file.map(|file| {
+        // the same command line resumes the search
+        let search = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+        SearchCheckpoint::open(file, &search).unwrap_or_else(|err| {
+            exit_with_error(stderr, format!("Could not open checkpoint: {err}").as_str());
+        })
+    });
+    if let Some(checkpoint) = &checkpoint {
+        if checkpoint.is_resumed() {
+            writeln!(stderr, "Resuming search, skipping the parts searched before.").unwrap();
+        }
+        search_options.with_checkpoint(checkpoint);
+    }
+    let match_histogram = matches.get_one::<String>("histogram").map(|key| match key.as_str() {
+        "size" => MatchHistogram::new(HistogramKey::TextSize),
+        "namespace" => MatchHistogram::new(HistogramKey::Namespace),
+        _ => unreachable!(),
+    });
+    if let Some(match_histogram) = &match_histogram {
+        search_options.with_match_histogram(match_histogram);
+    }
+    let sha1_verifier = matches.get_flag("verify-sha1").then(Sha1Verifier::new);
+    if let Some(sha1_verifier) = &sha1_verifier {
+        search_options.with_sha1_verifier(sha1_verifier);
+    }
+
+    if matches.get_flag("plan") {
+        if remote_urls.is_some() {
+            exit_with_error(stderr, "Streamed dumps are searched as they are downloaded.");
+        }
+        match plan_search(search_term, &dump_files, &search_options) {
+            Ok(search_plan) => print_search_plan(&search_plan),
+            Err(err) => exit_with_error(stderr, format!("Error while planning the search: {err}").as_str()),
+        }
+        return;
+    }
+
+    let now = Instant::now();
+    if let Some(&text_window_size) = matches.get_one::<u64>("text-window-size") {
+        search_options.with_text_window_size(text_window_size as usize * 1024 * 1024);
+    }
+    if let Some(&page_timeout) = matches.get_one::<u64>("page-timeout") {
+        let action = if matches.get_flag("page-timeout-abort") {
+            PageTimeLimitAction::Abort
+        } else {
+            PageTimeLimitAction::Skip
+        };
+        search_options.with_page_time_limit(Duration::from_secs(page_timeout), action);
+    }
+    if let Some(&timeout) = matches.get_one::<u64>("timeout") {
+        search_options.with_deadline(now + Duration::from_secs(timeout));
+    }
+    let search_res = match &remote_urls {
+        Some(urls) => remote::search_remote_dump(search_term, urls, &search_options).map_err(|err| err.to_string()),
+        None => search_dump(search_term, &dump_files, &search_options)
+            .map(|search_dump_result| (search_dump_result, total_size))
+            .map_err(|err| err.to_string()),
+    };
+    // the progress is printed until the sender is gone
+    drop(progress_send);
+    if let Some(progress_printer) = progress_printer {
+        // UNWRAP: the printer thread does not panic
+        progress_printer.join().unwrap();
+    }
+    match search_res {
+        Ok((search_dump_result, total_size)) => {
+            if let Some(match_sink) = match_sink {
+                match_sink.finish().unwrap_or_else(|err| {
+                    exit_with_error(stderr, format!("Error writing output: {err}").as_str());
+                });
+            }
+            if let Some(result_recorder) = &result_recorder {
+                // UNWRAP: the recorder is only created with a file name
+                let result_set_file = matches.get_one::<String>("save-results").unwrap();
+                result_recorder
+                    .result_set()
+                    .write(Path::new(result_set_file))
+                    .unwrap_or_else(|err| {
+                        exit_with_error(stderr, format!("Could not save result set: {err}").as_str());
+                    });
+            }
+            if search_dump_result.truncated {
+                stderr.set_color(ColorSpec::new().set_fg(Some(Color::Yellow))).unwrap();
+                writeln!(stderr, "Warning: Search stopped after timeout, results are incomplete.").unwrap();
+                stderr.reset().unwrap();
+            } else if let Some(checkpoint_file) = checkpoint_file {
+                // the search is complete, running it again should start from the beginning
+                std::fs::remove_file(checkpoint_file).unwrap_or_else(|err| {
+                    exit_with_error(stderr, format!("Could not remove checkpoint: {err}").as_str());
+                });
+            }
+            if matches.get_flag("count-deleted") || matches.get_flag("report-deleted") {
+                let deleted_revisions = search_dump_result.deleted_revisions;
+                writeln!(
+                    stderr,
+                    "Skipped {} revisions with deleted text, found {} revisions with deleted contributor.",
+                    deleted_revisions.deleted_texts, deleted_revisions.deleted_contributors
+                )
+                .unwrap();
+            }
+            if let Some(match_histogram) = &match_histogram {
+                print_match_histogram(stderr, match_histogram);
+            }
+            if matches.get_flag("verbose") {
+                print_performance_statistics(stderr, &search_dump_result, total_size, now);
+                let match_counts = search_dump_result.match_counts;
+                writeln!(
+                    stderr,
+                    "Found {} matching revisions in {} of {} searched pages.",
+                    match_counts.revisions_matched, match_counts.pages_matched, match_counts.pages_scanned
+                )
+                .unwrap();
+                for (dump_file, streams) in &search_dump_result.bzip2_streams {
+                    if streams.len() > 1 {
+                        writeln!(
+                            stderr,
+                            "{dump_file} consists of {} concatenated bzip2 streams.",
+                            streams.len()
+                        )
+                        .unwrap();
+                    }
+                }
+            }
+            if let Some(sha1_verifier) = &sha1_verifier {
+                let mismatches = sha1_verifier.mismatches();
+                for mismatch in &mismatches {
+                    writeln!(
+                        stderr,
+                        "{} (revision {}): SHA1 is {} instead of {}",
+                        mismatch.title, mismatch.revision_id, mismatch.actual, mismatch.expected
+                    )
+                    .unwrap();
+                }
+                if !mismatches.is_empty() {
+                    exit_with_error(
+                        stderr,
+                        &format!(
+                            "The text of {} of {} verified revisions does not match their SHA1.",
+                            mismatches.len(),
+                            sha1_verifier.verified()
+                        ),
+                    );
+                }
+                if matches.get_flag("verbose") {
+                    writeln!(stderr, "Verified the SHA1 of {} revisions.", sha1_verifier.verified()).unwrap();
+                }
+            }
+            if !search_dump_result.failed_files.is_empty() {
+                let mut msg = format!(
+                    "Errors in {} of {} dump files, their results are incomplete:",
+                    search_dump_result.failed_files.len(),
+                    dump_files.len()
+                );
+                for (dump_file, err) in &search_dump_result.failed_files {
+                    msg.push_str(&format!("\n{dump_file}: {err}"));
+                }
+                exit_with_error(stderr, &msg);
+            }
+        }
+        Err(err) => {
+            // keep the matches found before the error
+            if let Some(match_sink) = match_sink {
+                if let Err(err) = match_sink.finish() {
+                    writeln!(stderr, "Error writing output: {err}").unwrap();
+                }
+            }
+            exit_with_error(stderr, format!("Error during search: {err}").as_str());
+        }
+    }
+}
+
+fn stats(matches: &ArgMatches, color_choice: ColorChoice, stderr: &mut StandardStream) {
+    let (dump_files, total_size) = get_dump_files_or_exit(matches, stderr);
+
+    let mut search_options = SearchOptions::new();
+    search_options.with_color_choice(color_choice);
+    let dump_args = DumpArgs::from_matches(matches, &dump_files, stderr);
+    dump_args.configure(matches, &dump_files, &mut search_options, stderr);
+
+    println!("{PAGE_STATISTICS_HEADER}");
+    let now = Instant::now();
+    match process_dump(&dump_files, &PageStatistics, &search_options) {
+        Ok(search_dump_result) => {
+            if matches.get_flag("verbose") {
+                print_performance_statistics(stderr, &search_dump_result, total_size, now);
+            }
+        }
+        Err(err) => {
+            exit_with_error(stderr, format!("Error while reading dump: {err}").as_str());
+        }
+    }
+}
+
+fn reverts(matches: &ArgMatches, color_choice: ColorChoice, stderr: &mut StandardStream) {
+    let (dump_files, total_size) = get_dump_files_or_exit(matches, stderr);
+
+    let mut search_options = SearchOptions::new();
+    search_options.with_color_choice(color_choice);
+    let dump_args = DumpArgs::from_matches(matches, &dump_files, stderr);
+    dump_args.configure(matches, &dump_files, &mut search_options, stderr);
+
+    let revert_detector = RevertDetector::new();
+    println!("{REVERTS_HEADER}");
+    let now = Instant::now();
+    match process_dump(&dump_files, &revert_detector, &search_options) {
+        Ok(search_dump_result) => {
+            let statistics = revert_detector.statistics();
+            writeln!(
+                stderr,
+                "{} revisions, {} identical to the previous revision, {} reverts undoing {} revisions.",
+                statistics.revisions, statistics.identical, statistics.reverts, statistics.reverted
+            )
+            .unwrap();
+            if matches.get_flag("verbose") {
+                print_performance_statistics(stderr, &search_dump_result, total_size, now);
+            }
+        }
+        Err(err) => {
+            exit_with_error(stderr, format!("Error while reading dump: {err}").as_str());
+        }
+    }
+}
+
+fn terms(matches: &ArgMatches, color_choice: ColorChoice, stderr: &mut StandardStream) {
+    let (dump_files, total_size) = get_dump_files_or_exit(matches, stderr);
+
+    let mut terms: Vec<String> = matches
+        .get_many::<String>("terms")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    if let Some(terms_file) = matches.get_one::<String>("terms-file") {
+        let content = std::fs::read_to_string(terms_file).unwrap_or_else(|err| {
+            exit_with_error(
+                stderr,
+                format!("Could not read terms file {terms_file}: {err}").as_str(),
+            );
+        });
+        terms.extend(content.lines().map(str::to_owned));
+    }
+    let term_frequencies = TermFrequencies::new(terms.iter().map(String::as_str), matches.get_flag("ignore-case"));
+    if term_frequencies.is_empty() {
+        exit_with_error(stderr, "No words found in the given terms.");
+    }
+
+    let mut search_options = SearchOptions::new();
+    search_options.with_color_choice(color_choice);
+    let dump_args = DumpArgs::from_matches(matches, &dump_files, stderr);
+    dump_args.configure(matches, &dump_files, &mut search_options, stderr);
+    search_options.strip_markup(matches.get_flag("strip-markup"));
+
+    println!("{TERM_FREQUENCIES_HEADER}");
+    let now = Instant::now();
+    match process_dump(&dump_files, &term_frequencies, &search_options) {
+        Ok(search_dump_result) => {
+            if matches.get_flag("verbose") {
+                print_performance_statistics(stderr, &search_dump_result, total_size, now);
+            }
+        }
+        Err(err) => {
+            exit_with_error(stderr, format!("Error while reading dump: {err}").as_str());
+        }
+    }
+}
+
+fn extract_page(matches: &ArgMatches, color_choice: ColorChoice, stderr: &mut StandardStream) {
+    let title = matches.get_one::<String>("title").unwrap();
+    let (dump_files, _) = get_dump_files_or_exit(matches, stderr);
+
+    let mut search_options = SearchOptions::new();
+    search_options.with_color_choice(color_choice);
+    let dump_args = DumpArgs::from_matches(matches, &dump_files, stderr);
+    dump_args.configure(matches, &dump_files, &mut search_options, stderr);
+
+    match wdgreplib::extract_page(&dump_files, title, &search_options) {
+        Ok(page) => {
+            if matches.get_flag("text") {
+                println!("{}", page.text.unwrap_or_default());
+            } else {
+                print!("{}", page.xml);
+            }
+        }
+        Err(err) => {
+            exit_with_error(stderr, format!("Error while reading dump: {err}").as_str());
+        }
+    }
+}
+
+fn info(matches: &ArgMatches, stderr: &mut StandardStream) {
+    let dump_file = matches.get_one::<String>("dump file").unwrap();
+    let header = siteinfo::parse_dump_header(dump_file).unwrap_or_else(|err| {
+        exit_with_error(stderr, format!("Error while reading dump: {err}").as_str());
+    });
+    if matches.get_flag("json") {
+        // UNWRAP: serializing strings and numbers cannot fail
+        println!("{}", serde_json::to_string_pretty(&header).unwrap());
+        return;
+    }
+    let mut tw = TabWriter::new(std::io::stdout());
+    writeln!(tw, "Site name:\t{}", header.sitename).unwrap();
+    writeln!(tw, "Database name:\t{}", header.dbname).unwrap();
+    writeln!(tw, "Base URL:\t{}", header.base).unwrap();
+    writeln!(tw, "Generator:\t{}", header.generator).unwrap();
+    writeln!(tw, "Case:\t{}", header.case).unwrap();
+    writeln!(tw, "Namespaces:").unwrap();
+    for (key, name) in &header.namespaces {
+        writeln!(tw, "{key}\t{name}").unwrap();
+    }
+    tw.flush().unwrap();
+}
+
+fn make_index(matches: &ArgMatches, stderr: &mut StandardStream) {
+    let dump_file = matches.get_one::<String>("dump file").unwrap();
+    if !index::is_indexable(dump_file) {
+        exit_with_error(
+            stderr,
+            "Only uncompressed (.xml), multistream (.xml.bz2) and seekable zstd (.xml.zst) dumps can be indexed.",
+        );
+    }
+    let index_file = matches
+        .get_one::<String>("output")
+        .cloned()
+        .unwrap_or_else(|| index::get_index_file_name(dump_file));
+    let now = Instant::now();
+    match index::make_index(dump_file, &index_file) {
+        Ok(page_count) => {
+            eprintln!(
+                "Indexed {page_count} pages in {:.2} seconds, written to {index_file}.",
+                now.elapsed().as_secs_f64()
+            );
+        }
+        Err(err) => {
+            exit_with_error(stderr, format!("Error while indexing dump: {err}").as_str());
+        }
+    }
+    if matches.get_flag("trigrams") {
+        let trigram_index_file = trigram::get_trigram_index_file_name(dump_file);
+        let now = Instant::now();
+        match trigram::make_trigram_index(dump_file, &index_file, &trigram_index_file) {
+            Ok(part_count) => {
+                eprintln!(
+                    "Indexed trigrams of {part_count} parts in {:.2} seconds, written to {trigram_index_file}.",
+                    now.elapsed().as_secs_f64()
+                );
+            }
+            Err(err) => {
+                exit_with_error(stderr, format!("Error while creating trigram index: {err}").as_str());
+            }
+        }
+    }
+}
+
+fn index_build(matches: &ArgMatches, color_choice: ColorChoice, stderr: &mut StandardStream) {
+    let (dump_files, total_size) = get_dump_files_or_exit(matches, stderr);
+    let index_directory = matches.get_one::<String>("index dir").unwrap();
+    let memory_budget = *matches.get_one::<usize>("memory").unwrap() * 1024 * 1024;
+    let index_builder = IndexBuilder::create(index_directory, memory_budget).unwrap_or_else(|err| {
+        exit_with_error(stderr, format!("Could not create full-text index: {err}").as_str());
+    });
+
+    let mut search_options = SearchOptions::new();
+    search_options.with_color_choice(color_choice);
+    let dump_args = DumpArgs::from_matches(matches, &dump_files, stderr);
+    dump_args.configure(matches, &dump_files, &mut search_options, stderr);
+
+    let now = Instant::now();
+    let res = process_dump(&dump_files, &index_builder, &search_options).and_then(|search_dump_result| {
+        index_builder.finish()?;
+        Ok(search_dump_result)
+    });
+    match res {
+        Ok(search_dump_result) => {
+            if matches.get_flag("verbose") {
+                print_performance_statistics(stderr, &search_dump_result, total_size, now);
+            }
+        }
+        Err(err) => {
+            exit_with_error(stderr, format!("Error while indexing dump: {err}").as_str());
+        }
+    }
+}
+
+fn index_search(matches: &ArgMatches, stderr: &mut StandardStream) {
+    let index_directory = matches.get_one::<String>("index dir").unwrap();
+    let query = matches.get_one::<String>("query").unwrap();
+    let limit = *matches.get_one::<usize>("limit").unwrap();
+    match fulltext::search_index(index_directory, query, limit) {
+        Ok(hits) => {
+            let with_scores = matches.get_flag("scores");
+            for hit in hits {
+                if with_scores {
+                    println!("{:.3}\t{}@{}", hit.score, hit.title, hit.revision_id);
+                } else {
+                    println!("{}@{}", hit.title, hit.revision_id);
+                }
+            }
+        }
+        Err(err) => {
+            exit_with_error(stderr, format!("Error while searching full-text index: {err}").as_str());
+        }
+    }
+}
+
+fn build_cli() -> Command {
+    Command::new("WikiDumpGrep")
         .version(crate_version!())
         .author(crate_authors!())
         .about("Search through Wikipedia and other Wikimedia wiki dumps using regular expressions.")
-        .arg(Arg::new("search term").help("regex search term").required(true))
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
         .arg(
-            Arg::new("dump file or prefix")
-                .help("The dump file or common prefix of muliple dump files to search")
+            Arg::new("search term")
+                .help(
+                    "regex search term, \\template{Name}, \\link{Title} and \\category{Name} match the wikitext of \
+                     templates, links and categories",
+                )
                 .required(true),
         )
         .arg(
-            Arg::new("namespaces")
-                .long("ns")
-                .value_delimiter(',')
-                .help("Restrict search to those namespaces (comma-separated list of numeric namespaces)"),
+            Arg::new("dump file or prefix")
+                .help(
+                    "The dump file or common prefix of muliple dump files to search or a downloaded dump in the \
+                     cache given as wiki:date:type",
+                )
+                .required_unless_present("fetch"),
         )
+        .args(dump_args())
         .arg(
-            Arg::new("verbose")
-                .short('v')
-                .long("verbose")
-                .help("Print performance statistics")
+            Arg::new("stream")
+                .long("stream")
+                .requires("dump file or prefix")
+                .conflicts_with("fetch")
+                .help(
+                    "Search the dump given as wiki:date:type while downloading it without storing it, dump files \
+                     given as URL are always streamed",
+                )
                 .action(ArgAction::SetTrue),
         )
         .arg(
@@ -56,11 +1221,39 @@ fn main() {
                 .action(ArgAction::SetTrue),
         )
         .arg(
-            Arg::new("threads")
-                .short('j')
-                .long("threads")
-                .value_name("num")
-                .help("Number of parallel threads to use. The default is the number of logical cpus."),
+            Arg::new("replace")
+                .short('r')
+                .long("replace")
+                .value_name("template")
+                .help(
+                    "Print each match replaced by the template on its own line, capture groups can be referenced \
+                     with $1 or ${name}",
+                ),
+        )
+        .arg(
+            Arg::new("with-title")
+                .long("with-title")
+                .requires("replace")
+                .help("Prefix each value printed with --replace with the title and revision")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("group-by")
+                .long("group-by")
+                .value_parser(["page"])
+                .value_name("unit")
+                .conflicts_with_all(["replace", "revisions-with-matches", "count-unique"])
+                .help("Print one line per page with the first and last matching revision and the number of matches"),
+        )
+        .arg(
+            Arg::new("count-unique")
+                .long("count-unique")
+                .conflicts_with_all(["with-title", "revisions-with-matches"])
+                .help(
+                    "Print each distinct match (or value with --replace) with its number of occurrences, \
+                     most frequent first",
+                )
+                .action(ArgAction::SetTrue),
         )
         .arg(
             Arg::new("color")
@@ -68,32 +1261,431 @@ fn main() {
                 .value_parser(["always", "auto", "never"])
                 .default_value("auto")
                 .value_name("mode")
+                .global(true)
                 .help("Colorize output, defaults to \"auto\" - output is colorized only if a terminal is detected"),
         )
         .arg(
-            Arg::new("7z-binary")
-                .long("7z-binary")
+            Arg::new("format")
+                .long("format")
+                .value_parser(["text", "sqlite", "parquet"])
+                .default_value("text")
+                .value_name("format")
+                .help(
+                    "Output format, \"sqlite\" writes matching revisions to the database given by --output, \
+                     \"parquet\" writes them to Parquet files partitioned by namespace in the --output directory",
+                ),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
                 .value_name("path")
-                .help("Binary for extracting text from .7z files, defaults to \"7z\"."),
+                .help("Output file or directory for formats other than \"text\""),
         )
         .arg(
-            Arg::new("7z-options").long("7z-options").value_name("options").help(
-                "Options passed to 7z binary for extracting text from .7z files to stdout, defaults to \"e -so\".",
-            ),
+            Arg::new("include-text")
+                .long("include-text")
+                .help("Include the full text of matching revisions in the output")
+                .action(ArgAction::SetTrue),
         )
         .arg(
-            Arg::new("bzcat-binary")
-                .long("bzcat-binary")
-                .value_name("path")
-                .help("Binary for extracting text from .bz2 files to stdout, defaults to \"bzcat\"."),
+            Arg::new("field")
+                .long("field")
+                .value_parser(["text", "comment"])
+                .default_value("text")
+                .value_name("field")
+                .help("Search the revision text or the edit summary (comment) of each revision"),
+        )
+        .arg(
+            Arg::new("strip-markup")
+                .long("strip-markup")
+                .help(
+                    "Search an approximate plain text version of the wikitext: templates, tables, comments and \
+                     HTML tags are removed and links are reduced to their label",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("engine")
+                .long("engine")
+                .value_parser(ENGINES)
+                .default_value("auto")
+                .value_name("engine")
+                .help(
+                    "Matching engine, \"auto\" uses Aho-Corasick if the search term is only a list of plain literals \
+                     separated by | and --replace is not given, which is much faster for long lists, \"pcre2\" \
+                     (if compiled in) supports lookaround and backreferences",
+                ),
         )
         .arg(
-            Arg::new("bzcat-options")
-                .long("bzcat-options")
-                .value_name("options")
-                .help("Options passed to bzcat binary for extracting text from .bz2 files, defaults to no options."),
+            Arg::new("no-prefilter")
+                .long("no-prefilter")
+                .help(
+                    "Parse all pages of uncompressed dumps instead of only those containing a literal required by the \
+                     search term",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no-shortcuts")
+                .long("no-shortcuts")
+                .help("Do not expand \\template{...}, \\link{...} and \\category{...} in the search term")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help(
+                    "Exit with an error instead of printing a warning if the search term looks like a mistake, e.g. \
+                     matches the empty string or contains XML entities like &lt;",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("timeout")
+                .long("timeout")
+                .value_name("seconds")
+                .value_parser(value_parser!(u64))
+                .help("Stop the search after the given number of seconds and print the matches found so far"),
+        )
+        .arg(
+            Arg::new("page-timeout")
+                .long("page-timeout")
+                .value_name("seconds")
+                .value_parser(value_parser!(u64).range(1..))
+                .help(
+                    "Skip the remaining revisions of pages which take longer than the given number of seconds to \
+                     search and print their titles to stderr, checked before each revision and text window",
+                ),
+        )
+        .arg(
+            Arg::new("page-timeout-abort")
+                .long("page-timeout-abort")
+                .requires("page-timeout")
+                .help("Stop the search with an error instead of skipping pages exceeding --page-timeout")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("text-window-size")
+                .long("text-window-size")
+                .value_name("MiB")
+                .value_parser(value_parser!(u64).range(1..=4096))
+                .help(
+                    "Search revision texts larger than this in overlapping windows of this size to bound the memory \
+                     used by each thread, only if the matches have a bounded length",
+                ),
+        )
+        .arg(
+            Arg::new("split-output-by-ns")
+                .long("split-output-by-ns")
+                .value_name("directory")
+                .help(
+                    "Write the matches of the pages of each namespace to a file in the directory (0.txt, 1.txt, ...) \
+                     instead of printing them",
+                ),
+        )
+        .arg(
+            Arg::new("head")
+                .long("head")
+                .value_name("count")
+                .value_parser(value_parser!(u64))
+                .conflicts_with_all(["tail", "count-unique", "dump-matches"])
+                .help(
+                    "Print only the first results (matching revisions or pages if grouped by page), the dump is still \
+                     searched completely",
+                ),
+        )
+        .arg(
+            Arg::new("tail")
+                .long("tail")
+                .value_name("count")
+                .value_parser(value_parser!(u64))
+                .conflicts_with_all(["count-unique", "dump-matches"])
+                .help(
+                    "Print only the last results (matching revisions or pages if grouped by page) when the search is \
+                     finished",
+                ),
+        )
+        .arg(
+            Arg::new("compress-output")
+                .long("compress-output")
+                .conflicts_with_all(["split-output-by-ns", "count-unique"])
+                .help("Compress the output with gzip if stdout is not a terminal")
+                .action(ArgAction::SetTrue),
         )
-        .get_matches();
+        .arg(
+            Arg::new("dump-matches")
+                .long("dump-matches")
+                .value_name("directory")
+                .conflicts_with("split-output-by-ns")
+                .help(
+                    "Write the text of each matching revision to its own file in the directory \
+                     (<page id>_<revision id>.wiki) instead of printing the matches",
+                ),
+        )
+        .arg(
+            Arg::new("histogram")
+                .long("histogram")
+                .value_parser(["size", "namespace"])
+                .value_name("key")
+                .help(
+                    "Print a histogram of the matching revisions by the size of their text or by namespace to stderr \
+                     at the end",
+                ),
+        )
+        .arg(
+            Arg::new("save-results")
+                .long("save-results")
+                .value_name("file")
+                .help(
+                    "Save the page and revision ids of the matching revisions as a result set, which later searches \
+                     can be restricted to with --and or exclude with --not",
+                ),
+        )
+        .arg(
+            Arg::new("keep-going")
+                .long("keep-going")
+                .help(
+                    "Continue with the other dump files if searching one of them fails and print the errors at the \
+                     end, the exit code is still 1",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("checkpoint")
+                .long("checkpoint")
+                .value_name("file")
+                .conflicts_with_all(["stream", "count-unique", "histogram", "save-results", "count-deleted"])
+                .help(
+                    "Periodically save the parts of the dump files searched so far to the file, running the same \
+                     command again skips them and prints only the remaining matches, the file is removed when the \
+                     search completes",
+                ),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .help("Print the progress of reading compressed dump files to stderr")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("count-deleted")
+                .long("count-deleted")
+                .help(
+                    "Print the number of revisions skipped because their text was deleted and of revisions with a \
+                     deleted contributor to stderr",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("report-deleted")
+                .long("report-deleted")
+                .help("Print each page with revisions with deleted text or contributor to stderr, implies --count-deleted")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("plan")
+                .long("plan")
+                .help(
+                    "Print how each dump file would be split into parts, the decompressors and the number of threads \
+                     instead of searching",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("verify-sha1")
+                .long("verify-sha1")
+                .help(
+                    "Verify the text of each searched revision against its SHA1, print the mismatching revisions to \
+                     stderr and fail if there are any",
+                )
+                .action(ArgAction::SetTrue),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Print byte size, word, line, template and link counts of each revision as TSV")
+                .arg(
+                    Arg::new("dump file or prefix")
+                        .help("The dump file or common prefix of muliple dump files to read")
+                        .required_unless_present("fetch"),
+                )
+                .args(dump_args()),
+        )
+        .subcommand(
+            Command::new("reverts")
+                .about(
+                    "Print revisions identical to the previous revision of the page and reverts restoring an earlier \
+                     revision with the revisions they undo as TSV, meant for history dumps",
+                )
+                .arg(
+                    Arg::new("dump file or prefix")
+                        .help("The dump file or common prefix of muliple dump files to read")
+                        .required_unless_present("fetch"),
+                )
+                .args(dump_args()),
+        )
+        .subcommand(
+            Command::new("terms")
+                .about(
+                    "Print how often each of the given words or phrases occurs in each revision as TSV, the text is \
+                     split into words at Unicode word boundaries. History dumps give one row per revision and term, \
+                     not per page",
+                )
+                .arg(
+                    Arg::new("dump file or prefix")
+                        .help("The dump file or common prefix of muliple dump files to read")
+                        .required_unless_present("fetch"),
+                )
+                .arg(
+                    Arg::new("terms")
+                        .long("terms")
+                        .value_name("terms")
+                        .value_delimiter(',')
+                        .required_unless_present("terms-file")
+                        .help("Comma-separated words or phrases to count"),
+                )
+                .arg(
+                    Arg::new("terms-file")
+                        .long("terms-file")
+                        .value_name("path")
+                        .help("File with one word or phrase to count per line"),
+                )
+                .arg(
+                    Arg::new("ignore-case")
+                        .short('i')
+                        .long("ignore-case")
+                        .help("Match terms case-insensitively")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("strip-markup")
+                        .long("strip-markup")
+                        .help("Count words in an approximate plain text version of the wikitext")
+                        .action(ArgAction::SetTrue),
+                )
+                .args(dump_args()),
+        )
+        .subcommand(
+            Command::new("extract-page")
+                .about("Print the XML of a single page")
+                .arg(Arg::new("title").help("Title of the page").required(true))
+                .arg(
+                    Arg::new("dump file or prefix")
+                        .help("The dump file or common prefix of muliple dump files to read")
+                        .required_unless_present("fetch"),
+                )
+                .arg(
+                    Arg::new("text")
+                        .long("text")
+                        .help("Only print the wikitext of the last revision")
+                        .action(ArgAction::SetTrue),
+                )
+                .args(dump_args()),
+        )
+        .subcommand(
+            Command::new("make-index")
+                .about("Create a title index in the format of the multistream index files published by Wikimedia")
+                .arg(
+                    Arg::new("dump file")
+                        .help("The uncompressed or multistream dump file to index")
+                        .required(true),
+                )
+                .arg(Arg::new("output").short('o').long("output").value_name("path").help(
+                    "Index file to write, compressed if it ends with .bz2, defaults to the name used by Wikimedia. An \
+                     existing index file is not overwritten.",
+                ))
+                .arg(
+                    Arg::new("trigrams")
+                        .long("trigrams")
+                        .help(
+                            "Also create a trigram index (<dump>-trigrams.idx), which is used to only search the \
+                             parts of the dump containing a literal required by the search term. Needs memory in the \
+                             order of the compressed dump size.",
+                        )
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("index")
+                .about("Build and query a full-text index of a dump for repeated searches")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("build")
+                        .about("Create a full-text index of title, text and metadata of all revisions")
+                        .arg(
+                            Arg::new("index dir")
+                                .help("Directory to create the index in, must not contain an index yet")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("dump file or prefix")
+                                .help("The dump file or common prefix of muliple dump files to index")
+                                .required_unless_present("fetch"),
+                        )
+                        .arg(
+                            Arg::new("memory")
+                                .long("memory")
+                                .value_name("MiB")
+                                .value_parser(value_parser!(usize))
+                                .default_value("500")
+                                .help("Memory used for buffering documents before they are written to disk"),
+                        )
+                        .args(dump_args()),
+                )
+                .subcommand(
+                    Command::new("search")
+                        .about(
+                            "Query a full-text index, fields other than title and text can be queried by name, \
+                             e.g. 'namespace:0 AND text:\"foo bar\"'",
+                        )
+                        .arg(Arg::new("index dir").help("Directory of the index").required(true))
+                        .arg(
+                            Arg::new("query")
+                                .help("Query in the tantivy query syntax")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("limit")
+                                .short('n')
+                                .long("limit")
+                                .value_parser(value_parser!(usize))
+                                .default_value("20")
+                                .help("Maximum number of revisions printed, best matching first"),
+                        )
+                        .arg(
+                            Arg::new("scores")
+                                .long("scores")
+                                .help("Prefix each revision with its score")
+                                .action(ArgAction::SetTrue),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("info")
+                .about("Print the site name, database name and namespaces found in the siteinfo of the dump")
+                .arg(Arg::new("dump file").help("The dump file to read").required(true))
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Print as JSON")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .hide(true)
+                .arg(
+                    Arg::new("shell")
+                        .help("Shell to generate the completion script for")
+                        .value_parser(value_parser!(Shell))
+                        .required(true),
+                ),
+        )
+}
+
+fn main() {
+    let matches = build_cli().get_matches();
 
     let color_choice = match matches.get_one::<String>("color").unwrap().as_str() {
         "auto" => {
@@ -110,124 +1702,24 @@ fn main() {
 
     let mut stderr = StandardStream::stderr(color_choice);
 
-    let search_term = matches.get_one::<String>("search term").unwrap();
-    let dump_file_or_prefix = matches.get_one::<String>("dump file or prefix").unwrap();
-    if dump_file_or_prefix.is_empty() {
-        exit_with_error(&mut stderr, "Non-empty dump file (prefix) needs to be specified.");
-    }
-
-    let (dump_files, total_size) = get_dump_files(dump_file_or_prefix).unwrap_or_else(|err| {
-        exit_with_error(&mut stderr, format!("{err}").as_str());
-    });
-
-    let mut search_options = SearchOptions::new();
-
-    search_options.with_color_choice(color_choice);
-
-    let namespaces: Option<Vec<&str>> = matches
-        .get_many::<String>("namespaces")
-        .map(|val| val.map(|s| str::trim(s)).filter(|x| !x.is_empty()).collect());
-    namespaces
-        .as_deref()
-        .map(|namespaces| search_options.restrict_namespaces(namespaces));
-
-    matches
-        .get_one::<String>("threads")
-        .map(|s| str::parse::<NonZeroUsize>(s))
-        .transpose()
-        .unwrap_or_else(|_err| {
-            exit_with_error(&mut stderr, "Invalid number specified for thread count");
-        })
-        .map(|thread_count| search_options.with_thread_count(thread_count));
-
-    search_options.only_print_title(matches.get_flag("revisions-with-matches"));
-
-    matches
-        .get_one::<String>("7z-binary")
-        .map(|binary| search_options.with_binary_7z(binary));
-
-    let options_7z = matches
-        .get_one::<String>("7z-options")
-        .map(|s| s.split(' ').collect::<Vec<_>>());
-    if let Some(options) = options_7z.as_ref() {
-        search_options.with_options_7z(options);
-    }
-
-    matches
-        .get_one::<String>("bzcat-binary")
-        .map(|binary| search_options.with_binary_bzcat(binary));
-
-    let options_bzcat = matches
-        .get_one::<String>("bzcat-options")
-        .map(|s| s.split(' ').collect::<Vec<_>>());
-    if let Some(options) = options_bzcat.as_ref() {
-        search_options.with_options_bzcat(options);
-    }
-
-    if dump_files.iter().any(|f| f.ends_with(".bz2")) {
-        stderr.set_color(ColorSpec::new().set_fg(Some(Color::Yellow))).unwrap();
-        writeln!(
-            stderr,
-            "Warning: Searching compressed .bz2 files is very slow, use .7z files or uncompressed files instead."
-        )
-        .unwrap();
-    }
-
-    let now = Instant::now();
-    match search_dump(search_term, &dump_files, &search_options) {
-        Ok(SearchDumpResult {
-            bytes_processed,
-            compressed_files_found,
-        }) => {
-            let elapsed_seconds = now.elapsed().as_secs_f64();
-            let mib_read = total_size as f64 / 1024.0 / 1024.0;
-            let mib_read_uncompressed = bytes_processed as f64 / 1024.0 / 1024.0;
-            if matches.get_flag("verbose") {
-                let mut number_hl_color = ColorSpec::new();
-                number_hl_color.set_fg(Some(Color::Yellow));
-
-                stderr.reset().unwrap();
-                if compressed_files_found {
-                    write!(stderr, "Searched ").unwrap();
-                    stderr.set_color(&number_hl_color).unwrap();
-                    write!(stderr, "{mib_read:.2}").unwrap();
-                    stderr.reset().unwrap();
-                    write!(stderr, " MiB compressed, ").unwrap();
-                    stderr.set_color(&number_hl_color).unwrap();
-                    write!(stderr, "{mib_read_uncompressed:.2}").unwrap();
-                    stderr.reset().unwrap();
-                    write!(stderr, " MiB uncompressed in ").unwrap();
-                    stderr.set_color(&number_hl_color).unwrap();
-                    write!(stderr, "{elapsed_seconds:.2}").unwrap();
-                    stderr.reset().unwrap();
-                    write!(stderr, " seconds (").unwrap();
-                    stderr.set_color(&number_hl_color).unwrap();
-                    write!(stderr, "{:.2}", mib_read / elapsed_seconds).unwrap();
-                    stderr.reset().unwrap();
-                    write!(stderr, " MiB/s compressed, ").unwrap();
-                    stderr.set_color(&number_hl_color).unwrap();
-                    write!(stderr, "{:.2}", mib_read_uncompressed / elapsed_seconds).unwrap();
-                    stderr.reset().unwrap();
-                    writeln!(stderr, " MiB/s uncompressed).").unwrap();
-                } else {
-                    write!(stderr, "Searched ").unwrap();
-                    stderr.set_color(&number_hl_color).unwrap();
-                    write!(stderr, "{mib_read:.2}").unwrap();
-                    stderr.reset().unwrap();
-                    write!(stderr, " MiB in ").unwrap();
-                    stderr.set_color(&number_hl_color).unwrap();
-                    write!(stderr, "{elapsed_seconds:.2}").unwrap();
-                    stderr.reset().unwrap();
-                    write!(stderr, " seconds (").unwrap();
-                    stderr.set_color(&number_hl_color).unwrap();
-                    write!(stderr, "{:.2}", mib_read / elapsed_seconds).unwrap();
-                    stderr.reset().unwrap();
-                    writeln!(stderr, " MiB/s).").unwrap();
-                }
-            }
-        }
-        Err(err) => {
-            exit_with_error(&mut stderr, format!("Error during search: {err}").as_str());
+    match matches.subcommand() {
+        Some(("stats", subcommand_matches)) => stats(subcommand_matches, color_choice, &mut stderr),
+        Some(("reverts", subcommand_matches)) => reverts(subcommand_matches, color_choice, &mut stderr),
+        Some(("terms", subcommand_matches)) => terms(subcommand_matches, color_choice, &mut stderr),
+        Some(("extract-page", subcommand_matches)) => extract_page(subcommand_matches, color_choice, &mut stderr),
+        Some(("make-index", subcommand_matches)) => make_index(subcommand_matches, &mut stderr),
+        Some(("index", subcommand_matches)) => match subcommand_matches.subcommand() {
+            Some(("build", build_matches)) => index_build(build_matches, color_choice, &mut stderr),
+            Some(("search", search_matches)) => index_search(search_matches, &mut stderr),
+            _ => unreachable!("Unknown subcommand, should be caught by arg matching."),
+        },
+        Some(("info", subcommand_matches)) => info(subcommand_matches, &mut stderr),
+        Some(("completions", subcommand_matches)) => {
+            // UNWRAP: required argument
+            let shell = *subcommand_matches.get_one::<Shell>("shell").unwrap();
+            generate(shell, &mut build_cli(), "wdgrep", &mut std::io::stdout());
         }
+        Some(_) => unreachable!("Unknown subcommand, should be caught by arg matching."),
+        None => search(&matches, color_choice, &mut stderr),
     }
 }
@@ -0,0 +1,145 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use arrow_array::builder::{ListBuilder, StringBuilder, UInt64Builder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use simdutf8::basic::from_utf8;
+use wdgreplib::{MatchSink, MatchedRevision, Result};
+
+const ROWS_PER_BATCH: usize = 8192;
+
+struct NamespacePartition {
+    writer: ArrowWriter<File>,
+    page_ids: UInt64Builder,
+    titles: StringBuilder,
+    revision_ids: UInt64Builder,
+    match_counts: UInt64Builder,
+    match_lines: ListBuilder<UInt64Builder>,
+    match_columns: ListBuilder<UInt64Builder>,
+    texts: StringBuilder,
+    row_count: usize,
+}
+
+impl NamespacePartition {
+    fn write_batch(&mut self, schema: &SchemaRef, include_text: bool) -> Result<()> {
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.page_ids.finish()),
+            Arc::new(self.titles.finish()),
+            Arc::new(self.revision_ids.finish()),
+            Arc::new(self.match_counts.finish()),
+            Arc::new(self.match_lines.finish()),
+            Arc::new(self.match_columns.finish()),
+        ];
+        if include_text {
+            columns.push(Arc::new(self.texts.finish()));
+        }
+        self.writer.write(&RecordBatch::try_new(schema.clone(), columns)?)?;
+        self.row_count = 0;
+        Ok(())
+    }
+}
+
+/// Writes matching revisions to Parquet files partitioned by namespace
+/// (`<dir>/namespace=<ns>/matches.parquet`), readable as a Hive-partitioned dataset. The lines and columns of the
+/// matches are stored as lists in the same order.
+pub struct ParquetExport {
+    directory: PathBuf,
+    schema: SchemaRef,
+    include_text: bool,
+    partitions: Mutex<BTreeMap<i32, NamespacePartition>>,
+}
+
+impl ParquetExport {
+    pub fn create<P: AsRef<Path>>(directory: P, include_text: bool) -> Result<ParquetExport> {
+        let directory = directory.as_ref().to_owned();
+        fs::create_dir_all(&directory)?;
+        let mut fields = vec![
+            Field::new("page_id", DataType::UInt64, false),
+            Field::new("title", DataType::Utf8, false),
+            Field::new("revision_id", DataType::UInt64, false),
+            Field::new("match_count", DataType::UInt64, false),
+            Field::new_list("match_lines", Field::new("item", DataType::UInt64, true), false),
+            Field::new_list("match_columns", Field::new("item", DataType::UInt64, true), false),
+        ];
+        if include_text {
+            fields.push(Field::new("text", DataType::Utf8, false));
+        }
+        Ok(ParquetExport {
+            directory,
+            schema: Arc::new(Schema::new(fields)),
+            include_text,
+            partitions: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    fn create_partition(&self, namespace: i32) -> Result<NamespacePartition> {
+        let mut partition_dir = self.directory.clone();
+        partition_dir.push(format!("namespace={namespace}"));
+        fs::create_dir_all(&partition_dir)?;
+        partition_dir.push("matches.parquet");
+        let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+        Ok(NamespacePartition {
+            writer: ArrowWriter::try_new(File::create(partition_dir)?, self.schema.clone(), Some(properties))?,
+            page_ids: UInt64Builder::with_capacity(ROWS_PER_BATCH),
+            titles: StringBuilder::new(),
+            revision_ids: UInt64Builder::with_capacity(ROWS_PER_BATCH),
+            match_counts: UInt64Builder::with_capacity(ROWS_PER_BATCH),
+            match_lines: ListBuilder::new(UInt64Builder::new()),
+            match_columns: ListBuilder::new(UInt64Builder::new()),
+            texts: StringBuilder::new(),
+            row_count: 0,
+        })
+    }
+}
+
+impl MatchSink for ParquetExport {
+    fn add_match(&self, matched_revision: &MatchedRevision) -> Result<()> {
+        let mut partitions = self.partitions.lock().unwrap();
+        let partition = match partitions.entry(matched_revision.namespace) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(self.create_partition(matched_revision.namespace)?),
+        };
+        partition.page_ids.append_value(matched_revision.page_id);
+        partition.titles.append_value(matched_revision.title);
+        partition.revision_ids.append_value(matched_revision.revision_id);
+        partition.match_counts.append_value(matched_revision.match_count as u64);
+        for position in matched_revision.positions {
+            partition.match_lines.values().append_value(position.line as u64);
+            partition.match_columns.values().append_value(position.column as u64);
+        }
+        partition.match_lines.append(true);
+        partition.match_columns.append(true);
+        if self.include_text {
+            partition.texts.append_value(from_utf8(matched_revision.text)?);
+        }
+        partition.row_count += 1;
+        if partition.row_count == ROWS_PER_BATCH {
+            partition.write_batch(&self.schema, self.include_text)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<()> {
+        let partitions = std::mem::take(&mut *self.partitions.lock().unwrap());
+        for (_, mut partition) in partitions {
+            if partition.row_count > 0 {
+                partition.write_batch(&self.schema, self.include_text)?;
+            }
+            partition.writer.close()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::cell::Cell;
+use std::io::Read;
+
+use anyhow::{anyhow, Result};
+use clap::crate_version;
+use wdgetlib::cache::parse_dump_spec;
+use wdgetlib::{get_dump_file_url, get_dump_files, get_latest_available_date, resolve_dump_tree};
+use wdgreplib::{search_dump_streams, SearchDumpResult, SearchOptions};
+
+const USER_AGENT: &str = concat!(
+    "wdgrep/",
+    crate_version!(),
+    " (https://github.com/Count-Count/wikidumptools)"
+);
+
+pub fn is_url(dump: &str) -> bool {
+    dump.starts_with("http://") || dump.starts_with("https://")
+}
+
+/// Returns the download URLs of the files of a dump given as `wiki:date:type`.
+pub fn get_dump_urls(dump_spec: &str) -> Result<Vec<String>> {
+    let (wiki, date_spec, dump_type) = parse_dump_spec(dump_spec)
+        .ok_or_else(|| anyhow!("Dump to stream must be given as URL or as wiki:date:type."))?;
+    let (wiki, dump_type) = resolve_dump_tree(wiki, dump_type);
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    runtime.block_on(async {
+        let client = reqwest::Client::builder().user_agent(USER_AGENT).build()?;
+        let date = if date_spec == "latest" {
+            get_latest_available_date(&client, &wiki, Some(&dump_type)).await?
+        } else {
+            date_spec.to_owned()
+        };
+        let files = get_dump_files(&client, &wiki, &date, &dump_type).await?;
+        Ok(files
+            .keys()
+            .map(|file_name| get_dump_file_url(None, &wiki, &date, file_name))
+            .collect())
+    })
+}
+
+/// Counts the bytes downloaded.
+struct CountingReader<'a, R: Read> {
+    reader: R,
+    bytes_read: &'a Cell<u64>,
+}
+
+impl<'a, R: Read> Read for CountingReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.bytes_read.set(self.bytes_read.get() + len as u64);
+        Ok(len)
+    }
+}
+
+/// Searches the dump files while they are downloaded one after another without storing them. Returns the search
+/// result and the number of bytes downloaded.
+pub fn search_remote_dump(
+    regex: &str,
+    urls: &[String],
+    search_options: &SearchOptions,
+) -> Result<(SearchDumpResult, u64)> {
+    // the default timeout would abort downloads of large files
+    let client = reqwest::blocking::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(None)
+        .build()?;
+    let bytes_downloaded = Cell::new(0);
+    let streams = urls.iter().map(|url| {
+        let response = client
+            .get(url)
+            .send()
+            .and_then(reqwest::blocking::Response::error_for_status)
+            .map_err(|e| wdgreplib::Error::Io(std::io::Error::other(e)))?;
+        // the format is determined by the file name
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        let file_name = path.rsplit('/').next().unwrap_or_default().to_owned();
+        Ok((
+            file_name,
+            CountingReader {
+                reader: response,
+                bytes_read: &bytes_downloaded,
+            },
+        ))
+    });
+    let search_dump_result = search_dump_streams(regex, streams, search_options)?;
+    Ok((search_dump_result, bytes_downloaded.get()))
+}
@@ -0,0 +1,112 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use sha1::{Digest, Sha1};
+use termcolor::Buffer;
+use wdgreplib::{Result, RevisionInfo, RevisionProcessor};
+
+pub const REVERTS_HEADER: &str =
+    "page_id\tnamespace\ttitle\trevision_id\tkind\trestored_revision_id\treverted_revisions";
+
+struct PageRevisions {
+    namespace: String,
+    title: String,
+    // revision id and SHA-1 of the text of each revision in dump order
+    revisions: Vec<(String, [u8; 20])>,
+}
+
+#[derive(Default)]
+pub struct RevertStatistics {
+    pub revisions: u64,
+    /// Revisions with the same text as the previous revision
+    pub identical: u64,
+    /// Revisions restoring the text of an earlier revision which is not the previous one
+    pub reverts: u64,
+    /// Revisions undone by reverts, counted once per revert
+    pub reverted: u64,
+}
+
+/// Finds revisions identical to the previous revision of the page and reverts, i.e. revisions restoring the text
+/// of an earlier revision, and prints them as tab-separated values. For reverts the undone revisions are listed.
+#[derive(Default)]
+pub struct RevertDetector {
+    // revisions of the pages currently being read by the threads, by page id
+    pages: Mutex<HashMap<String, PageRevisions>>,
+    revisions: AtomicU64,
+    identical: AtomicU64,
+    reverts: AtomicU64,
+    reverted: AtomicU64,
+}
+
+impl RevertDetector {
+    pub fn new() -> RevertDetector {
+        RevertDetector::default()
+    }
+
+    pub fn statistics(&self) -> RevertStatistics {
+        RevertStatistics {
+            revisions: self.revisions.load(Ordering::Relaxed),
+            identical: self.identical.load(Ordering::Relaxed),
+            reverts: self.reverts.load(Ordering::Relaxed),
+            reverted: self.reverted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl RevisionProcessor for RevertDetector {
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], _output: &mut Buffer) -> Result<bool> {
+        let hash = Sha1::digest(text).into();
+        self.revisions.fetch_add(1, Ordering::Relaxed);
+        self.pages
+            .lock()
+            .unwrap()
+            .entry(revision.page_id.to_owned())
+            .or_insert_with(|| PageRevisions {
+                namespace: revision.namespace.to_owned(),
+                title: revision.title.to_owned(),
+                revisions: Vec::new(),
+            })
+            .revisions
+            .push((revision.revision_id.to_owned(), hash));
+        Ok(false)
+    }
+
+    fn page_finished(&self, page_id: &str, output: &mut Buffer) -> Result<()> {
+        let page = match self.pages.lock().unwrap().remove(page_id) {
+            Some(page) => page,
+            None => return Ok(()),
+        };
+        let revisions = &page.revisions;
+        // index of the last revision with each text
+        let mut last_seen: HashMap<&[u8; 20], usize> = HashMap::new();
+        for (i, (revision_id, hash)) in revisions.iter().enumerate() {
+            if let Some(&restored) = last_seen.get(hash) {
+                let (kind, reverted) = if restored + 1 == i {
+                    self.identical.fetch_add(1, Ordering::Relaxed);
+                    ("identical", &revisions[i..i])
+                } else {
+                    self.reverts.fetch_add(1, Ordering::Relaxed);
+                    self.reverted.fetch_add((i - restored - 1) as u64, Ordering::Relaxed);
+                    ("revert", &revisions[restored + 1..i])
+                };
+                let reverted = reverted.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>().join(",");
+                writeln!(
+                    output,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    page_id, page.namespace, page.title, revision_id, kind, revisions[restored].0, reverted
+                )
+                .unwrap();
+            }
+            last_seen.insert(hash, i);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection};
+use simdutf8::basic::from_utf8;
+use wdgreplib::{MatchSink, MatchedRevision, Result};
+
+/// Number of matching revisions written in each transaction. Committing in batches keeps the rows written so far
+/// if the search is interrupted.
+const COMMIT_INTERVAL: u64 = 10_000;
+
+pub struct SqliteExport {
+    /// The connection with the number of matching revisions written in the current transaction
+    connection: Mutex<(Connection, u64)>,
+    include_text: bool,
+    commit_interval: u64,
+}
+
+impl SqliteExport {
+    /// Opens (or creates) the database file and creates the `matches` and `match_positions` tables if necessary.
+    pub fn create<P: AsRef<Path>>(path: P, include_text: bool) -> Result<SqliteExport> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS matches (
+                revision_id INTEGER PRIMARY KEY,
+                page_id INTEGER NOT NULL,
+                namespace INTEGER NOT NULL,
+                title TEXT NOT NULL,
+                match_count INTEGER NOT NULL,
+                text TEXT
+            );
+            CREATE INDEX IF NOT EXISTS matches_page_id ON matches (page_id);
+            CREATE INDEX IF NOT EXISTS matches_title ON matches (title);
+            CREATE TABLE IF NOT EXISTS match_positions (
+                revision_id INTEGER NOT NULL,
+                line_number INTEGER NOT NULL,
+                column_number INTEGER NOT NULL,
+                byte_start INTEGER NOT NULL,
+                byte_end INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS match_positions_revision_id ON match_positions (revision_id);
+            BEGIN;",
+        )?;
+        Ok(SqliteExport {
+            connection: Mutex::new((connection, 0)),
+            include_text,
+            commit_interval: COMMIT_INTERVAL,
+        })
+    }
+}
+
+impl MatchSink for SqliteExport {
+    fn add_match(&self, matched_revision: &MatchedRevision) -> Result<()> {
+        let text = if self.include_text {
+            Some(from_utf8(matched_revision.text)?)
+        } else {
+            None
+        };
+        let mut guard = self.connection.lock().unwrap();
+        let (connection, uncommitted) = &mut *guard;
+        let mut statement = connection.prepare_cached(
+            "INSERT OR REPLACE INTO matches (revision_id, page_id, namespace, title, match_count, text)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        statement.execute(params![
+            matched_revision.revision_id,
+            matched_revision.page_id,
+            matched_revision.namespace,
+            matched_revision.title,
+            matched_revision.match_count,
+            text
+        ])?;
+        // positions of an earlier search of the same revision are replaced as well
+        connection
+            .prepare_cached("DELETE FROM match_positions WHERE revision_id = ?1")?
+            .execute(params![matched_revision.revision_id])?;
+        let mut statement = connection.prepare_cached(
+            "INSERT INTO match_positions (revision_id, line_number, column_number, byte_start, byte_end)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for position in matched_revision.positions {
+            statement.execute(params![
+                matched_revision.revision_id,
+                position.line,
+                position.column,
+                position.start,
+                position.end
+            ])?;
+        }
+        *uncommitted += 1;
+        if *uncommitted >= self.commit_interval {
+            connection.execute_batch("COMMIT; BEGIN;")?;
+            *uncommitted = 0;
+        }
+        Ok(())
+    }
+
+    /// Commits the matching revisions written since the last batch, also called if the search failed.
+    fn finish(&self) -> Result<()> {
+        self.connection.lock().unwrap().0.execute_batch("COMMIT;")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wdgreplib::MatchPosition;
+
+    use super::*;
+
+    fn add_match(export: &SqliteExport, revision_id: u64) {
+        let positions = [MatchPosition {
+            line: 1,
+            column: 5,
+            start: 4,
+            end: 10,
+        }];
+        export
+            .add_match(&MatchedRevision {
+                page_id: 1,
+                namespace: 0,
+                title: "Alpha",
+                revision_id,
+                match_count: 1,
+                positions: &positions,
+                text: b"The needle",
+            })
+            .unwrap();
+    }
+
+    fn count_rows(path: &Path, table: &str) -> u64 {
+        let connection = Connection::open(path).unwrap();
+        connection
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_commit_in_batches() {
+        let path = std::env::temp_dir().join(format!("wdgrep-sqlite-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut export = SqliteExport::create(&path, true).unwrap();
+        export.commit_interval = 2;
+        for revision_id in 1..=3 {
+            add_match(&export, revision_id);
+        }
+        // the first batch is committed even if the export is not finished
+        assert_eq!(count_rows(&path, "matches"), 2);
+        assert_eq!(count_rows(&path, "match_positions"), 2);
+        export.finish().unwrap();
+        drop(export);
+        assert_eq!(count_rows(&path, "matches"), 3);
+        let connection = Connection::open(&path).unwrap();
+        let (title, text): (String, String) = connection
+            .query_row("SELECT title, text FROM matches WHERE revision_id = 3", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((title.as_str(), text.as_str()), ("Alpha", "The needle"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
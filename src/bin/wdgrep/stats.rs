@@ -0,0 +1,46 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::io::Write;
+
+use memchr::{memchr_iter, memmem};
+use simdutf8::basic::from_utf8;
+use termcolor::Buffer;
+use wdgreplib::{Result, RevisionInfo, RevisionProcessor};
+
+pub const PAGE_STATISTICS_HEADER: &str =
+    "page_id\tnamespace\ttitle\trevision_id\tbytes\twords\tlines\ttemplates\tlinks";
+
+/// Prints per-revision text statistics as tab-separated values.
+pub struct PageStatistics;
+
+impl RevisionProcessor for PageStatistics {
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], output: &mut Buffer) -> Result<bool> {
+        let words = from_utf8(text)?.split_whitespace().count();
+        let lines = match text.last() {
+            None => 0,
+            Some(b'\n') => memchr_iter(b'\n', text).count(),
+            Some(_) => memchr_iter(b'\n', text).count() + 1,
+        };
+        let templates = memmem::find_iter(text, b"{{").count();
+        let links = memmem::find_iter(text, b"[[").count();
+        writeln!(
+            output,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            revision.page_id,
+            revision.namespace,
+            revision.title,
+            revision.revision_id,
+            text.len(),
+            words,
+            lines,
+            templates,
+            links
+        )
+        .unwrap();
+        Ok(false)
+    }
+}
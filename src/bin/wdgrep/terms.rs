@@ -0,0 +1,203 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use simdutf8::basic::from_utf8;
+use termcolor::Buffer;
+use unicode_segmentation::UnicodeSegmentation;
+use wdgreplib::{Result, RevisionInfo, RevisionProcessor};
+
+pub const TERM_FREQUENCIES_HEADER: &str = "page_id\tnamespace\ttitle\trevision_id\tterm\tcount";
+
+/// Counts the occurrences of words and phrases in each revision and prints them as tab-separated values, history
+/// dumps give one row per revision and term. Text and terms are split into words at Unicode word boundaries, so
+/// punctuation and whitespace between words are ignored.
+pub struct TermFrequencies {
+    terms: Vec<String>,
+    // words of each term, lowercased if case-insensitive
+    term_words: Vec<Vec<String>>,
+    // first word -> indexes of the terms starting with it
+    terms_by_first_word: HashMap<String, Vec<usize>>,
+    case_insensitive: bool,
+}
+
+impl TermFrequencies {
+    /// Terms without any words are ignored, as are terms with the same words as an earlier one, which would only
+    /// duplicate its counts.
+    pub fn new<'t, I: IntoIterator<Item = &'t str>>(terms: I, case_insensitive: bool) -> TermFrequencies {
+        let mut term_frequencies = TermFrequencies {
+            terms: Vec::new(),
+            term_words: Vec::new(),
+            terms_by_first_word: HashMap::new(),
+            case_insensitive,
+        };
+        let mut seen_words = HashSet::new();
+        for term in terms {
+            let words: Vec<String> = term
+                .unicode_words()
+                .map(|word| term_frequencies.normalize(word).into_owned())
+                .collect();
+            if words.is_empty() || !seen_words.insert(words.clone()) {
+                continue;
+            }
+            if let Some(first_word) = words.first() {
+                term_frequencies
+                    .terms_by_first_word
+                    .entry(first_word.clone())
+                    .or_default()
+                    .push(term_frequencies.terms.len());
+                term_frequencies.terms.push(term.to_owned());
+                term_frequencies.term_words.push(words);
+            }
+        }
+        term_frequencies
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    fn normalize<'w>(&self, word: &'w str) -> Cow<'w, str> {
+        if self.case_insensitive && word.chars().any(char::is_uppercase) {
+            Cow::Owned(word.to_lowercase())
+        } else {
+            Cow::Borrowed(word)
+        }
+    }
+}
+
+impl RevisionProcessor for TermFrequencies {
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], output: &mut Buffer) -> Result<bool> {
+        let words: Vec<Cow<str>> = from_utf8(text)?
+            .unicode_words()
+            .map(|word| self.normalize(word))
+            .collect();
+        let mut counts = vec![0_u64; self.terms.len()];
+        for (i, word) in words.iter().enumerate() {
+            if let Some(terms) = self.terms_by_first_word.get(word.as_ref()) {
+                for &term in terms {
+                    let term_words = &self.term_words[term];
+                    if words.len() - i >= term_words.len()
+                        && term_words[1..]
+                            .iter()
+                            .zip(&words[i + 1..])
+                            .all(|(term_word, word)| term_word == word)
+                    {
+                        counts[term] += 1;
+                    }
+                }
+            }
+        }
+        let found = counts.iter().any(|&count| count > 0);
+        for (term, count) in self.terms.iter().zip(counts).filter(|(_, count)| *count > 0) {
+            writeln!(
+                output,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                revision.page_id, revision.namespace, revision.title, revision.revision_id, term, count
+            )
+            .unwrap();
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the counted terms and their counts in the text.
+    fn count(term_frequencies: &TermFrequencies, text: &str) -> Vec<(String, u64)> {
+        let revision = RevisionInfo {
+            page_id: "1",
+            namespace: "0",
+            title: "Alpha",
+            revision_id: "101",
+            model: "wikitext",
+            format: "text/x-wiki",
+            minor: false,
+            contributor_id: None,
+            contributor_ip: None,
+            text_len: None,
+        };
+        let mut output = Buffer::no_color();
+        let found = term_frequencies
+            .process_revision(&revision, text.as_bytes(), &mut output)
+            .unwrap();
+        let counts: Vec<(String, u64)> = std::str::from_utf8(output.as_slice())
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                assert_eq!(fields[..4], ["1", "0", "Alpha", "101"]);
+                (fields[4].to_owned(), fields[5].parse().unwrap())
+            })
+            .collect();
+        assert_eq!(found, !counts.is_empty());
+        counts
+    }
+
+    fn counts(expected: &[(&str, u64)]) -> Vec<(String, u64)> {
+        expected.iter().map(|&(term, count)| (term.to_owned(), count)).collect()
+    }
+
+    #[test]
+    fn test_phrases() {
+        let term_frequencies = TermFrequencies::new(["needle", "red needle", "red needle case"], false);
+        assert_eq!(
+            count(&term_frequencies, "A red needle, another needle and a red needle case."),
+            counts(&[("needle", 3), ("red needle", 2), ("red needle case", 1)])
+        );
+        // phrases do not match across other words or at the end of the text
+        assert_eq!(
+            count(&term_frequencies, "red thread, red needle"),
+            counts(&[("needle", 1), ("red needle", 1)])
+        );
+        assert_eq!(count(&term_frequencies, "a red"), counts(&[]));
+    }
+
+    #[test]
+    fn test_case_folding() {
+        let text = "Needle, NEEDLE and needle in the Red Needle.";
+        let case_sensitive = TermFrequencies::new(["needle", "Red Needle"], false);
+        assert_eq!(
+            count(&case_sensitive, text),
+            counts(&[("needle", 1), ("Red Needle", 1)])
+        );
+        let case_insensitive = TermFrequencies::new(["needle", "Red Needle"], true);
+        assert_eq!(
+            count(&case_insensitive, text),
+            counts(&[("needle", 4), ("Red Needle", 1)])
+        );
+        let case_insensitive = TermFrequencies::new(["ÄRGER"], true);
+        assert_eq!(count(&case_insensitive, "Ärger und ärger"), counts(&[("ÄRGER", 2)]));
+    }
+
+    #[test]
+    fn test_punctuation() {
+        let term_frequencies = TermFrequencies::new(["red needle", "New-York"], false);
+        assert_eq!(
+            count(
+                &term_frequencies,
+                "red, needle; red\n\n'''needle''' red-needle New York"
+            ),
+            counts(&[("red needle", 3), ("New-York", 1)])
+        );
+    }
+
+    #[test]
+    fn test_duplicate_terms() {
+        let term_frequencies = TermFrequencies::new(["needle", "red needle", "red, needle", "Needle", "", "--"], true);
+        assert_eq!(term_frequencies.terms, ["needle", "red needle"]);
+        assert_eq!(
+            count(&term_frequencies, "Red needle"),
+            counts(&[("needle", 1), ("red needle", 1)])
+        );
+        assert!(TermFrequencies::new([" ", "..."], false).is_empty());
+    }
+}
@@ -0,0 +1,35 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use wdgreplib::{MatchSink, MatchedRevision, Result};
+
+/// Writes the text of each matching revision to its own file `<dir>/<page id>_<revision id>.wiki`.
+pub struct WikiTextExport {
+    directory: PathBuf,
+}
+
+impl WikiTextExport {
+    pub fn create<P: AsRef<Path>>(directory: P) -> Result<WikiTextExport> {
+        let directory = directory.as_ref().to_owned();
+        fs::create_dir_all(&directory)?;
+        Ok(WikiTextExport { directory })
+    }
+}
+
+impl MatchSink for WikiTextExport {
+    fn add_match(&self, matched_revision: &MatchedRevision) -> Result<()> {
+        let file_name = format!("{}_{}.wiki", matched_revision.page_id, matched_revision.revision_id);
+        fs::write(self.directory.join(file_name), matched_revision.text)?;
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<()> {
+        Ok(())
+    }
+}
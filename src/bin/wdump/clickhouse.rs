@@ -0,0 +1,435 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::DateTime;
+use chrono_tz::Tz;
+use clickhouse_rs::types::Block;
+use clickhouse_rs::{row, ClientHandle, Pool};
+use quick_xml::de::Deserializer;
+use quick_xml::DeError;
+use serde::Deserialize;
+
+// Elements which are not needed, e.g. <origin> of revisions in newer export formats, are ignored.
+#[derive(Debug, Deserialize, PartialEq)]
+struct Page {
+    title: String,
+    ns: i16,
+    id: u32,
+    redirect: Option<Redirect>,
+    #[serde(rename = "revision", default)]
+    revisions: Vec<Revision>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Revision {
+    id: u32,
+    parentid: Option<u32>,
+    contributor: Contributor,
+    timestamp: String,
+    comment: Option<Comment>,
+    model: String,
+    format: String,
+    text: Text,
+    sha1: String,
+    minor: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Comment {
+    #[serde(rename = "$value")]
+    comment: Option<String>,
+    deleted: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Contributor {
+    ip: Option<String>,
+    username: Option<String>,
+    id: Option<u32>,
+    deleted: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Text {
+    bytes: Option<u32>,
+    id: Option<u32>,
+    deleted: Option<String>,
+    #[serde(rename = "$value")]
+    text: Option<String>,
+    #[serde(rename = "xml:space")]
+    xml_space: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Redirect {
+    title: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TableSchema {
+    /// All revisions, as found in history dumps. Revisions inserted again by a run resumed from a progress file
+    /// which was not updated after the last insert are deduplicated by revision id.
+    Revision,
+    /// Only the latest revision of each page, deduplicated by revision id.
+    Latest,
+}
+
+pub struct IngestOptions<'a> {
+    pub database_url: &'a str,
+    pub database: &'a str,
+    pub table: &'a str,
+    pub schema: TableSchema,
+    pub batch_size: usize,
+    pub progress_file: Option<&'a Path>,
+    pub dry_run: bool,
+    pub show_progress: bool,
+}
+
+/// Returns the database or table name quoted for statements, only names of ASCII letters, digits and underscores
+/// are accepted.
+fn quote_identifier(name: &str) -> Result<String> {
+    let is_valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !is_valid {
+        return Err(anyhow!(
+            "Invalid database or table name '{name}', only ASCII letters, digits and underscores are allowed."
+        ));
+    }
+    Ok(format!("`{name}`"))
+}
+
+fn get_qualified_table_name(options: &IngestOptions<'_>) -> Result<String> {
+    Ok(format!(
+        "{}.{}",
+        quote_identifier(options.database)?,
+        quote_identifier(options.table)?
+    ))
+}
+
+fn create_table_stmt(table: &str, schema: TableSchema) -> String {
+    match schema {
+        TableSchema::Revision => format!(
+            "
+    CREATE TABLE IF NOT EXISTS {table}
+    (
+        pageid UInt32 CODEC(Delta, ZSTD),
+        namespace Int16 CODEC(Delta, ZSTD),
+        title String CODEC(ZSTD),
+        timestamp DateTime('UTC') CODEC(Delta, ZSTD),
+        revisionid UInt32 CODEC(Delta, ZSTD),
+        parentid UInt32 CODEC(Delta, ZSTD),
+        userid UInt32 CODEC(Delta, ZSTD),
+        username String CODEC(ZSTD),
+        ipv4 IPv4 CODEC(Delta, ZSTD),
+        ipv6 IPv6 CODEC(ZSTD),
+        comment String CODEC(ZSTD),
+        text String CODEC(ZSTD(5)),
+        textid UInt32 CODEC(Delta, ZSTD),
+        textbytes UInt32 CODEC(Delta, ZSTD),
+        model LowCardinality(String) CODEC(ZSTD),
+        format LowCardinality(String) CODEC(ZSTD),
+        sha1 FixedString(32) CODEC(ZSTD),
+        minor UInt8 CODEC(Delta, ZSTD),
+        commentdeleted UInt8 CODEC(Delta, ZSTD),
+        userdeleted UInt8 CODEC(Delta, ZSTD),
+        textdeleted UInt8 CODEC(Delta, ZSTD)
+    )
+    ENGINE = ReplacingMergeTree()
+    PRIMARY KEY (pageid, timestamp)
+    ORDER BY (pageid, timestamp, revisionid)
+    "
+        ),
+        TableSchema::Latest => format!(
+            "
+    CREATE TABLE IF NOT EXISTS {table}
+    (
+        pageid UInt32 CODEC(Delta, ZSTD),
+        namespace Int16 CODEC(Delta, ZSTD),
+        title String CODEC(ZSTD),
+        timestamp DateTime('UTC') CODEC(Delta, ZSTD),
+        revisionid UInt32 CODEC(Delta, ZSTD),
+        parentid UInt32 CODEC(Delta, ZSTD),
+        userid UInt32 CODEC(Delta, ZSTD),
+        username String CODEC(ZSTD),
+        ipv4 IPv4 CODEC(Delta, ZSTD),
+        ipv6 IPv6 CODEC(ZSTD),
+        comment String CODEC(ZSTD),
+        textid UInt32 CODEC(Delta, ZSTD),
+        textbytes UInt32 CODEC(Delta, ZSTD),
+        text String CODEC(ZSTD(5)),
+        model LowCardinality(String) CODEC(ZSTD),
+        format LowCardinality(String) CODEC(ZSTD),
+        sha1 FixedString(32) CODEC(ZSTD),
+        minor UInt8 CODEC(Delta, ZSTD),
+        commentdeleted UInt8 CODEC(Delta, ZSTD),
+        userdeleted UInt8 CODEC(Delta, ZSTD),
+        textdeleted UInt8 CODEC(Delta, ZSTD)
+    )
+    ENGINE = ReplacingMergeTree(revisionid)
+    ORDER BY pageid
+    "
+        ),
+    }
+}
+
+// Pages are ordered by page id in all dumps, so the id of the last completely ingested page
+// is sufficient to resume an interrupted ingestion.
+fn read_progress(progress_file: &Path) -> Result<Option<u32>> {
+    match fs::read_to_string(progress_file) {
+        Ok(content) => {
+            Ok(Some(content.trim().parse().map_err(|_| {
+                anyhow!("Invalid progress file {}", progress_file.to_string_lossy())
+            })?))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_progress(progress_file: &Path, last_page_id: u32) -> Result<()> {
+    let mut part_file_name = progress_file.as_os_str().to_owned();
+    part_file_name.push(".part");
+    fs::write(&part_file_name, last_page_id.to_string())?;
+    fs::rename(&part_file_name, progress_file)?;
+    Ok(())
+}
+
+// The deserializer cannot skip elements, so consume everything up to and including </siteinfo>.
+fn skip_siteinfo<T: BufRead>(buf_reader: &mut T) -> Result<()> {
+    let mut line = Vec::with_capacity(1024);
+    loop {
+        line.clear();
+        if buf_reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(anyhow!("EOF while looking for end tag </siteinfo>"));
+        }
+        if line.trim_ascii() == b"</siteinfo>" {
+            return Ok(());
+        }
+    }
+}
+
+/// Ingests the pages of the dump, they are only parsed if there is no client for a dry run.
+pub async fn ingest_stream<T: BufRead + Send>(
+    buf_reader: &mut T,
+    client: &mut Option<ClientHandle>,
+    options: &IngestOptions<'_>,
+) -> Result<()> {
+    skip_siteinfo(buf_reader)?;
+    let mut deserializer = Deserializer::from_reader(buf_reader);
+    let table = get_qualified_table_name(options)?;
+    let resume_after = match options.progress_file {
+        Some(progress_file) => read_progress(progress_file)?,
+        None => None,
+    };
+    let mut record_count = 0;
+    let mut total_record_count = 0_u64;
+    let mut block = Block::with_capacity(options.batch_size);
+    let mut last_page_id = None;
+    loop {
+        let page_res = Page::deserialize(&mut deserializer);
+        if let Err(DeError::End) = page_res {
+            // done
+            break;
+        }
+        let page = page_res?;
+        if resume_after.is_some_and(|resume_after| page.id <= resume_after) {
+            continue;
+        }
+        for revision in page.revisions {
+            let timestamp = DateTime::parse_from_rfc3339(revision.timestamp.as_ref())?.with_timezone(&Tz::Zulu);
+
+            let mut comment = "";
+            let mut commentdeleted = 0_u8;
+            if let Some(ref rev_comment) = revision.comment {
+                if let Some(ref rev_comment_text) = rev_comment.comment {
+                    comment = rev_comment_text.as_str();
+                } else if rev_comment.deleted.is_some() {
+                    commentdeleted = 1;
+                }
+            }
+            let mut ipv4 = "0.0.0.0";
+            let mut ipv6 = "::";
+            if let Some(s) = revision.contributor.ip.as_deref() {
+                if s.contains('.') {
+                    ipv4 = s;
+                } else if s.contains(':') {
+                    ipv6 = s;
+                } else {
+                    return Err(anyhow!("Could not parse IP address '{}'", s.to_owned()));
+                }
+            }
+            block.push(row! {
+                pageid: page.id,
+                namespace: page.ns,
+                title: page.title.as_str(),
+                revisionid: revision.id,
+                parentid: revision.parentid.unwrap_or(0),
+                timestamp: timestamp,
+                comment: comment,
+                model: revision.model.as_str(),
+                format: revision.format.as_str(),
+                sha1: revision.sha1.as_str(),
+                ipv4: ipv4,
+                ipv6: ipv6,
+                username: revision.contributor.username.as_deref().unwrap_or(""),
+                userid: revision.contributor.id.unwrap_or(0),
+                textid: revision.text.id.unwrap_or(0),
+                textbytes: revision.text.bytes.unwrap_or(0),
+                text: revision.text.text.as_deref().unwrap_or(""),
+                commentdeleted: commentdeleted,
+                userdeleted: u8::from(revision.contributor.deleted.is_some()),
+                textdeleted: u8::from(revision.text.deleted.is_some()),
+                minor: u8::from(revision.minor.is_some())
+            })?;
+            record_count += 1;
+        }
+        last_page_id = Some(page.id);
+        // only insert complete pages so that the progress file is accurate
+        if record_count >= options.batch_size {
+            total_record_count += record_count as u64;
+            insert_batch(client, &table, block, page.id, options).await?;
+            record_count = 0;
+            block = Block::with_capacity(options.batch_size);
+            if options.show_progress {
+                eprint!("\rIngested {total_record_count} revisions.");
+                std::io::stderr().flush().unwrap();
+            }
+        }
+    }
+    if let Some(last_page_id) = last_page_id {
+        if record_count > 0 {
+            total_record_count += record_count as u64;
+            insert_batch(client, &table, block, last_page_id, options).await?;
+        }
+    }
+    if options.show_progress {
+        eprintln!("\rIngested {total_record_count} revisions.");
+    }
+    Ok(())
+}
+
+async fn insert_batch(
+    client: &mut Option<ClientHandle>,
+    table: &str,
+    block: Block,
+    last_page_id: u32,
+    options: &IngestOptions<'_>,
+) -> Result<()> {
+    if let Some(client) = client {
+        // if the progress file is not written after the insert, the batch is inserted again by a resumed run and its
+        // revisions are removed when ClickHouse merges the parts of the table
+        client.insert(table, block).await?;
+        if let Some(progress_file) = options.progress_file {
+            write_progress(progress_file, last_page_id)?;
+        }
+    }
+    Ok(())
+}
+
+/// Connects to the database and creates the table if necessary, a dry run does not connect.
+pub async fn connect(options: &IngestOptions<'_>) -> Result<Option<ClientHandle>> {
+    let table = get_qualified_table_name(options)?;
+    if options.dry_run {
+        return Ok(None);
+    }
+    let pool = Pool::new(options.database_url);
+    let mut client = pool.get_handle().await?;
+    client
+        .execute(format!(
+            "CREATE DATABASE IF NOT EXISTS {}",
+            quote_identifier(options.database)?
+        ))
+        .await?;
+    client.execute(create_table_stmt(&table, options.schema)).await?;
+    Ok(Some(client))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_progress() {
+        let progress_file = std::env::temp_dir().join(format!("wdump-progress-test-{}", std::process::id()));
+        let _ = fs::remove_file(&progress_file);
+        assert_eq!(read_progress(&progress_file).unwrap(), None);
+        write_progress(&progress_file, 42).unwrap();
+        assert_eq!(read_progress(&progress_file).unwrap(), Some(42));
+        write_progress(&progress_file, 1234).unwrap();
+        assert_eq!(read_progress(&progress_file).unwrap(), Some(1234));
+        fs::write(&progress_file, "not a page id").unwrap();
+        assert!(read_progress(&progress_file).is_err());
+        fs::remove_file(&progress_file).unwrap();
+    }
+
+    #[test]
+    fn test_create_table_stmt() {
+        let revision = create_table_stmt("`wiki`.`dewiki`", TableSchema::Revision);
+        assert!(revision.contains("CREATE TABLE IF NOT EXISTS `wiki`.`dewiki`\n"));
+        // revisions inserted again after resuming are removed
+        assert!(revision.contains("ENGINE = ReplacingMergeTree()"));
+        assert!(revision.contains("PRIMARY KEY (pageid, timestamp)"));
+        assert!(revision.contains("ORDER BY (pageid, timestamp, revisionid)"));
+        let latest = create_table_stmt("`wiki`.`dewiki_latest`", TableSchema::Latest);
+        assert!(latest.contains("CREATE TABLE IF NOT EXISTS `wiki`.`dewiki_latest`\n"));
+        // later revisions of a page replace earlier ones
+        assert!(latest.contains("ENGINE = ReplacingMergeTree(revisionid)"));
+        assert!(latest.contains("ORDER BY pageid"));
+        for stmt in [revision, latest] {
+            assert!(stmt.contains("text String CODEC(ZSTD(5))"));
+            assert!(stmt.contains("sha1 FixedString(32)"));
+        }
+    }
+
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("dewiki").unwrap(), "`dewiki`");
+        assert_eq!(quote_identifier("be_x_oldwiki").unwrap(), "`be_x_oldwiki`");
+        assert_eq!(quote_identifier("_tmp2").unwrap(), "`_tmp2`");
+        for name in [
+            "",
+            "2wiki",
+            "de-wiki",
+            "wiki.revision",
+            "wiki`; DROP TABLE x; --",
+            "wiki revision",
+        ] {
+            assert!(quote_identifier(name).is_err(), "{name} was accepted");
+        }
+    }
+
+    #[test]
+    fn test_ignore_unknown_elements() {
+        let xml = r#"<page>
+    <title>Alpha</title>
+    <ns>0</ns>
+    <id>1</id>
+    <revision>
+      <id>101</id>
+      <origin>101</origin>
+      <timestamp>2024-01-01T00:00:00Z</timestamp>
+      <contributor>
+        <username>Tester</username>
+        <id>7</id>
+      </contributor>
+      <model>wikitext</model>
+      <format>text/x-wiki</format>
+      <text bytes="10" sha1="phoiac9h4m842xq45sp7s6u21eteeq1" xml:space="preserve">The needle</text>
+      <sha1>phoiac9h4m842xq45sp7s6u21eteeq1</sha1>
+    </revision>
+  </page>"#;
+        let page: Page = quick_xml::de::from_str(xml).unwrap();
+        assert_eq!(page.title, "Alpha");
+        assert_eq!(page.revisions.len(), 1);
+        assert_eq!(page.revisions[0].contributor.username.as_deref(), Some("Tester"));
+        assert_eq!(page.revisions[0].text.text.as_deref(), Some("The needle"));
+    }
+}
@@ -0,0 +1,112 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Helpers shared by the `extract` subcommands.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use termcolor::Buffer;
+use wdgreplib::siteinfo::DumpHeader;
+use wdgreplib::Result;
+
+/// Normalizes titles found in wikitext the way MediaWiki does, using the namespaces and title case of the wiki.
+pub struct Titles {
+    // lowercase namespace name, namespace name and number, without the main namespace
+    namespaces: Vec<(String, String, i32)>,
+    first_letter_case: bool,
+}
+
+impl Titles {
+    pub fn new(dump_header: &DumpHeader) -> Titles {
+        Titles {
+            namespaces: dump_header
+                .namespaces
+                .iter()
+                .filter(|(_, name)| !name.is_empty())
+                .map(|(&key, name)| (name.to_lowercase(), name.clone(), key))
+                .collect(),
+            first_letter_case: dump_header.case.is_empty() || dump_header.case == "first-letter",
+        }
+    }
+
+    fn capitalize(&self, title: &str) -> String {
+        let mut chars = title.chars();
+        match chars.next() {
+            Some(first) if self.first_letter_case => first.to_uppercase().chain(chars).collect(),
+            _ => title.to_owned(),
+        }
+    }
+
+    /// Replaces underscores with spaces, collapses whitespace and uppercases the first letter if the wiki does so.
+    pub fn normalize(&self, title: &str) -> String {
+        let title = title.replace('_', " ");
+        self.capitalize(&title.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    /// Splits a normalized title into namespace number, namespace name as given in the dump header and the rest of
+    /// the title with its first letter case applied. Returns `None` if the title has no namespace prefix.
+    pub fn split_namespace(&self, title: &str) -> Option<(i32, &str, String)> {
+        let (prefix, rest) = title.split_once(':')?;
+        let prefix = prefix.trim_end().to_lowercase();
+        self.namespaces
+            .iter()
+            .find(|(lowercase_name, _, _)| *lowercase_name == prefix)
+            .map(|(_, name, key)| (*key, name.as_str(), self.capitalize(rest.trim_start())))
+    }
+}
+
+/// Tab-separated output written to a file or, if none is given, to stdout.
+pub struct TsvOutput {
+    output_file: Option<Mutex<BufWriter<File>>>,
+}
+
+impl TsvOutput {
+    /// Creates the output file if given and writes the header line.
+    pub fn create(output_file: Option<&Path>, header: &str) -> Result<TsvOutput> {
+        let output_file = match output_file {
+            Some(output_file) => {
+                let mut writer = BufWriter::new(File::create(output_file)?);
+                writeln!(writer, "{header}")?;
+                Some(Mutex::new(writer))
+            }
+            None => {
+                println!("{header}");
+                None
+            }
+        };
+        Ok(TsvOutput { output_file })
+    }
+
+    /// Writes complete lines, either to the output file or to the output buffer of the revision.
+    pub fn write(&self, lines: &[u8], output: &mut Buffer) -> Result<()> {
+        match &self.output_file {
+            Some(output_file) => output_file.lock().unwrap().write_all(lines)?,
+            None => output.write_all(lines)?,
+        }
+        Ok(())
+    }
+
+    /// Writes lines computed after processing the dump and flushes the output file.
+    pub fn finish_with(self, lines: &[u8]) -> Result<()> {
+        match self.output_file {
+            Some(output_file) => {
+                let mut writer = output_file.into_inner().unwrap();
+                writer.write_all(lines)?;
+                writer.flush()?;
+            }
+            None => std::io::stdout().lock().write_all(lines)?,
+        }
+        Ok(())
+    }
+
+    /// Flushes the output file.
+    pub fn finish(self) -> Result<()> {
+        self.finish_with(&[])
+    }
+}
@@ -0,0 +1,92 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::bytes::Regex;
+use simdutf8::basic::from_utf8;
+use termcolor::Buffer;
+use wdgreplib::siteinfo::DumpHeader;
+use wdgreplib::{Result, RevisionInfo, RevisionProcessor};
+
+use crate::extract::{Titles, TsvOutput};
+
+const LINKS_HEADER: &str = "source_page_id\ttarget_title";
+
+const FILE_NAMESPACE: i32 = 6;
+const CATEGORY_NAMESPACE: i32 = 14;
+
+lazy_static! {
+    // only the target is captured, the label may contain nested links, e.g. in file captions
+    static ref LINK_REGEX: Regex = Regex::new(r"\[\[([^\[\]|\n]+)(?:\||\]\])").unwrap();
+}
+
+/// Extracts the targets of the wikilinks in the text of each revision and writes one (source page id, target title)
+/// edge per distinct target. Links generated by templates are not found since templates are not expanded.
+pub struct LinkExtractor {
+    titles: Titles,
+    output: TsvOutput,
+}
+
+impl LinkExtractor {
+    /// Namespace prefixes and title case are taken from the dump header. Edges are written to the output file if
+    /// given, otherwise to stdout.
+    pub fn new(dump_header: &DumpHeader, output_file: Option<&Path>) -> Result<LinkExtractor> {
+        Ok(LinkExtractor {
+            titles: Titles::new(dump_header),
+            output: TsvOutput::create(output_file, LINKS_HEADER)?,
+        })
+    }
+
+    pub fn finish(self) -> Result<()> {
+        self.output.finish()
+    }
+
+    /// Normalizes a link target to the title of the linked page as found in the dump. Returns `None` for links to
+    /// sections of the same page and for file and category links, which embed files or categorize the page.
+    fn get_target_title(&self, target: &str) -> Option<String> {
+        let target = target.split('#').next().unwrap_or_default().trim();
+        let (target, leading_colon) = match target.strip_prefix(':') {
+            Some(target) => (target, true),
+            None => (target, false),
+        };
+        let target = self.titles.normalize(target);
+        if target.is_empty() {
+            return None;
+        }
+        match self.titles.split_namespace(&target) {
+            Some((key, _, _)) if !leading_colon && (key == FILE_NAMESPACE || key == CATEGORY_NAMESPACE) => None,
+            Some((_, _, title)) if title.is_empty() => None,
+            Some((_, name, title)) => Some(format!("{name}:{title}")),
+            None => Some(target),
+        }
+    }
+}
+
+impl RevisionProcessor for LinkExtractor {
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], output: &mut Buffer) -> Result<bool> {
+        let mut targets = HashSet::new();
+        let mut edges = Vec::new();
+        for captures in LINK_REGEX.captures_iter(text) {
+            // UNWRAP: group 1 always participates in a match
+            let target = from_utf8(captures.get(1).unwrap().as_bytes())?;
+            if let Some(target_title) = self.get_target_title(target) {
+                if targets.insert(target_title.clone()) {
+                    writeln!(edges, "{}\t{}", revision.page_id, target_title)?;
+                }
+            }
+        }
+        self.output.write(&edges, output)?;
+        Ok(false)
+    }
+
+    fn required_literal(&self) -> Option<&[u8]> {
+        Some(b"[[")
+    }
+}
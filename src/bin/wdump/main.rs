@@ -0,0 +1,553 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+mod clickhouse;
+mod extract;
+mod links;
+mod recompress;
+mod serve;
+mod templates;
+mod urls;
+mod validate;
+
+use std::io::BufReader;
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::process;
+use std::time::Instant;
+
+use anyhow::{anyhow, bail, Result};
+use clap::{crate_authors, crate_version, Arg, ArgAction, ArgMatches};
+use clickhouse::{IngestOptions, TableSchema};
+use links::LinkExtractor;
+use recompress::RecompressOptions;
+use templates::TemplateExtractor;
+use urls::UrlExtractor;
+use wdgreplib::index::{get_index_file_name, make_index};
+use wdgreplib::siteinfo::{self, DumpHeader};
+use wdgreplib::{is_compressed, open_dump_file, process_dump, DecompressingReader, SearchOptions};
+
+#[global_allocator]
+static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+/// Opens a dump file, compressed files are decompressed the same way as by wdgrep.
+fn open_dump(dump_file: &Path) -> Result<DecompressingReader> {
+    let dump_file = dump_file
+        .to_str()
+        .ok_or_else(|| anyhow!("Dump file name is invalid."))?;
+    Ok(open_dump_file(dump_file, &SearchOptions::new())?)
+}
+
+fn get_dump_file_and_name(subcommand_matches: &ArgMatches) -> Result<(&Path, &str)> {
+    let dump_file = Path::new(subcommand_matches.get_one::<String>("dump file").unwrap());
+    if !dump_file.is_file() {
+        bail!(
+            "Dump file {} does not exist or is not accessible.",
+            dump_file.to_string_lossy()
+        );
+    }
+    let file_name = dump_file
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .ok_or_else(|| anyhow!("Dump file name is invalid."))?;
+    Ok((dump_file, file_name))
+}
+
+async fn ingest_clickhouse(subcommand_matches: &ArgMatches) -> Result<()> {
+    let (dump_file, file_name) = get_dump_file_and_name(subcommand_matches)?;
+    let database = match subcommand_matches.get_one::<String>("database") {
+        Some(database) => database.as_str(),
+        None => {
+            &file_name[..file_name.find('-').ok_or_else(|| {
+                anyhow!("Could not determine database name from dump file name, please specify --database.")
+            })?]
+        }
+    };
+    let schema = match subcommand_matches.get_one::<String>("schema").map(String::as_str) {
+        Some("revision") => TableSchema::Revision,
+        Some("latest") => TableSchema::Latest,
+        Some(_) => unreachable!("Unknown schema, should be caught by arg matching."),
+        None if file_name.contains("-history") => TableSchema::Revision,
+        None => TableSchema::Latest,
+    };
+    let table = match subcommand_matches.get_one::<String>("table") {
+        Some(table) => table.as_str(),
+        None if schema == TableSchema::Revision => "revision",
+        None => "latest",
+    };
+    let batch_size = subcommand_matches
+        .get_one::<String>("batch-size")
+        .map(|s| str::parse::<NonZeroUsize>(s))
+        .transpose()
+        .map_err(|_| anyhow!("Invalid number for batch size option."))?
+        .map_or(1000, NonZeroUsize::get);
+    let options = IngestOptions {
+        database_url: subcommand_matches.get_one::<String>("url").unwrap(),
+        database,
+        table,
+        schema,
+        batch_size,
+        progress_file: subcommand_matches.get_one::<String>("progress-file").map(Path::new),
+        dry_run: subcommand_matches.get_flag("dry-run"),
+        show_progress: !subcommand_matches.get_flag("quiet") && atty::is(atty::Stream::Stderr),
+    };
+
+    let mut client = clickhouse::connect(&options).await?;
+    let mut reader = open_dump(dump_file)?;
+    let mut buf_reader = BufReader::with_capacity(2 * 1024 * 1024, &mut reader);
+    clickhouse::ingest_stream(&mut buf_reader, &mut client, &options).await?;
+    reader.finish()?;
+    Ok(())
+}
+
+fn validate(subcommand_matches: &ArgMatches) -> Result<()> {
+    let (dump_file, file_name) = get_dump_file_and_name(subcommand_matches)?;
+    let max_violations = subcommand_matches
+        .get_one::<String>("max-violations")
+        .map(|s| str::parse::<NonZeroUsize>(s))
+        .transpose()
+        .map_err(|_| anyhow!("Invalid number for maximum violations option."))?
+        .map_or(10, NonZeroUsize::get);
+    let thread_count = subcommand_matches
+        .get_one::<String>("threads")
+        .map(|s| str::parse::<NonZeroUsize>(s))
+        .transpose()
+        .map_err(|_| anyhow!("Invalid number for threads option."))?
+        .map_or_else(rayon::current_num_threads, NonZeroUsize::get);
+
+    let violations = if is_compressed(file_name) {
+        let mut reader = open_dump(dump_file)?;
+        let violations =
+            validate::validate_stream(BufReader::with_capacity(2 * 1024 * 1024, &mut reader), max_violations);
+        if violations.len() < max_violations {
+            reader.finish()?;
+        } else {
+            // stopped early
+            reader.abort();
+        }
+        violations
+    } else {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(thread_count)
+            .build()?
+            .install(|| validate::validate_file(dump_file, max_violations, thread_count))?
+    };
+    for violation in &violations {
+        println!("{}: {}", violation.offset, violation.message);
+    }
+    if !violations.is_empty() {
+        bail!("Dump is invalid.");
+    }
+    eprintln!("Dump is valid.");
+    Ok(())
+}
+
+async fn serve(subcommand_matches: &ArgMatches) -> Result<()> {
+    let dump_file_or_prefix = subcommand_matches.get_one::<String>("dump file or prefix").unwrap();
+    let (dump_files, _) = wdgreplib::get_dump_files(dump_file_or_prefix)?;
+    let thread_count = subcommand_matches
+        .get_one::<String>("threads")
+        .map(|s| str::parse::<NonZeroUsize>(s))
+        .transpose()
+        .map_err(|_| anyhow!("Invalid number for threads option."))?;
+    serve::serve(
+        dump_files,
+        thread_count,
+        subcommand_matches.get_one::<String>("listen").unwrap(),
+    )
+    .await
+}
+
+/// Returns the dump files, their header and the search options shared by the extract subcommands, which only
+/// read the main namespace.
+fn prepare_extraction(subcommand_matches: &ArgMatches) -> Result<(Vec<String>, DumpHeader, SearchOptions<'static>)> {
+    let dump_file_or_prefix = subcommand_matches.get_one::<String>("dump file or prefix").unwrap();
+    let (dump_files, _) = wdgreplib::get_dump_files(dump_file_or_prefix)?;
+    let thread_count = subcommand_matches
+        .get_one::<String>("threads")
+        .map(|s| str::parse::<NonZeroUsize>(s))
+        .transpose()
+        .map_err(|_| anyhow!("Invalid number for threads option."))?;
+    // CirrusSearch dumps have no siteinfo, the defaults treat all prefixes as part of the title
+    let dump_header = siteinfo::parse_dump_header(&dump_files[0]).unwrap_or_default();
+    let mut search_options = SearchOptions::new();
+    search_options.restrict_namespaces(&["0"]);
+    if let Some(thread_count) = thread_count {
+        search_options.with_thread_count(thread_count);
+    }
+    Ok((dump_files, dump_header, search_options))
+}
+
+fn extract_links(subcommand_matches: &ArgMatches) -> Result<()> {
+    let (dump_files, dump_header, search_options) = prepare_extraction(subcommand_matches)?;
+    let output_file = subcommand_matches.get_one::<String>("output").map(Path::new);
+    let link_extractor = LinkExtractor::new(&dump_header, output_file)?;
+    process_dump(&dump_files, &link_extractor, &search_options)?;
+    link_extractor.finish()?;
+    Ok(())
+}
+
+fn extract_templates(subcommand_matches: &ArgMatches) -> Result<()> {
+    let (dump_files, dump_header, search_options) = prepare_extraction(subcommand_matches)?;
+    let output_file = subcommand_matches.get_one::<String>("output").map(Path::new);
+    let parameter = subcommand_matches.get_one::<String>("parameter").map(String::as_str);
+    let template_extractor = TemplateExtractor::new(&dump_header, output_file, parameter)?;
+    process_dump(&dump_files, &template_extractor, &search_options)?;
+    template_extractor.finish()?;
+    Ok(())
+}
+
+fn extract_urls(subcommand_matches: &ArgMatches) -> Result<()> {
+    let (dump_files, _, search_options) = prepare_extraction(subcommand_matches)?;
+    let output_file = subcommand_matches.get_one::<String>("output").map(Path::new);
+    let url_extractor = UrlExtractor::new(output_file, subcommand_matches.get_flag("domains"))?;
+    process_dump(&dump_files, &url_extractor, &search_options)?;
+    url_extractor.finish()?;
+    Ok(())
+}
+
+fn recompress(subcommand_matches: &ArgMatches) -> Result<()> {
+    let (dump_file, file_name) = get_dump_file_and_name(subcommand_matches)?;
+    if file_name.ends_with(".zst") {
+        bail!("Dump file is already zstd-compressed.");
+    }
+    let output_file = match subcommand_matches.get_one::<String>("output") {
+        Some(output_file) => output_file.to_owned(),
+        None => recompress::get_output_file_name(&dump_file.to_string_lossy())?,
+    };
+    if !output_file.ends_with(".xml.zst") {
+        bail!("The output file name must end with .xml.zst.");
+    }
+    let index_file = get_index_file_name(&output_file);
+    if Path::new(&index_file).exists() {
+        bail!("Index file {index_file} already exists.");
+    }
+    let pages_per_frame = subcommand_matches
+        .get_one::<String>("pages")
+        .map(|s| str::parse::<NonZeroUsize>(s))
+        .transpose()
+        .map_err(|_| anyhow!("Invalid number for pages option."))?
+        .unwrap_or(NonZeroUsize::new(100).unwrap());
+    let level = subcommand_matches
+        .get_one::<String>("level")
+        .map(|s| str::parse::<i32>(s))
+        .transpose()
+        .map_err(|_| anyhow!("Invalid number for level option."))?
+        .unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+    let thread_count = subcommand_matches
+        .get_one::<String>("threads")
+        .map(|s| str::parse::<NonZeroUsize>(s))
+        .transpose()
+        .map_err(|_| anyhow!("Invalid number for threads option."))?;
+    let options = RecompressOptions {
+        pages_per_frame,
+        level,
+        thread_count,
+    };
+
+    let now = Instant::now();
+    let mut reader = open_dump(dump_file)?;
+    let result = recompress::recompress_stream(
+        BufReader::with_capacity(2 * 1024 * 1024, &mut reader),
+        Path::new(&output_file),
+        &options,
+    );
+    reader.finish()?;
+    let result = result?;
+    eprintln!(
+        "Recompressed {} pages into {} frames ({} bytes) in {:.2} seconds, content SHA-1 {} verified.",
+        result.page_count,
+        result.frame_count,
+        result.compressed_size,
+        now.elapsed().as_secs_f64(),
+        result.sha1
+    );
+    let page_count = make_index(&output_file, &index_file)?;
+    eprintln!("Indexed {page_count} pages, written to {index_file}.");
+    Ok(())
+}
+
+async fn run() -> Result<()> {
+    let matches = clap::Command::new("WikiDump")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("Process Wikipedia and other Wikimedia wiki dumps.")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            clap::Command::new("ingest")
+                .about("Ingest a wiki dump into a database")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    clap::Command::new("clickhouse")
+                        .about("Ingest a pages or history dump into ClickHouse")
+                        .arg(
+                            Arg::new("dump file")
+                                .help("The dump file to ingest (plain, .gz, .bz2 or .7z)")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("url")
+                                .long("url")
+                                .value_name("url")
+                                .default_value("tcp://localhost:9000/?compression=lz4")
+                                .help("ClickHouse connection URL"),
+                        )
+                        .arg(
+                            Arg::new("database")
+                                .long("database")
+                                .value_name("name")
+                                .help("Database name, defaults to the wiki name from the dump file name"),
+                        )
+                        .arg(
+                            Arg::new("table")
+                                .long("table")
+                                .value_name("name")
+                                .help("Table name, defaults to \"revision\" or \"latest\" depending on the schema"),
+                        )
+                        .arg(
+                            Arg::new("schema")
+                                .long("schema")
+                                .value_parser(["revision", "latest"])
+                                .value_name("schema")
+                                .help(
+                                    "Table schema: all revisions or only the latest revision per page, \
+                                     defaults to \"revision\" for history dumps and \"latest\" otherwise",
+                                ),
+                        )
+                        .arg(
+                            Arg::new("batch-size")
+                                .long("batch-size")
+                                .value_name("num")
+                                .help("Minimum number of revisions inserted at once, defaults to 1000"),
+                        )
+                        .arg(
+                            Arg::new("progress-file")
+                                .long("progress-file")
+                                .value_name("path")
+                                .help("File tracking ingestion progress, an interrupted run resumes from it"),
+                        )
+                        .arg(
+                            Arg::new("dry-run")
+                                .short('n')
+                                .long("dry-run")
+                                .help("Parse the dump without inserting anything")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("quiet")
+                                .short('q')
+                                .long("quiet")
+                                .help("Don't print progress updates")
+                                .action(ArgAction::SetTrue),
+                        ),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("validate")
+                .about("Check a dump for XML well-formedness and the expected page and revision structure")
+                .arg(
+                    Arg::new("dump file")
+                        .help("The dump file to check (plain, .gz, .bz2 or .7z), compressed files are checked sequentially")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("max-violations")
+                        .short('n')
+                        .long("max-violations")
+                        .value_name("num")
+                        .help("Number of violations reported, defaults to 10"),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .short('j')
+                        .long("threads")
+                        .value_name("num")
+                        .help("Number of parts checked in parallel, defaults to the number of logical cpus"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("serve")
+                .about("Answer search (/search?q=regex&ns=0) and page (/page?title=title) requests over HTTP")
+                .arg(
+                    Arg::new("dump file or prefix")
+                        .help("The dump file or common prefix of muliple dump files to search")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("listen")
+                        .long("listen")
+                        .value_name("address")
+                        .default_value("127.0.0.1:8080")
+                        .help("Address and port to listen on"),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .short('j')
+                        .long("threads")
+                        .value_name("num")
+                        .help("Number of threads searching in parallel, defaults to the number of logical cpus"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("recompress")
+                .about(
+                    "Recompress a dump into a seekable zstd dump with an index, which is searched in parallel \
+                     like an uncompressed dump",
+                )
+                .arg(
+                    Arg::new("dump file")
+                        .help("The dump file to recompress (plain, .gz, .bz2 or .7z)")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("file")
+                        .help("The seekable zstd dump file, defaults to the dump file name ending with .xml.zst"),
+                )
+                .arg(
+                    Arg::new("pages")
+                        .long("pages")
+                        .value_name("num")
+                        .help("Number of pages per zstd frame, defaults to 100 like multistream dumps"),
+                )
+                .arg(
+                    Arg::new("level")
+                        .long("level")
+                        .value_name("num")
+                        .help("zstd compression level, defaults to 3"),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .short('j')
+                        .long("threads")
+                        .value_name("num")
+                        .help("Number of frames compressed in parallel, defaults to the number of logical cpus"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("extract")
+                .about("Extract data from the pages of a wiki dump")
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    clap::Command::new("links")
+                        .about("Write the wikilinks of main namespace pages as a (source page id, target title) edge list")
+                        .arg(
+                            Arg::new("dump file or prefix")
+                                .help("The dump file or common prefix of muliple dump files to read")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .short('o')
+                                .long("output")
+                                .value_name("file")
+                                .help("File to write the tab-separated edges to, defaults to stdout"),
+                        )
+                        .arg(
+                            Arg::new("threads")
+                                .short('j')
+                                .long("threads")
+                                .value_name("num")
+                                .help("Number of threads reading in parallel, defaults to the number of logical cpus"),
+                        ),
+                )
+                .subcommand(
+                    clap::Command::new("templates")
+                        .about(
+                            "Write the template invocations of main namespace pages as \
+                             (page id, title, template, parameter count) tuples",
+                        )
+                        .arg(
+                            Arg::new("dump file or prefix")
+                                .help("The dump file or common prefix of muliple dump files to read")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("parameter")
+                                .short('p')
+                                .long("parameter")
+                                .value_name("name")
+                                .help("Also write the unexpanded value of this parameter, positional parameters are named 1, 2, ..."),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .short('o')
+                                .long("output")
+                                .value_name("file")
+                                .help("File to write the tab-separated tuples to, defaults to stdout"),
+                        )
+                        .arg(
+                            Arg::new("threads")
+                                .short('j')
+                                .long("threads")
+                                .value_name("num")
+                                .help("Number of threads reading in parallel, defaults to the number of logical cpus"),
+                        ),
+                )
+                .subcommand(
+                    clap::Command::new("urls")
+                        .about("Write the external links of main namespace pages as (page id, title, URL) tuples")
+                        .arg(
+                            Arg::new("dump file or prefix")
+                                .help("The dump file or common prefix of muliple dump files to read")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("domains")
+                                .long("domains")
+                                .help("Write the number of linking pages and URLs per domain instead")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .short('o')
+                                .long("output")
+                                .value_name("file")
+                                .help("File to write the tab-separated tuples to, defaults to stdout"),
+                        )
+                        .arg(
+                            Arg::new("threads")
+                                .short('j')
+                                .long("threads")
+                                .value_name("num")
+                                .help("Number of threads reading in parallel, defaults to the number of logical cpus"),
+                        ),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        Some(("ingest", ingest_matches)) => match ingest_matches.subcommand() {
+            Some(("clickhouse", subcommand_matches)) => ingest_clickhouse(subcommand_matches).await?,
+            _ => unreachable!("Unknown subcommand, should be caught by arg matching."),
+        },
+        Some(("validate", subcommand_matches)) => validate(subcommand_matches)?,
+        Some(("serve", subcommand_matches)) => serve(subcommand_matches).await?,
+        Some(("recompress", subcommand_matches)) => recompress(subcommand_matches)?,
+        Some(("extract", extract_matches)) => match extract_matches.subcommand() {
+            Some(("links", subcommand_matches)) => extract_links(subcommand_matches)?,
+            Some(("templates", subcommand_matches)) => extract_templates(subcommand_matches)?,
+            Some(("urls", subcommand_matches)) => extract_urls(subcommand_matches)?,
+            _ => unreachable!("Unknown subcommand, should be caught by arg matching."),
+        },
+        _ => unreachable!("Unknown subcommand, should be caught by arg matching."),
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let res = run().await;
+    if let Err(e) = res {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+}
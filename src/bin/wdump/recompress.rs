@@ -0,0 +1,206 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Recompression of dumps into seekable zstd dumps: the siteinfo header and each group of pages are compressed into
+//! their own zstd frame, so pages can be read starting at the frame offsets listed in the page index like the
+//! bzip2 streams of multistream dumps.
+
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::path::Path;
+use std::sync::mpsc::{channel, sync_channel, Receiver, SyncSender};
+use std::thread;
+
+use anyhow::{anyhow, bail, Result};
+use wdgetlib::hashing::{hash_reader, HashAlgorithm, Hasher};
+
+pub struct RecompressOptions {
+    pub pages_per_frame: NonZeroUsize,
+    pub level: i32,
+    pub thread_count: Option<NonZeroUsize>,
+}
+
+pub struct RecompressResult {
+    pub page_count: u64,
+    pub frame_count: u64,
+    pub compressed_size: u64,
+    /// SHA-1 of the decompressed content, verified by decompressing the written file
+    pub sha1: String,
+}
+
+/// Compresses the frames in the thread pool and writes them in order.
+struct FrameWriter<'a> {
+    pool: &'a rayon::ThreadPool,
+    level: i32,
+    frame_send: SyncSender<Receiver<std::io::Result<Vec<u8>>>>,
+}
+
+impl FrameWriter<'_> {
+    /// Returns false if the writer thread stopped because of an error.
+    fn add_frame(&self, content: Vec<u8>) -> bool {
+        let (compressed_send, compressed_receive) = channel();
+        let level = self.level;
+        self.pool.spawn(move || {
+            // the receiver is gone if writing failed
+            let _ = compressed_send.send(zstd::bulk::compress(&content, level));
+        });
+        self.frame_send.send(compressed_receive).is_ok()
+    }
+}
+
+/// Reads a decompressed dump line by line and writes it as a seekable zstd dump. Returns after the written file has
+/// been decompressed again and its content found to be identical.
+pub fn recompress_stream<R: BufRead>(
+    mut reader: R,
+    output_file: &Path,
+    options: &RecompressOptions,
+) -> Result<RecompressResult> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.thread_count.map_or(0, NonZeroUsize::get))
+        .build()?;
+    let mut writer = BufWriter::new(File::create(output_file)?);
+
+    let (page_count, content_sha1, (frame_count, compressed_size)) = thread::scope(|scope| -> Result<_> {
+        // limits the number of frames held in memory, the writer thread stops when the frame writer is dropped
+        let (frame_send, frame_receive) = sync_channel(2 * pool.current_num_threads());
+        let frame_writer = FrameWriter {
+            pool: &pool,
+            level: options.level,
+            frame_send,
+        };
+        let writer_thread = scope.spawn(move || -> Result<(u64, u64)> {
+            let (mut frame_count, mut compressed_size) = (0, 0);
+            for frame in frame_receive {
+                // UNWRAP: the sender only goes away after sending
+                let frame = frame.recv().unwrap()?;
+                writer.write_all(&frame)?;
+                frame_count += 1;
+                compressed_size += frame.len() as u64;
+            }
+            writer.flush()?;
+            Ok((frame_count, compressed_size))
+        });
+
+        let mut hasher = Hasher::new(HashAlgorithm::Sha1);
+        let mut frame = Vec::with_capacity(4 * 1024 * 1024);
+        let mut line = Vec::with_capacity(1024);
+        let (mut page_count, mut pages_in_frame) = (0_u64, 0);
+        let mut in_header = true;
+        let mut writer_stopped = false;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            hasher.update(&line);
+            let trimmed_line = line.trim_ascii();
+            if in_header && trimmed_line == b"<page>" {
+                // the siteinfo header gets its own frame so that frames with pages start with a page
+                in_header = false;
+                if !frame_writer.add_frame(std::mem::take(&mut frame)) {
+                    writer_stopped = true;
+                    break;
+                }
+            }
+            frame.extend_from_slice(&line);
+            if trimmed_line == b"</page>" {
+                page_count += 1;
+                pages_in_frame += 1;
+                if pages_in_frame == options.pages_per_frame.get() {
+                    pages_in_frame = 0;
+                    if !frame_writer.add_frame(std::mem::take(&mut frame)) {
+                        writer_stopped = true;
+                        break;
+                    }
+                }
+            }
+        }
+        if !frame.is_empty() && !writer_stopped {
+            frame_writer.add_frame(frame);
+        }
+        drop(frame_writer);
+        // UNWRAP: the writer thread does not panic
+        let frames = writer_thread.join().unwrap()?;
+        Ok((page_count, hasher.finalize_hex(), frames))
+    })?;
+
+    let recompressed_sha1 = hash_reader(
+        zstd::stream::read::Decoder::new(File::open(output_file)?)?,
+        HashAlgorithm::Sha1,
+    )?;
+    if recompressed_sha1 != content_sha1 {
+        bail!(
+            "Content SHA-1 of the recompressed dump is {recompressed_sha1} instead of {content_sha1}, {} is corrupt.",
+            output_file.display()
+        );
+    }
+    Ok(RecompressResult {
+        page_count,
+        frame_count,
+        compressed_size,
+        sha1: content_sha1,
+    })
+}
+
+/// Returns the seekable zstd dump file name for a dump file, e.g. `enwiki-20240101-pages-articles.xml.zst` for
+/// `enwiki-20240101-pages-articles.xml.bz2`.
+pub fn get_output_file_name(dump_file: &str) -> Result<String> {
+    let stem = [".bz2", ".gz", ".7z"]
+        .iter()
+        .find_map(|ext| dump_file.strip_suffix(ext))
+        .unwrap_or(dump_file);
+    if !stem.ends_with(".xml") {
+        return Err(anyhow!("Only XML dumps can be recompressed."));
+    }
+    Ok(format!("{stem}.zst"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_recompress_stream() {
+        let dump =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/testwiki-20240101-pages-articles.xml");
+        let content = std::fs::read(&dump).unwrap();
+        let output_file = std::env::temp_dir().join(format!("wdump-recompress-{}.xml.zst", std::process::id()));
+        let options = RecompressOptions {
+            pages_per_frame: NonZeroUsize::new(4).unwrap(),
+            level: 3,
+            thread_count: NonZeroUsize::new(2),
+        };
+
+        // the header frame, a frame with four pages and one with the last two pages and the end of the dump
+        let result = recompress_stream(content.as_slice(), &output_file, &options).unwrap();
+        assert_eq!((result.page_count, result.frame_count), (6, 3));
+        assert_eq!(result.compressed_size, std::fs::metadata(&output_file).unwrap().len());
+        let mut decompressed = Vec::new();
+        zstd::stream::read::Decoder::new(File::open(&output_file).unwrap())
+            .unwrap()
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, content);
+        std::fs::remove_file(&output_file).unwrap();
+    }
+
+    #[test]
+    fn test_get_output_file_name() {
+        assert_eq!(
+            get_output_file_name("enwiki-20240101-pages-articles.xml.bz2").unwrap(),
+            "enwiki-20240101-pages-articles.xml.zst"
+        );
+        assert_eq!(
+            get_output_file_name("enwiki-20240101-pages-articles.xml").unwrap(),
+            "enwiki-20240101-pages-articles.xml.zst"
+        );
+        assert!(get_output_file_name("enwiki-20240101-cirrussearch-content.json.gz").is_err());
+    }
+}
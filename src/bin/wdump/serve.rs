@@ -0,0 +1,243 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::spawn_blocking;
+use wdgreplib::index::{get_index_file_name, index_file_exists, is_indexable, load_index};
+use wdgreplib::{extract_page_at, search_dump, Error, MatchPosition, MatchSink, MatchedRevision, SearchOptions};
+
+/// Dump files and title indexes, loaded once when the server starts.
+struct ServerState {
+    dump_files: Vec<String>,
+    thread_count: Option<NonZeroUsize>,
+    // title -> index of dump file and offset
+    title_offsets: HashMap<String, (usize, u64)>,
+}
+
+impl ServerState {
+    fn load(dump_files: Vec<String>, thread_count: Option<NonZeroUsize>) -> Result<ServerState> {
+        let mut title_offsets = HashMap::new();
+        for (i, dump_file) in dump_files.iter().enumerate() {
+            if is_indexable(dump_file) && index_file_exists(dump_file) {
+                for (title, offset) in load_index(&get_index_file_name(dump_file))? {
+                    title_offsets.entry(title).or_insert((i, offset));
+                }
+            }
+        }
+        Ok(ServerState {
+            dump_files,
+            thread_count,
+            title_offsets,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct SearchMatch {
+    page_id: u64,
+    namespace: i32,
+    title: String,
+    revision_id: u64,
+    match_count: usize,
+    positions: Vec<MatchPosition>,
+}
+
+#[derive(Serialize)]
+struct SearchResponse {
+    bytes_processed: u64,
+    matches: Vec<SearchMatch>,
+}
+
+#[derive(Serialize)]
+struct PageResponse {
+    xml: String,
+    text: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+#[derive(Default)]
+struct MatchCollector {
+    matches: Mutex<Vec<SearchMatch>>,
+}
+
+impl MatchSink for MatchCollector {
+    fn add_match(&self, matched_revision: &MatchedRevision) -> wdgreplib::Result<()> {
+        self.matches.lock().unwrap().push(SearchMatch {
+            page_id: matched_revision.page_id,
+            namespace: matched_revision.namespace,
+            title: matched_revision.title.to_owned(),
+            revision_id: matched_revision.revision_id,
+            match_count: matched_revision.match_count,
+            positions: matched_revision.positions.to_vec(),
+        });
+        Ok(())
+    }
+
+    fn finish(&self) -> wdgreplib::Result<()> {
+        Ok(())
+    }
+}
+
+struct Response {
+    status: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json<T: Serialize>(status: &'static str, value: &T) -> Response {
+        Response {
+            status,
+            // UNWRAP: serializing these structs cannot fail
+            body: serde_json::to_string(value).unwrap(),
+        }
+    }
+
+    fn error(status: &'static str, error: String) -> Response {
+        Response::json(status, &ErrorResponse { error })
+    }
+}
+
+fn search(state: &ServerState, regex: &str, namespaces: Option<&str>) -> Response {
+    let namespaces: Option<Vec<&str>> =
+        namespaces.map(|namespaces| namespaces.split(',').map(str::trim).filter(|x| !x.is_empty()).collect());
+    let match_collector = MatchCollector::default();
+    let mut search_options = SearchOptions::new();
+    if let Some(namespaces) = namespaces.as_deref() {
+        search_options.restrict_namespaces(namespaces);
+    }
+    if let Some(thread_count) = state.thread_count {
+        search_options.with_thread_count(thread_count);
+    }
+    search_options.with_match_sink(&match_collector);
+    match search_dump(regex, &state.dump_files, &search_options) {
+        Ok(search_dump_result) => {
+            let mut matches = match_collector.matches.into_inner().unwrap();
+            // results of parallel searches arrive in no particular order
+            matches.sort_unstable_by_key(|m| (m.page_id, m.revision_id));
+            Response::json(
+                "200 OK",
+                &SearchResponse {
+                    bytes_processed: search_dump_result.bytes_processed,
+                    matches,
+                },
+            )
+        }
+        Err(err @ Error::Regex(_)) => Response::error("400 Bad Request", err.to_string()),
+        Err(err) => Response::error("500 Internal Server Error", err.to_string()),
+    }
+}
+
+fn get_page(state: &ServerState, title: &str) -> Response {
+    let page = match state.title_offsets.get(title) {
+        Some(&(dump_file_index, offset)) => extract_page_at(&state.dump_files[dump_file_index], offset, title),
+        // dump files without index are scanned
+        None => wdgreplib::extract_page(&state.dump_files, title, &SearchOptions::new())
+            .map(Some)
+            .or_else(|err| match err {
+                Error::PageNotFound(_) => Ok(None),
+                err => Err(err),
+            }),
+    };
+    match page {
+        Ok(Some(page)) => Response::json(
+            "200 OK",
+            &PageResponse {
+                xml: page.xml,
+                text: page.text,
+            },
+        ),
+        Ok(None) => Response::error("404 Not Found", format!("Page {title} not found")),
+        Err(err) => Response::error("500 Internal Server Error", err.to_string()),
+    }
+}
+
+fn route(state: &ServerState, method: &str, target: &str) -> Response {
+    if method != "GET" {
+        return Response::error("405 Method Not Allowed", "Only GET requests are supported".to_owned());
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let params: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+    match path {
+        "/search" => match params.get("q") {
+            Some(regex) => search(state, regex, params.get("ns").map(String::as_str)),
+            None => Response::error("400 Bad Request", "Parameter q is missing".to_owned()),
+        },
+        "/page" => match params.get("title") {
+            Some(title) => get_page(state, title),
+            None => Response::error("400 Bad Request", "Parameter title is missing".to_owned()),
+        },
+        _ => Response::error("404 Not Found", format!("Unknown path {path}")),
+    }
+}
+
+async fn handle_connection(stream: TcpStream, state: Arc<ServerState>) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    // headers are not needed
+    let mut header_line = String::new();
+    loop {
+        header_line.clear();
+        if reader.read_line(&mut header_line).await? == 0 || header_line.trim_end().is_empty() {
+            break;
+        }
+    }
+    let mut parts = request_line.split_whitespace();
+    let response = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => {
+            let (method, target) = (method.to_owned(), target.to_owned());
+            // searching is CPU-bound and uses the rayon thread pool
+            spawn_blocking(move || route(&state, &method, &target)).await?
+        }
+        _ => Response::error("400 Bad Request", "Invalid request line".to_owned()),
+    };
+    let mut stream = reader.into_inner();
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                response.status,
+                response.body.len()
+            )
+            .as_bytes(),
+        )
+        .await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Answers search and page requests over HTTP until interrupted.
+pub async fn serve(dump_files: Vec<String>, thread_count: Option<NonZeroUsize>, address: &str) -> Result<()> {
+    let state = Arc::new(ServerState::load(dump_files, thread_count)?);
+    let listener = TcpListener::bind(address).await?;
+    eprintln!(
+        "Serving {} dump file(s) with {} indexed titles on http://{}/",
+        state.dump_files.len(),
+        state.title_offsets.len(),
+        listener.local_addr()?
+    );
+    loop {
+        let (stream, peer_address) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, state).await {
+                eprintln!("Error handling request from {peer_address}: {err}");
+            }
+        });
+    }
+}
@@ -0,0 +1,235 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::io::Write;
+use std::path::Path;
+
+use memchr::memmem;
+use simdutf8::basic::from_utf8;
+use termcolor::Buffer;
+use wdgreplib::siteinfo::DumpHeader;
+use wdgreplib::{Result, RevisionInfo, RevisionProcessor};
+
+use crate::extract::{Titles, TsvOutput};
+
+const TEMPLATE_NAMESPACE: i32 = 10;
+
+/// Returns the index after the end of an HTML comment starting at the index, if there is one.
+fn skip_comment(text: &[u8], i: usize) -> Option<usize> {
+    if !text[i..].starts_with(b"<!--") {
+        return None;
+    }
+    Some(memmem::find(&text[i + 4..], b"-->").map_or(text.len(), |pos| i + 4 + pos + 3))
+}
+
+/// Returns the index after the braces closing the ones opened at the start index, if they are closed.
+fn find_closing_braces(text: &[u8], start: usize) -> Option<usize> {
+    let mut depth = 0_usize;
+    let mut i = start;
+    while i < text.len() {
+        if let Some(next) = skip_comment(text, i) {
+            i = next;
+            continue;
+        }
+        match text[i] {
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Returns the contents between the outer braces of the template invocations which are not nested in other
+/// invocations. Template parameters (`{{{1}}}`) and invocations with unbalanced braces are skipped.
+fn find_templates(text: &[u8]) -> Vec<&[u8]> {
+    let mut templates = Vec::new();
+    let mut i = 0;
+    while i + 1 < text.len() {
+        if let Some(next) = skip_comment(text, i) {
+            i = next;
+            continue;
+        }
+        if text[i] != b'{' {
+            i += 1;
+            continue;
+        }
+        let run = text[i..].iter().take_while(|&&b| b == b'{').count();
+        if run != 2 {
+            i += run;
+            continue;
+        }
+        match find_closing_braces(text, i) {
+            Some(end) => {
+                templates.push(&text[i + 2..end - 2]);
+                i = end;
+            }
+            None => i += 2,
+        }
+    }
+    templates
+}
+
+/// Splits at the separator where it is not nested in braces or links.
+fn split_top_level(text: &[u8], separator: u8, max_parts: usize) -> Vec<&[u8]> {
+    let mut parts = Vec::new();
+    let (mut brace_depth, mut link_depth) = (0_usize, 0_usize);
+    let mut start = 0;
+    let mut i = 0;
+    while i < text.len() {
+        if let Some(next) = skip_comment(text, i) {
+            i = next;
+            continue;
+        }
+        match text[i] {
+            b'{' => brace_depth += 1,
+            b'}' => brace_depth = brace_depth.saturating_sub(1),
+            b'[' if text[i..].starts_with(b"[[") => {
+                link_depth += 1;
+                i += 1;
+            }
+            b']' if text[i..].starts_with(b"]]") => {
+                link_depth = link_depth.saturating_sub(1);
+                i += 1;
+            }
+            b if b == separator && brace_depth == 0 && link_depth == 0 && parts.len() + 1 < max_parts => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Replaces tabs and line breaks so values fit in a tab-separated column.
+fn to_column(value: &str) -> String {
+    value.trim().replace(['\t', '\r', '\n'], " ")
+}
+
+/// Extracts the template invocations in the text of each revision and writes one (page id, title, template, number
+/// of parameters) tuple per invocation. Invocations nested in the parameters of other invocations are not
+/// included, parser functions and magic words are skipped.
+pub struct TemplateExtractor<'a> {
+    titles: Titles,
+    output: TsvOutput,
+    parameter: Option<&'a str>,
+}
+
+impl<'a> TemplateExtractor<'a> {
+    /// If a parameter name is given, the unexpanded value of the parameter is written as an additional column,
+    /// positional parameters are named by their number starting at 1.
+    pub fn new(
+        dump_header: &DumpHeader,
+        output_file: Option<&Path>,
+        parameter: Option<&'a str>,
+    ) -> Result<TemplateExtractor<'a>> {
+        let header = match parameter {
+            Some(_) => "page_id\ttitle\ttemplate\tparameter_count\tparameter_value",
+            None => "page_id\ttitle\ttemplate\tparameter_count",
+        };
+        Ok(TemplateExtractor {
+            titles: Titles::new(dump_header),
+            output: TsvOutput::create(output_file, header)?,
+            parameter,
+        })
+    }
+
+    pub fn finish(self) -> Result<()> {
+        self.output.finish()
+    }
+
+    /// Returns the name of the template without the namespace prefix or the full title of other transcluded pages.
+    /// Returns `None` for parser functions, magic words and names constructed from parameters.
+    fn get_template_name(&self, name: &str) -> Option<String> {
+        let mut name = name.trim();
+        for prefix in ["subst:", "safesubst:"] {
+            if name.get(..prefix.len()).is_some_and(|p| p.eq_ignore_ascii_case(prefix)) {
+                name = &name[prefix.len()..];
+            }
+        }
+        if name.starts_with('#') || name.contains(['{', '}', '<', '>', '[', ']']) {
+            return None;
+        }
+        // a leading colon transcludes a page of the main namespace
+        if let Some(title) = name.strip_prefix(':') {
+            let title = self.titles.normalize(title);
+            return (!title.is_empty()).then(|| format!(":{title}"));
+        }
+        let name = self.titles.normalize(name);
+        match self.titles.split_namespace(&name) {
+            Some((_, _, title)) if title.is_empty() => None,
+            Some((TEMPLATE_NAMESPACE, _, title)) => Some(title),
+            Some((_, namespace_name, title)) => Some(format!("{namespace_name}:{title}")),
+            None if name.is_empty() || name.contains(':') => None,
+            None => Some(name),
+        }
+    }
+
+    fn get_parameter_value<'t>(&self, parameters: &[&'t [u8]], parameter: &str) -> Result<&'t str> {
+        let mut position = 0;
+        for param in parameters {
+            match split_top_level(param, b'=', 2).as_slice() {
+                [name, value] => {
+                    if from_utf8(name)?.trim() == parameter {
+                        return Ok(from_utf8(value)?);
+                    }
+                }
+                _ => {
+                    position += 1;
+                    if position.to_string() == parameter {
+                        return Ok(from_utf8(param)?);
+                    }
+                }
+            }
+        }
+        Ok("")
+    }
+}
+
+impl RevisionProcessor for TemplateExtractor<'_> {
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], output: &mut Buffer) -> Result<bool> {
+        let mut lines = Vec::new();
+        for template in find_templates(text) {
+            let parts = split_top_level(template, b'|', usize::MAX);
+            let name = match self.get_template_name(from_utf8(parts[0])?) {
+                Some(name) => name,
+                None => continue,
+            };
+            let parameters = &parts[1..];
+            write!(
+                lines,
+                "{}\t{}\t{}\t{}",
+                revision.page_id,
+                revision.title,
+                name,
+                parameters.len()
+            )?;
+            if let Some(parameter) = self.parameter {
+                write!(
+                    lines,
+                    "\t{}",
+                    to_column(self.get_parameter_value(parameters, parameter)?)
+                )?;
+            }
+            writeln!(lines)?;
+        }
+        self.output.write(&lines, output)?;
+        Ok(false)
+    }
+
+    fn required_literal(&self) -> Option<&[u8]> {
+        Some(b"{{")
+    }
+}
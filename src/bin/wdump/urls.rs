@@ -0,0 +1,137 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use regex::bytes::Regex;
+use simdutf8::basic::from_utf8;
+use termcolor::Buffer;
+use url::Url;
+use wdgreplib::{Result, RevisionInfo, RevisionProcessor};
+
+use crate::extract::TsvOutput;
+
+lazy_static! {
+    // replaced with a space so URLs end there like in the rendered page
+    static ref NOWIKI_REGEX: Regex = Regex::new(r"(?is)<nowiki\s*/>|<nowiki[^>]*>.*?</nowiki\s*>|<!--.*?-->").unwrap();
+    // bracketed links ([http://example.org label]) may be protocol-relative, bare URLs end at whitespace and markup
+    static ref URL_REGEX: Regex =
+        Regex::new(r#"(?i)\[((?:https?:)?//[^\s\[\]<>"]+)[^\]\n]*\]|(https?://[^\s\[\]<>"{}|]+)"#).unwrap();
+}
+
+/// Removes punctuation which MediaWiki does not treat as part of bare URLs.
+fn trim_bare_url(url: &str) -> &str {
+    // two apostrophes start bold or italic text
+    let url = url.split("''").next().unwrap_or_default();
+    let url = url.trim_end_matches(['.', ',', ';', ':', '!', '?', '\'']);
+    if url.contains('(') {
+        url
+    } else {
+        url.trim_end_matches(')')
+    }
+}
+
+fn get_domain(url: &str) -> Option<String> {
+    let url = if url.starts_with("//") {
+        Url::parse(&format!("https:{url}"))
+    } else {
+        Url::parse(url)
+    };
+    url.ok()?.host_str().map(str::to_owned)
+}
+
+#[derive(Default)]
+struct DomainCounts {
+    pages: u64,
+    urls: u64,
+}
+
+/// Extracts the external links in the text of each revision, both bracketed and bare URLs outside of nowiki sections
+/// and comments, and writes one (page id, title, URL) tuple per distinct URL of each page. If domains are
+/// aggregated, the number of pages linking to each domain and the number of distinct URLs of the domain summed over
+/// these pages are written instead once the dump has been read.
+pub struct UrlExtractor {
+    output: TsvOutput,
+    domain_counts: Option<Mutex<HashMap<String, DomainCounts>>>,
+}
+
+impl UrlExtractor {
+    pub fn new(output_file: Option<&Path>, aggregate_domains: bool) -> Result<UrlExtractor> {
+        let header = if aggregate_domains {
+            "domain\tpage_count\turl_count"
+        } else {
+            "page_id\ttitle\turl"
+        };
+        Ok(UrlExtractor {
+            output: TsvOutput::create(output_file, header)?,
+            domain_counts: aggregate_domains.then(|| Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Writes the domain counts, most linked domains first, if domains are aggregated.
+    pub fn finish(self) -> Result<()> {
+        let domain_counts = match self.domain_counts {
+            Some(domain_counts) => domain_counts.into_inner().unwrap(),
+            None => return self.output.finish(),
+        };
+        let mut domain_counts: Vec<_> = domain_counts.into_iter().collect();
+        domain_counts.sort_unstable_by(|(domain1, counts1), (domain2, counts2)| {
+            counts2.pages.cmp(&counts1.pages).then_with(|| domain1.cmp(domain2))
+        });
+        let mut lines = Vec::new();
+        for (domain, counts) in domain_counts {
+            writeln!(lines, "{}\t{}\t{}", domain, counts.pages, counts.urls)?;
+        }
+        self.output.finish_with(&lines)
+    }
+}
+
+impl RevisionProcessor for UrlExtractor {
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], output: &mut Buffer) -> Result<bool> {
+        let text = NOWIKI_REGEX.replace_all(text, &b" "[..]);
+        let mut seen_urls = HashSet::new();
+        let mut urls = Vec::new();
+        for captures in URL_REGEX.captures_iter(&text) {
+            let url = match (captures.get(1), captures.get(2)) {
+                (Some(bracketed), _) => from_utf8(bracketed.as_bytes())?,
+                // UNWRAP: one of the alternatives matched
+                (None, bare) => trim_bare_url(from_utf8(bare.unwrap().as_bytes())?),
+            };
+            if seen_urls.insert(url) {
+                urls.push(url);
+            }
+        }
+        if let Some(domain_counts) = &self.domain_counts {
+            let mut domains: HashMap<String, u64> = HashMap::new();
+            for url in urls {
+                if let Some(domain) = get_domain(url) {
+                    *domains.entry(domain).or_default() += 1;
+                }
+            }
+            let mut domain_counts = domain_counts.lock().unwrap();
+            for (domain, url_count) in domains {
+                let counts = domain_counts.entry(domain).or_default();
+                counts.pages += 1;
+                counts.urls += url_count;
+            }
+            return Ok(false);
+        }
+        let mut lines = Vec::new();
+        for url in urls {
+            writeln!(lines, "{}\t{}\t{}", revision.page_id, revision.title, url)?;
+        }
+        self.output.write(&lines, output)?;
+        Ok(false)
+    }
+
+    fn required_literal(&self) -> Option<&[u8]> {
+        Some(b"//")
+    }
+}
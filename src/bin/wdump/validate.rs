@@ -0,0 +1,268 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::DateTime;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rayon::prelude::*;
+
+/// A problem found in the dump at the given byte offset.
+pub struct Violation {
+    pub offset: u64,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct PageState {
+    offset: u64,
+    has_title: bool,
+    has_ns: bool,
+    has_id: bool,
+}
+
+/// Position of a chunk in the dump, the chunks between the first and the last one consist only of pages.
+#[derive(Clone, Copy)]
+struct ChunkPosition {
+    is_first: bool,
+    is_last: bool,
+}
+
+/// Checks a part of the dump for well-formedness and the expected page and revision structure.
+struct ChunkValidator {
+    chunk_offset: u64,
+    max_violations: usize,
+    violations: Vec<Violation>,
+    // open elements with their offsets
+    stack: Vec<(Vec<u8>, u64)>,
+    text: Vec<u8>,
+    page: Option<PageState>,
+}
+
+impl ChunkValidator {
+    fn new(chunk_offset: u64, position: ChunkPosition, max_violations: usize) -> Self {
+        let mut stack = Vec::new();
+        if !position.is_first {
+            // opened in the first chunk
+            stack.push((b"mediawiki".to_vec(), 0));
+        }
+        ChunkValidator {
+            chunk_offset,
+            max_violations,
+            violations: Vec::new(),
+            stack,
+            text: Vec::new(),
+            page: None,
+        }
+    }
+
+    fn add_violation(&mut self, offset: u64, message: String) {
+        self.violations.push(Violation { offset, message });
+    }
+
+    fn is_full(&self) -> bool {
+        self.violations.len() >= self.max_violations
+    }
+
+    fn parent_name(&self) -> Option<&[u8]> {
+        self.stack.len().checked_sub(2).map(|i| self.stack[i].0.as_slice())
+    }
+
+    fn validate<R: BufRead>(mut self, reader: R, position: ChunkPosition) -> Vec<Violation> {
+        let mut reader = Reader::from_reader(reader);
+        // end tags are matched here since chunks do not start at the root element
+        reader.check_end_names(false);
+        let mut buf: Vec<u8> = Vec::with_capacity(64 * 1024);
+        while !self.is_full() {
+            let event = reader.read_event(&mut buf);
+            let position_after_event = self.chunk_offset + reader.buffer_position() as u64;
+            match event {
+                Ok(Event::Start(ref e)) => {
+                    // the start tag is enclosed in < and >
+                    let offset = position_after_event - e.len() as u64 - 2;
+                    if e.name() == b"page" {
+                        self.page = Some(PageState {
+                            offset,
+                            ..PageState::default()
+                        });
+                    }
+                    self.stack.push((e.name().to_owned(), offset));
+                    self.text.clear();
+                }
+                Ok(Event::Text(ref e)) => {
+                    self.text.extend_from_slice(e);
+                }
+                Ok(Event::CData(ref e)) => {
+                    self.text.extend_from_slice(e);
+                }
+                Ok(Event::End(ref e)) => {
+                    let offset = position_after_event - e.name().len() as u64 - 3;
+                    match self.stack.last() {
+                        Some((name, _)) if name.as_slice() == e.name() => {
+                            self.check_element();
+                            self.stack.pop();
+                        }
+                        Some((name, _)) => {
+                            let message = format!(
+                                "Expected end tag </{}>, found </{}>",
+                                String::from_utf8_lossy(name),
+                                String::from_utf8_lossy(e.name())
+                            );
+                            self.add_violation(offset, message);
+                            break;
+                        }
+                        None => {
+                            let message = format!("Unexpected end tag </{}>", String::from_utf8_lossy(e.name()));
+                            self.add_violation(offset, message);
+                            break;
+                        }
+                    }
+                }
+                Ok(Event::Eof) => {
+                    let expected_depth = usize::from(!position.is_last);
+                    if self.stack.len() > expected_depth {
+                        // UNWRAP: stack is not empty
+                        let (name, offset) = self.stack.last().unwrap();
+                        let message = format!("Element <{}> is not closed", String::from_utf8_lossy(name));
+                        self.add_violation(*offset, message);
+                    } else if self.stack.len() < expected_depth {
+                        self.add_violation(position_after_event, "Unexpected end of root element".to_owned());
+                    }
+                    break;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    self.add_violation(position_after_event, format!("XML format error: {e}"));
+                    break;
+                }
+            }
+            buf.clear();
+        }
+        self.violations
+    }
+
+    /// Checks the content of the element about to be closed.
+    fn check_element(&mut self) {
+        // UNWRAP: only called with an open element
+        let (name, offset) = self.stack.last().unwrap().clone();
+        let parent_name = self.parent_name().map(<[u8]>::to_vec);
+        let text = String::from_utf8_lossy(&self.text).into_owned();
+        match (parent_name.as_deref(), name.as_slice()) {
+            (Some(b"page"), b"title") => {
+                if let Some(page) = &mut self.page {
+                    page.has_title = !text.is_empty();
+                }
+            }
+            (Some(b"page"), b"ns") => {
+                if let Some(page) = &mut self.page {
+                    page.has_ns = true;
+                }
+                if text.parse::<i32>().is_err() {
+                    self.add_violation(offset, format!("Invalid namespace '{text}'"));
+                }
+            }
+            (Some(b"page"), b"id") => {
+                if let Some(page) = &mut self.page {
+                    page.has_id = true;
+                }
+                if text.parse::<u64>().is_err() {
+                    self.add_violation(offset, format!("Invalid page id '{text}'"));
+                }
+            }
+            (Some(b"revision"), b"id") if text.parse::<u64>().is_err() => {
+                self.add_violation(offset, format!("Invalid revision id '{text}'"));
+            }
+            (Some(b"revision"), b"timestamp") if DateTime::parse_from_rfc3339(&text).is_err() => {
+                self.add_violation(offset, format!("Invalid timestamp '{text}'"));
+            }
+            (_, b"page") => {
+                if let Some(page) = self.page.take() {
+                    for (present, element) in [(page.has_title, "title"), (page.has_ns, "ns"), (page.has_id, "id")] {
+                        if !present {
+                            self.add_violation(page.offset, format!("Page without {element}"));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        self.text.clear();
+    }
+}
+
+/// Returns the offset of the first `<page>` line at or after the given offset or the file size if there is none.
+fn find_next_page_start(file: &Path, offset: u64, file_size: u64) -> Result<u64> {
+    let mut f = File::open(file)?;
+    f.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(f);
+    let mut line: Vec<u8> = Vec::with_capacity(1024);
+    let mut line_offset = offset;
+    // skip the possibly partial line
+    line_offset += reader.read_until(b'\n', &mut line)? as u64;
+    loop {
+        line.clear();
+        let len = reader.read_until(b'\n', &mut line)?;
+        if len == 0 {
+            return Ok(file_size);
+        }
+        if line.trim_ascii() == b"<page>" {
+            return Ok(line_offset);
+        }
+        line_offset += len as u64;
+    }
+}
+
+/// Validates an uncompressed dump by splitting it into chunks at page boundaries which are checked in parallel.
+/// Returns the first `max_violations` violations found.
+pub fn validate_file(dump_file: &Path, max_violations: usize, thread_count: usize) -> Result<Vec<Violation>> {
+    let file_size = std::fs::metadata(dump_file)?.len();
+    let mut boundaries = vec![0];
+    for i in 1..thread_count as u64 {
+        boundaries.push(find_next_page_start(
+            dump_file,
+            file_size * i / thread_count as u64,
+            file_size,
+        )?);
+    }
+    boundaries.push(file_size);
+    boundaries.dedup();
+    let chunk_count = boundaries.len() - 1;
+    let chunk_violations = boundaries
+        .windows(2)
+        .enumerate()
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(i, chunk)| -> Result<Vec<Violation>> {
+            let mut f = File::open(dump_file)?;
+            f.seek(SeekFrom::Start(chunk[0]))?;
+            let reader = BufReader::with_capacity(2 * 1024 * 1024, f.take(chunk[1] - chunk[0]));
+            let position = ChunkPosition {
+                is_first: i == 0,
+                is_last: i + 1 == chunk_count,
+            };
+            Ok(ChunkValidator::new(chunk[0], position, max_violations).validate(reader, position))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut violations = chunk_violations.into_iter().flatten().collect::<Vec<_>>();
+    violations.sort_by_key(|violation| violation.offset);
+    violations.truncate(max_violations);
+    Ok(violations)
+}
+
+/// Validates a decompressed dump read sequentially, offsets refer to the decompressed data.
+pub fn validate_stream<R: BufRead>(reader: R, max_violations: usize) -> Vec<Violation> {
+    let position = ChunkPosition {
+        is_first: true,
+        is_last: true,
+    };
+    let mut violations = ChunkValidator::new(0, position, max_violations).validate(reader, position);
+    violations.sort_by_key(|violation| violation.offset);
+    violations
+}
@@ -0,0 +1,624 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! End-to-end tests running wdgrep on the miniature dumps in `tests/fixtures`:
+//!
+//! - `testwiki-20240101-pages-articles.xml` (and the same content as `.xml.bz2`): pages in the main, talk and
+//!   template namespaces, a page with empty text and one with deleted text
+//! - `testwiki-20240101-pages-articles2.xml.bz2`: a second part only available compressed
+//! - `testwiki-20240102-pages-articles-multistream.xml.bz2` with its index: five pages in bzip2 streams of two
+//! - `brokenwiki-20240101-pages-articles.xml`: truncated in the middle of the second page
+//! - `prefixwiki-20240101-pages-articles.xml`: export-0.11 dump with `mw:` prefixed elements and a secondary slot
+//! - `oldwiki-20060101-pages-articles.xml`: export-0.3 dump without `<ns>`, `<model>` and `<sha1>` elements
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use wdgreplib::get_dump_files;
+
+fn fixture(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+fn run_wdgrep(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_wdgrep"))
+        .args(["--color", "never", "--ordered-output"])
+        .args(args)
+        .output()
+        .expect("Could not run wdgrep")
+}
+
+/// Runs wdgrep, checks that it was successful and returns stdout.
+fn wdgrep(args: &[&str]) -> String {
+    let output = run_wdgrep(args);
+    assert!(
+        output.status.success(),
+        "wdgrep {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("Output is not UTF-8")
+}
+
+const PLAIN_MATCHES: &str = "Alpha@101\nThe needle is in the haystack.\nAnother needle here.\n\n\
+                             Talk:Alpha@102\nWhere is the needle?\n\n\
+                             Template:Needle@106\n<includeonly>needle</includeonly>\n\n";
+
+#[test]
+fn search_plain_dump() {
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    assert_eq!(wdgrep(&["needle", &dump]), PLAIN_MATCHES);
+    // single-threaded plain files are searched without the thread pool
+    assert_eq!(wdgrep(&["-j", "1", "needle", &dump]), PLAIN_MATCHES);
+    assert_eq!(wdgrep(&["--no-prefilter", "needle", &dump]), PLAIN_MATCHES);
+}
+
+#[test]
+fn search_bzip2_dump() {
+    let dump = fixture("testwiki-20240101-pages-articles.xml.bz2");
+    assert_eq!(wdgrep(&["needle", &dump]), PLAIN_MATCHES);
+}
+
+#[test]
+fn search_multistream_dump() {
+    let dump = fixture("testwiki-20240102-pages-articles-multistream.xml.bz2");
+    assert_eq!(
+        wdgrep(&["-r", "$0", "--with-title", "needle", &dump]),
+        "Delta@111\tneedle\nZeta@113\tneedle\nTalk:Zeta@114\tneedle\nEta@115\tneedle\n"
+    );
+    // only the stream containing the page is decompressed
+    assert_eq!(
+        wdgrep(&["--title-filter", "^Zeta$", "needle", &dump]),
+        "Zeta@113\nSecond needle of the multistream dump.\n\n"
+    );
+}
+
+#[test]
+fn search_modes() {
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    assert_eq!(
+        wdgrep(&["-r", "${word}!", "(?P<word>\\w+) needle", &dump]),
+        "The!\nAnother!\nthe!\n"
+    );
+    assert_eq!(
+        wdgrep(&["--count-unique", "-r", "$1", "(\\w+) needle", &dump]),
+        "1\tAnother\n1\tThe\n1\tthe\n"
+    );
+    assert_eq!(
+        wdgrep(&["--group-by", "page", "needle", &dump]),
+        "Alpha@101 (1 revision)\nTalk:Alpha@102 (1 revision)\nTemplate:Needle@106 (1 revision)\n"
+    );
+    // entities are unescaped before searching
+    assert_eq!(wdgrep(&["-r", "$0", "hay & straw", &dump]), "hay & straw\n");
+    assert_eq!(wdgrep(&["--strip-markup", "-r", "$0", "<includeonly>", &dump]), "");
+}
+
+#[test]
+fn namespace_filter() {
+    for dump in [
+        fixture("testwiki-20240101-pages-articles.xml"),
+        fixture("testwiki-20240101-pages-articles.xml.bz2"),
+    ] {
+        assert_eq!(
+            wdgrep(&["--ns", "1", "needle", &dump]),
+            "Talk:Alpha@102\nWhere is the needle?\n\n"
+        );
+        assert_eq!(
+            wdgrep(&["--ns", "0,10", "-r", "$0", "--with-title", "needle", &dump]),
+            "Alpha@101\tneedle\nAlpha@101\tneedle\nTemplate:Needle@106\tneedle\n"
+        );
+    }
+    let dump = fixture("testwiki-20240102-pages-articles-multistream.xml.bz2");
+    assert_eq!(
+        wdgrep(&["--ns", "1", "-r", "$0", "--with-title", "needle", &dump]),
+        "Talk:Zeta@114\tneedle\n"
+    );
+}
+
+#[test]
+fn empty_and_deleted_text() {
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    // pages with empty or deleted text are parsed but never match
+    assert_eq!(
+        wdgrep(&["--no-prefilter", "-r", "$0", "--with-title", "^", &dump]),
+        "Alpha@101\t\nTalk:Alpha@102\t\nBeta@105\t\nTemplate:Needle@106\t\n"
+    );
+    let output = run_wdgrep(&["--report-deleted", "needle", &dump]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Deleted: 1 revisions with deleted text, 0 revisions with deleted contributor\n\
+         Skipped 1 revisions with deleted text, found 0 revisions with deleted contributor.\n"
+    );
+}
+
+#[test]
+fn titles_file() {
+    let titles_file = std::env::temp_dir().join(format!("wdgrep-test-titles-{}.txt", std::process::id()));
+    let titles_file_name = titles_file.to_str().unwrap();
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    std::fs::write(&titles_file, "Talk:Alpha\nTemplate:Needle\n\nMissing\n").unwrap();
+    assert_eq!(
+        wdgrep(&[
+            "--titles-file",
+            titles_file_name,
+            "-r",
+            "$0",
+            "--with-title",
+            "needle",
+            &dump
+        ]),
+        "Talk:Alpha@102\tneedle\nTemplate:Needle@106\tneedle\n"
+    );
+    // titles are normalized like on the wiki
+    std::fs::write(&titles_file, "talk:alpha\n template : needle_\n").unwrap();
+    assert_eq!(
+        wdgrep(&["--titles-file", titles_file_name, "-r", "$0", "needle", &dump]),
+        "needle\nneedle\n"
+    );
+    // page ids, only the streams of listed pages are read from multistream dumps
+    std::fs::write(&titles_file, "1\n14\n").unwrap();
+    assert_eq!(
+        wdgrep(&[
+            "--titles-file",
+            titles_file_name,
+            "-r",
+            "$0",
+            "--with-title",
+            "needle",
+            &dump
+        ]),
+        "Alpha@101\tneedle\nAlpha@101\tneedle\n"
+    );
+    let dump = fixture("testwiki-20240102-pages-articles-multistream.xml.bz2");
+    assert_eq!(
+        wdgrep(&[
+            "--titles-file",
+            titles_file_name,
+            "-r",
+            "$0",
+            "--with-title",
+            "needle",
+            &dump
+        ]),
+        "Talk:Zeta@114\tneedle\n"
+    );
+    std::fs::remove_file(&titles_file).unwrap();
+}
+
+#[test]
+fn result_sets() {
+    let result_set = |name: &str| std::env::temp_dir().join(format!("wdgrep-test-{name}-{}.res", std::process::id()));
+    let (needle, haystack) = (result_set("needle"), result_set("haystack"));
+    let (needle_name, haystack_name) = (needle.to_str().unwrap(), haystack.to_str().unwrap());
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    wdgrep(&["--save-results", needle_name, "needle", &dump]);
+    assert_eq!(
+        std::fs::read_to_string(&needle).unwrap(),
+        "page_id\trevision_id\n1\t101\n2\t102\n6\t106\n"
+    );
+    wdgrep(&["--save-results", haystack_name, "-r", "$0", "haystack", &dump]);
+    assert_eq!(
+        wdgrep(&[
+            "--and",
+            needle_name,
+            "--and",
+            haystack_name,
+            "-r",
+            "$0",
+            "--with-title",
+            "needle",
+            &dump
+        ]),
+        "Alpha@101\tneedle\nAlpha@101\tneedle\n"
+    );
+    assert_eq!(
+        wdgrep(&[
+            "--and",
+            needle_name,
+            "--not",
+            haystack_name,
+            "-r",
+            "$0",
+            "--with-title",
+            "needle",
+            &dump
+        ]),
+        "Talk:Alpha@102\tneedle\nTemplate:Needle@106\tneedle\n"
+    );
+    std::fs::remove_file(&needle).unwrap();
+    std::fs::remove_file(&haystack).unwrap();
+}
+
+#[test]
+fn split_output_by_ns() {
+    let directory = std::env::temp_dir().join(format!("wdgrep-test-split-{}", std::process::id()));
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    let output = wdgrep(&[
+        "--split-output-by-ns",
+        directory.to_str().unwrap(),
+        "-r",
+        "$0",
+        "--with-title",
+        "needle",
+        &dump,
+    ]);
+    assert_eq!(output, "");
+    let read = |namespace: &str| std::fs::read_to_string(directory.join(format!("{namespace}.txt"))).unwrap();
+    assert_eq!(read("0"), "Alpha@101\tneedle\nAlpha@101\tneedle\n");
+    assert_eq!(read("1"), "Talk:Alpha@102\tneedle\n");
+    assert_eq!(read("10"), "Template:Needle@106\tneedle\n");
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn dump_matches() {
+    let directory = std::env::temp_dir().join(format!("wdgrep-test-dump-matches-{}", std::process::id()));
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    assert_eq!(
+        wdgrep(&["--dump-matches", directory.to_str().unwrap(), "needle", &dump]),
+        ""
+    );
+    let read = |file_name: &str| std::fs::read_to_string(directory.join(file_name)).unwrap();
+    assert_eq!(read("2_102.wiki"), "Where is the needle?");
+    assert_eq!(read("6_106.wiki"), "<includeonly>needle</includeonly>");
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 3);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn truncated_dump() {
+    // pages before the truncated one are still found
+    let dump = fixture("brokenwiki-20240101-pages-articles.xml");
+    assert_eq!(
+        wdgrep(&["needle", &dump]),
+        "Alpha@101\nThe needle is in the haystack.\nAnother needle here.\n\n"
+    );
+}
+
+#[test]
+fn namespace_prefix() {
+    let dump = fixture("prefixwiki-20240101-pages-articles.xml");
+    // only the text of the main slot is searched
+    let matches = "Alpha@101\nA needle with a prefix.\n\nTalk:Alpha@102\nWhere is the needle?\n\n";
+    assert_eq!(wdgrep(&["needle", &dump]), matches);
+    assert_eq!(wdgrep(&["--no-prefilter", "needle", &dump]), matches);
+    assert_eq!(
+        wdgrep(&["--ns", "1", "-r", "$0", "--with-title", "needle", &dump]),
+        "Talk:Alpha@102\tneedle\n"
+    );
+}
+
+#[test]
+fn old_export_schema() {
+    let dump = fixture("oldwiki-20060101-pages-articles.xml");
+    // pages without <ns> are in the main namespace
+    assert_eq!(
+        wdgrep(&["--ns", "0", "-r", "$0", "--with-title", "needle", &dump]),
+        "Alpha@101\tneedle\nBeta@102\tneedle\n"
+    );
+    assert_eq!(wdgrep(&["--ns", "1", "needle", &dump]), "");
+    // the contributor id of the upload is not taken as page id
+    assert_eq!(
+        wdgrep(&["--pageid-range", "1-1", "-r", "$0", "--with-title", "needle", &dump]),
+        "Alpha@101\tneedle\n"
+    );
+    assert_eq!(
+        wdgrep(&["--field", "comment", "-r", "$0", "--with-title", "needle", &dump]),
+        "Alpha@101\tneedle\n"
+    );
+}
+
+#[test]
+fn search_prefix() {
+    // the plain file is preferred to the .bz2 file with the same content, the second part is only compressed
+    let prefix = fixture("testwiki-20240101");
+    assert_eq!(
+        wdgrep(&["-r", "$0", "--with-title", "needle", &prefix]),
+        "Alpha@101\tneedle\nAlpha@101\tneedle\nTalk:Alpha@102\tneedle\nTemplate:Needle@106\tneedle\n\
+         Gamma@107\tneedle\n"
+    );
+    let output = run_wdgrep(&["needle", &fixture("nonexistingwiki-")]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn get_dump_files_of_prefix() {
+    let file_name = |dump_file: &String| {
+        PathBuf::from(dump_file)
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned()
+    };
+    let (dump_files, total_size) = get_dump_files(&fixture("testwiki-20240101")).unwrap();
+    assert_eq!(
+        dump_files.iter().map(file_name).collect::<Vec<_>>(),
+        [
+            "testwiki-20240101-pages-articles.xml",
+            "testwiki-20240101-pages-articles2.xml.bz2"
+        ]
+    );
+    let expected_size: u64 = dump_files
+        .iter()
+        .map(|dump_file| std::fs::metadata(dump_file).unwrap().len())
+        .sum();
+    assert_eq!(total_size, expected_size);
+
+    // index files are not dump files
+    let (dump_files, _) = get_dump_files(&fixture("testwiki-20240102")).unwrap();
+    assert_eq!(
+        dump_files.iter().map(file_name).collect::<Vec<_>>(),
+        ["testwiki-20240102-pages-articles-multistream.xml.bz2"]
+    );
+
+    let dump = fixture("testwiki-20240101-pages-articles.xml.bz2");
+    assert_eq!(get_dump_files(&dump).unwrap().0, std::slice::from_ref(&dump));
+    assert!(get_dump_files(&fixture("nonexistingwiki-")).is_err());
+}
+
+#[test]
+fn pageid_range() {
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    assert_eq!(
+        wdgrep(&["-r", "$0", "--with-title", "--pageid-range", "2-6", "needle", &dump]),
+        "Talk:Alpha@102\tneedle\nTemplate:Needle@106\tneedle\n"
+    );
+    // only the streams containing pages in the range are decompressed
+    let dump = fixture("testwiki-20240102-pages-articles-multistream.xml.bz2");
+    assert_eq!(
+        wdgrep(&["-r", "$0", "--with-title", "--pageid-range", "12-14", "needle", &dump]),
+        "Zeta@113\tneedle\nTalk:Zeta@114\tneedle\n"
+    );
+    assert!(!run_wdgrep(&["--pageid-range", "6-2", "needle", &dump]).status.success());
+}
+
+#[test]
+fn checkpoint() {
+    let checkpoint = std::env::temp_dir().join(format!("wdgrep-test-checkpoint-{}", std::process::id()));
+    let checkpoint_name = checkpoint.to_str().unwrap();
+    let dump = fixture("testwiki-20240102-pages-articles-multistream.xml.bz2");
+    let args = [
+        "--title-filter",
+        "^(Delta|Zeta)$",
+        "-r",
+        "$0",
+        "--with-title",
+        "--checkpoint",
+        checkpoint_name,
+        "needle",
+        &dump,
+    ];
+    // the first stream with pages was searched before the interruption
+    std::fs::write(
+        &checkpoint,
+        format!(
+            "search\t--color never --ordered-output {}\n{dump}\t0\t643\n",
+            args.join(" ")
+        ),
+    )
+    .unwrap();
+    assert_eq!(wdgrep(&args), "Zeta@113\tneedle\n");
+    assert!(!checkpoint.exists());
+    // checkpoints of other searches are not resumed
+    std::fs::write(&checkpoint, format!("search\tneedle {dump}\n{dump}\t0\t643\n")).unwrap();
+    assert!(!run_wdgrep(&args).status.success());
+    std::fs::remove_file(&checkpoint).unwrap();
+}
+
+#[test]
+fn keep_going() {
+    // the first part is a truncated bzip2 file
+    let directory = std::env::temp_dir().join(format!("wdgrep-test-keep-going-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let compressed = std::fs::read(fixture("testwiki-20240101-pages-articles2.xml.bz2")).unwrap();
+    std::fs::write(
+        directory.join("testwiki-20240101-pages-articles1.xml.bz2"),
+        &compressed[..compressed.len() / 2],
+    )
+    .unwrap();
+    std::fs::copy(
+        fixture("testwiki-20240101-pages-articles.xml"),
+        directory.join("testwiki-20240101-pages-articles2.xml"),
+    )
+    .unwrap();
+    let prefix = directory.join("testwiki-20240101");
+    let args = ["-r", "$0", "--with-title", "needle", prefix.to_str().unwrap()];
+    assert!(!run_wdgrep(&args).status.success());
+    let output = run_wdgrep(&[&["--keep-going"], &args[..]].concat());
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Alpha@101\tneedle\nAlpha@101\tneedle\nTalk:Alpha@102\tneedle\nTemplate:Needle@106\tneedle\n"
+    );
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Errors in 1 of 2 dump files"));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn verify_sha1() {
+    // the SHA1 of all revisions in the fixture is 0
+    let output = run_wdgrep(&[
+        "--verify-sha1",
+        "needle",
+        &fixture("testwiki-20240101-pages-articles.xml"),
+    ]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Talk:Alpha (revision 102): SHA1 is tmphezscd9g3d4985p493n7uwi096py instead of 0\n"));
+    // the revision with deleted text is not verified
+    assert!(stderr.contains("The text of 5 of 5 verified revisions does not match their SHA1."));
+}
+
+#[test]
+fn plan() {
+    let plan = wdgrep(&["--plan", "-j", "3", "needle", &fixture("testwiki-20240101")]);
+    let plain = fixture("testwiki-20240101-pages-articles.xml");
+    let compressed = fixture("testwiki-20240101-pages-articles2.xml.bz2");
+    assert!(
+        plan.contains(&format!("{plain} (0.00 MiB): 1 slices of 0.00 MiB\n")),
+        "{plan}"
+    );
+    assert!(
+        plan.contains(&format!(
+            "{compressed} (0.00 MiB): decompressed and searched as a whole\n"
+        )),
+        "{plan}"
+    );
+    assert!(plan.contains("Decompressor for .bz2: "), "{plan}");
+    assert!(plan.contains("Threads: 3\n"), "{plan}");
+    assert!(
+        plan.contains("Chunk size: 16.00 MiB (0.00 MiB of plain files / 3 threads, bounded to 16.00-500.00 MiB)\n"),
+        "{plan}"
+    );
+
+    let plan = wdgrep(&["--plan", "--chunk-size", "1", "needle", &plain]);
+    assert!(
+        plan.contains("Chunk size: 1.00 MiB (set with --chunk-size)\n"),
+        "{plan}"
+    );
+}
+
+#[test]
+fn head_and_tail() {
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    assert_eq!(
+        wdgrep(&["--head", "1", "needle", &dump]),
+        "Alpha@101\nThe needle is in the haystack.\nAnother needle here.\n\n"
+    );
+    assert_eq!(
+        wdgrep(&["--tail", "2", "needle", &dump]),
+        "Talk:Alpha@102\nWhere is the needle?\n\nTemplate:Needle@106\n<includeonly>needle</includeonly>\n\n"
+    );
+    assert_eq!(wdgrep(&["--head", "10", "needle", &dump]), PLAIN_MATCHES);
+}
+
+/// Returns a page with a single revision in the format of the fixtures.
+fn dump_page(id: u64, title: &str, text: &str) -> String {
+    format!(
+        "  <page>\n    <title>{title}</title>\n    <ns>0</ns>\n    <id>{id}</id>\n    <revision>\n      \
+         <id>{}</id>\n      <timestamp>2024-01-01T00:00:00Z</timestamp>\n      <contributor>\n        \
+         <username>Example</username>\n        <id>1</id>\n      </contributor>\n      <model>wikitext</model>\n      \
+         <format>text/x-wiki</format>\n      <text bytes=\"{}\" xml:space=\"preserve\">{text}</text>\n      \
+         <sha1>0</sha1>\n    </revision>\n  </page>\n",
+        id + 1000,
+        text.len()
+    )
+}
+
+#[test]
+fn prefilter_across_block_boundary() {
+    // the prefilter scans plain dumps in blocks of 16 MiB
+    const BLOCK_SIZE: usize = 16 * 1024 * 1024;
+    let plain = std::fs::read_to_string(fixture("testwiki-20240101-pages-articles.xml")).unwrap();
+    let header = &plain[..plain.find("  <page>").unwrap()];
+    let filler = "filler text\n".repeat((BLOCK_SIZE - 10_000) / 12);
+    let mut dump = format!("{header}{}", dump_page(1, "Filler", &filler));
+    // the literal starts 4 bytes before the end of the first block in a page starting in the first block
+    let text_start = |text: &str| dump.len() + dump_page(2, "Crossing", text).find(text).unwrap();
+    // the length of the text is part of the page, a text of about the same length has the same number of digits
+    let text_len = BLOCK_SIZE - 4 - text_start("x") + "bigneedle here".len();
+    let padding = BLOCK_SIZE - 4 - text_start(&"x".repeat(text_len)) - 1;
+    let text = format!("{}\nbigneedle here", "a".repeat(padding));
+    dump.push_str(&dump_page(2, "Crossing", &text));
+    assert_eq!(dump.find("bigneedle"), Some(BLOCK_SIZE - 4));
+    dump.push_str(&dump_page(3, "After", "another bigneedle"));
+    dump.push_str("</mediawiki>\n");
+    let dump_file = std::env::temp_dir().join(format!("wdgrep-test-boundary-{}.xml", std::process::id()));
+    std::fs::write(&dump_file, dump).unwrap();
+    let dump_file = dump_file.to_str().unwrap();
+
+    let expected = "Crossing@1002\nbigneedle here\n\nAfter@1003\nanother bigneedle\n\n";
+    assert_eq!(wdgrep(&["-j", "1", "bigneedle", dump_file]), expected);
+    assert_eq!(wdgrep(&["-j", "1", "--no-prefilter", "bigneedle", dump_file]), expected);
+    std::fs::remove_file(dump_file).unwrap();
+}
+
+#[test]
+fn chunk_size_smaller_than_page() {
+    // pages of about 1.5 MiB split into slices of 1 MiB, most slices start in the middle of a page
+    let plain = std::fs::read_to_string(fixture("testwiki-20240101-pages-articles.xml")).unwrap();
+    let header = &plain[..plain.find("  <page>").unwrap()];
+    let filler = "filler text\n".repeat(1536 * 1024 / 12);
+    let mut dump = header.to_owned();
+    dump.push_str(&dump_page(
+        1,
+        "First",
+        &format!("needle at the start\n{filler}needle at the end"),
+    ));
+    dump.push_str(&dump_page(2, "Second", &filler));
+    dump.push_str(&dump_page(3, "Third", &format!("{filler}needle at the end")));
+    dump.push_str(&dump_page(4, "Small", "small needle"));
+    dump.push_str("</mediawiki>\n");
+    let dump_file = std::env::temp_dir().join(format!("wdgrep-test-chunk-size-{}.xml", std::process::id()));
+    std::fs::write(&dump_file, dump).unwrap();
+    let dump_file = dump_file.to_str().unwrap();
+
+    let expected = "First@1001\nneedle at the start\nneedle at the end\n\n\
+                    Third@1003\nneedle at the end\n\n\
+                    Small@1004\nsmall needle\n\n";
+    // a single part
+    assert_eq!(wdgrep(&["-j", "1", "needle", dump_file]), expected);
+    assert_eq!(wdgrep(&["-j", "4", "--chunk-size", "1", "needle", dump_file]), expected);
+    assert_eq!(
+        wdgrep(&["-j", "4", "--chunk-size", "1", "--no-prefilter", "needle", dump_file]),
+        expected
+    );
+    // pages searched in several slices are counted once
+    for prefilter_args in [&[][..], &["--no-prefilter"]] {
+        assert_eq!(
+            get_match_counts(&[&["-j", "4", "--chunk-size", "1", "needle", dump_file], prefilter_args].concat()),
+            "Found 3 matching revisions in 3 of 4 searched pages."
+        );
+    }
+    std::fs::remove_file(dump_file).unwrap();
+}
+
+/// Runs wdgrep with verbose statistics and returns the line with the numbers of matching revisions and pages.
+fn get_match_counts(args: &[&str]) -> String {
+    let output = run_wdgrep(&[&["-v"], args].concat());
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("Output is not UTF-8");
+    // UNWRAP: always printed with -v
+    stderr
+        .lines()
+        .find(|line| line.starts_with("Found "))
+        .unwrap()
+        .to_owned()
+}
+
+#[test]
+fn match_counts() {
+    // pages without text are searched, pages skipped by the prefilter as well
+    let expected = "Found 3 matching revisions in 3 of 6 searched pages.";
+    let dump = fixture("testwiki-20240101-pages-articles.xml");
+    assert_eq!(get_match_counts(&["needle", &dump]), expected);
+    assert_eq!(get_match_counts(&["--no-prefilter", "needle", &dump]), expected);
+    let dump = fixture("testwiki-20240101-pages-articles.xml.bz2");
+    assert_eq!(get_match_counts(&["needle", &dump]), expected);
+    assert_eq!(
+        get_match_counts(&[
+            "--ns",
+            "0",
+            "needle",
+            &fixture("testwiki-20240101-pages-articles.xml.bz2")
+        ]),
+        "Found 1 matching revisions in 1 of 4 searched pages."
+    );
+    let dump = fixture("testwiki-20240102-pages-articles-multistream.xml.bz2");
+    assert_eq!(
+        get_match_counts(&["-j", "3", "needle", &dump]),
+        "Found 4 matching revisions in 4 of 5 searched pages."
+    );
+}
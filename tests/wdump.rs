@@ -0,0 +1,100 @@
+// wdump
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! End-to-end tests running wdump on the miniature dumps in `tests/fixtures` (see `tests/wdgrep.rs`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+fn run(binary: &str, args: &[&str]) -> Output {
+    Command::new(binary)
+        .args(args)
+        .output()
+        .unwrap_or_else(|err| panic!("Could not run {binary}: {err}"))
+}
+
+/// Runs wdgrep, checks that it was successful and returns stdout.
+fn wdgrep(args: &[&str]) -> String {
+    let mut wdgrep_args = vec!["--color", "never", "--ordered-output"];
+    wdgrep_args.extend_from_slice(args);
+    let output = run(env!("CARGO_BIN_EXE_wdgrep"), &wdgrep_args);
+    assert!(
+        output.status.success(),
+        "wdgrep {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).expect("Output is not UTF-8")
+}
+
+#[test]
+fn recompress_and_search() {
+    // the multistream dump is recompressed next to the index published with it
+    let directory = std::env::temp_dir().join(format!("wdump-test-recompress-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    for name in [
+        "testwiki-20240102-pages-articles-multistream.xml.bz2",
+        "testwiki-20240102-pages-articles-multistream-index.txt.bz2",
+    ] {
+        fs::copy(fixture(name), directory.join(name)).unwrap();
+    }
+    let path = |name: &str| directory.join(name).to_str().unwrap().to_owned();
+    let multistream_dump = path("testwiki-20240102-pages-articles-multistream.xml.bz2");
+    let zstd_dump = path("testwiki-20240102-pages-articles-multistream.xml.zst");
+    let multistream_index = path("testwiki-20240102-pages-articles-multistream-index.txt.bz2");
+    let zstd_index = path("testwiki-20240102-pages-articles-multistream-zst-index.txt.bz2");
+
+    let output = run(
+        env!("CARGO_BIN_EXE_wdump"),
+        &["recompress", "--pages", "2", &multistream_dump],
+    );
+    assert!(
+        output.status.success(),
+        "wdump recompress failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(Path::new(&zstd_index).is_file());
+    assert_eq!(
+        fs::read(&multistream_index).unwrap(),
+        fs::read(fixture("testwiki-20240102-pages-articles-multistream-index.txt.bz2")).unwrap()
+    );
+
+    // both dumps are searched in full and starting at the offsets in their own index
+    let all_matches = "Delta@111\tneedle\nZeta@113\tneedle\nTalk:Zeta@114\tneedle\nEta@115\tneedle\n";
+    let zeta_matches = "Zeta@113\nSecond needle of the multistream dump.\n\n";
+    for dump in [&multistream_dump, &zstd_dump] {
+        assert_eq!(wdgrep(&["-r", "$0", "--with-title", "needle", dump]), all_matches);
+        assert_eq!(wdgrep(&["--title-filter", "^Zeta$", "needle", dump]), zeta_matches);
+        assert_eq!(
+            wdgrep(&["--title-filter", "^Eta$", "-r", "$0", "--with-title", "needle", dump]),
+            "Eta@115\tneedle\n"
+        );
+    }
+
+    // an existing index is not overwritten
+    fs::remove_file(&zstd_dump).unwrap();
+    let output = run(
+        env!("CARGO_BIN_EXE_wdump"),
+        &["recompress", "--pages", "1", &multistream_dump],
+    );
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    assert!(!Path::new(&zstd_dump).exists());
+    let output = run(env!("CARGO_BIN_EXE_wdgrep"), &["make-index", &multistream_dump]);
+    assert!(!output.status.success());
+    assert_eq!(
+        fs::read(&multistream_index).unwrap(),
+        fs::read(fixture("testwiki-20240102-pages-articles-multistream-index.txt.bz2")).unwrap()
+    );
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,119 @@
+// wdget
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! The index of the latest dump runs of all wikis, `backup-index.html` on the dump website, and the sizes of the dump
+//! runs listed in it.
+
+use std::collections::BTreeMap;
+
+use futures::stream::{self, StreamExt};
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::Client;
+use serde::Serialize;
+
+use crate::{get_dump_status_from, Result};
+
+/// The latest dump run of a wiki as listed in the index.
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct DumpRun {
+    pub wiki: String,
+    pub date: String,
+    /// Time of the last update of the run (`YYYY-MM-DD HH:MM:SS`)
+    pub updated: String,
+    /// Status of the run, e.g. `done`, `in-progress`, `partial` or `failed`
+    pub status: String,
+}
+
+impl DumpRun {
+    pub fn is_complete(&self) -> bool {
+        self.status == "done"
+    }
+}
+
+/// Parses the entries `<li>updated <a href="wiki/date">wiki</a>: <span class='status'>...</span></li>` of the index,
+/// lines in another format are skipped.
+pub fn parse_backup_index(html: &str) -> Vec<DumpRun> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(
+            r#"<li>([0-9]{4}-[0-9]{2}-[0-9]{2} [0-9:]{8}) <a href="([^"/]+)/([1-9][0-9]{7})">[^<]*</a>: <span class=['"]([a-z-]+)['"]>"#
+        )
+        .expect("Error parsing backup index regex constant");
+    }
+    RE.captures_iter(html)
+        .map(|cap| DumpRun {
+            wiki: cap[2].to_owned(),
+            date: cap[3].to_owned(),
+            updated: cap[1].to_owned(),
+            status: cap[4].to_owned(),
+        })
+        .collect()
+}
+
+/// Returns the latest dump run of each wiki listed in the index of the dump website at `base_url`.
+pub async fn get_latest_dump_runs_from(client: &Client, base_url: &str) -> Result<BTreeMap<String, DumpRun>> {
+    let url = format!("{base_url}/backup-index.html");
+    let body = client.get(url).send().await?.error_for_status()?.text().await?;
+    Ok(parse_backup_index(&body)
+        .into_iter()
+        .map(|dump_run| (dump_run.wiki.clone(), dump_run))
+        .collect())
+}
+
+/// Returns the total size of the files of the completed jobs of each dump run, retrieving at most `concurrency`
+/// dump status files at the same time. Runs whose dump status cannot be retrieved are left out.
+pub async fn get_dump_run_sizes_from(
+    client: &Client,
+    base_url: &str,
+    dump_runs: &[&DumpRun],
+    concurrency: usize,
+) -> BTreeMap<String, u64> {
+    stream::iter(dump_runs)
+        .map(|dump_run| async move {
+            let dump_status = get_dump_status_from(client, base_url, &dump_run.wiki, &dump_run.date).await;
+            (
+                dump_run.wiki.clone(),
+                dump_status.map(|dump_status| dump_status.completed_size()),
+            )
+        })
+        .buffer_unordered(concurrency)
+        .filter_map(|(wiki, size)| async move { Some((wiki, size.ok()?)) })
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_backup_index() {
+        let html = "<html><body><ul>\n\
+                    <li>2024-01-21 08:12:45 <a href=\"enwiki/20240120\">enwiki</a>: <span class='in-progress'>Dump \
+                    in progress</span></li>\n\
+                    <li>2024-01-05 10:00:00 <a href=\"dewiki/20240101\">dewiki</a>: <span class='done'>Dump \
+                    complete</span></li>\n\
+                    <li>2024-01-05 10:00:00 <a href=\"testwiki\">testwiki</a>: <span class='waiting'>Idle</span></li>\n\
+                    </ul></body></html>";
+        assert_eq!(
+            parse_backup_index(html),
+            [
+                DumpRun {
+                    wiki: "enwiki".to_owned(),
+                    date: "20240120".to_owned(),
+                    updated: "2024-01-21 08:12:45".to_owned(),
+                    status: "in-progress".to_owned(),
+                },
+                DumpRun {
+                    wiki: "dewiki".to_owned(),
+                    date: "20240101".to_owned(),
+                    updated: "2024-01-05 10:00:00".to_owned(),
+                    status: "done".to_owned(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,357 @@
+// wdget
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Cache of downloaded dumps shared by wdget and wdgrep.
+//!
+//! Each dump is kept in its own directory `<cache root>/<wiki>/<date>/<job>/`. A dump is complete once
+//! `manifest.json` has been written to its directory after all files were downloaded. The manifest lists the
+//! files with their sizes, so that partially deleted or overwritten dumps are not used.
+//!
+//! The list of available wikis is cached in `<cache root>/wikis.json`.
+
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, Wiki};
+
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+const WIKI_LIST_FILE_NAME: &str = "wikis.json";
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct CachedFile {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct CacheManifest {
+    pub wiki: String,
+    pub date: String,
+    pub job: String,
+    pub files: Vec<CachedFile>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct CachedWikiList {
+    /// Root URL of the dump website the list was retrieved for
+    pub base_url: String,
+    /// Seconds since the Unix epoch when the list was retrieved
+    pub retrieved: u64,
+    pub wikis: Vec<Wiki>,
+}
+
+impl CachedWikiList {
+    /// Creates the list of wikis just retrieved from the dump website at `base_url`.
+    pub fn new(base_url: &str, wikis: Vec<Wiki>) -> CachedWikiList {
+        CachedWikiList {
+            base_url: base_url.to_owned(),
+            retrieved: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            wikis,
+        }
+    }
+
+    /// Returns the time since the list was retrieved, zero if the clock was set back in the meantime.
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH + Duration::from_secs(self.retrieved))
+            .unwrap_or_default()
+    }
+}
+
+/// Splits a dump specification of the form `wiki:date:job`, e.g. `enwiki:20240101:articlesdump`.
+pub fn parse_dump_spec(dump_spec: &str) -> Option<(&str, &str, &str)> {
+    match dump_spec.splitn(3, ':').collect::<Vec<_>>()[..] {
+        [wiki, date, job] if !wiki.is_empty() && !date.is_empty() && !job.is_empty() => Some((wiki, date, job)),
+        _ => None,
+    }
+}
+
+pub struct DumpCache {
+    root: PathBuf,
+}
+
+impl DumpCache {
+    pub fn new<P: AsRef<Path>>(root: P) -> DumpCache {
+        DumpCache {
+            root: root.as_ref().to_owned(),
+        }
+    }
+
+    /// Opens the cache at `$WIKIDUMPTOOLS_CACHE`, `$XDG_CACHE_HOME/wikidumptools` or `~/.cache/wikidumptools`
+    /// (`%LOCALAPPDATA%\wikidumptools` on Windows).
+    pub fn open_default() -> Result<DumpCache> {
+        if let Some(root) = std::env::var_os("WIKIDUMPTOOLS_CACHE") {
+            return Ok(DumpCache::new(root));
+        }
+        let cache_home = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .ok_or(Error::CacheDirectoryNotFound())?;
+        Ok(DumpCache::new(cache_home.join("wikidumptools")))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns the directory of the dump, which may not exist yet.
+    pub fn dump_directory(&self, wiki: &str, date: &str, job: &str) -> PathBuf {
+        self.root.join(wiki).join(date).join(job)
+    }
+
+    /// Creates the directory of the dump and returns its path.
+    pub fn create_dump_directory(&self, wiki: &str, date: &str, job: &str) -> Result<PathBuf> {
+        let directory = self.dump_directory(wiki, date, job);
+        fs::create_dir_all(&directory).map_err(|e| cache_access_error(&directory, e))?;
+        Ok(directory)
+    }
+
+    /// Marks the dump as complete by writing a manifest listing all files in its directory.
+    pub fn write_manifest(&self, wiki: &str, date: &str, job: &str) -> Result<CacheManifest> {
+        let directory = self.dump_directory(wiki, date, job);
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&directory).map_err(|e| cache_access_error(&directory, e))? {
+            let entry = entry.map_err(|e| cache_access_error(&directory, e))?;
+            let metadata = entry.metadata().map_err(|e| cache_access_error(&entry.path(), e))?;
+            match entry.file_name().to_str() {
+                Some(name) if metadata.is_file() && name != MANIFEST_FILE_NAME && !name.ends_with(".part") => {
+                    files.push(CachedFile {
+                        name: name.to_owned(),
+                        size: metadata.len(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        files.sort_unstable_by(|f1, f2| f1.name.cmp(&f2.name));
+        let manifest = CacheManifest {
+            wiki: wiki.to_owned(),
+            date: date.to_owned(),
+            job: job.to_owned(),
+            files,
+        };
+        let manifest_path = directory.join(MANIFEST_FILE_NAME);
+        let mut writer =
+            BufWriter::new(File::create(&manifest_path).map_err(|e| cache_access_error(&manifest_path, e))?);
+        serde_json::to_writer_pretty(&mut writer, &manifest)?;
+        writer.flush().map_err(|e| cache_access_error(&manifest_path, e))?;
+        Ok(manifest)
+    }
+
+    /// Returns the manifest of the dump if it has been completely downloaded.
+    pub fn read_manifest(&self, wiki: &str, date: &str, job: &str) -> Result<Option<CacheManifest>> {
+        let manifest_path = self.dump_directory(wiki, date, job).join(MANIFEST_FILE_NAME);
+        let file = match File::open(&manifest_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(cache_access_error(&manifest_path, e)),
+        };
+        let manifest = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| Error::InvalidCacheManifest(manifest_path, e.to_string()))?;
+        Ok(Some(manifest))
+    }
+
+    /// Returns the paths of the files of the dump if it is complete and all files still have the size recorded
+    /// in the manifest.
+    pub fn get_dump_files(&self, wiki: &str, date: &str, job: &str) -> Result<Option<Vec<PathBuf>>> {
+        let manifest = match self.read_manifest(wiki, date, job)? {
+            Some(manifest) => manifest,
+            None => return Ok(None),
+        };
+        let directory = self.dump_directory(wiki, date, job);
+        let mut paths = Vec::with_capacity(manifest.files.len());
+        for file in manifest.files {
+            let path = directory.join(&file.name);
+            match fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() && metadata.len() == file.size => paths.push(path),
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(paths))
+    }
+
+    /// Returns the most recent date for which the dump of this job is complete in the cache.
+    pub fn get_latest_date(&self, wiki: &str, job: &str) -> Result<Option<String>> {
+        let wiki_directory = self.root.join(wiki);
+        let entries = match fs::read_dir(&wiki_directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(cache_access_error(&wiki_directory, e)),
+        };
+        let mut latest_date = None;
+        for entry in entries {
+            let entry = entry.map_err(|e| cache_access_error(&wiki_directory, e))?;
+            if let Some(date) = entry.file_name().to_str() {
+                if latest_date.as_deref().is_none_or(|latest_date| date > latest_date)
+                    && self.read_manifest(wiki, date, job)?.is_some()
+                {
+                    latest_date = Some(date.to_owned());
+                }
+            }
+        }
+        Ok(latest_date)
+    }
+
+    /// Caches the list of wikis, replacing the list of any other dump website.
+    pub fn write_wiki_list(&self, wiki_list: &CachedWikiList) -> Result<()> {
+        fs::create_dir_all(&self.root).map_err(|e| cache_access_error(&self.root, e))?;
+        let path = self.root.join(WIKI_LIST_FILE_NAME);
+        let mut writer = BufWriter::new(File::create(&path).map_err(|e| cache_access_error(&path, e))?);
+        serde_json::to_writer(&mut writer, wiki_list)?;
+        writer.flush().map_err(|e| cache_access_error(&path, e))?;
+        Ok(())
+    }
+
+    /// Returns the cached list of wikis if it was retrieved from the dump website at `base_url`, regardless of its
+    /// age.
+    pub fn read_wiki_list(&self, base_url: &str) -> Result<Option<CachedWikiList>> {
+        let path = self.root.join(WIKI_LIST_FILE_NAME);
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(cache_access_error(&path, e)),
+        };
+        let wiki_list: CachedWikiList = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| Error::InvalidCachedWikiList(path, e.to_string()))?;
+        Ok(Some(wiki_list).filter(|wiki_list| wiki_list.base_url == base_url))
+    }
+}
+
+fn cache_access_error(path: &Path, e: std::io::Error) -> Error {
+    Error::DumpFileAccessError(path.to_owned(), e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_cache(name: &str) -> DumpCache {
+        let root = std::env::temp_dir().join(format!("wdgetlib-cache-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        DumpCache::new(root)
+    }
+
+    #[test]
+    fn test_parse_dump_spec() {
+        assert_eq!(
+            parse_dump_spec("enwiki:20240101:articlesdump"),
+            Some(("enwiki", "20240101", "articlesdump"))
+        );
+        assert_eq!(
+            parse_dump_spec("other/cirrussearch:latest:enwiki-cirrussearch-content"),
+            Some(("other/cirrussearch", "latest", "enwiki-cirrussearch-content"))
+        );
+        assert_eq!(parse_dump_spec("enwiki-20240101-pages-articles.xml"), None);
+        assert_eq!(parse_dump_spec("enwiki::articlesdump"), None);
+    }
+
+    #[test]
+    fn test_manifest_and_files() {
+        let cache = create_test_cache("manifest");
+        assert_eq!(
+            cache.get_dump_files("testwiki", "20240101", "articlesdump").unwrap(),
+            None
+        );
+
+        let directory = cache
+            .create_dump_directory("testwiki", "20240101", "articlesdump")
+            .unwrap();
+        assert_eq!(
+            directory,
+            cache.root().join("testwiki").join("20240101").join("articlesdump")
+        );
+        fs::write(directory.join("testwiki-20240101-pages-articles2.xml.bz2"), b"22").unwrap();
+        fs::write(directory.join("testwiki-20240101-pages-articles1.xml.bz2"), b"1").unwrap();
+        fs::write(directory.join("testwiki-20240101-pages-articles3.xml.bz2.part"), b"333").unwrap();
+        // not complete without manifest
+        assert_eq!(
+            cache.get_dump_files("testwiki", "20240101", "articlesdump").unwrap(),
+            None
+        );
+
+        let manifest = cache.write_manifest("testwiki", "20240101", "articlesdump").unwrap();
+        assert_eq!(
+            manifest.files,
+            vec![
+                CachedFile {
+                    name: "testwiki-20240101-pages-articles1.xml.bz2".to_owned(),
+                    size: 1
+                },
+                CachedFile {
+                    name: "testwiki-20240101-pages-articles2.xml.bz2".to_owned(),
+                    size: 2
+                },
+            ]
+        );
+        assert_eq!(
+            cache.read_manifest("testwiki", "20240101", "articlesdump").unwrap(),
+            Some(manifest)
+        );
+        assert_eq!(
+            cache.get_dump_files("testwiki", "20240101", "articlesdump").unwrap(),
+            Some(vec![
+                directory.join("testwiki-20240101-pages-articles1.xml.bz2"),
+                directory.join("testwiki-20240101-pages-articles2.xml.bz2"),
+            ])
+        );
+
+        // changed files invalidate the cached dump
+        fs::write(directory.join("testwiki-20240101-pages-articles2.xml.bz2"), b"2").unwrap();
+        assert_eq!(
+            cache.get_dump_files("testwiki", "20240101", "articlesdump").unwrap(),
+            None
+        );
+
+        fs::remove_dir_all(cache.root()).unwrap();
+    }
+
+    #[test]
+    fn test_latest_date() {
+        let cache = create_test_cache("latest");
+        assert_eq!(cache.get_latest_date("testwiki", "articlesdump").unwrap(), None);
+        for date in ["20240101", "20240201", "20240301"] {
+            cache.create_dump_directory("testwiki", date, "articlesdump").unwrap();
+        }
+        cache.write_manifest("testwiki", "20240101", "articlesdump").unwrap();
+        cache.write_manifest("testwiki", "20240201", "articlesdump").unwrap();
+        // 20240301 is incomplete
+        assert_eq!(
+            cache.get_latest_date("testwiki", "articlesdump").unwrap(),
+            Some("20240201".to_owned())
+        );
+        assert_eq!(cache.get_latest_date("testwiki", "metacurrentdump").unwrap(), None);
+
+        fs::remove_dir_all(cache.root()).unwrap();
+    }
+
+    #[test]
+    fn test_wiki_list() {
+        let cache = create_test_cache("wikis");
+        assert_eq!(cache.read_wiki_list("https://dumps.example.org").unwrap(), None);
+        let wikis = vec![Wiki {
+            id: "testwiki".to_owned(),
+            name: "Test Wiki".to_owned(),
+        }];
+        let written = CachedWikiList::new("https://dumps.example.org", wikis);
+        assert!(written.age() < Duration::from_secs(60));
+        cache.write_wiki_list(&written).unwrap();
+        assert_eq!(
+            cache.read_wiki_list("https://dumps.example.org").unwrap(),
+            Some(written)
+        );
+        // lists of other dump websites are not used
+        assert_eq!(cache.read_wiki_list("https://dumps.wikimedia.org").unwrap(), None);
+        fs::remove_dir_all(cache.root()).unwrap();
+    }
+}
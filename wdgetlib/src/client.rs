@@ -0,0 +1,200 @@
+// wdget
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! A client bundling the settings needed to talk to the dump website, so they do not have to be passed to every
+//! function call.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use reqwest::Client;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::cache::DumpCache;
+use crate::{
+    download_dump, get_available_dates_from, get_available_wikis_from, get_dump_status_from, get_file_info_from,
+    get_latest_available_date_from, resolve_file_url, DownloadOptions, DownloadProgress, DumpFileInfo, DumpStatus,
+    Error, ErrorClass, Result, Wiki, DEFAULT_BASE_URL,
+};
+
+/// How often requests failing with a network error are attempted. The delay doubles after each failed attempt.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+pub struct WdClient {
+    client: Client,
+    base_url: String,
+    mirrors: Vec<String>,
+    retry_policy: RetryPolicy,
+    cache: Option<DumpCache>,
+    offline: bool,
+}
+
+impl WdClient {
+    pub fn new(client: Client) -> WdClient {
+        WdClient {
+            client,
+            base_url: DEFAULT_BASE_URL.to_owned(),
+            mirrors: Vec::new(),
+            retry_policy: RetryPolicy::default(),
+            cache: None,
+            offline: false,
+        }
+    }
+
+    /// Root URL of the dump website used for dump listings and status files.
+    pub fn with_base_url(&mut self, base_url: &str) -> &mut Self {
+        self.base_url = base_url.trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Adds a mirror to download dump files from. With several mirrors each retry of a download uses the next one.
+    pub fn with_mirror(&mut self, mirror: &str) -> &mut Self {
+        self.mirrors.push(mirror.trim_end_matches('/').to_owned());
+        self
+    }
+
+    pub fn with_retry_policy(&mut self, retry_policy: RetryPolicy) -> &mut Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Downloads without a target directory go to the cache.
+    pub fn with_cache(&mut self, cache: DumpCache) -> &mut Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// In offline mode all functions needing the network fail with `Error::Offline` without sending requests or
+    /// retrying, so code using the client can be tested without network access.
+    pub fn with_offline(&mut self, offline: bool) -> &mut Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn cache(&self) -> Option<&DumpCache> {
+        self.cache.as_ref()
+    }
+
+    async fn retry<T, F, Fut>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut(u32) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if self.offline {
+            return Err(Error::Offline());
+        }
+        let mut delay = self.retry_policy.initial_delay;
+        let mut attempt = 0;
+        loop {
+            match f(attempt).await {
+                Err(e) if e.class() == ErrorClass::Network && attempt + 1 < self.retry_policy.max_attempts => {
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    pub async fn available_wikis(&self) -> Result<Vec<Wiki>> {
+        self.retry(|_| get_available_wikis_from(&self.client, &self.base_url))
+            .await
+    }
+
+    pub async fn dump_status(&self, wiki: &str, date: &str) -> Result<DumpStatus> {
+        self.retry(|_| get_dump_status_from(&self.client, &self.base_url, wiki, date))
+            .await
+    }
+
+    pub async fn available_dates(&self, wiki: &str) -> Result<Vec<String>> {
+        self.retry(|_| get_available_dates_from(&self.client, &self.base_url, wiki))
+            .await
+    }
+
+    pub async fn latest_available_date(&self, wiki: &str, dump_type: Option<&str>) -> Result<String> {
+        self.retry(|_| get_latest_available_date_from(&self.client, &self.base_url, wiki, dump_type))
+            .await
+    }
+
+    pub async fn file_info(&self, wiki: &str, date: &str, job: &str, file_name: &str) -> Result<DumpFileInfo> {
+        self.retry(|_| get_file_info_from(&self.client, &self.base_url, wiki, date, job, file_name))
+            .await
+    }
+
+    /// Returns the download URL of a dump file from the first mirror or the base URL.
+    pub fn file_url(&self, wiki: &str, date: &str, file_name: &str, file_info: &DumpFileInfo) -> String {
+        let root_url = self.mirrors.first().unwrap_or(&self.base_url);
+        resolve_file_url(Some(root_url), wiki, date, file_name, file_info)
+    }
+
+    /// Downloads a dump to the target directory or, if none is given, to the cache and returns the directory the
+    /// files were downloaded to. The base URL and mirror of the client take precedence over those of the options.
+    /// Files already downloaded are skipped, so retries only fetch the remaining files.
+    pub async fn download(
+        &self,
+        wiki: &str,
+        date: &str,
+        dump_type: &str,
+        target_directory: Option<&Path>,
+        download_options: &DownloadOptions<'_>,
+        progress_send: Option<UnboundedSender<DownloadProgress>>,
+    ) -> Result<PathBuf> {
+        let (target_directory, cache) = match (target_directory, &self.cache) {
+            (Some(target_directory), _) => (target_directory.to_owned(), None),
+            (None, Some(cache)) => (cache.create_dump_directory(wiki, date, dump_type)?, Some(cache)),
+            (None, None) => return Err(Error::NoTargetDirectory()),
+        };
+        self.retry(|attempt| {
+            let mirror = match self.mirrors.len() {
+                0 => download_options.mirror,
+                len => Some(self.mirrors[attempt as usize % len].as_str()),
+            };
+            let download_options = DownloadOptions {
+                base_url: Some(&self.base_url),
+                mirror,
+                ..*download_options
+            };
+            let target_directory = &target_directory;
+            let progress_send = progress_send.clone();
+            async move {
+                download_dump(
+                    &self.client,
+                    wiki,
+                    date,
+                    dump_type,
+                    target_directory,
+                    &download_options,
+                    progress_send,
+                )
+                .await
+            }
+        })
+        .await?;
+        if let Some(cache) = cache {
+            cache.write_manifest(wiki, date, dump_type)?;
+        }
+        Ok(target_directory)
+    }
+}
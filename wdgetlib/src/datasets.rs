@@ -0,0 +1,199 @@
+// wdget
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Analytics datasets published below `other/` on the dump website, e.g. pageview counts and clickstream data.
+//! Unlike dumps they are not organized in dump runs but in directories per month or year, so files are selected by
+//! a date range.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use regex::Regex;
+use reqwest::Client;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    download_files, get_staging_directory_name, list_directory, DownloadOptions, DownloadProgress, DumpFileInfo, Error,
+    Result, DEFAULT_BASE_URL,
+};
+
+/// The period covered by a directory of a dataset.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DirectoryPeriod {
+    Month,
+    Year,
+}
+
+pub struct Dataset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Directory below `other/`, `{year}` and `{month}` are replaced by the date of the files
+    pub directory: &'static str,
+    pub directory_period: DirectoryPeriod,
+    /// Matches the file names of the dataset and captures their date in the groups `year`, `month` and, for files
+    /// not covering a whole month, `day`
+    file_pattern: &'static str,
+}
+
+pub const DATASETS: &[Dataset] = &[
+    Dataset {
+        name: "pageviews",
+        description: "Hourly pageview counts of all wikis",
+        directory: "pageviews/{year}/{year}-{month}",
+        directory_period: DirectoryPeriod::Month,
+        file_pattern: r"^pageviews-(?P<year>\d{4})(?P<month>\d{2})(?P<day>\d{2})-\d{6}\.gz$",
+    },
+    Dataset {
+        name: "pageview-complete",
+        description: "Daily pageview counts of all wikis by agent type (user, automated, spider)",
+        directory: "pageview_complete/{year}/{year}-{month}",
+        directory_period: DirectoryPeriod::Month,
+        file_pattern: r"^pageviews-(?P<year>\d{4})(?P<month>\d{2})(?P<day>\d{2})-[a-z]+\.bz2$",
+    },
+    Dataset {
+        name: "clickstream",
+        description: "Monthly counts of referrer and article pairs of some Wikipedias",
+        directory: "clickstream/{year}-{month}",
+        directory_period: DirectoryPeriod::Month,
+        file_pattern: r"^clickstream-[a-z_]+-(?P<year>\d{4})-(?P<month>\d{2})\.tsv\.gz$",
+    },
+    Dataset {
+        name: "mediacounts",
+        description: "Daily request counts of media files on upload.wikimedia.org",
+        directory: "mediacounts/daily/{year}",
+        directory_period: DirectoryPeriod::Year,
+        file_pattern: r"^mediacounts\.(?P<year>\d{4})-(?P<month>\d{2})-(?P<day>\d{2})\.v00\.tsv\.bz2$",
+    },
+];
+
+pub fn find_dataset(name: &str) -> Result<&'static Dataset> {
+    DATASETS
+        .iter()
+        .find(|dataset| dataset.name == name)
+        .ok_or_else(|| Error::DatasetNotFound(name.to_owned()))
+}
+
+/// The files of a dataset with dates in the range from `from` to `to` (inclusive, YYYYMMDD). Files covering a
+/// whole month are selected if the month is in the range.
+pub struct DatasetSelection<'a> {
+    pub dataset: &'static Dataset,
+    pub from: &'a str,
+    pub to: &'a str,
+    /// Only select files with names matching the regular expression
+    pub filter: Option<&'a Regex>,
+}
+
+fn parse_date(date: &str) -> Result<(u32, u32, u32)> {
+    let invalid_date = || Error::InvalidDatasetDate(date.to_owned());
+    if date.len() != 8 || !date.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(invalid_date());
+    }
+    // UNWRAP: only digits
+    let (year, month, day) = (
+        date[..4].parse().unwrap(),
+        date[4..6].parse().unwrap(),
+        date[6..].parse().unwrap(),
+    );
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid_date());
+    }
+    Ok((year, month, day))
+}
+
+/// Returns the directories below `other/` which may contain files in the date range.
+fn get_directories(dataset: &Dataset, from: (u32, u32, u32), to: (u32, u32, u32)) -> Vec<String> {
+    let mut directories = Vec::new();
+    let (mut year, mut month) = (from.0, from.1);
+    while (year, month) <= (to.0, to.1) {
+        directories.push(
+            dataset
+                .directory
+                .replace("{year}", &format!("{year:04}"))
+                .replace("{month}", &format!("{month:02}")),
+        );
+        match dataset.directory_period {
+            DirectoryPeriod::Month if month == 12 => (year, month) = (year + 1, 1),
+            DirectoryPeriod::Month => month += 1,
+            DirectoryPeriod::Year => (year, month) = (year + 1, 1),
+        }
+    }
+    directories
+}
+
+/// Lists the selected files of a dataset, the `url` of the returned file infos is the path of the file on the dump
+/// website. Directories not published yet are skipped.
+pub async fn get_dataset_files(
+    client: &Client,
+    selection: &DatasetSelection<'_>,
+) -> Result<BTreeMap<String, DumpFileInfo>> {
+    let from = parse_date(selection.from)?;
+    let to = parse_date(selection.to)?;
+    // UNWRAP: constant
+    let file_re = Regex::new(selection.dataset.file_pattern).unwrap();
+    let mut files = BTreeMap::new();
+    for directory in get_directories(selection.dataset, from, to) {
+        let url = format!("{DEFAULT_BASE_URL}/other/{directory}/");
+        let directory_files = match list_directory(client, &url).await? {
+            Some(directory_files) => directory_files,
+            None => continue,
+        };
+        for (file_name, mut file_info) in directory_files {
+            let is_selected = file_re.captures(&file_name).is_some_and(|cap| {
+                // UNWRAP: regex matched, so the groups are numbers
+                let year: u32 = cap["year"].parse().unwrap();
+                let month: u32 = cap["month"].parse().unwrap();
+                match cap.name("day") {
+                    Some(day) => (from..=to).contains(&(year, month, day.as_str().parse().unwrap())),
+                    None => ((from.0, from.1)..=(to.0, to.1)).contains(&(year, month)),
+                }
+            }) && selection.filter.is_none_or(|filter| filter.is_match(&file_name));
+            if is_selected {
+                file_info.url = Some(format!("/other/{directory}/{file_name}"));
+                files.insert(file_name, file_info);
+            }
+        }
+    }
+    if files.is_empty() {
+        return Err(Error::NoDatasetFilesFound());
+    }
+    Ok(files)
+}
+
+/// Returns the download URL of a file of a dataset from the given mirror or the main Wikimedia dump website.
+pub fn get_dataset_file_url(mirror: Option<&str>, file_info: &DumpFileInfo) -> String {
+    let root_url = mirror.unwrap_or(DEFAULT_BASE_URL);
+    // UNWRAP: set for all files of datasets
+    format!("{}{}", root_url, file_info.url.as_deref().unwrap())
+}
+
+/// Downloads the selected files of a dataset, existing files are skipped.
+pub async fn download_dataset<T>(
+    client: &Client,
+    selection: &DatasetSelection<'_>,
+    target_directory: T,
+    download_options: &DownloadOptions<'_>,
+    progress_send: Option<UnboundedSender<DownloadProgress>>,
+) -> Result<()>
+where
+    T: AsRef<Path> + Send,
+{
+    let target_directory = target_directory.as_ref();
+    if !target_directory.exists() {
+        return Err(Error::TargetDirectoryDoesNotExist(target_directory.to_owned()));
+    }
+    let files = get_dataset_files(client, selection).await?;
+    let date_range = format!("{}-{}", selection.from, selection.to);
+    download_files(
+        client,
+        &files,
+        |_, file_info| get_dataset_file_url(download_options.mirror, file_info),
+        &get_staging_directory_name(selection.dataset.name, &date_range, "dataset"),
+        target_directory,
+        download_options,
+        progress_send,
+    )
+    .await
+}
@@ -0,0 +1,156 @@
+// wdget
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Checksums computed while data is read or written, so files are verified while they are downloaded, copied or
+//! decompressed instead of reading them again afterwards.
+
+use std::io::{self, Read, Write};
+
+use md5::Md5;
+use sha1::{Digest, Sha1};
+
+/// Checksum algorithms used in dump status files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HashAlgorithm {
+    Sha1,
+    Md5,
+}
+
+/// Incremental hash using one of the algorithms.
+#[derive(Clone)]
+pub enum Hasher {
+    Sha1(Sha1),
+    Md5(Md5),
+}
+
+impl Hasher {
+    pub fn new(algorithm: HashAlgorithm) -> Hasher {
+        match algorithm {
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Md5(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Returns the digest as lowercase hex digits like in dump status files.
+    pub fn finalize_hex(self) -> String {
+        match self {
+            Hasher::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Md5(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+/// Hashes all data read through it.
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Hasher,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R, algorithm: HashAlgorithm) -> HashingReader<R> {
+        HashingReader::with_hasher(inner, Hasher::new(algorithm))
+    }
+
+    /// Continues hashing with a hasher which has already hashed the preceding data.
+    pub fn with_hasher(inner: R, hasher: Hasher) -> HashingReader<R> {
+        HashingReader { inner, hasher }
+    }
+
+    /// Returns the reader and the hash of the data read so far.
+    pub fn into_parts(self) -> (R, Hasher) {
+        (self.inner, self.hasher)
+    }
+
+    pub fn finalize_hex(self) -> String {
+        self.hasher.finalize_hex()
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read_len = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read_len]);
+        Ok(read_len)
+    }
+}
+
+/// Hashes all data written through it.
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Hasher,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W, algorithm: HashAlgorithm) -> HashingWriter<W> {
+        HashingWriter::with_hasher(inner, Hasher::new(algorithm))
+    }
+
+    /// Continues hashing with a hasher which has already hashed the preceding data.
+    pub fn with_hasher(inner: W, hasher: Hasher) -> HashingWriter<W> {
+        HashingWriter { inner, hasher }
+    }
+
+    /// Returns the writer and the hash of the data written so far.
+    pub fn into_parts(self) -> (W, Hasher) {
+        (self.inner, self.hasher)
+    }
+
+    pub fn finalize_hex(self) -> String {
+        self.hasher.finalize_hex()
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written_len = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written_len]);
+        Ok(written_len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns the hex digest of the remaining data of the reader.
+pub fn hash_reader<R: Read>(reader: R, algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut reader = HashingReader::new(reader, algorithm);
+    io::copy(&mut reader, &mut io::sink())?;
+    Ok(reader.finalize_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashing_reader_and_writer() {
+        let data = b"<mediawiki>\n</mediawiki>\n".repeat(1000);
+        let sha1 = format!("{:x}", Sha1::digest(&data));
+        assert_eq!(hash_reader(data.as_slice(), HashAlgorithm::Sha1).unwrap(), sha1);
+        assert_eq!(
+            hash_reader(&b"abc"[..], HashAlgorithm::Md5).unwrap(),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+
+        let mut writer = HashingWriter::new(Vec::new(), HashAlgorithm::Sha1);
+        writer.write_all(&data[..100]).unwrap();
+        let (written, hasher) = writer.into_parts();
+        // continued after resuming
+        let mut writer = HashingWriter::with_hasher(written, hasher);
+        writer.write_all(&data[100..]).unwrap();
+        let (written, hasher) = writer.into_parts();
+        assert_eq!(written, data);
+        assert_eq!(hasher.finalize_hex(), sha1);
+    }
+}
@@ -3,30 +3,57 @@
 // (C) 2020 Count Count
 //
 // Distributed under the terms of the MIT license.
+
+//! Downloading and verifying Wikimedia dumps.
+//!
+//! This crate is the single implementation of talking to the dump website used by the wdget binary, which only
+//! parses its arguments and prints the results. The public API consists of:
+//!
+//! - [`download_dump()`] reporting [`DownloadProgress`], aggregated by [`progress::DownloadProgressTracker`]
+//!   implementing the [`progress::Progress`] trait shared with wdgreplib,
+//! - the dump status and file lookups like [`get_dump_status()`], [`get_dump_files()`] and [`get_file_info()`], with
+//!   [`client::WdClient`] bundling the settings for them,
+//! - the latest dump runs of all wikis in [`backup_index`], and the [`cache`], [`datasets`], [`hashing`] and
+//!   [`store`] modules.
+//!
+//! It follows semantic versioning: [`Error`] is non-exhaustive, so error variants can be added in minor versions.
+
 use std::cmp::min;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
 use std::fs;
 use std::fs::OpenOptions;
-use std::io::{Read, Write};
+use std::io::{self, BufRead, Read, Write};
 use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 
 use bytes::Bytes;
-use bzip2::read::MultiBzDecoder;
+use bzip2::bufread::BzDecoder;
 use fs::remove_file;
 use futures::stream::{self, StreamExt};
 use futures::TryFutureExt;
 use lazy_static::lazy_static;
 use regex::Regex;
+use reqwest::header::RANGE;
 use reqwest::{Client, StatusCode};
 use scopeguard::defer;
-use serde::Deserialize;
-use sha1::{Digest, Sha1};
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::Semaphore;
 use tokio::task::{spawn_blocking, JoinError};
 
+use crate::hashing::{HashAlgorithm, Hasher, HashingReader};
+
+pub mod backup_index;
+pub mod cache;
+pub mod client;
+pub mod datasets;
+pub mod hashing;
+pub mod progress;
+pub mod store;
+
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum Error {
     #[error("Network I/O error {0}")]
     HttpError(#[from] reqwest::Error),
@@ -36,10 +63,14 @@ pub enum Error {
     DecompressorError(std::io::Error),
     #[error("Error running decompression process: {0}")]
     DecompressorJoinError(JoinError),
+    #[error("Error verifying existing file: {0}")]
+    VerificationJoinError(JoinError),
     #[error("Received invalid JSON data from Wikidata")]
     InvalidJsonFromWikidata(),
     #[error("Dump of this type was not found")]
     DumpTypeNotFound(),
+    #[error("Dump file {0} was not found")]
+    DumpFileNotFound(String),
     #[error("Dump is still in progress")]
     DumpNotComplete(),
     #[error("Dump does not contain any files")]
@@ -60,12 +91,96 @@ pub enum Error {
     DecompressedFileCannotBeVerified(String),
     #[error("Expected file {0} not found")]
     FileToBeVerifiedNotFound(String),
+    #[error("Multistream index file {0} not found")]
+    MultistreamIndexNotFound(String),
+    #[error("Invalid line in multistream index: '{0}'")]
+    InvalidMultistreamIndexLine(String),
+    #[error("Multistream index does not match the pages of {0} at offset {1}")]
+    MultistreamIndexMismatch(PathBuf, u64),
+    #[error("Invalid dump store index {0}")]
+    InvalidDumpStoreIndex(PathBuf),
+    #[error("Page {0} is too large for the dump store")]
+    PageTooLargeForDumpStore(u64),
+    #[error("Cache directory could not be determined, set WIKIDUMPTOOLS_CACHE")]
+    CacheDirectoryNotFound(),
+    #[error("Invalid cache manifest {0} - {1}")]
+    InvalidCacheManifest(PathBuf, String),
+    #[error("Invalid cached wiki list {0} - {1}")]
+    InvalidCachedWikiList(PathBuf, String),
+    #[error("Checksum of {0} differs from the expected one")]
+    ChecksumMismatch(PathBuf),
+    #[error("Size of {0} does not match the expected size. Expected: {1}, actual: {2}")]
+    FileSizeMismatch(PathBuf, u64, u64),
+    #[error("Unknown dataset {0}")]
+    DatasetNotFound(String),
+    #[error("Invalid date {0}, must be YYYYMMDD")]
+    InvalidDatasetDate(String),
+    #[error("No files of the dataset found in the date range")]
+    NoDatasetFilesFound(),
+    #[error("No target directory given and no cache configured")]
+    NoTargetDirectory(),
+    #[error("Network access is disabled in offline mode")]
+    Offline(),
     #[error("Could not send to progress channel")]
     ProgressChannelSendError(#[from] tokio::sync::mpsc::error::SendError<DownloadProgress>),
 }
 
+/// Broad classes of errors, which allow callers to decide whether retrying later makes sense.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorClass {
+    Network,
+    /// Downloaded or existing files do not match the dump status
+    Verification,
+    /// The dump has not been started or finished yet
+    DumpNotReady,
+    UserAbort,
+    Disk,
+    Other,
+}
+
+impl Error {
+    pub fn class(&self) -> ErrorClass {
+        match self {
+            Error::HttpError(_) | Error::InvalidJsonFromWikidata() => ErrorClass::Network,
+            Error::DecompressorError(_)
+            | Error::DecompressedFileCannotBeVerified(_)
+            | Error::FileToBeVerifiedNotFound(_)
+            | Error::MultistreamIndexNotFound(_)
+            | Error::InvalidMultistreamIndexLine(_)
+            | Error::MultistreamIndexMismatch(_, _)
+            | Error::ChecksumMismatch(_)
+            | Error::FileSizeMismatch(_, _, _) => ErrorClass::Verification,
+            Error::DumpNotComplete() | Error::DumpStatusFileNotFound() | Error::NoDumpDatesFound() => {
+                ErrorClass::DumpNotReady
+            }
+            Error::AbortedByUser() => ErrorClass::UserAbort,
+            Error::DumpFileAccessError(_, _)
+            | Error::TargetDirectoryDoesNotExist(_)
+            | Error::InvalidDumpStoreIndex(_)
+            | Error::CacheDirectoryNotFound()
+            | Error::InvalidCacheManifest(_, _)
+            | Error::InvalidCachedWikiList(_, _) => ErrorClass::Disk,
+            Error::JsonError(_)
+            | Error::DecompressorJoinError(_)
+            | Error::VerificationJoinError(_)
+            | Error::DumpTypeNotFound()
+            | Error::DumpFileNotFound(_)
+            | Error::DumpHasNoFiles()
+            | Error::InvalidDumpDate()
+            | Error::DatasetNotFound(_)
+            | Error::InvalidDatasetDate(_)
+            | Error::NoDatasetFilesFound()
+            | Error::NoTargetDirectory()
+            | Error::PageTooLargeForDumpStore(_)
+            | Error::Offline()
+            | Error::ProgressChannelSendError(_) => ErrorClass::Other,
+        }
+    }
+}
+
 type Result<T> = std::result::Result<T, Error>;
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
 pub struct Wiki {
     pub id: String,
     pub name: String,
@@ -110,12 +225,48 @@ pub async fn get_available_wikis_from_wikidata(client: &Client) -> Result<Vec<Wi
     Ok(wikis)
 }
 
+/// Lists the wikis with dumps on the dump website at `base_url`. For the Wikimedia dump website they are taken from
+/// Wikidata, other dump websites need to list the wikis as subdirectories of their root.
+pub async fn get_available_wikis_from(client: &Client, base_url: &str) -> Result<Vec<Wiki>> {
+    if base_url == DEFAULT_BASE_URL {
+        return get_available_wikis_from_wikidata(client).await;
+    }
+    let r = client.get(format!("{base_url}/")).send().await?.error_for_status()?;
+    lazy_static! {
+        static ref RE: Regex =
+            Regex::new(r#"<a href="([a-z0-9_]+)/">"#).expect("Error parsing HTML wiki regex constant");
+    }
+    let body = r.text().await?;
+    Ok(RE
+        .captures_iter(&body)
+        .map(|cap| cap[1].to_owned())
+        // the dump trees below other/ are listed with their own commands
+        .filter(|id| id != "other")
+        .map(|id| Wiki {
+            id,
+            name: String::new(),
+        })
+        .collect())
+}
+
 #[derive(Deserialize)]
 pub struct DumpStatus {
     pub version: String,
     pub jobs: BTreeMap<String, DumpJobInfo>,
 }
 
+impl DumpStatus {
+    /// Returns the total size of the files of the completed jobs.
+    pub fn completed_size(&self) -> u64 {
+        self.jobs
+            .values()
+            .filter(|job| job.status == "done")
+            .flat_map(|job| job.files.iter().flat_map(|files| files.values()))
+            .map(|file_info| file_info.size.unwrap_or(0))
+            .sum()
+    }
+}
+
 #[derive(Deserialize)]
 pub struct DumpJobInfo {
     pub updated: String,
@@ -123,7 +274,7 @@ pub struct DumpJobInfo {
     pub files: Option<BTreeMap<String, DumpFileInfo>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct DumpFileInfo {
     pub url: Option<String>,
     pub sha1: Option<String>,
@@ -131,8 +282,27 @@ pub struct DumpFileInfo {
     pub md5: Option<String>,
 }
 
+impl DumpFileInfo {
+    /// Returns the expected checksum, the SHA1 digest if the dump status contains it, otherwise the MD5 digest.
+    pub fn checksum(&self) -> Option<(HashAlgorithm, &str)> {
+        match (&self.sha1, &self.md5) {
+            (Some(sha1), _) => Some((HashAlgorithm::Sha1, sha1)),
+            (None, Some(md5)) => Some((HashAlgorithm::Md5, md5)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Root URL of the Wikimedia dump website.
+pub const DEFAULT_BASE_URL: &str = "https://dumps.wikimedia.org";
+
 pub async fn get_dump_status(client: &Client, wiki: &str, date: &str) -> Result<DumpStatus> {
-    let url = format!("https://dumps.wikimedia.org/{wiki}/{date}/dumpstatus.json");
+    get_dump_status_from(client, DEFAULT_BASE_URL, wiki, date).await
+}
+
+/// Like `get_dump_status()`, but from the dump website at `base_url`.
+pub async fn get_dump_status_from(client: &Client, base_url: &str, wiki: &str, date: &str) -> Result<DumpStatus> {
+    let url = format!("{base_url}/{wiki}/{date}/dumpstatus.json");
     let r = client.get(url.as_str()).send().await?.error_for_status().map_err(|e| {
         if let Some(StatusCode::NOT_FOUND) = e.status() {
             Error::DumpStatusFileNotFound()
@@ -145,10 +315,33 @@ pub async fn get_dump_status(client: &Client, wiki: &str, date: &str) -> Result<
 }
 
 pub async fn get_latest_available_date(client: &Client, wiki: &str, dump_type: Option<&str>) -> Result<String> {
-    let mut available_dates = get_available_dates(client, wiki).await?;
+    get_latest_available_date_from(client, DEFAULT_BASE_URL, wiki, dump_type).await
+}
+
+/// Like `get_latest_available_date()`, but from the dump website at `base_url`.
+pub async fn get_latest_available_date_from(
+    client: &Client,
+    base_url: &str,
+    wiki: &str,
+    dump_type: Option<&str>,
+) -> Result<String> {
+    let mut available_dates = get_available_dates_from(client, base_url, wiki).await?;
     available_dates.reverse();
+    if is_other_dump_tree(wiki) {
+        for date in available_dates {
+            let files = get_other_dump_files_from(client, base_url, wiki, &date).await?;
+            if dump_type.map_or(!files.is_empty(), |dump_type| {
+                files
+                    .keys()
+                    .any(|file_name| other_dump_file_matches(file_name, &date, dump_type))
+            }) {
+                return Ok(date);
+            }
+        }
+        return Err(Error::NoDumpDatesFound());
+    }
     for date in available_dates {
-        let res = get_dump_status(client, wiki, &date).await;
+        let res = get_dump_status_from(client, base_url, wiki, &date).await;
         match res {
             Ok(dump_status) => {
                 if let Some(dump_type) = dump_type {
@@ -184,15 +377,144 @@ fn get_file_in_dir(directory: &Path, file_name: &str) -> PathBuf {
     file
 }
 
-fn verify_hash(expected_sha1: Option<&String>, hasher: Sha1, file_path: &Path) -> Result<()> {
-    if let Some(expected_sha1) = expected_sha1 {
-        let sha1_bytes = hasher.finalize();
-        let actual_sha1 = format!("{sha1_bytes:x}");
-        if expected_sha1 != &actual_sha1 {
-            return Err(Error::DumpFileAccessError(
-                file_path.to_owned(),
-                "SHA1 digest differs from the expected one.".to_owned(),
-            ));
+/// Returns false if the size or the checksum of the file do not match, files without checksums are only checked for
+/// their size.
+fn verify_existing_file(file_path: &Path, file_info: &DumpFileInfo) -> Result<bool> {
+    let access_error = |e: std::io::Error| Error::DumpFileAccessError(file_path.to_owned(), e.to_string());
+    let file_len = fs::metadata(file_path).map_err(access_error)?.len();
+    if file_info.size.is_some_and(|size| size != file_len) {
+        return Ok(false);
+    }
+    if let Some((algorithm, expected_checksum)) = file_info.checksum() {
+        let hasher = hash_file(file_path, Hasher::new(algorithm))?;
+        return Ok(expected_checksum == hasher.finalize_hex());
+    }
+    Ok(true)
+}
+
+/// Continues hashing with the content of the file.
+fn hash_file(file_path: &Path, hasher: Hasher) -> Result<Hasher> {
+    let access_error = |e: std::io::Error| Error::DumpFileAccessError(file_path.to_owned(), e.to_string());
+    let file = fs::File::open(file_path).map_err(access_error)?;
+    let mut reader = HashingReader::with_hasher(file, hasher);
+    io::copy(&mut reader, &mut io::sink()).map_err(access_error)?;
+    Ok(reader.into_parts().1)
+}
+
+/// Returns the name of a dump file without the `<wiki>-<date>-` prefix, e.g. `page.sql.gz` for
+/// `enwiki-20240101-page.sql.gz`, which identifies the same file in other runs of the dump.
+fn get_dump_file_suffix(file_name: &str) -> Option<&str> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^.+?-[0-9]{8}-(.+)$").expect("Error parsing dump file name regex constant");
+    }
+    Some(RE.captures(file_name)?.get(1)?.as_str())
+}
+
+/// Returns the dump files in the directory of an older dump run by their suffix.
+fn get_older_files(directory: &Path) -> Result<HashMap<String, PathBuf>> {
+    let access_error = |e: std::io::Error| Error::DumpFileAccessError(directory.to_owned(), e.to_string());
+    let mut older_files = HashMap::new();
+    for entry in fs::read_dir(directory).map_err(access_error)? {
+        let path = entry.map_err(access_error)?.path();
+        let suffix = path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .and_then(get_dump_file_suffix)
+            .map(str::to_owned);
+        if let Some(suffix) = suffix.filter(|_| path.is_file()) {
+            older_files.insert(suffix, path);
+        }
+    }
+    Ok(older_files)
+}
+
+/// Links the file of an older dump run to the target path if it has the size and checksum given in the dump status.
+/// If it cannot be hard linked, e.g. because it is on another file system, it is copied, which creates a reflink on
+/// file systems supporting them.
+async fn link_older_file(older_file_path: &Path, target_file_path: &Path, file_info: &DumpFileInfo) -> Result<bool> {
+    // files are only identified by size and checksum
+    if file_info.size.is_none() || file_info.checksum().is_none() {
+        return Ok(false);
+    }
+    let older_file_path = older_file_path.to_owned();
+    let target_file_path = target_file_path.to_owned();
+    let file_info = file_info.clone();
+    spawn_blocking(move || {
+        if !verify_existing_file(&older_file_path, &file_info)? {
+            return Ok(false);
+        }
+        let access_error = |e: std::io::Error| Error::DumpFileAccessError(target_file_path.clone(), e.to_string());
+        // an invalid existing file might itself be a link to another file
+        if target_file_path.exists() {
+            remove_file(&target_file_path).map_err(access_error)?;
+        }
+        if fs::hard_link(&older_file_path, &target_file_path).is_err() {
+            fs::copy(&older_file_path, &target_file_path).map_err(access_error)?;
+        }
+        Ok(true)
+    })
+    .await
+    .map_err(Error::VerificationJoinError)?
+}
+
+fn get_part_file_path(file_path: &Path) -> PathBuf {
+    let mut part_file_path = file_path.as_os_str().to_owned();
+    part_file_path.push(".part");
+    PathBuf::from(part_file_path)
+}
+
+/// Decompressing downloads write a checkpoint after a bz2 stream if at least this much has been decompressed since the
+/// last one.
+const CHECKPOINT_INTERVAL: u64 = 64 * 1024 * 1024;
+
+/// Position after a complete bz2 stream from which a decompressing download can be resumed.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+struct DecompressionCheckpoint {
+    compressed_offset: u64,
+    decompressed_len: u64,
+}
+
+impl DecompressionCheckpoint {
+    /// Returns the checkpoint saved next to the part file if the part file has been written up to it.
+    fn read(checkpoint_path: &Path, partfile_path: &Path) -> Option<DecompressionCheckpoint> {
+        let content = fs::read_to_string(checkpoint_path).ok()?;
+        let (compressed_offset, decompressed_len) = content.trim_end().split_once('\t')?;
+        let checkpoint = DecompressionCheckpoint {
+            compressed_offset: compressed_offset.parse().ok()?,
+            decompressed_len: decompressed_len.parse().ok()?,
+        };
+        let part_len = fs::metadata(partfile_path).ok()?.len();
+        (checkpoint.compressed_offset > 0 && part_len >= checkpoint.decompressed_len).then_some(checkpoint)
+    }
+
+    fn write(&self, checkpoint_path: &Path) -> Result<()> {
+        fs::write(
+            checkpoint_path,
+            format!("{}\t{}\n", self.compressed_offset, self.decompressed_len),
+        )
+        .map_err(|e| Error::DumpFileAccessError(checkpoint_path.to_owned(), std::format!("Write error: {e}")))
+    }
+}
+
+fn get_checkpoint_file_path(partfile_path: &Path) -> PathBuf {
+    let mut checkpoint_file_path = partfile_path.as_os_str().to_owned();
+    checkpoint_file_path.push(".checkpoint");
+    PathBuf::from(checkpoint_file_path)
+}
+
+fn remove_checkpoint_file(checkpoint_path: &Path) -> Result<()> {
+    match remove_file(checkpoint_path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            Err(Error::DumpFileAccessError(checkpoint_path.to_owned(), e.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn verify_hash(expected_checksum: Option<&str>, hasher: Hasher, file_path: &Path) -> Result<()> {
+    if let Some(expected_checksum) = expected_checksum {
+        if expected_checksum != hasher.finalize_hex() {
+            return Err(Error::ChecksumMismatch(file_path.to_owned()));
         };
     }
     Ok(())
@@ -201,54 +523,105 @@ fn verify_hash(expected_sha1: Option<&String>, hasher: Sha1, file_path: &Path) -
 struct BytesChannelRead {
     current_bytes: Bytes,
     receiver: tokio::sync::mpsc::Receiver<Bytes>,
+    /// Number of bytes read or consumed so far
+    consumed_len: u64,
 }
 impl BytesChannelRead {
     fn from(receiver: tokio::sync::mpsc::Receiver<Bytes>) -> Self {
         Self {
             current_bytes: Bytes::new(),
             receiver,
+            consumed_len: 0,
         }
     }
 }
 impl Read for BytesChannelRead {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = min(available.len(), buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        std::io::Result::Ok(len)
+    }
+}
+impl BufRead for BytesChannelRead {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
         while self.current_bytes.is_empty() {
             match self.receiver.blocking_recv() {
                 None => {
-                    return std::io::Result::Ok(0);
+                    return std::io::Result::Ok(&[]);
                 }
                 Some(bytes) => self.current_bytes = bytes,
             }
         }
-        let len = min(self.current_bytes.len(), buf.len());
-        buf[..len].copy_from_slice(&self.current_bytes[..len]);
-        self.current_bytes = self.current_bytes.slice(len..);
-        std::io::Result::Ok(len)
+        std::io::Result::Ok(&self.current_bytes)
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.current_bytes = self.current_bytes.slice(amt..);
+        self.consumed_len += amt as u64;
     }
 }
 
 async fn download_file(
     url: String,
     file_path: PathBuf,
-    partfile_path: PathBuf,
     client: &Client,
     decompress: bool,
+    resume: bool,
     verify_file_data: Option<&DumpFileInfo>,
     progress_send: Option<UnboundedSender<DownloadProgress>>,
 ) -> Result<()> {
-    let mut r = client.get(url).send().await?.error_for_status()?;
+    let partfile_path = get_part_file_path(&file_path);
+    let checkpoint_path = get_checkpoint_file_path(&partfile_path);
+    // decompressing downloads are continued after the last checkpointed bz2 stream, the state of the decompressor
+    // within a stream is lost
+    let checkpoint = if resume && decompress {
+        DecompressionCheckpoint::read(&checkpoint_path, &partfile_path)
+    } else {
+        None
+    };
+    let part_len = if let Some(checkpoint) = checkpoint {
+        checkpoint.compressed_offset
+    } else if resume && !decompress {
+        fs::metadata(&partfile_path).map_or(0, |metadata| metadata.len())
+    } else {
+        0
+    };
+    let mut request = client.get(url);
+    if part_len > 0 {
+        request = request.header(RANGE, format!("bytes={part_len}-"));
+    }
+    let mut r = request.send().await?.error_for_status()?;
+    // the server may ignore the range and send the whole file
+    let is_resumed = part_len > 0 && r.status() == StatusCode::PARTIAL_CONTENT;
     let mut partfile = OpenOptions::new()
         .create(true)
-        .truncate(true)
+        .append(is_resumed)
+        .truncate(!is_resumed)
         .write(true)
         .open(&partfile_path)
         .map_err(|e| {
             Error::DumpFileAccessError(partfile_path.clone(), std::format!("Could not create part file: {e}"))
         })?;
+    let checkpoint = if is_resumed { checkpoint } else { None };
+    if let Some(checkpoint) = checkpoint {
+        // drop the data decompressed after the checkpoint
+        partfile.set_len(checkpoint.decompressed_len).map_err(|e| {
+            Error::DumpFileAccessError(partfile_path.clone(), std::format!("Could not truncate part file: {e}"))
+        })?;
+    } else if decompress {
+        remove_checkpoint_file(&checkpoint_path)?;
+    }
 
     let progress_send_clone = progress_send.clone();
+    let checkpoint_path_clone = checkpoint_path.clone();
     defer! {
-        if partfile_path.is_file() {
+        // part files of resumable downloads are kept for the next run
+        if !resume && checkpoint_path_clone.is_file() {
+            remove_file(&checkpoint_path_clone).ok();
+        }
+        if !resume && partfile_path.is_file() {
             if let Err(err) = remove_file(&partfile_path) {
                 if let Some(progress_send_clone) = progress_send_clone {
                     progress_send_clone
@@ -267,7 +640,42 @@ async fn download_file(
         }
     }
 
-    let expected_sha1 = verify_file_data.and_then(|info| info.sha1.as_ref());
+    let expected_checksum = verify_file_data.and_then(DumpFileInfo::checksum);
+    let mut hasher = Hasher::new(expected_checksum.map_or(HashAlgorithm::Sha1, |(algorithm, _)| algorithm));
+    let mut expected_checksum = expected_checksum.map(|(_, checksum)| checksum);
+    if is_resumed {
+        if decompress && expected_checksum.is_some() {
+            // the compressed data before the checkpoint is not kept
+            expected_checksum = None;
+            if let Some(ref progress_send) = progress_send {
+                progress_send.send(DownloadProgress::ChecksumNotVerified(
+                    file_path.clone(),
+                    file_path
+                        .file_name()
+                        .unwrap_or_else(|| OsStr::new("<unknown>"))
+                        .to_string_lossy()
+                        .to_string(),
+                ))?;
+            }
+        }
+        if expected_checksum.is_some() {
+            let partfile_path = partfile_path.clone();
+            hasher = spawn_blocking(move || hash_file(&partfile_path, hasher))
+                .await
+                .map_err(Error::VerificationJoinError)??;
+        }
+        if let Some(ref progress_send) = progress_send {
+            progress_send.send(DownloadProgress::PartFileResumed(
+                partfile_path.clone(),
+                file_path
+                    .file_name()
+                    .unwrap_or_else(|| OsStr::new("<unknown>"))
+                    .to_string_lossy()
+                    .to_string(),
+                part_len,
+            ))?;
+        }
+    }
 
     if decompress {
         let (decompress_send, decompress_receive) = mpsc::channel(1);
@@ -276,9 +684,8 @@ async fn download_file(
         let copy_net_to_decompressor_in = {
             let progress_send = progress_send.clone();
             async move {
-                let mut hasher = Sha1::new();
                 while let Some(chunk) = r.chunk().await? {
-                    if expected_sha1.is_some() {
+                    if expected_checksum.is_some() {
                         hasher.update(chunk.as_ref());
                     }
                     let len = chunk.len() as u64;
@@ -290,29 +697,46 @@ async fn download_file(
                         progress_send.send(DownloadProgress::BytesReadFromNet(len))?;
                     }
                 }
-                verify_hash(expected_sha1, hasher, file_path.as_ref())?;
+                verify_hash(expected_checksum, hasher, file_path.as_ref())?;
                 Result::Ok(())
             }
         };
 
         let partfile_path = partfile_path.clone(); // clone since captured
+        let checkpoint_path = checkpoint_path.clone(); // clone since captured
         let decompression = spawn_blocking(move || {
-            let compressed_read = BytesChannelRead::from(decompress_receive);
-            let mut decompressor = MultiBzDecoder::new(compressed_read);
+            let mut compressed_read = BytesChannelRead::from(decompress_receive);
+            let mut last_checkpoint = checkpoint.unwrap_or_default();
+            let mut decompressed_len = last_checkpoint.decompressed_len;
             let mut buf = [0; 65536];
+            // the streams of multistream dumps are decompressed one by one to checkpoint after complete streams
             loop {
-                let read_len = decompressor.read(&mut buf).map_err(Error::DecompressorError)?;
-                if read_len > 0 {
-                    let write_buf = &buf[..read_len];
-                    partfile.write_all(write_buf).map_err(|e| {
-                        Error::DumpFileAccessError(partfile_path.clone(), std::format!("Write error: {e}"))
-                    })?;
-                    if let Some(ref progress_send) = progress_send {
-                        progress_send.send(DownloadProgress::DecompressedBytesWrittenToDisk(read_len as u64))?;
+                let mut decompressor = BzDecoder::new(&mut compressed_read);
+                loop {
+                    let read_len = decompressor.read(&mut buf).map_err(Error::DecompressorError)?;
+                    if read_len > 0 {
+                        let write_buf = &buf[..read_len];
+                        partfile.write_all(write_buf).map_err(|e| {
+                            Error::DumpFileAccessError(partfile_path.clone(), std::format!("Write error: {e}"))
+                        })?;
+                        decompressed_len += read_len as u64;
+                        if let Some(ref progress_send) = progress_send {
+                            progress_send.send(DownloadProgress::DecompressedBytesWrittenToDisk(read_len as u64))?;
+                        }
+                    } else {
+                        break;
                     }
-                } else {
+                }
+                if compressed_read.fill_buf().map_err(Error::DecompressorError)?.is_empty() {
                     break;
                 }
+                if resume && decompressed_len - last_checkpoint.decompressed_len >= CHECKPOINT_INTERVAL {
+                    last_checkpoint = DecompressionCheckpoint {
+                        compressed_offset: part_len + compressed_read.consumed_len,
+                        decompressed_len,
+                    };
+                    last_checkpoint.write(&checkpoint_path)?;
+                }
             }
             Result::Ok(())
         })
@@ -321,9 +745,8 @@ async fn download_file(
         let (_, decompression_joined) = tokio::try_join!(copy_net_to_decompressor_in, decompression)?;
         decompression_joined?;
     } else {
-        let mut hasher = Sha1::new();
         while let Some(chunk) = r.chunk().await? {
-            if expected_sha1.is_some() {
+            if expected_checksum.is_some() {
                 hasher.update(chunk.as_ref());
             }
             partfile
@@ -333,30 +756,248 @@ async fn download_file(
                 progress_send.send(DownloadProgress::BytesReadFromNet(chunk.len() as u64))?;
             }
         }
-        verify_hash(expected_sha1, hasher, file_path.as_ref())?;
+        verify_hash(expected_checksum, hasher, file_path.as_ref())?;
     }
 
     std::fs::rename(&partfile_path, &file_path).map_err(|e| {
         Error::DumpFileAccessError(partfile_path.clone(), std::format!("Could not rename part file: {e}"))
     })?;
+    if decompress {
+        remove_checkpoint_file(&checkpoint_path)?;
+    }
 
     Ok(())
 }
+/// Order in which the files of a dump are downloaded.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum DownloadOrder {
+    /// By file name
+    #[default]
+    Name,
+    LargestFirst,
+    SmallestFirst,
+    /// Alternating between the largest and the smallest remaining file, keeps all connections busy without
+    /// leaving only huge files for the end
+    Interleaved,
+}
+
+/// Arrangement of the dumps downloaded to a target directory.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum DirectoryLayout {
+    /// All files directly in the target directory
+    #[default]
+    Flat,
+    /// The files of each dump in `<target>/<wiki>/<date>/`
+    WikiDate,
+}
+
+impl DirectoryLayout {
+    /// Returns the directory of the dump files in the target directory.
+    pub fn dump_directory(self, target_directory: &Path, wiki: &str, date: &str) -> PathBuf {
+        match self {
+            DirectoryLayout::Flat => target_directory.to_owned(),
+            DirectoryLayout::WikiDate => target_directory.join(wiki).join(date),
+        }
+    }
+
+    /// Creates the directory of the dump files in the existing target directory and returns its path.
+    pub fn create_dump_directory(self, target_directory: &Path, wiki: &str, date: &str) -> Result<PathBuf> {
+        if !target_directory.is_dir() {
+            return Err(Error::TargetDirectoryDoesNotExist(target_directory.to_owned()));
+        }
+        let directory = self.dump_directory(target_directory, wiki, date);
+        fs::create_dir_all(&directory).map_err(|e| Error::DumpFileAccessError(directory.clone(), e.to_string()))?;
+        Ok(directory)
+    }
+}
+
 #[derive(Default)]
 pub struct DownloadOptions<'a> {
+    /// Root URL of the dump website used to find the files of the dump, defaults to `DEFAULT_BASE_URL`
+    pub base_url: Option<&'a str>,
     pub mirror: Option<&'a str>,
     pub decompress: bool,
     pub concurrency: Option<NonZeroUsize>,
+    pub order: DownloadOrder,
+    /// Download into a staging subdirectory and only move the files into the target directory once all files of
+    /// the dump have been downloaded and verified. Files staged by an interrupted run are verified again, and moves
+    /// interrupted after all files had been staged are finished by the next run.
+    pub all_or_nothing: bool,
+    /// Do not check the SHA1 checksums of downloaded files
+    pub skip_verification: bool,
+    /// Check the size and SHA1 checksum of files which already exist in the target directory and download them
+    /// again if they do not match instead of skipping them
+    pub verify_existing: bool,
+    /// Limit of concurrent file downloads shared by several dump downloads running at the same time, each download
+    /// holds a permit while it runs
+    pub connection_limit: Option<&'a Semaphore>,
+    /// Continue downloads from the part files left by interrupted runs and keep the part files of failed downloads,
+    /// otherwise stale part files are removed. Decompressing downloads are continued after the last bz2 stream
+    /// recorded in a checkpoint file, so only multistream dumps can be continued, and their checksum cannot be
+    /// verified anymore.
+    pub resume: bool,
+    /// Directory of an older run of the dump, files in it with the size and checksum of a file of this run are
+    /// hard linked (or copied if that fails) instead of downloaded. Files are not linked if they are decompressed.
+    pub link_from: Option<&'a Path>,
+}
+
+/// Returns the number of parallel connections used for the download, `concurrency` if given, otherwise one for the
+/// main Wikimedia dump website and more for mirrors.
+pub fn get_concurrency(download_options: &DownloadOptions) -> usize {
+    download_options.concurrency.map_or_else(
+        || {
+            if download_options.mirror.is_some() {
+                if download_options.decompress {
+                    num_cpus::get()
+                } else {
+                    4
+                }
+            } else {
+                1
+            }
+        },
+        NonZeroUsize::get,
+    )
+}
+
+/// Returns the name of the staging subdirectory used for downloads with `all_or_nothing`.
+fn get_staging_directory_name(wiki: &str, date: &str, dump_type: &str) -> String {
+    format!(".wdget-{}-{}-{}.partial", wiki.replace('/', "_"), date, dump_type)
+}
+
+/// Removes the part files and staging directories left by interrupted downloads into the directory and returns their
+/// paths. The files of staging directories which had been completely downloaded are moved into the directory first.
+pub fn clean_directory(directory: &Path) -> Result<Vec<PathBuf>> {
+    let access_error = |path: &Path, e: std::io::Error| Error::DumpFileAccessError(path.to_owned(), e.to_string());
+    if !directory.exists() {
+        return Err(Error::TargetDirectoryDoesNotExist(directory.to_owned()));
+    }
+    let mut removed = Vec::new();
+    for entry in fs::read_dir(directory).map_err(|e| access_error(directory, e))? {
+        let path = entry.map_err(|e| access_error(directory, e))?.path();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_file() && (file_name.ends_with(".part") || file_name.ends_with(".part.checkpoint")) {
+            remove_file(&path).map_err(|e| access_error(&path, e))?;
+        } else if path.is_dir() && file_name.starts_with(".wdget-") && file_name.ends_with(".partial") {
+            if !finish_staged_files(&path, directory)? {
+                fs::remove_dir_all(&path).map_err(|e| access_error(&path, e))?;
+            }
+        } else {
+            continue;
+        }
+        removed.push(path);
+    }
+    removed.sort();
+    Ok(removed)
+}
+
+/// Name of the file in a staging directory listing the files to be moved into the target directory, it is only
+/// written once all of them have been downloaded and verified.
+const STAGING_COMPLETE_FILE_NAME: &str = ".complete";
+
+/// Moves the staged files into the target directory and removes the staging directory. The files are listed in the
+/// completion marker first, so if the moves are interrupted they are finished by the next download or clean of the
+/// target directory instead of leaving only some of the files in it.
+fn move_staged_files(staging_directory: &Path, target_directory: &Path, file_names: &[String]) -> Result<()> {
+    let marker_path = get_file_in_dir(staging_directory, STAGING_COMPLETE_FILE_NAME);
+    let temp_marker_path = get_part_file_path(&marker_path);
+    let access_error = |path: &Path, e: std::io::Error| Error::DumpFileAccessError(path.to_owned(), e.to_string());
+    fs::write(&temp_marker_path, file_names.join("\n")).map_err(|e| access_error(&temp_marker_path, e))?;
+    fs::rename(&temp_marker_path, &marker_path).map_err(|e| access_error(&marker_path, e))?;
+    finish_staged_files(staging_directory, target_directory)?;
+    Ok(())
+}
+
+/// Moves the files listed in the completion marker of the staging directory which are still in it into the target
+/// directory and removes the staging directory. Returns false without changing anything if there is no marker, i.e.
+/// the download into the staging directory has not been completed.
+fn finish_staged_files(staging_directory: &Path, target_directory: &Path) -> Result<bool> {
+    let access_error = |path: &Path, e: std::io::Error| Error::DumpFileAccessError(path.to_owned(), e.to_string());
+    let marker_path = get_file_in_dir(staging_directory, STAGING_COMPLETE_FILE_NAME);
+    let file_names = match fs::read_to_string(&marker_path) {
+        Ok(file_names) => file_names,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(access_error(&marker_path, e)),
+    };
+    for file_name in file_names.lines() {
+        let staged_file_path = get_file_in_dir(staging_directory, file_name);
+        // files missing here were moved before the interruption
+        if staged_file_path.exists() {
+            let target_file_path = get_file_in_dir(target_directory, file_name);
+            fs::rename(&staged_file_path, &target_file_path).map_err(|e| access_error(&staged_file_path, e))?;
+        }
+    }
+    fs::remove_dir_all(staging_directory).map_err(|e| access_error(staging_directory, e))?;
+    Ok(true)
+}
+
+/// Sorts the files in the given download order, files with unknown size are treated as empty.
+fn order_files(files: &mut Vec<(&String, &DumpFileInfo)>, order: DownloadOrder) {
+    match order {
+        DownloadOrder::Name => {}
+        DownloadOrder::LargestFirst => files.sort_by_key(|(_, info)| std::cmp::Reverse(info.size.unwrap_or(0))),
+        DownloadOrder::SmallestFirst => files.sort_by_key(|(_, info)| info.size.unwrap_or(0)),
+        DownloadOrder::Interleaved => {
+            files.sort_by_key(|(_, info)| std::cmp::Reverse(info.size.unwrap_or(0)));
+            let mut sorted = std::mem::take(files).into_iter();
+            let mut from_largest = true;
+            loop {
+                let next = if from_largest {
+                    sorted.next()
+                } else {
+                    sorted.next_back()
+                };
+                match next {
+                    Some(file) => files.push(file),
+                    None => break,
+                }
+                from_largest = !from_largest;
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum DownloadProgress {
     TotalDownloadSize(u64),
+    /// Number of files to be downloaded, sent once all existing files have been checked
+    TotalFileCount(u64),
     BytesReadFromNet(u64),
     DecompressedBytesWrittenToDisk(u64),
     ExistingFileIgnored(PathBuf, String),
+    ExistingFileVerified(PathBuf, String),
+    /// The existing file is downloaded again
+    ExistingFileInvalid(PathBuf, String),
     CouldNotRemoveTempFile(PathBuf, String, std::io::Error),
+    /// A part file left by an earlier run is continued with the given number of bytes already downloaded
+    PartFileResumed(PathBuf, String, u64),
+    /// The checksum of a resumed decompressing download cannot be verified
+    ChecksumNotVerified(PathBuf, String),
+    StalePartFileRemoved(PathBuf, String),
     FileFinished(PathBuf, String),
+    /// The file was linked from an older run of the dump instead of being downloaded
+    FileLinked(PathBuf, String),
+}
+
+/// Returns the download URL of a dump file from the given mirror or the main Wikimedia dump website.
+pub fn get_dump_file_url(mirror: Option<&str>, wiki: &str, date: &str, file_name: &str) -> String {
+    let root_url = mirror.unwrap_or(DEFAULT_BASE_URL);
+    format!("{root_url}/{wiki}/{date}/{file_name}")
+}
+
+/// Returns the download URL of a dump file from the given mirror or the main Wikimedia dump website. The path given
+/// in the dump status is used if there is one, since it is not below the dump run for all files.
+pub fn resolve_file_url(
+    mirror: Option<&str>,
+    wiki: &str,
+    date: &str,
+    file_name: &str,
+    file_info: &DumpFileInfo,
+) -> String {
+    match &file_info.url {
+        Some(path) => format!("{}{path}", mirror.unwrap_or(DEFAULT_BASE_URL)),
+        None => get_dump_file_url(mirror, wiki, date, file_name),
+    }
 }
 
 pub async fn download_dump<T>(
@@ -375,30 +1016,145 @@ where
     if !target_directory.exists() {
         return Err(Error::TargetDirectoryDoesNotExist(target_directory.to_owned()));
     }
-    let dump_status = get_dump_status(client, wiki, date).await?;
-    let job_info = dump_status.jobs.get(dump_type).ok_or(Error::DumpTypeNotFound())?;
-    if &job_info.status != "done" {
-        return Err(Error::DumpNotComplete());
+    let base_url = download_options.base_url.unwrap_or(DEFAULT_BASE_URL);
+    let files = get_dump_files_from(client, base_url, wiki, date, dump_type).await?;
+    let root_url = download_options.mirror.unwrap_or(base_url);
+    download_files(
+        client,
+        &files,
+        |file_name, _| get_dump_file_url(Some(root_url), wiki, date, file_name),
+        &get_staging_directory_name(wiki, date, dump_type),
+        target_directory,
+        download_options,
+        progress_send,
+    )
+    .await
+}
+
+/// Downloads the files which do not exist in the target directory yet, `get_url` returns the download URL of a
+/// file.
+async fn download_files<F>(
+    client: &Client,
+    files: &BTreeMap<String, DumpFileInfo>,
+    get_url: F,
+    staging_directory_name: &str,
+    target_directory: &Path,
+    download_options: &DownloadOptions<'_>,
+    progress_send: Option<UnboundedSender<DownloadProgress>>,
+) -> Result<()>
+where
+    F: Fn(&str, &DumpFileInfo) -> String,
+{
+    // a run interrupted while moving the staged files into place is finished first, files downloaded to the staging
+    // directory by a run interrupted before are kept, but verified again
+    let staging_directory = get_file_in_dir(target_directory, staging_directory_name);
+    if staging_directory.is_dir() {
+        finish_staged_files(&staging_directory, target_directory)?;
     }
-    let files = job_info.files.as_ref().ok_or(Error::DumpHasNoFiles())?;
-    let root_url = download_options.mirror.unwrap_or("https://dumps.wikimedia.org");
+    let staging_directory = if download_options.all_or_nothing {
+        fs::create_dir_all(&staging_directory)
+            .map_err(|e| Error::DumpFileAccessError(staging_directory.clone(), e.to_string()))?;
+        Some(staging_directory)
+    } else {
+        None
+    };
+    let download_directory = staging_directory.as_deref().unwrap_or(target_directory);
+    let older_files = download_options.link_from.map(get_older_files).transpose()?;
 
     // create futures for missing files
     let mut futures = Vec::with_capacity(files.len());
+    let mut staged_file_names = Vec::with_capacity(files.len());
     let mut total_data_size = Some(0_u64);
-    for (file_name, file_data) in files {
-        let target_file_name = get_target_file_name(file_name, download_options.decompress).to_owned();
+    let mut ordered_files = files.iter().collect::<Vec<_>>();
+    order_files(&mut ordered_files, download_options.order);
+    for (file_name, file_data) in ordered_files {
+        // only .bz2 files can be decompressed on the fly, other trees also contain .gz files
+        let decompress = download_options.decompress && file_name.ends_with(".bz2");
+        let target_file_name = get_target_file_name(file_name, decompress).to_owned();
         let target_file_path = get_file_in_dir(target_directory, target_file_name.as_str());
+        let part_file_path = get_part_file_path(&get_file_in_dir(download_directory, &target_file_name));
+        if part_file_path.exists() && (!download_options.resume || target_file_path.exists()) {
+            remove_file(&part_file_path)
+                .map_err(|e| Error::DumpFileAccessError(part_file_path.clone(), e.to_string()))?;
+            remove_checkpoint_file(&get_checkpoint_file_path(&part_file_path))?;
+            if let Some(ref progress_send) = progress_send {
+                progress_send.send(DownloadProgress::StalePartFileRemoved(
+                    part_file_path.clone(),
+                    target_file_name.clone() + ".part",
+                ))?;
+            }
+        }
         if target_file_path.exists() {
+            // decompressed files cannot be checked against the size and checksum of the compressed file
+            if !download_options.verify_existing || decompress {
+                if let Some(ref progress_send) = progress_send {
+                    progress_send.send(DownloadProgress::ExistingFileIgnored(
+                        target_file_path,
+                        target_file_name,
+                    ))?;
+                }
+                continue;
+            }
+            let is_valid = {
+                let target_file_path = target_file_path.clone();
+                let file_data = file_data.clone();
+                spawn_blocking(move || verify_existing_file(&target_file_path, &file_data))
+                    .await
+                    .map_err(Error::VerificationJoinError)??
+            };
+            if is_valid {
+                if let Some(ref progress_send) = progress_send {
+                    progress_send.send(DownloadProgress::ExistingFileVerified(
+                        target_file_path,
+                        target_file_name,
+                    ))?;
+                }
+                continue;
+            }
             if let Some(ref progress_send) = progress_send {
-                progress_send.send(DownloadProgress::ExistingFileIgnored(
-                    target_file_path,
-                    target_file_name,
+                progress_send.send(DownloadProgress::ExistingFileInvalid(
+                    target_file_path.clone(),
+                    target_file_name.clone(),
                 ))?;
             }
-            continue;
         }
-        let part_file_path = get_file_in_dir(target_directory, (target_file_name.clone() + ".part").as_str());
+        let target_file_path = get_file_in_dir(download_directory, target_file_name.as_str());
+        if staging_directory.is_some() {
+            staged_file_names.push(target_file_name.clone());
+            if target_file_path.exists() {
+                // the file might have been changed since it was downloaded by an interrupted run, decompressed files
+                // cannot be verified and are downloaded again
+                let is_valid = !decompress && {
+                    let target_file_path = target_file_path.clone();
+                    let file_data = file_data.clone();
+                    spawn_blocking(move || verify_existing_file(&target_file_path, &file_data))
+                        .await
+                        .map_err(Error::VerificationJoinError)??
+                };
+                if let Some(ref progress_send) = progress_send {
+                    progress_send.send(if is_valid {
+                        DownloadProgress::ExistingFileVerified(target_file_path.clone(), target_file_name.clone())
+                    } else {
+                        DownloadProgress::ExistingFileInvalid(target_file_path.clone(), target_file_name.clone())
+                    })?;
+                }
+                if is_valid {
+                    continue;
+                }
+            }
+        }
+        let older_file_path = older_files
+            .as_ref()
+            .filter(|_| !decompress)
+            .and_then(|older_files| older_files.get(get_dump_file_suffix(file_name)?));
+        if let Some(older_file_path) = older_file_path {
+            if link_older_file(older_file_path, &target_file_path, file_data).await? {
+                if let Some(ref progress_send) = progress_send {
+                    progress_send.send(DownloadProgress::FileLinked(target_file_path, target_file_name))?;
+                }
+                continue;
+            }
+        }
         if let Some(ref mut len) = total_data_size {
             match file_data.size {
                 Some(cur_len) => {
@@ -409,42 +1165,38 @@ where
                 }
             }
         }
-        let url = format!("{root_url}/{wiki}/{date}/{file_name}");
+        let url = get_url(file_name, file_data);
         let download_res = download_file(
             url,
             target_file_path.clone(),
-            part_file_path.clone(),
             client,
-            download_options.decompress,
-            Some(file_data),
+            decompress,
+            download_options.resume,
+            (!download_options.skip_verification).then_some(file_data),
             progress_send.clone(),
         )
         .map_ok(|_| (target_file_name, target_file_path));
-        futures.push(download_res);
+        let connection_limit = download_options.connection_limit;
+        futures.push(async move {
+            let _permit = match connection_limit {
+                // UNWRAP: the semaphore is not closed
+                Some(connection_limit) => Some(connection_limit.acquire().await.unwrap()),
+                None => None,
+            };
+            download_res.await
+        });
     }
-    if let Some(total_data_size) = total_data_size {
-        if let Some(ref progress_send) = progress_send {
+    if let Some(ref progress_send) = progress_send {
+        if let Some(total_data_size) = total_data_size {
             progress_send.send(DownloadProgress::TotalDownloadSize(total_data_size))?;
         }
+        progress_send.send(DownloadProgress::TotalFileCount(futures.len() as u64))?;
     }
 
     // download missing files
     let stream_of_downloads = stream::iter(futures);
 
-    let max_concurrent_downloads = download_options.concurrency.map_or_else(
-        || {
-            if download_options.mirror.is_some() {
-                if download_options.decompress {
-                    num_cpus::get()
-                } else {
-                    4
-                }
-            } else {
-                1
-            }
-        },
-        NonZeroUsize::get,
-    );
+    let max_concurrent_downloads = get_concurrency(download_options);
     let mut buffered = stream_of_downloads.buffer_unordered(max_concurrent_downloads);
     while let Some(res) = buffered.next().await {
         let (finished_file_name, finished_file_path) = res?;
@@ -452,12 +1204,20 @@ where
             progress_send.send(DownloadProgress::FileFinished(finished_file_path, finished_file_name))?;
         }
     }
+    if let Some(staging_directory) = staging_directory {
+        move_staged_files(&staging_directory, target_directory, &staged_file_names)?;
+    }
 
     Ok(())
 }
 
 pub async fn get_available_dates(client: &Client, wiki: &str) -> Result<Vec<String>> {
-    let url = format!("https://dumps.wikimedia.org/{wiki}/");
+    get_available_dates_from(client, DEFAULT_BASE_URL, wiki).await
+}
+
+/// Like `get_available_dates()`, but from the dump website at `base_url`.
+pub async fn get_available_dates_from(client: &Client, base_url: &str, wiki: &str) -> Result<Vec<String>> {
+    let url = format!("{base_url}/{wiki}/");
     let r = client.get(url.as_str()).send().await?.error_for_status()?;
     lazy_static! {
         static ref RE: Regex = Regex::new(r#"<a href="([1-9][0-9]{7})/">([1-9][0-9]{7})/</a>"#)
@@ -476,3 +1236,186 @@ pub async fn get_available_dates(client: &Client, wiki: &str) -> Result<Vec<Stri
     dates.sort_unstable();
     Ok(dates)
 }
+
+/// Dump types (job names in `dumpstatus.json`) commonly available for Wikimedia wikis, other dump types exist.
+pub const KNOWN_DUMP_TYPES: &[&str] = &[
+    "abstractsdump",
+    "allpagetitlesdump",
+    "articlesdump",
+    "articlesmultistreamdump",
+    "categorylinkstable",
+    "categorytable",
+    "cirrussearch-content",
+    "cirrussearch-general",
+    "externallinkstable",
+    "imagelinkstable",
+    "imagetable",
+    "langlinkstable",
+    "metacurrentdump",
+    "metahistory7zdump",
+    "metahistorybz2dump",
+    "pagelinkstable",
+    "pagepropstable",
+    "pagetable",
+    "pagetitlesdump",
+    "redirecttable",
+    "stubsdump",
+    "templatelinkstable",
+    "xmlpagelogsdump",
+    "xmlstubsdump",
+];
+
+/// Returns true if `wiki` denotes one of the dump trees below `other/` (e.g. `other/wikibase/wikidatawiki`
+/// or `other/cirrussearch`). These have dated directories too but no `dumpstatus.json`.
+pub fn is_other_dump_tree(wiki: &str) -> bool {
+    wiki.starts_with("other/")
+}
+
+/// Checks if a file in a dump run of an `other/` tree matches the given dump type. The dump type is matched
+/// against the file name with the date removed, starting at the beginning or after a dash, e.g. `all.json.gz`
+/// matches `wikidata-20240101-all.json.gz` and `enwiki-cirrussearch-content` matches
+/// `enwiki-20240101-cirrussearch-content.json.gz` but not `simpleenwiki-20240101-cirrussearch-content.json.gz`.
+fn other_dump_file_matches(file_name: &str, date: &str, dump_type: &str) -> bool {
+    let file_name = file_name.replace(&format!("-{date}"), "");
+    file_name.starts_with(dump_type) || file_name.contains(&format!("-{dump_type}"))
+}
+
+/// CirrusSearch dumps of all wikis are found in the `other/cirrussearch` tree. A dump type starting with
+/// `cirrussearch` is looked up there, so that e.g. `enwiki`/`cirrussearch-content` can be used instead of
+/// `other/cirrussearch`/`enwiki-cirrussearch-content`.
+pub fn resolve_dump_tree(wiki: &str, dump_type: &str) -> (String, String) {
+    if !is_other_dump_tree(wiki) && dump_type.starts_with("cirrussearch") {
+        ("other/cirrussearch".to_owned(), format!("{wiki}-{dump_type}"))
+    } else {
+        (wiki.to_owned(), dump_type.to_owned())
+    }
+}
+
+/// Lists the files of a dump run in an `other/` tree with their sizes and, if a SHA1 checksum file is part of
+/// the dump run, their SHA1 digests.
+pub async fn get_other_dump_files(client: &Client, tree: &str, date: &str) -> Result<BTreeMap<String, DumpFileInfo>> {
+    get_other_dump_files_from(client, DEFAULT_BASE_URL, tree, date).await
+}
+
+/// Like `get_other_dump_files()`, but from the dump website at `base_url`.
+pub async fn get_other_dump_files_from(
+    client: &Client,
+    base_url: &str,
+    tree: &str,
+    date: &str,
+) -> Result<BTreeMap<String, DumpFileInfo>> {
+    let url = format!("{base_url}/{tree}/{date}/");
+    list_directory(client, &url).await?.ok_or(Error::NoDumpDatesFound())
+}
+
+/// Lists the files in a directory of the dump website with their sizes and, if there is a SHA1 checksum file in
+/// the directory, their SHA1 digests. Returns `None` if the directory does not exist.
+async fn list_directory(client: &Client, url: &str) -> Result<Option<BTreeMap<String, DumpFileInfo>>> {
+    let r = client.get(url).send().await?;
+    if r.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let r = r.error_for_status()?;
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r#"<a href="([^"/?]+)">[^<]*</a>(?:\s+\S+\s+\S+\s+([0-9]+))?"#)
+            .expect("Error parsing HTML dump file regex constant");
+    }
+    let body = r.text().await?;
+    let mut files = BTreeMap::new();
+    let mut sha1sums_file = None;
+    for cap in RE.captures_iter(&body) {
+        let file_name = &cap[1];
+        if file_name.contains("sha1sums") {
+            sha1sums_file = Some(file_name.to_owned());
+        } else if !file_name.contains("md5sums") {
+            files.insert(
+                file_name.to_owned(),
+                DumpFileInfo {
+                    url: None,
+                    sha1: None,
+                    size: cap.get(2).and_then(|size| size.as_str().parse().ok()),
+                    md5: None,
+                },
+            );
+        }
+    }
+    if let Some(sha1sums_file) = sha1sums_file {
+        let sha1sums = client
+            .get(format!("{url}{sha1sums_file}"))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        for line in sha1sums.lines() {
+            if let Some((sha1, file_name)) = line.split_once(char::is_whitespace) {
+                if let Some(file_info) = files.get_mut(file_name.trim_start()) {
+                    file_info.sha1 = Some(sha1.to_owned());
+                }
+            }
+        }
+    }
+    Ok(Some(files))
+}
+
+/// Returns the files belonging to the given dump, which is either a job of a regular dump run or a file name
+/// pattern for `other/` dump trees.
+pub async fn get_dump_files(
+    client: &Client,
+    wiki: &str,
+    date: &str,
+    dump_type: &str,
+) -> Result<BTreeMap<String, DumpFileInfo>> {
+    get_dump_files_from(client, DEFAULT_BASE_URL, wiki, date, dump_type).await
+}
+
+/// Returns size and checksums of a single file of a dump, `job` is the dump type as for `get_dump_files()`.
+pub async fn get_file_info(
+    client: &Client,
+    wiki: &str,
+    date: &str,
+    job: &str,
+    file_name: &str,
+) -> Result<DumpFileInfo> {
+    get_file_info_from(client, DEFAULT_BASE_URL, wiki, date, job, file_name).await
+}
+
+/// Like `get_file_info()`, but from the dump website at `base_url`.
+pub async fn get_file_info_from(
+    client: &Client,
+    base_url: &str,
+    wiki: &str,
+    date: &str,
+    job: &str,
+    file_name: &str,
+) -> Result<DumpFileInfo> {
+    get_dump_files_from(client, base_url, wiki, date, job)
+        .await?
+        .remove(file_name)
+        .ok_or_else(|| Error::DumpFileNotFound(file_name.to_owned()))
+}
+
+/// Like `get_dump_files()`, but from the dump website at `base_url`.
+pub async fn get_dump_files_from(
+    client: &Client,
+    base_url: &str,
+    wiki: &str,
+    date: &str,
+    dump_type: &str,
+) -> Result<BTreeMap<String, DumpFileInfo>> {
+    if is_other_dump_tree(wiki) {
+        let mut files = get_other_dump_files_from(client, base_url, wiki, date).await?;
+        files.retain(|file_name, _| other_dump_file_matches(file_name, date, dump_type));
+        if files.is_empty() {
+            return Err(Error::DumpTypeNotFound());
+        }
+        Ok(files)
+    } else {
+        let mut dump_status = get_dump_status_from(client, base_url, wiki, date).await?;
+        let job_info = dump_status.jobs.remove(dump_type).ok_or(Error::DumpTypeNotFound())?;
+        if &job_info.status != "done" {
+            return Err(Error::DumpNotComplete());
+        }
+        job_info.files.ok_or(Error::DumpHasNoFiles())
+    }
+}
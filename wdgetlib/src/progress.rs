@@ -0,0 +1,72 @@
+// wdget
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Aggregation of the progress of downloads for frontends, which read it through the `Progress` trait shared with the
+//! searches of wdgreplib.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+pub use wdprogress::Progress;
+use wdprogress::ProgressCounters;
+
+use crate::DownloadProgress;
+
+/// Progress of one or more downloads in bytes received and files downloaded, updated with the progress sent by
+/// `download_dump()`. Existing and linked files are not counted.
+#[derive(Default)]
+pub struct DownloadProgressTracker {
+    counters: ProgressCounters,
+    decompressed_bytes_written: AtomicU64,
+}
+
+impl DownloadProgressTracker {
+    pub fn new() -> DownloadProgressTracker {
+        DownloadProgressTracker::default()
+    }
+
+    pub fn update(&self, progress: &DownloadProgress) {
+        match progress {
+            DownloadProgress::TotalDownloadSize(size) => self.counters.add_bytes_total(*size),
+            DownloadProgress::TotalFileCount(count) => self.counters.add_files_total(*count),
+            DownloadProgress::BytesReadFromNet(count) | DownloadProgress::PartFileResumed(_, _, count) => {
+                self.counters.add_bytes_done(*count)
+            }
+            DownloadProgress::DecompressedBytesWrittenToDisk(count) => {
+                self.decompressed_bytes_written.fetch_add(*count, Ordering::Relaxed);
+            }
+            DownloadProgress::FileFinished(_, _) => self.counters.add_files_done(1),
+            _ => {}
+        }
+    }
+
+    /// Bytes written to disk by decompressing downloads
+    pub fn decompressed_bytes_written(&self) -> u64 {
+        self.decompressed_bytes_written.load(Ordering::Relaxed)
+    }
+}
+
+impl Progress for DownloadProgressTracker {
+    fn bytes_done(&self) -> u64 {
+        self.counters.bytes_done()
+    }
+
+    fn bytes_total(&self) -> Option<u64> {
+        self.counters.bytes_total()
+    }
+
+    fn files_done(&self) -> u64 {
+        self.counters.files_done()
+    }
+
+    fn files_total(&self) -> Option<u64> {
+        self.counters.files_total()
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.counters.elapsed()
+    }
+}
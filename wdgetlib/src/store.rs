@@ -0,0 +1,351 @@
+// wdget
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Page-addressable store created from multistream dumps.
+//!
+//! A store is a directory containing `pages.zst`, the XML of each page compressed into its own zstd frame, and
+//! `index.bin`, which maps page ids and titles to these frames. Each index entry consists of the page id (u64), the
+//! frame offset (u64), the frame length (u32) and the title length (u16), all little-endian, followed by the UTF-8
+//! title.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use bzip2::read::{BzDecoder, MultiBzDecoder};
+
+use crate::{Error, Result};
+
+const PAGES_FILE_NAME: &str = "pages.zst";
+const INDEX_FILE_NAME: &str = "index.bin";
+const INDEX_MAGIC: &[u8; 8] = b"WDSTORE1";
+const ZSTD_LEVEL: i32 = 9;
+
+/// Returns the pairs of multistream dump files and their index files in the directory, e.g.
+/// `enwiki-20240101-pages-articles-multistream1.xml-p1p41242.bz2` and
+/// `enwiki-20240101-pages-articles-multistream-index1.txt-p1p41242.bz2`.
+pub fn find_multistream_files(directory: &Path, file_prefix: &str) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut pairs = Vec::new();
+    for entry in fs::read_dir(directory).map_err(|e| store_access_error(directory, e))? {
+        let entry = entry.map_err(|e| store_access_error(directory, e))?;
+        let file_name = entry.file_name();
+        let file_name = match file_name.to_str() {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+        if file_name.starts_with(file_prefix)
+            && file_name.contains("-multistream")
+            && !file_name.contains("-multistream-index")
+            && file_name.ends_with(".bz2")
+        {
+            let index_file_name = file_name
+                .replacen("-multistream", "-multistream-index", 1)
+                .replacen(".xml", ".txt", 1);
+            let index_file_path = directory.join(&index_file_name);
+            if !index_file_path.exists() {
+                return Err(Error::MultistreamIndexNotFound(index_file_name));
+            }
+            pairs.push((entry.path(), index_file_path));
+        }
+    }
+    pairs.sort_unstable();
+    Ok(pairs)
+}
+
+struct IndexEntry {
+    page_id: u64,
+    title: String,
+}
+
+/// Reads a multistream index and groups its entries by stream offset.
+fn read_multistream_index(index_file: &Path) -> Result<Vec<(u64, Vec<IndexEntry>)>> {
+    let file = File::open(index_file).map_err(|e| store_access_error(index_file, e))?;
+    let reader = BufReader::new(MultiBzDecoder::new(file));
+    let mut streams: Vec<(u64, Vec<IndexEntry>)> = Vec::new();
+    for line in reader.lines() {
+        let line = line.map_err(|e| store_access_error(index_file, e))?;
+        // titles may contain colons themselves
+        let mut parts = line.splitn(3, ':');
+        let (offset, page_id, title) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(offset), Some(page_id), Some(title)) => (offset, page_id, title),
+            _ => return Err(Error::InvalidMultistreamIndexLine(line)),
+        };
+        let (offset, page_id) = match (offset.parse::<u64>(), page_id.parse::<u64>()) {
+            (Ok(offset), Ok(page_id)) => (offset, page_id),
+            _ => return Err(Error::InvalidMultistreamIndexLine(line)),
+        };
+        let entry = IndexEntry {
+            page_id,
+            title: title.to_owned(),
+        };
+        match streams.last_mut() {
+            Some((last_offset, entries)) if *last_offset == offset => entries.push(entry),
+            _ => streams.push((offset, vec![entry])),
+        }
+    }
+    Ok(streams)
+}
+
+/// Splits the decompressed content of a stream into the XML of its pages.
+fn split_pages(stream: &str) -> Vec<&str> {
+    let mut pages = Vec::new();
+    let mut rest = stream;
+    while let Some(start) = rest.find("<page>") {
+        let end = match rest[start..].find("</page>") {
+            Some(end) => start + end + "</page>".len(),
+            None => break,
+        };
+        // include indentation and line break so the page XML looks the same as in the dump
+        let line_start = rest[..start].rfind('\n').map_or(0, |pos| pos + 1);
+        let line_end = rest[end..].find('\n').map_or(end, |pos| end + pos + 1);
+        pages.push(&rest[line_start..line_end]);
+        rest = &rest[line_end..];
+    }
+    pages
+}
+
+fn get_page_id(page: &str) -> Option<u64> {
+    let start = page.find("<id>")? + "<id>".len();
+    let end = start + page[start..].find("</id>")?;
+    page[start..end].parse().ok()
+}
+
+/// Creates a store from multistream dump files and their indexes and returns the number of pages stored.
+pub fn create_dump_store(sources: &[(PathBuf, PathBuf)], store_directory: &Path) -> Result<u64> {
+    fs::create_dir_all(store_directory).map_err(|e| store_access_error(store_directory, e))?;
+    let pages_path = store_directory.join(PAGES_FILE_NAME);
+    let index_path = store_directory.join(INDEX_FILE_NAME);
+    let mut pages_writer = BufWriter::new(File::create(&pages_path).map_err(|e| store_access_error(&pages_path, e))?);
+    let mut index_writer = BufWriter::new(File::create(&index_path).map_err(|e| store_access_error(&index_path, e))?);
+    index_writer
+        .write_all(INDEX_MAGIC)
+        .map_err(|e| store_access_error(&index_path, e))?;
+
+    let mut frame_offset = 0_u64;
+    let mut page_count = 0_u64;
+    let mut stream = String::with_capacity(4 * 1024 * 1024);
+    for (dump_file, index_file) in sources {
+        let mut file = File::open(dump_file).map_err(|e| store_access_error(dump_file, e))?;
+        for (stream_offset, entries) in read_multistream_index(index_file)? {
+            file.seek(SeekFrom::Start(stream_offset))
+                .map_err(|e| store_access_error(dump_file, e))?;
+            stream.clear();
+            // BzDecoder stops at the end of the stream
+            BzDecoder::new(&mut file)
+                .read_to_string(&mut stream)
+                .map_err(|e| store_access_error(dump_file, e))?;
+            let pages = split_pages(&stream);
+            if pages.len() != entries.len() {
+                return Err(Error::MultistreamIndexMismatch(dump_file.clone(), stream_offset));
+            }
+            for (page, entry) in pages.iter().zip(entries) {
+                if get_page_id(page) != Some(entry.page_id) {
+                    return Err(Error::MultistreamIndexMismatch(dump_file.clone(), stream_offset));
+                }
+                let frame = zstd::bulk::compress(page.as_bytes(), ZSTD_LEVEL)
+                    .map_err(|e| store_access_error(&pages_path, e))?;
+                pages_writer
+                    .write_all(&frame)
+                    .map_err(|e| store_access_error(&pages_path, e))?;
+                let frame_len: u32 = frame
+                    .len()
+                    .try_into()
+                    .map_err(|_| Error::PageTooLargeForDumpStore(entry.page_id))?;
+                let title_len: u16 = entry
+                    .title
+                    .len()
+                    .try_into()
+                    .map_err(|_| Error::PageTooLargeForDumpStore(entry.page_id))?;
+                let mut index_entry = Vec::with_capacity(22 + entry.title.len());
+                index_entry.extend_from_slice(&entry.page_id.to_le_bytes());
+                index_entry.extend_from_slice(&frame_offset.to_le_bytes());
+                index_entry.extend_from_slice(&frame_len.to_le_bytes());
+                index_entry.extend_from_slice(&title_len.to_le_bytes());
+                index_entry.extend_from_slice(entry.title.as_bytes());
+                index_writer
+                    .write_all(&index_entry)
+                    .map_err(|e| store_access_error(&index_path, e))?;
+                frame_offset += frame.len() as u64;
+                page_count += 1;
+            }
+        }
+    }
+    pages_writer.flush().map_err(|e| store_access_error(&pages_path, e))?;
+    index_writer.flush().map_err(|e| store_access_error(&index_path, e))?;
+    Ok(page_count)
+}
+
+struct PageLocation {
+    offset: u64,
+    len: u32,
+}
+
+/// Random access to the pages of a store created by `create_dump_store`.
+pub struct DumpStore {
+    pages_path: PathBuf,
+    locations: Vec<PageLocation>,
+    by_id: HashMap<u64, usize>,
+    by_title: HashMap<String, usize>,
+}
+
+impl DumpStore {
+    pub fn open(store_directory: &Path) -> Result<DumpStore> {
+        let index_path = store_directory.join(INDEX_FILE_NAME);
+        let index = fs::read(&index_path).map_err(|e| store_access_error(&index_path, e))?;
+        let mut rest = index
+            .strip_prefix(INDEX_MAGIC.as_ref())
+            .ok_or_else(|| Error::InvalidDumpStoreIndex(index_path.clone()))?;
+        let mut store = DumpStore {
+            pages_path: store_directory.join(PAGES_FILE_NAME),
+            locations: Vec::new(),
+            by_id: HashMap::new(),
+            by_title: HashMap::new(),
+        };
+        while !rest.is_empty() {
+            if rest.len() < 22 {
+                return Err(Error::InvalidDumpStoreIndex(index_path));
+            }
+            // UNWRAP: slice lengths are fixed
+            let page_id = u64::from_le_bytes(rest[0..8].try_into().unwrap());
+            let offset = u64::from_le_bytes(rest[8..16].try_into().unwrap());
+            let len = u32::from_le_bytes(rest[16..20].try_into().unwrap());
+            let title_len = u16::from_le_bytes(rest[20..22].try_into().unwrap()) as usize;
+            let title = rest
+                .get(22..22 + title_len)
+                .and_then(|title| std::str::from_utf8(title).ok())
+                .ok_or_else(|| Error::InvalidDumpStoreIndex(index_path.clone()))?;
+            let location_index = store.locations.len();
+            store.locations.push(PageLocation { offset, len });
+            store.by_id.insert(page_id, location_index);
+            store.by_title.insert(title.to_owned(), location_index);
+            rest = &rest[22 + title_len..];
+        }
+        Ok(store)
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Returns the XML of the page with the given title.
+    pub fn get_page_by_title(&self, title: &str) -> Result<Option<String>> {
+        match self.by_title.get(title) {
+            Some(&location_index) => self.read_page(&self.locations[location_index]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the XML of the page with the given page id.
+    pub fn get_page_by_id(&self, page_id: u64) -> Result<Option<String>> {
+        match self.by_id.get(&page_id) {
+            Some(&location_index) => self.read_page(&self.locations[location_index]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read_page(&self, location: &PageLocation) -> Result<String> {
+        let mut file = File::open(&self.pages_path).map_err(|e| store_access_error(&self.pages_path, e))?;
+        file.seek(SeekFrom::Start(location.offset))
+            .map_err(|e| store_access_error(&self.pages_path, e))?;
+        let mut frame = vec![0; location.len as usize];
+        file.read_exact(&mut frame)
+            .map_err(|e| store_access_error(&self.pages_path, e))?;
+        let mut page = String::new();
+        zstd::stream::read::Decoder::new(frame.as_slice())
+            .and_then(|mut decoder| decoder.read_to_string(&mut page))
+            .map_err(|e| store_access_error(&self.pages_path, e))?;
+        Ok(page)
+    }
+}
+
+fn store_access_error(path: &Path, e: std::io::Error) -> Error {
+    Error::DumpFileAccessError(path.to_owned(), e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use bzip2::write::BzEncoder;
+    use bzip2::Compression;
+
+    use super::*;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/fixtures")
+    }
+
+    fn temp_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("wdgetlib-store-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn test_create_and_open_store() {
+        let sources = find_multistream_files(&fixtures(), "testwiki-20240102").unwrap();
+        assert_eq!(sources.len(), 1);
+        let directory = temp_directory("roundtrip");
+        assert_eq!(create_dump_store(&sources, &directory).unwrap(), 5);
+
+        let store = DumpStore::open(&directory).unwrap();
+        assert_eq!(store.page_count(), 5);
+        let page = store.get_page_by_id(14).unwrap().unwrap();
+        assert!(page.trim_start().starts_with("<page>"), "{}", page);
+        assert!(page.contains("<title>Talk:Zeta</title>"), "{}", page);
+        assert!(page.trim_end().ends_with("</page>"), "{}", page);
+        assert_eq!(store.get_page_by_title("Talk:Zeta").unwrap(), Some(page));
+        assert!(store.get_page_by_title("Eta").unwrap().unwrap().contains("<id>15</id>"));
+        assert_eq!(store.get_page_by_id(16).unwrap(), None);
+        assert_eq!(store.get_page_by_title("Missing").unwrap(), None);
+
+        // entries cut off in the fixed part or in the title
+        let index_path = directory.join(INDEX_FILE_NAME);
+        let index = fs::read(&index_path).unwrap();
+        for truncated_len in [index.len() - 1, index.len() - "Eta".len() - 1, 1] {
+            fs::write(&index_path, &index[..truncated_len]).unwrap();
+            assert!(matches!(
+                DumpStore::open(&directory),
+                Err(Error::InvalidDumpStoreIndex(_))
+            ));
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_index_mismatch() {
+        let (dump_file, _) = find_multistream_files(&fixtures(), "testwiki-20240102")
+            .unwrap()
+            .remove(0);
+        let directory = temp_directory("mismatch");
+        fs::create_dir_all(&directory).unwrap();
+        let index_file = directory.join("index.txt.bz2");
+        let write_index = |index: &str| {
+            let mut encoder = BzEncoder::new(File::create(&index_file).unwrap(), Compression::best());
+            encoder.write_all(index.as_bytes()).unwrap();
+            encoder.finish().unwrap();
+        };
+        let sources = [(dump_file.clone(), index_file.clone())];
+        // wrong page id
+        write_index("303:11:Delta\n303:99:Epsilon\n");
+        assert!(matches!(
+            create_dump_store(&sources, &directory.join("store")),
+            Err(Error::MultistreamIndexMismatch(file, 303)) if file == dump_file
+        ));
+        // page missing in the index
+        write_index("303:11:Delta\n");
+        assert!(matches!(
+            create_dump_store(&sources, &directory.join("store")),
+            Err(Error::MultistreamIndexMismatch(_, 303))
+        ));
+        write_index("303:11\n");
+        assert!(matches!(
+            create_dump_store(&sources, &directory.join("store")),
+            Err(Error::InvalidMultistreamIndexLine(_))
+        ));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,658 @@
+// wdget
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Tests of the client against a mock of the dump website serving a single dump run of `testwiki` and the index of the
+//! latest dump runs.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bzip2::write::BzEncoder;
+use bzip2::Compression;
+use sha1::{Digest, Sha1};
+use wdgetlib::backup_index::{get_dump_run_sizes_from, get_latest_dump_runs_from};
+use wdgetlib::client::{RetryPolicy, WdClient};
+use wdgetlib::progress::{DownloadProgressTracker, Progress};
+use wdgetlib::{DownloadOptions, Error, ErrorClass};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const CONTENT: &[u8] = b"<mediawiki>\n  <page>\n    <title>Test</title>\n  </page>\n</mediawiki>\n";
+const FILE_NAME: &str = "testwiki-20240101-pages-articles.xml.bz2";
+
+fn compressed_content() -> Vec<u8> {
+    let mut encoder = BzEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(CONTENT).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn sha1_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(data))
+}
+
+fn dump_status(file_content: &[u8], sha1: &str) -> serde_json::Value {
+    serde_json::json!({
+        "version": "0.8",
+        "jobs": {
+            "articlesdump": {
+                "updated": "2024-01-02 03:04:05",
+                "status": "done",
+                "files": {
+                    FILE_NAME: {
+                        "url": format!("/testwiki/20240101/{FILE_NAME}"),
+                        "size": file_content.len(),
+                        "sha1": sha1,
+                    }
+                }
+            },
+            "metahistorybz2dump": {
+                "updated": "2024-01-02 03:04:05",
+                "status": "in-progress",
+            }
+        }
+    })
+}
+
+/// Serves the dump status and listing of the dump run with the given SHA1 checksum of the dump file.
+async fn start_server(sha1: &str) -> MockServer {
+    let server = MockServer::start().await;
+    let listing = "<html><body><pre>\n<a href=\"../\">../</a>\n\
+                   <a href=\"20231201/\">20231201/</a>  01-Dec-2023 00:00    -\n\
+                   <a href=\"20240101/\">20240101/</a>  01-Jan-2024 00:00    -\n\
+                   <a href=\"latest/\">latest/</a>  01-Jan-2024 00:00    -\n</pre></body></html>";
+    Mock::given(method("GET"))
+        .and(path("/testwiki/"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(listing))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/testwiki/20240101/dumpstatus.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(dump_status(&compressed_content(), sha1)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/testwiki/20231201/dumpstatus.json"))
+        .respond_with(ResponseTemplate::new(404))
+        .mount(&server)
+        .await;
+    server
+}
+
+async fn mount_file(server: &MockServer, body: Vec<u8>) {
+    Mock::given(method("GET"))
+        .and(path(format!("/testwiki/20240101/{FILE_NAME}")))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(body))
+        .mount(server)
+        .await;
+}
+
+fn create_client(server: &MockServer) -> WdClient {
+    let mut client = WdClient::new(reqwest::Client::new());
+    client.with_base_url(&server.uri()).with_retry_policy(RetryPolicy {
+        max_attempts: 1,
+        initial_delay: Duration::from_millis(1),
+    });
+    client
+}
+
+fn create_target_directory(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("wdgetlib-mock-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+#[tokio::test]
+async fn test_dump_status_and_dates() {
+    let server = start_server(&sha1_hex(&compressed_content())).await;
+    let client = create_client(&server);
+
+    let dump_status = client.dump_status("testwiki", "20240101").await.unwrap();
+    assert_eq!(dump_status.version, "0.8");
+    let job = &dump_status.jobs["articlesdump"];
+    assert_eq!(job.status, "done");
+    let file_info = &job.files.as_ref().unwrap()[FILE_NAME];
+    assert_eq!(file_info.size, Some(compressed_content().len() as u64));
+    assert!(dump_status.jobs["metahistorybz2dump"].files.is_none());
+
+    assert_eq!(
+        client.available_dates("testwiki").await.unwrap(),
+        ["20231201", "20240101"]
+    );
+    // the dump run of 20231201 has no status file
+    assert_eq!(
+        client
+            .latest_available_date("testwiki", Some("articlesdump"))
+            .await
+            .unwrap(),
+        "20240101"
+    );
+    assert!(matches!(
+        client
+            .latest_available_date("testwiki", Some("metahistorybz2dump"))
+            .await,
+        Err(Error::NoDumpDatesFound())
+    ));
+}
+
+#[tokio::test]
+async fn test_available_wikis() {
+    let server = start_server(&sha1_hex(&compressed_content())).await;
+    let listing = "<html><body><pre>\n<a href=\"../\">../</a>\n\
+                   <a href=\"other/\">other/</a>  01-Jan-2024 00:00    -\n\
+                   <a href=\"testwiki/\">testwiki/</a>  01-Jan-2024 00:00    -\n</pre></body></html>";
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(listing))
+        .mount(&server)
+        .await;
+    let client = create_client(&server);
+
+    // listed from the root of dump websites other than the Wikimedia one
+    let wikis = client.available_wikis().await.unwrap();
+    assert_eq!(
+        wikis.iter().map(|wiki| wiki.id.as_str()).collect::<Vec<_>>(),
+        ["testwiki"]
+    );
+}
+
+#[tokio::test]
+async fn test_file_info() {
+    let sha1 = sha1_hex(&compressed_content());
+    let server = start_server(&sha1).await;
+    let client = create_client(&server);
+
+    let file_info = client
+        .file_info("testwiki", "20240101", "articlesdump", FILE_NAME)
+        .await
+        .unwrap();
+    assert_eq!(file_info.sha1.as_deref(), Some(sha1.as_str()));
+    assert_eq!(
+        client.file_url("testwiki", "20240101", FILE_NAME, &file_info),
+        format!("{}/testwiki/20240101/{FILE_NAME}", server.uri())
+    );
+    assert!(matches!(
+        client
+            .file_info(
+                "testwiki",
+                "20240101",
+                "articlesdump",
+                "testwiki-20240101-stub-articles.xml.gz"
+            )
+            .await,
+        Err(Error::DumpFileNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_not_found() {
+    let server = start_server(&sha1_hex(&compressed_content())).await;
+    let client = create_client(&server);
+
+    let err = client.dump_status("testwiki", "20231201").await.err().unwrap();
+    assert!(matches!(err, Error::DumpStatusFileNotFound()));
+    assert_eq!(err.class(), ErrorClass::DumpNotReady);
+
+    // the listing of other wikis is not mocked
+    let err = client.available_dates("otherwiki").await.err().unwrap();
+    assert!(matches!(err, Error::HttpError(_)));
+    assert_eq!(err.class(), ErrorClass::Network);
+
+    let directory = create_target_directory("not-found");
+    let err = client
+        .download(
+            "testwiki",
+            "20240101",
+            "metahistorybz2dump",
+            Some(&directory),
+            &DownloadOptions::default(),
+            None,
+        )
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::DumpNotComplete()));
+    let err = client
+        .download(
+            "testwiki",
+            "20240101",
+            "stubsdump",
+            Some(&directory),
+            &DownloadOptions::default(),
+            None,
+        )
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::DumpTypeNotFound()));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_download() {
+    let compressed = compressed_content();
+    let server = start_server(&sha1_hex(&compressed)).await;
+    mount_file(&server, compressed.clone()).await;
+    let client = create_client(&server);
+
+    let directory = create_target_directory("download");
+    let (progress_send, mut progress_receive) = tokio::sync::mpsc::unbounded_channel();
+    let target_directory = client
+        .download(
+            "testwiki",
+            "20240101",
+            "articlesdump",
+            Some(&directory),
+            &DownloadOptions::default(),
+            Some(progress_send),
+        )
+        .await
+        .unwrap();
+    assert_eq!(target_directory, directory);
+    let tracker = DownloadProgressTracker::new();
+    while let Some(progress) = progress_receive.recv().await {
+        tracker.update(&progress);
+    }
+    assert_eq!(tracker.bytes_done(), compressed.len() as u64);
+    assert_eq!(tracker.bytes_total(), Some(compressed.len() as u64));
+    assert_eq!((tracker.files_done(), tracker.files_total()), (1, Some(1)));
+    assert_eq!(fs::read(directory.join(FILE_NAME)).unwrap(), compressed);
+    assert!(!directory.join(format!("{FILE_NAME}.part")).exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_link_from() {
+    let compressed = compressed_content();
+    // the file is not served, so it can only be linked
+    let server = start_server(&sha1_hex(&compressed)).await;
+    let client = create_client(&server);
+
+    let older_directory = create_target_directory("link-from-older");
+    fs::write(
+        older_directory.join("testwiki-20231201-pages-articles.xml.bz2"),
+        &compressed,
+    )
+    .unwrap();
+    let directory = create_target_directory("link-from");
+    client
+        .download(
+            "testwiki",
+            "20240101",
+            "articlesdump",
+            Some(&directory),
+            &DownloadOptions {
+                link_from: Some(&older_directory),
+                ..DownloadOptions::default()
+            },
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(fs::read(directory.join(FILE_NAME)).unwrap(), compressed);
+    fs::remove_dir_all(&directory).unwrap();
+    fs::remove_dir_all(&older_directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_download_decompressed() {
+    let compressed = compressed_content();
+    let server = start_server(&sha1_hex(&compressed)).await;
+    mount_file(&server, compressed).await;
+    let client = create_client(&server);
+
+    let directory = create_target_directory("decompress");
+    let download_options = DownloadOptions {
+        decompress: true,
+        ..DownloadOptions::default()
+    };
+    client
+        .download(
+            "testwiki",
+            "20240101",
+            "articlesdump",
+            Some(&directory),
+            &download_options,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read(directory.join("testwiki-20240101-pages-articles.xml")).unwrap(),
+        CONTENT
+    );
+    assert!(!directory.join(FILE_NAME).exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_resume() {
+    let compressed = compressed_content();
+    let server = start_server(&sha1_hex(&compressed)).await;
+    let part_len = compressed.len() / 2;
+    Mock::given(method("GET"))
+        .and(path(format!("/testwiki/20240101/{FILE_NAME}")))
+        .and(header("range", format!("bytes={part_len}-").as_str()))
+        .respond_with(ResponseTemplate::new(206).set_body_bytes(&compressed[part_len..]))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let client = create_client(&server);
+
+    let directory = create_target_directory("resume");
+    fs::write(directory.join(format!("{FILE_NAME}.part")), &compressed[..part_len]).unwrap();
+    let download_options = DownloadOptions {
+        resume: true,
+        ..DownloadOptions::default()
+    };
+    client
+        .download(
+            "testwiki",
+            "20240101",
+            "articlesdump",
+            Some(&directory),
+            &download_options,
+            None,
+        )
+        .await
+        .unwrap();
+    // the checksum covers the part downloaded before
+    assert_eq!(fs::read(directory.join(FILE_NAME)).unwrap(), compressed);
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_resume_decompressed() {
+    // multistream file, the checkpoint was written after the first stream
+    let first_stream = compressed_content();
+    let compressed = [first_stream.clone(), compressed_content()].concat();
+    let server = start_server(&sha1_hex(&compressed)).await;
+    let part_len = first_stream.len();
+    Mock::given(method("GET"))
+        .and(path(format!("/testwiki/20240101/{FILE_NAME}")))
+        .and(header("range", format!("bytes={part_len}-").as_str()))
+        .respond_with(ResponseTemplate::new(206).set_body_bytes(&compressed[part_len..]))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let client = create_client(&server);
+
+    let directory = create_target_directory("resume-decompressed");
+    let target_file_name = "testwiki-20240101-pages-articles.xml";
+    // the data decompressed after the checkpoint is dropped
+    fs::write(
+        directory.join(format!("{target_file_name}.part")),
+        [CONTENT, &CONTENT[..10]].concat(),
+    )
+    .unwrap();
+    fs::write(
+        directory.join(format!("{target_file_name}.part.checkpoint")),
+        format!("{}\t{}\n", part_len, CONTENT.len()),
+    )
+    .unwrap();
+    let download_options = DownloadOptions {
+        decompress: true,
+        resume: true,
+        ..DownloadOptions::default()
+    };
+    client
+        .download(
+            "testwiki",
+            "20240101",
+            "articlesdump",
+            Some(&directory),
+            &download_options,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read(directory.join(target_file_name)).unwrap(),
+        [CONTENT, CONTENT].concat()
+    );
+    assert!(!directory.join(format!("{target_file_name}.part.checkpoint")).exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_checksum_mismatch() {
+    let compressed = compressed_content();
+    let server = start_server(&sha1_hex(b"something else")).await;
+    mount_file(&server, compressed).await;
+    let client = create_client(&server);
+
+    let directory = create_target_directory("mismatch");
+    let err = client
+        .download(
+            "testwiki",
+            "20240101",
+            "articlesdump",
+            Some(&directory),
+            &DownloadOptions::default(),
+            None,
+        )
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::ChecksumMismatch(_)));
+    assert_eq!(err.class(), ErrorClass::Verification);
+    assert!(!directory.join(FILE_NAME).exists());
+    assert!(!directory.join(format!("{FILE_NAME}.part")).exists());
+
+    // the corrupt file is ignored if verification is skipped
+    let download_options = DownloadOptions {
+        skip_verification: true,
+        ..DownloadOptions::default()
+    };
+    client
+        .download(
+            "testwiki",
+            "20240101",
+            "articlesdump",
+            Some(&directory),
+            &download_options,
+            None,
+        )
+        .await
+        .unwrap();
+    assert!(directory.join(FILE_NAME).exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+const STAGING_DIRECTORY_NAME: &str = ".wdget-testwiki-20240101-articlesdump.partial";
+
+#[tokio::test]
+async fn test_all_or_nothing_resume() {
+    let compressed = compressed_content();
+    let server = start_server(&sha1_hex(&compressed)).await;
+    Mock::given(method("GET"))
+        .and(path(format!("/testwiki/20240101/{FILE_NAME}")))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(compressed.clone()))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let client = create_client(&server);
+
+    // a file staged by an interrupted run which has been corrupted since is downloaded again
+    let directory = create_target_directory("all-or-nothing-resume");
+    let staging_directory = directory.join(STAGING_DIRECTORY_NAME);
+    fs::create_dir(&staging_directory).unwrap();
+    let mut corrupted = compressed.clone();
+    corrupted[0] ^= 0xff;
+    fs::write(staging_directory.join(FILE_NAME), &corrupted).unwrap();
+    let download_options = DownloadOptions {
+        all_or_nothing: true,
+        ..DownloadOptions::default()
+    };
+    client
+        .download(
+            "testwiki",
+            "20240101",
+            "articlesdump",
+            Some(&directory),
+            &download_options,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(fs::read(directory.join(FILE_NAME)).unwrap(), compressed);
+    assert!(!staging_directory.exists());
+
+    // a valid staged file is moved into place without downloading it
+    fs::remove_file(directory.join(FILE_NAME)).unwrap();
+    fs::create_dir(&staging_directory).unwrap();
+    fs::write(staging_directory.join(FILE_NAME), &compressed).unwrap();
+    client
+        .download(
+            "testwiki",
+            "20240101",
+            "articlesdump",
+            Some(&directory),
+            &download_options,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(fs::read(directory.join(FILE_NAME)).unwrap(), compressed);
+    assert!(!staging_directory.exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_all_or_nothing_interrupted_publish() {
+    let compressed = compressed_content();
+    // the file is not served, so it can only be taken from the staging directory
+    let server = start_server(&sha1_hex(&compressed)).await;
+    let client = create_client(&server);
+
+    // the run was interrupted after all files had been staged and the marker had been written
+    let directory = create_target_directory("all-or-nothing-publish");
+    let staging_directory = directory.join(STAGING_DIRECTORY_NAME);
+    fs::create_dir(&staging_directory).unwrap();
+    fs::write(staging_directory.join(FILE_NAME), &compressed).unwrap();
+    fs::write(
+        staging_directory.join(".complete"),
+        format!("{FILE_NAME}\ntestwiki-20240101-moved-before.xml.bz2"),
+    )
+    .unwrap();
+    for all_or_nothing in [false, true] {
+        client
+            .download(
+                "testwiki",
+                "20240101",
+                "articlesdump",
+                Some(&directory),
+                &DownloadOptions {
+                    all_or_nothing,
+                    ..DownloadOptions::default()
+                },
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(fs::read(directory.join(FILE_NAME)).unwrap(), compressed);
+        assert!(!staging_directory.exists());
+    }
+
+    // clean finishes complete staging directories and removes incomplete ones
+    let incomplete_staging_directory = directory.join(".wdget-testwiki-20240101-metacurrentdump.partial");
+    for staging_directory in [&staging_directory, &incomplete_staging_directory] {
+        fs::create_dir(staging_directory).unwrap();
+    }
+    fs::rename(directory.join(FILE_NAME), staging_directory.join(FILE_NAME)).unwrap();
+    fs::write(staging_directory.join(".complete"), FILE_NAME).unwrap();
+    fs::write(
+        incomplete_staging_directory.join("testwiki-20240101-pages-meta-current.xml.bz2"),
+        b"",
+    )
+    .unwrap();
+    assert_eq!(
+        wdgetlib::clean_directory(&directory).unwrap(),
+        [staging_directory.clone(), incomplete_staging_directory.clone()]
+    );
+    assert_eq!(fs::read(directory.join(FILE_NAME)).unwrap(), compressed);
+    assert!(!directory.join("testwiki-20240101-pages-meta-current.xml.bz2").exists());
+    assert!(!staging_directory.exists() && !incomplete_staging_directory.exists());
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_offline() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&server)
+        .await;
+    let mut client = create_client(&server);
+    client.with_offline(true);
+
+    assert!(matches!(
+        client.dump_status("testwiki", "20240101").await,
+        Err(Error::Offline())
+    ));
+    assert!(matches!(
+        client.available_dates("testwiki").await,
+        Err(Error::Offline())
+    ));
+    let directory = create_target_directory("offline");
+    assert!(matches!(
+        client
+            .download(
+                "testwiki",
+                "20240101",
+                "articlesdump",
+                Some(&directory),
+                &DownloadOptions::default(),
+                None,
+            )
+            .await,
+        Err(Error::Offline())
+    ));
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[tokio::test]
+async fn test_latest_dump_runs() {
+    let server = start_server(&sha1_hex(&compressed_content())).await;
+    let backup_index =
+        fs::read_to_string(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/backup-index.html")).unwrap();
+    Mock::given(method("GET"))
+        .and(path("/backup-index.html"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(backup_index))
+        .mount(&server)
+        .await;
+    let client = reqwest::Client::new();
+
+    // wikis without a dump run are not listed
+    let dump_runs = get_latest_dump_runs_from(&client, &server.uri()).await.unwrap();
+    assert_eq!(
+        dump_runs
+            .values()
+            .map(|dump_run| (
+                dump_run.wiki.as_str(),
+                dump_run.date.as_str(),
+                dump_run.status.as_str(),
+                dump_run.is_complete()
+            ))
+            .collect::<Vec<_>>(),
+        [
+            ("failedwiki", "20240101", "failed", false),
+            ("otherwiki", "20240120", "in-progress", false),
+            ("testwiki", "20240101", "done", true),
+        ]
+    );
+    assert_eq!(dump_runs["testwiki"].updated, "2024-01-05 10:00:00");
+
+    // only the completed jobs count and runs without a dump status are left out
+    let sizes = get_dump_run_sizes_from(&client, &server.uri(), &dump_runs.values().collect::<Vec<_>>(), 2).await;
+    assert_eq!(
+        sizes.into_iter().collect::<Vec<_>>(),
+        [("testwiki".to_owned(), compressed_content().len() as u64)]
+    );
+}
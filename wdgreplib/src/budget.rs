@@ -0,0 +1,56 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Memory budget shared by the worker threads. Before a large revision text is read into memory its size is reserved
+//! and the worker waits while the reservations of the other workers would exceed the budget together with it. A text
+//! larger than the whole budget is read once no other reservations are held, so giant pages hit by several workers at
+//! once are processed one after another instead of in parallel.
+
+use std::sync::{Condvar, Mutex};
+
+/// Texts smaller than this are read without a reservation, the memory used for them is part of the base memory
+/// usage of each worker.
+pub(crate) const MIN_RESERVED_SIZE: u64 = 1024 * 1024;
+
+pub(crate) struct MemoryBudget {
+    limit: u64,
+    reserved: Mutex<u64>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(limit: u64) -> MemoryBudget {
+        MemoryBudget {
+            limit,
+            reserved: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits until the size fits into the budget or no other reservations are held.
+    pub(crate) fn reserve(&self, size: u64) -> Reservation<'_> {
+        // UNWRAP: the lock is not held while panicking
+        let mut reserved = self.reserved.lock().unwrap();
+        while *reserved > 0 && *reserved + size > self.limit {
+            reserved = self.released.wait(reserved).unwrap();
+        }
+        *reserved += size;
+        Reservation { budget: self, size }
+    }
+}
+
+/// Returned to the budget when dropped.
+pub(crate) struct Reservation<'a> {
+    budget: &'a MemoryBudget,
+    size: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        *self.budget.reserved.lock().unwrap() -= self.size;
+        self.budget.released.notify_all();
+    }
+}
@@ -0,0 +1,117 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! In-process decompression of .bz2 files consisting of several concatenated bzip2 streams, like multistream dumps
+//! or files recompressed with parallel compressors. The start of each stream is recorded so that the streams of a
+//! file could later be decompressed in parallel.
+
+#[cfg(feature = "internal-decompression")]
+use std::io::{BufRead, Read};
+#[cfg(feature = "internal-decompression")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "internal-decompression")]
+use bzip2::bufread::BzDecoder;
+
+/// Start of a bzip2 stream in the compressed file and in the decompressed data.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Bzip2Stream {
+    pub compressed_offset: u64,
+    pub decompressed_offset: u64,
+}
+
+/// Decompresses all streams of the input one after another like `MultiBzDecoder`.
+#[cfg(feature = "internal-decompression")]
+pub(crate) struct Bzip2StreamsDecoder<R> {
+    // only None while switching to the next stream
+    decoder: Option<BzDecoder<R>>,
+    current_stream: Bzip2Stream,
+    streams: Arc<Mutex<Vec<Bzip2Stream>>>,
+}
+
+#[cfg(feature = "internal-decompression")]
+impl<R: BufRead> Bzip2StreamsDecoder<R> {
+    pub(crate) fn new(reader: R) -> Bzip2StreamsDecoder<R> {
+        Bzip2StreamsDecoder {
+            decoder: Some(BzDecoder::new(reader)),
+            current_stream: Bzip2Stream::default(),
+            streams: Arc::new(Mutex::new(vec![Bzip2Stream::default()])),
+        }
+    }
+
+    /// Returns the streams started so far, which can be read while the decoder is used by another thread.
+    pub(crate) fn streams(&self) -> Arc<Mutex<Vec<Bzip2Stream>>> {
+        Arc::clone(&self.streams)
+    }
+}
+
+#[cfg(feature = "internal-decompression")]
+impl<R: BufRead> Read for Bzip2StreamsDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            // UNWRAP: replaced before returning
+            let decoder = self.decoder.as_mut().unwrap();
+            let len = decoder.read(buf)?;
+            if len > 0 || buf.is_empty() || decoder.get_mut().fill_buf()?.is_empty() {
+                return Ok(len);
+            }
+            // the current stream ended and another one follows
+            let next_stream = Bzip2Stream {
+                compressed_offset: self.current_stream.compressed_offset + decoder.total_in(),
+                decompressed_offset: self.current_stream.decompressed_offset + decoder.total_out(),
+            };
+            self.streams.lock().unwrap().push(next_stream);
+            self.current_stream = next_stream;
+            // UNWRAP: checked above
+            let reader = self.decoder.take().unwrap().into_inner();
+            self.decoder = Some(BzDecoder::new(reader));
+        }
+    }
+}
+
+#[cfg(all(test, feature = "internal-decompression"))]
+mod tests {
+    use std::io::Write;
+
+    use bzip2::write::BzEncoder;
+    use bzip2::Compression;
+
+    use super::*;
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        let mut encoder = BzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_concatenated_streams() {
+        let first = compress(b"<mediawiki>\n");
+        let second = compress(b"  <page></page>\n");
+        let third = compress(b"</mediawiki>\n");
+        let concatenated = [first.as_slice(), &second, &third].concat();
+
+        let mut decoder = Bzip2StreamsDecoder::new(concatenated.as_slice());
+        let streams = decoder.streams();
+        let mut decompressed = String::new();
+        decoder.read_to_string(&mut decompressed).unwrap();
+        assert_eq!(decompressed, "<mediawiki>\n  <page></page>\n</mediawiki>\n");
+        assert_eq!(
+            *streams.lock().unwrap(),
+            [
+                Bzip2Stream::default(),
+                Bzip2Stream {
+                    compressed_offset: first.len() as u64,
+                    decompressed_offset: 12,
+                },
+                Bzip2Stream {
+                    compressed_offset: (first.len() + second.len()) as u64,
+                    decompressed_offset: 28,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Checkpoints of long searches: the byte ranges of the dump files whose output has been printed are saved
+//! periodically, so an interrupted search can be restarted without searching them again.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+/// The checkpoint file is written at most this often while searching.
+const WRITE_INTERVAL: Duration = Duration::from_secs(10);
+
+const SEARCH_PREFIX: &str = "search\t";
+
+struct CompletedRanges {
+    /// Completed byte ranges of each dump file by their start, adjacent ranges are merged
+    ranges: HashMap<String, BTreeMap<u64, u64>>,
+    last_write: Instant,
+}
+
+impl CompletedRanges {
+    fn add(&mut self, dump_file: &str, mut start: u64, mut end: u64) {
+        let ranges = self.ranges.entry(dump_file.to_owned()).or_default();
+        if let Some((&previous_start, &previous_end)) = ranges.range(..=start).next_back() {
+            if previous_end >= start {
+                start = previous_start;
+                end = end.max(previous_end);
+            }
+        }
+        while let Some((&next_start, &next_end)) = ranges.range(start..).next() {
+            if next_start > end {
+                break;
+            }
+            ranges.remove(&next_start);
+            end = end.max(next_end);
+        }
+        ranges.insert(start, end);
+    }
+
+    fn contains(&self, dump_file: &str, start: u64, end: u64) -> bool {
+        self.ranges
+            .get(dump_file)
+            .and_then(|ranges| ranges.range(..=start).next_back())
+            .is_some_and(|(_, &completed_end)| completed_end >= end)
+    }
+}
+
+/// Parses a line `<dump file>\t<start>\t<end>`.
+fn parse_line(line: &str) -> Option<(&str, u64, u64)> {
+    let mut fields = line.rsplitn(3, '\t');
+    let end = fields.next()?.parse().ok()?;
+    let start = fields.next()?.parse().ok()?;
+    Some((fields.next()?, start, end))
+}
+
+/// Checkpoint of a search, see `SearchOptions::with_checkpoint()`.
+pub struct SearchCheckpoint {
+    file: PathBuf,
+    /// Description of the search, e.g. its command line, a checkpoint can only be resumed by the same search
+    search: String,
+    completed: Mutex<CompletedRanges>,
+}
+
+impl SearchCheckpoint {
+    /// Opens the checkpoint file of the search, which is created when the first part of a dump file is completed.
+    /// Fails if the file exists but was written for a different search.
+    pub fn open(file: &Path, search: &str) -> Result<SearchCheckpoint> {
+        let mut completed = CompletedRanges {
+            ranges: HashMap::new(),
+            last_write: Instant::now(),
+        };
+        if file.exists() {
+            let content = fs::read_to_string(file)?;
+            let mut lines = content.lines();
+            if lines.next().and_then(|line| line.strip_prefix(SEARCH_PREFIX)) != Some(search) {
+                return Err(Error::CheckpointOfDifferentSearch(file.display().to_string()));
+            }
+            for line in lines.filter(|line| !line.is_empty()) {
+                let (dump_file, start, end) =
+                    parse_line(line).ok_or_else(|| Error::InvalidCheckpointLine(line.to_owned()))?;
+                completed.add(dump_file, start, end);
+            }
+        }
+        Ok(SearchCheckpoint {
+            file: file.to_owned(),
+            search: search.to_owned(),
+            completed: Mutex::new(completed),
+        })
+    }
+
+    /// Returns whether parts of dump files were completed by an earlier search.
+    pub fn is_resumed(&self) -> bool {
+        !self.completed.lock().unwrap().ranges.is_empty()
+    }
+
+    pub(crate) fn is_completed(&self, dump_file: &str, start: u64, end: u64) -> bool {
+        self.completed.lock().unwrap().contains(dump_file, start, end)
+    }
+
+    /// Adds the parts whose output has been printed, the file is written if it has not been written for a while.
+    pub(crate) fn add<'b>(&self, parts: impl IntoIterator<Item = &'b (&'b str, u64, u64)>) -> Result<()> {
+        let mut completed = self.completed.lock().unwrap();
+        for &(dump_file, start, end) in parts {
+            completed.add(dump_file, start, end);
+        }
+        if completed.last_write.elapsed() >= WRITE_INTERVAL {
+            self.write_locked(&mut completed)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the checkpoint file.
+    pub fn write(&self) -> Result<()> {
+        self.write_locked(&mut self.completed.lock().unwrap())
+    }
+
+    fn write_locked(&self, completed: &mut CompletedRanges) -> Result<()> {
+        // written to a temporary file first so that an interruption never leaves a truncated checkpoint
+        let mut temp_file = self.file.clone().into_os_string();
+        temp_file.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&temp_file)?);
+        writeln!(writer, "{SEARCH_PREFIX}{}", self.search)?;
+        for (dump_file, ranges) in &completed.ranges {
+            for (start, end) in ranges {
+                writeln!(writer, "{dump_file}\t{start}\t{end}")?;
+            }
+        }
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temp_file, &self.file)?;
+        completed.last_write = Instant::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completed_ranges() {
+        let mut completed = CompletedRanges {
+            ranges: HashMap::new(),
+            last_write: Instant::now(),
+        };
+        completed.add("a.xml", 100, 200);
+        completed.add("a.xml", 300, 400);
+        assert!(completed.contains("a.xml", 100, 200));
+        assert!(!completed.contains("a.xml", 100, 400));
+        assert!(!completed.contains("b.xml", 100, 200));
+        // merged with both neighbors
+        completed.add("a.xml", 200, 300);
+        assert!(completed.contains("a.xml", 100, 400));
+        assert_eq!(completed.ranges["a.xml"].len(), 1);
+        completed.add("a.xml", 0, u64::MAX);
+        assert!(completed.contains("a.xml", 500, 600));
+    }
+}
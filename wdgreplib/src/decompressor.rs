@@ -0,0 +1,406 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use simdutf8::basic::from_utf8;
+
+use crate::bzip2_streams::Bzip2Stream;
+#[cfg(feature = "internal-decompression")]
+use crate::bzip2_streams::Bzip2StreamsDecoder;
+use crate::path::native_path;
+use crate::{Error, Result};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompressionFormat {
+    SevenZip,
+    Bzip2,
+    Gzip,
+    Zstd,
+}
+
+impl CompressionFormat {
+    pub fn of_file(file: &str) -> Option<CompressionFormat> {
+        if file.ends_with(".7z") {
+            Some(CompressionFormat::SevenZip)
+        } else if file.ends_with(".bz2") {
+            Some(CompressionFormat::Bzip2)
+        } else if file.ends_with(".gz") {
+            Some(CompressionFormat::Gzip)
+        } else if file.ends_with(".zst") {
+            Some(CompressionFormat::Zstd)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) const fn extension(self) -> &'static str {
+        match self {
+            CompressionFormat::SevenZip => ".7z",
+            CompressionFormat::Bzip2 => ".bz2",
+            CompressionFormat::Gzip => ".gz",
+            CompressionFormat::Zstd => ".zst",
+        }
+    }
+
+    /// External binaries which can decompress this format to stdout, in order of preference.
+    const fn candidates(self) -> &'static [(&'static str, &'static [&'static str])] {
+        match self {
+            CompressionFormat::SevenZip => &[("7z", &["e", "-so"]), ("7za", &["e", "-so"]), ("7zz", &["e", "-so"])],
+            CompressionFormat::Bzip2 => &[("bzcat", &[])],
+            CompressionFormat::Gzip => &[("gzip", &["-dc"]), ("pigz", &["-dc"])],
+            CompressionFormat::Zstd => &[("zstd", &["-dc"]), ("zstdcat", &[])],
+        }
+    }
+}
+
+pub enum Decompressor {
+    External {
+        binary: PathBuf,
+        options: Vec<String>,
+        /// The file is written to stdin of the decompressor instead of being passed as an argument, so the number
+        /// of compressed bytes read is known. 7z and user-supplied binaries or options are passed the file name.
+        feed_stdin: bool,
+    },
+    #[cfg(feature = "internal-decompression")]
+    InternalBzip2,
+    #[cfg(feature = "internal-decompression")]
+    InternalGzip,
+    #[cfg(feature = "internal-decompression")]
+    InternalZstd,
+}
+
+/// Multi-threaded bzip2 decompressors, the single-threaded ones are the bottleneck when searching .bz2 files.
+fn find_parallel_bzip2_decompressor(thread_count: Option<NonZeroUsize>) -> Option<Decompressor> {
+    if let Ok(binary) = which::which("lbzip2") {
+        let mut options = vec!["-dc".to_owned()];
+        if let Some(thread_count) = thread_count {
+            options.push("-n".to_owned());
+            options.push(thread_count.to_string());
+        }
+        Some(Decompressor::External {
+            binary,
+            options,
+            feed_stdin: true,
+        })
+    } else if let Ok(binary) = which::which("pbzip2") {
+        let mut options = vec!["-dc".to_owned()];
+        if let Some(thread_count) = thread_count {
+            options.push(format!("-p{thread_count}"));
+        }
+        Some(Decompressor::External {
+            binary,
+            options,
+            feed_stdin: true,
+        })
+    } else {
+        None
+    }
+}
+
+/// Finds a decompressor for the given format. Unless a binary or options are given, parallel decompressors
+/// (for .bz2) are preferred, then internal decompression, then the first external candidate found on the PATH.
+pub fn find_decompressor(
+    format: CompressionFormat,
+    binary: Option<&str>,
+    options: Option<&[&str]>,
+    thread_count: Option<NonZeroUsize>,
+) -> Result<Decompressor> {
+    if binary.is_none() && options.is_none() && format == CompressionFormat::Bzip2 {
+        if let Some(decompressor) = find_parallel_bzip2_decompressor(thread_count) {
+            return Ok(decompressor);
+        }
+    }
+    #[cfg(feature = "internal-decompression")]
+    if binary.is_none() && options.is_none() {
+        match format {
+            CompressionFormat::Bzip2 => return Ok(Decompressor::InternalBzip2),
+            CompressionFormat::Gzip => return Ok(Decompressor::InternalGzip),
+            CompressionFormat::Zstd => return Ok(Decompressor::InternalZstd),
+            CompressionFormat::SevenZip => {}
+        }
+    }
+    let candidates = format.candidates();
+    let feed_stdin = binary.is_none() && options.is_none() && format != CompressionFormat::SevenZip;
+    let found = match binary {
+        Some(binary) => which::which(binary)
+            .ok()
+            .map(|path| (path, options.unwrap_or(candidates[0].1))),
+        None => candidates.iter().find_map(|(candidate, candidate_options)| {
+            which::which(candidate)
+                .ok()
+                .map(|path| (path, options.unwrap_or(candidate_options)))
+        }),
+    };
+    match found {
+        Some((binary, options)) => Ok(Decompressor::External {
+            binary,
+            options: options.iter().map(|&option| option.to_owned()).collect(),
+            feed_stdin,
+        }),
+        None => {
+            let looked_for = match binary {
+                Some(binary) => binary.to_owned(),
+                None => candidates
+                    .iter()
+                    .map(|(candidate, _)| *candidate)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            };
+            Err(Error::DecompressorNotFound(format.extension().to_owned(), looked_for))
+        }
+    }
+}
+
+/// Decompresses a stream in-process since external decompressors are only used for reading files.
+pub fn decompress_stream<'a, R: Read + 'a>(format: CompressionFormat, reader: R) -> Result<Box<dyn Read + 'a>> {
+    match format {
+        CompressionFormat::Bzip2 => Ok(Box::new(bzip2::read::MultiBzDecoder::new(reader))),
+        CompressionFormat::Zstd => Ok(Box::new(zstd::stream::read::Decoder::new(reader)?)),
+        #[cfg(feature = "internal-decompression")]
+        CompressionFormat::Gzip => Ok(Box::new(flate2::read::MultiGzDecoder::new(reader))),
+        _ => Err(Error::StreamDecompressionNotSupported(format.extension().to_owned())),
+    }
+}
+
+/// Counts the bytes read from the inner reader.
+pub struct CountingReader<R> {
+    reader: R,
+    count: Arc<AtomicU64>,
+}
+
+impl<R: Read> CountingReader<R> {
+    pub fn new(reader: R) -> CountingReader<R> {
+        CountingReader {
+            reader,
+            count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Returns the counter, which can be read while the reader is used by another thread.
+    pub fn counter(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.count)
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.count.fetch_add(len as u64, Ordering::Relaxed);
+        Ok(len)
+    }
+}
+
+/// Decompressed content of a dump file.
+pub struct DecompressingReader {
+    reader: Box<dyn Read + Send>,
+    child: Option<Child>,
+    // writes the file to stdin of the external decompressor
+    feeder: Option<JoinHandle<std::io::Result<u64>>>,
+    compressed_bytes_read: Option<Arc<AtomicU64>>,
+    // only recorded if .bz2 files are decompressed in-process
+    bzip2_streams: Option<Arc<Mutex<Vec<Bzip2Stream>>>>,
+}
+
+impl DecompressingReader {
+    /// Reads an uncompressed file.
+    pub(crate) fn uncompressed(file: File) -> DecompressingReader {
+        DecompressingReader {
+            reader: Box::new(file),
+            child: None,
+            feeder: None,
+            compressed_bytes_read: None,
+            bzip2_streams: None,
+        }
+    }
+
+    #[cfg(feature = "internal-decompression")]
+    fn internal<R: Read + Send + 'static>(
+        file: &str,
+        decoder: impl FnOnce(CountingReader<File>) -> std::io::Result<R>,
+    ) -> Result<DecompressingReader> {
+        let file = CountingReader::new(File::open(file)?);
+        let compressed_bytes_read = Some(file.counter());
+        Ok(DecompressingReader {
+            reader: Box::new(decoder(file)?),
+            child: None,
+            feeder: None,
+            compressed_bytes_read,
+            bzip2_streams: None,
+        })
+    }
+}
+
+impl fmt::Display for Decompressor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decompressor::External { binary, options, .. } => {
+                write!(f, "{}", binary.display())?;
+                for option in options {
+                    write!(f, " {option}")?;
+                }
+                Ok(())
+            }
+            #[cfg(feature = "internal-decompression")]
+            Decompressor::InternalBzip2 => write!(f, "internal bzip2 decompression"),
+            #[cfg(feature = "internal-decompression")]
+            Decompressor::InternalGzip => write!(f, "internal gzip decompression"),
+            #[cfg(feature = "internal-decompression")]
+            Decompressor::InternalZstd => write!(f, "internal zstd decompression"),
+        }
+    }
+}
+
+impl Decompressor {
+    /// Whether this is lbzip2 or pbzip2, which decompress .bz2 files with multiple threads.
+    pub(crate) fn is_parallel_bzip2(&self) -> bool {
+        match self {
+            Decompressor::External { binary, .. } => binary
+                .file_stem()
+                .is_some_and(|stem| stem == "lbzip2" || stem == "pbzip2"),
+            #[cfg(feature = "internal-decompression")]
+            _ => false,
+        }
+    }
+
+    pub fn open(&self, file: &str) -> Result<DecompressingReader> {
+        match self {
+            Decompressor::External {
+                binary,
+                options,
+                feed_stdin,
+            } => {
+                let mut command = Command::new(binary);
+                command.args(options);
+                // necessary on Windows otherwise terminal colors are messed up with MSYS binaries (even /bin/false)
+                command.stderr(Stdio::piped()).stdin(Stdio::piped());
+                // opened before starting the decompressor so a missing file is reported as such
+                let input = if *feed_stdin {
+                    Some(CountingReader::new(File::open(file)?))
+                } else {
+                    command.arg(native_path(file).as_ref());
+                    None
+                };
+
+                let mut child = command
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .map_err(Error::SubCommandCouldNotBeStarted)?;
+                let stdout = child.stdout.take().unwrap(); // UNWRAP: we have stdout bcs of command config
+                let compressed_bytes_read = input.as_ref().map(CountingReader::counter);
+                let feeder = input.map(|mut input| {
+                    let mut stdin = child.stdin.take().unwrap(); // UNWRAP: we have stdin bcs of command config
+                                                                 // stdin is closed when the thread finishes, which ends the input of the decompressor
+                    thread::spawn(move || std::io::copy(&mut input, &mut stdin))
+                });
+                Ok(DecompressingReader {
+                    reader: Box::new(stdout),
+                    child: Some(child),
+                    feeder,
+                    compressed_bytes_read,
+                    bzip2_streams: None,
+                })
+            }
+            #[cfg(feature = "internal-decompression")]
+            Decompressor::InternalBzip2 => {
+                let mut bzip2_streams = None;
+                let mut reader = DecompressingReader::internal(file, |file| {
+                    let decoder = Bzip2StreamsDecoder::new(std::io::BufReader::new(file));
+                    bzip2_streams = Some(decoder.streams());
+                    Ok(decoder)
+                })?;
+                reader.bzip2_streams = bzip2_streams;
+                Ok(reader)
+            }
+            #[cfg(feature = "internal-decompression")]
+            Decompressor::InternalGzip => {
+                DecompressingReader::internal(file, |file| Ok(flate2::read::MultiGzDecoder::new(file)))
+            }
+            #[cfg(feature = "internal-decompression")]
+            Decompressor::InternalZstd => DecompressingReader::internal(file, zstd::stream::read::Decoder::new),
+        }
+    }
+}
+
+impl Read for DecompressingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl DecompressingReader {
+    /// Returns the counter of compressed bytes read so far, unless the file name was passed to an external
+    /// decompressor.
+    pub fn compressed_bytes_read(&self) -> Option<Arc<AtomicU64>> {
+        self.compressed_bytes_read.clone()
+    }
+
+    /// Returns the bzip2 streams started so far if a .bz2 file is decompressed in-process.
+    pub fn bzip2_streams(&self) -> Option<Vec<Bzip2Stream>> {
+        Some(self.bzip2_streams.as_ref()?.lock().unwrap().clone())
+    }
+
+    /// Stops reading before the end, an external decompressor is killed.
+    pub fn abort(self) {
+        drop(self.reader);
+        if let Some(mut child) = self.child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        if let Some(feeder) = self.feeder {
+            // fails writing to the killed decompressor
+            let _ = feeder.join();
+        }
+    }
+
+    /// Waits for the external decompressor to exit and checks if it was successful.
+    pub fn finish(self) -> Result<()> {
+        drop(self.reader);
+        if let Some(child) = self.child {
+            let res = child.wait_with_output()?; // needed since stderr is piped
+            if !res.status.success() {
+                return Err(Error::SubCommandTerminatedUnsuccessfully(
+                    res.status,
+                    from_utf8(res.stderr.as_ref())?.to_owned(),
+                ));
+            }
+        }
+        if let Some(feeder) = self.feeder {
+            // UNWRAP: the feeder thread does not panic
+            feeder.join().unwrap()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_argument() {
+        // passed as a single argument without any quoting
+        let file = r"/srv/dumps dir/\server\dumps\enwiki-20240101-pages-articles.xml.7z";
+        let decompressor = Decompressor::External {
+            binary: PathBuf::from("echo"),
+            options: Vec::new(),
+            feed_stdin: false,
+        };
+        let mut reader = decompressor.open(file).unwrap();
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        reader.finish().unwrap();
+        assert_eq!(output, format!("{file}\n"));
+    }
+}
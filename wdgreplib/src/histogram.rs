@@ -0,0 +1,92 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Histograms of the matching revisions by the size of their text or by namespace, to decide whether later searches
+//! can be restricted to some namespaces or skip large pages.
+
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::thread;
+
+use crate::{parse_number_in_tag, Result};
+
+/// Lower limits of the text size buckets after the first one.
+const TEXT_SIZE_BUCKET_LIMITS: [u64; 6] = [1 << 10, 4 << 10, 16 << 10, 64 << 10, 256 << 10, 1 << 20];
+const TEXT_SIZE_BUCKET_LABELS: [&str; 7] = [
+    "< 1 KiB",
+    "1-4 KiB",
+    "4-16 KiB",
+    "16-64 KiB",
+    "64-256 KiB",
+    "256 KiB-1 MiB",
+    ">= 1 MiB",
+];
+
+/// What the matching revisions are bucketed by.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HistogramKey {
+    TextSize,
+    Namespace,
+}
+
+/// Counts the matching revisions per bucket while searching, see `SearchOptions::with_match_histogram()`.
+pub struct MatchHistogram {
+    key: HistogramKey,
+    /// Counts per bucket, one map per worker thread so that the workers don't wait for each other
+    worker_counts: Vec<Mutex<BTreeMap<i64, u64>>>,
+}
+
+impl MatchHistogram {
+    pub fn new(key: HistogramKey) -> MatchHistogram {
+        // the last map is used by threads outside of the thread pools
+        let slots = thread::available_parallelism().map_or(1, NonZeroUsize::get) + 1;
+        MatchHistogram {
+            key,
+            worker_counts: (0..slots).map(|_| Mutex::new(BTreeMap::new())).collect(),
+        }
+    }
+
+    pub fn key(&self) -> HistogramKey {
+        self.key
+    }
+
+    pub(crate) fn add(&self, namespace: &str, text_len: u64) -> Result<()> {
+        let bucket = match self.key {
+            HistogramKey::TextSize => TEXT_SIZE_BUCKET_LIMITS
+                .iter()
+                .take_while(|&&limit| text_len >= limit)
+                .count() as i64,
+            HistogramKey::Namespace => parse_number_in_tag("ns", namespace)?,
+        };
+        let worker_slots = self.worker_counts.len() - 1;
+        let slot = rayon::current_thread_index().map_or(worker_slots, |index| index % worker_slots);
+        *self.worker_counts[slot].lock().unwrap().entry(bucket).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// Returns the label and the number of matching revisions of each bucket. All text size buckets are returned in
+    /// ascending order, namespaces only if revisions matched in them.
+    pub fn buckets(&self) -> Vec<(String, u64)> {
+        let mut counts = BTreeMap::new();
+        for worker_counts in &self.worker_counts {
+            for (&bucket, &count) in worker_counts.lock().unwrap().iter() {
+                *counts.entry(bucket).or_insert(0) += count;
+            }
+        }
+        match self.key {
+            HistogramKey::TextSize => TEXT_SIZE_BUCKET_LABELS
+                .iter()
+                .enumerate()
+                .map(|(bucket, label)| ((*label).to_owned(), counts.get(&(bucket as i64)).copied().unwrap_or(0)))
+                .collect(),
+            HistogramKey::Namespace => counts
+                .into_iter()
+                .map(|(namespace, count)| (namespace.to_string(), count))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,355 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Page indexes in the format Wikimedia publishes alongside multistream dumps: one `offset:page_id:title` line per
+//! page. For multistream dumps the offset is the one of the bzip2 stream containing the page, for seekable zstd dumps
+//! (see `wdump recompress`) the one of the zstd frame containing it and for uncompressed dumps the one of the `<page>`
+//! line.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use bzip2::read::{BzDecoder, MultiBzDecoder};
+use bzip2::write::BzEncoder;
+use bzip2::{Compression, Decompress, Status};
+use simdutf8::basic::from_utf8;
+use zstd::stream::raw::{InBuffer, Operation, OutBuffer};
+
+use crate::trigram::TRIGRAM_INDEX_SUFFIX;
+use crate::{Error, Result};
+
+/// Returns the name of the index file belonging to a dump file, e.g.
+/// `enwiki-20240101-pages-articles-multistream-index.txt.bz2` for
+/// `enwiki-20240101-pages-articles-multistream.xml.bz2`. Seekable zstd dumps list frame offsets instead of bzip2 stream
+/// offsets, so their index is named differently, e.g. `enwiki-20240101-pages-articles-zst-index.txt.bz2` for
+/// `enwiki-20240101-pages-articles.xml.zst`, and does not overwrite the index of the dump they were recompressed from.
+pub fn get_index_file_name(dump_file: &str) -> String {
+    if let Some(stem) = dump_file.strip_suffix(".xml.bz2") {
+        format!("{stem}-index.txt.bz2")
+    } else if let Some(stem) = dump_file.strip_suffix(".xml.zst") {
+        format!("{stem}-zst-index.txt.bz2")
+    } else {
+        format!("{}-index.txt", dump_file.strip_suffix(".xml").unwrap_or(dump_file))
+    }
+}
+
+pub fn is_index_file(file: &str) -> bool {
+    file.ends_with("-index.txt") || file.ends_with("-index.txt.bz2") || file.ends_with(TRIGRAM_INDEX_SUFFIX)
+}
+
+/// Only uncompressed, bzip2-compressed and zstd-compressed dumps can be read starting from an indexed offset.
+pub fn is_indexable(dump_file: &str) -> bool {
+    dump_file.ends_with(".xml") || dump_file.ends_with(".xml.bz2") || dump_file.ends_with(".xml.zst")
+}
+
+/// Collects the index entries from the dump lines fed to it.
+struct PageIndexer<W: Write> {
+    index: W,
+    page_offset: Option<u64>,
+    title: Option<Vec<u8>>,
+    page_count: u64,
+}
+
+impl<W: Write> PageIndexer<W> {
+    fn new(index: W) -> Self {
+        PageIndexer {
+            index,
+            page_offset: None,
+            title: None,
+            page_count: 0,
+        }
+    }
+
+    fn process_line(&mut self, line: &[u8], offset: u64) -> Result<()> {
+        let trimmed_line = line.trim_ascii();
+        if trimmed_line == b"<page>" {
+            self.page_offset = Some(offset);
+            self.title = None;
+        } else if let Some(page_offset) = self.page_offset {
+            if let Some(escaped_title) = trimmed_line
+                .strip_prefix(b"<title>")
+                .and_then(|t| t.strip_suffix(b"</title>"))
+            {
+                let title = quick_xml::escape::unescape(escaped_title).map_err(quick_xml::Error::EscapeError)?;
+                self.title = Some(title.into_owned());
+            } else if let Some(page_id) = trimmed_line
+                .strip_prefix(b"<id>")
+                .and_then(|t| t.strip_suffix(b"</id>"))
+            {
+                // the first id in a page is the page id, the following ones belong to revisions and contributors
+                let title = self
+                    .title
+                    .take()
+                    .ok_or_else(|| Error::UnexpectedEmptyTag("title".to_owned()))?;
+                write!(self.index, "{page_offset}:{}:", from_utf8(page_id)?)?;
+                self.index.write_all(&title)?;
+                self.index.write_all(b"\n")?;
+                self.page_offset = None;
+                self.page_count += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Creates the index for a dump and returns the number of pages indexed. The index is bzip2-compressed if the
+/// index file name ends with `.bz2`.
+pub fn make_index(dump_file: &str, index_file: &str) -> Result<u64> {
+    // an existing index may be the one published with the dump
+    let index_writer = match File::options().write(true).create_new(true).open(index_file) {
+        Ok(file) => BufWriter::new(file),
+        Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+            return Err(Error::IndexFileExists(index_file.to_owned()))
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if index_file.ends_with(".bz2") {
+        let mut encoder = BzEncoder::new(index_writer, Compression::best());
+        let page_count = make_index_with_writer(dump_file, &mut encoder)?;
+        encoder.finish()?.flush()?;
+        Ok(page_count)
+    } else {
+        let mut index_writer = index_writer;
+        let page_count = make_index_with_writer(dump_file, &mut index_writer)?;
+        index_writer.flush()?;
+        Ok(page_count)
+    }
+}
+
+fn make_index_with_writer<W: Write>(dump_file: &str, index: W) -> Result<u64> {
+    let mut indexer = PageIndexer::new(index);
+    let mut reader = BufReader::with_capacity(2 * 1024 * 1024, File::open(dump_file)?);
+    if dump_file.ends_with(".bz2") {
+        index_multistream_dump(&mut reader, &mut indexer)?;
+    } else if dump_file.ends_with(".zst") {
+        index_seekable_zstd_dump(&mut reader, &mut indexer)?;
+    } else {
+        let mut line: Vec<u8> = Vec::with_capacity(1024);
+        let mut offset = 0;
+        loop {
+            line.clear();
+            let len = reader.read_until(b'\n', &mut line)?;
+            if len == 0 {
+                break;
+            }
+            indexer.process_line(&line, offset)?;
+            offset += len as u64;
+        }
+    }
+    Ok(indexer.page_count)
+}
+
+// Each bzip2 stream is decompressed separately to learn where it ends in the compressed file.
+fn index_multistream_dump<R: BufRead, W: Write>(reader: &mut R, indexer: &mut PageIndexer<W>) -> Result<()> {
+    let mut decompress = Decompress::new(false);
+    let mut stream_offset = 0;
+    let mut output_buf = vec![0; 2 * 1024 * 1024];
+    let mut pending: Vec<u8> = Vec::with_capacity(4 * 1024 * 1024);
+    loop {
+        let input = reader.fill_buf()?;
+        if input.is_empty() {
+            if decompress.total_in() > 0 {
+                return Err(Error::TruncatedBzip2Stream());
+            }
+            break;
+        }
+        let total_in_before = decompress.total_in();
+        let total_out_before = decompress.total_out();
+        let status = decompress.decompress(input, &mut output_buf)?;
+        let consumed = (decompress.total_in() - total_in_before) as usize;
+        let produced = (decompress.total_out() - total_out_before) as usize;
+        reader.consume(consumed);
+        pending.extend_from_slice(&output_buf[..produced]);
+
+        // streams of multistream dumps always end with a complete line
+        let complete_lines_len = match status {
+            Status::StreamEnd => pending.len(),
+            _ => pending.iter().rposition(|&b| b == b'\n').map_or(0, |pos| pos + 1),
+        };
+        for line in pending[..complete_lines_len].split_inclusive(|&b| b == b'\n') {
+            indexer.process_line(line, stream_offset)?;
+        }
+        pending.drain(..complete_lines_len);
+
+        if status == Status::StreamEnd {
+            stream_offset += decompress.total_in();
+            decompress = Decompress::new(false);
+        }
+    }
+    Ok(())
+}
+
+// Each zstd frame is decompressed separately to learn where it ends in the compressed file, pages must not span
+// frames.
+fn index_seekable_zstd_dump<R: BufRead, W: Write>(reader: &mut R, indexer: &mut PageIndexer<W>) -> Result<()> {
+    let mut decoder = zstd::stream::raw::Decoder::new()?;
+    let mut frame_offset = 0;
+    let mut total_in = 0;
+    let mut in_frame = false;
+    let mut output_buf = vec![0; 2 * 1024 * 1024];
+    let mut pending: Vec<u8> = Vec::with_capacity(4 * 1024 * 1024);
+    loop {
+        let input = reader.fill_buf()?;
+        if input.is_empty() {
+            if in_frame {
+                return Err(Error::TruncatedZstdFrame());
+            }
+            break;
+        }
+        let mut in_buffer = InBuffer::around(input);
+        let mut out_buffer = OutBuffer::around(output_buf.as_mut_slice());
+        // the decoder stops at the end of each frame, a hint of 0 means the frame is complete
+        let hint = decoder.run(&mut in_buffer, &mut out_buffer)?;
+        let consumed = in_buffer.pos();
+        let produced = out_buffer.pos();
+        reader.consume(consumed);
+        total_in += consumed as u64;
+        pending.extend_from_slice(&output_buf[..produced]);
+        in_frame = hint != 0;
+
+        let complete_lines_len = if in_frame {
+            pending.iter().rposition(|&b| b == b'\n').map_or(0, |pos| pos + 1)
+        } else {
+            pending.len()
+        };
+        for line in pending[..complete_lines_len].split_inclusive(|&b| b == b'\n') {
+            indexer.process_line(line, frame_offset)?;
+        }
+        pending.drain(..complete_lines_len);
+
+        if !in_frame {
+            frame_offset = total_in;
+        }
+    }
+    Ok(())
+}
+
+fn open_index(index_file: &str) -> Result<BufReader<Box<dyn Read>>> {
+    let file = File::open(index_file)?;
+    let reader: Box<dyn Read> = if index_file.ends_with(".bz2") {
+        Box::new(MultiBzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+    Ok(BufReader::with_capacity(1024 * 1024, reader))
+}
+
+/// Splits an index line into offset, page id and title.
+fn parse_index_line(line: &str) -> Result<(u64, u64, &str)> {
+    let entry = line.trim_end_matches(['\n', '\r']);
+    // titles may contain colons themselves
+    let mut parts = entry.splitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(offset), Some(page_id), Some(title)) => match (offset.parse(), page_id.parse()) {
+            (Ok(offset), Ok(page_id)) => Ok((offset, page_id, title)),
+            _ => Err(Error::InvalidIndexLine(entry.to_owned())),
+        },
+        _ => Err(Error::InvalidIndexLine(entry.to_owned())),
+    }
+}
+
+/// Looks up a page by its title and returns its offset and page id.
+pub fn find_in_index(index_file: &str, title: &str) -> Result<Option<(u64, u64)>> {
+    let mut reader = open_index(index_file)?;
+    let mut line = String::with_capacity(256);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let (offset, page_id, entry_title) = parse_index_line(&line)?;
+        if entry_title == title {
+            return Ok(Some((offset, page_id)));
+        }
+    }
+}
+
+/// Reads the whole index into memory and returns the offset of each title.
+pub fn load_index(index_file: &str) -> Result<HashMap<String, u64>> {
+    let mut reader = open_index(index_file)?;
+    let mut offsets = HashMap::new();
+    let mut line = String::with_capacity(256);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(offsets);
+        }
+        let (offset, _, title) = parse_index_line(&line)?;
+        offsets.insert(title.to_owned(), offset);
+    }
+}
+
+/// Returns the distinct offsets in the index in ascending order, i.e. those of the pages of uncompressed dumps or of
+/// the bzip2 streams of multistream dumps.
+pub fn load_offsets(index_file: &str) -> Result<Vec<u64>> {
+    let mut reader = open_index(index_file)?;
+    let mut offsets = Vec::new();
+    let mut line = String::with_capacity(256);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let (offset, _, _) = parse_index_line(&line)?;
+        if offsets.last() != Some(&offset) {
+            offsets.push(offset);
+        }
+    }
+    offsets.sort_unstable();
+    offsets.dedup();
+    Ok(offsets)
+}
+
+/// Returns the ranges of the dump containing the pages for which `is_match` returns true given their page id and
+/// title, sorted by offset. A range starts at the offset of a matching page and ends at the next different offset in
+/// the index. For multistream dumps this is the bzip2 stream containing the page.
+pub fn find_matching_ranges(index_file: &str, is_match: impl Fn(u64, &str) -> bool) -> Result<Vec<(u64, u64)>> {
+    let mut reader = open_index(index_file)?;
+    let mut ranges = Vec::new();
+    let mut range_start = None;
+    let mut line = String::with_capacity(256);
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let (offset, page_id, title) = parse_index_line(&line)?;
+        if let Some(start) = range_start {
+            if offset != start {
+                ranges.push((start, offset));
+                range_start = None;
+            }
+        }
+        if range_start.is_none() && is_match(page_id, title) {
+            range_start = Some(offset);
+        }
+    }
+    if let Some(start) = range_start {
+        ranges.push((start, u64::MAX));
+    }
+    ranges.sort_unstable();
+    Ok(ranges)
+}
+
+/// Returns a reader starting at the given offset of an indexed dump. For multistream and seekable zstd dumps only the
+/// bzip2 stream or zstd frame at that offset is decompressed.
+pub fn open_dump_at_offset(dump_file: &str, offset: u64) -> Result<Box<dyn Read>> {
+    let mut file = File::open(dump_file)?;
+    file.seek(SeekFrom::Start(offset))?;
+    if dump_file.ends_with(".bz2") {
+        Ok(Box::new(BzDecoder::new(file)))
+    } else if dump_file.ends_with(".zst") {
+        Ok(Box::new(zstd::stream::read::Decoder::new(file)?.single_frame()))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+pub fn index_file_exists(dump_file: &str) -> bool {
+    Path::new(&get_index_file_name(dump_file)).is_file()
+}
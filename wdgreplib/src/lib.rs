@@ -0,0 +1,3503 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Searching MediaWiki XML dumps and CirrusSearch dumps with regular expressions.
+//!
+//! This crate is the single implementation of reading and searching dumps used by the wdgrep and wdump binaries,
+//! which only parse their arguments and print the results. The public API consists of:
+//!
+//! - [`search_dump()`] and [`search_dump_streams()`] configured with [`SearchOptions`], and [`plan_search()`]
+//!   returning how a search would be run,
+//! - [`process_dump()`] and [`process_dump_streams()`] for custom [`RevisionProcessor`]s,
+//! - [`get_dump_files()`], [`open_dump_file()`] and [`extract_page()`] for finding and reading dump files,
+//! - the collectors filled while searching and passed by reference to [`SearchOptions`]: [`MatchHistogram`],
+//!   [`ResultRecorder`], [`Sha1Verifier`] and [`SearchProgressTracker`] implementing [`Progress`],
+//! - [`Namespaces`], [`Title`] and [`PageList`] for selecting pages, [`expand_shortcuts()`] and [`check_pattern()`]
+//!   for patterns.
+//!
+//! It follows semantic versioning: [`Error`], [`SearchDumpResult`] and [`DeletedRevisionCounts`] are
+//! non-exhaustive, so error variants and result fields can be added in minor versions. The modules [`index`],
+//! [`siteinfo`] and [`trigram`] are shared with the wikidumptools binaries and may change with them.
+
+mod budget;
+mod bzip2_streams;
+mod checkpoint;
+mod decompressor;
+mod histogram;
+pub mod index;
+mod match_counts;
+mod matcher;
+mod output;
+mod page_list;
+mod path;
+mod plaintext;
+mod plan;
+mod progress;
+mod result_set;
+mod revision_sha1;
+mod seekable;
+mod shortcuts;
+pub mod siteinfo;
+mod title;
+pub mod trigram;
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{metadata, File};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::num::NonZeroUsize;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{fs, thread};
+
+use memchr::{memchr, memchr_iter, memmem, memrchr};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use serde::{Deserialize, Serialize};
+use simdutf8::basic::from_utf8;
+use termcolor::{Buffer, BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
+
+use crate::budget::{MemoryBudget, MIN_RESERVED_SIZE};
+pub use crate::bzip2_streams::Bzip2Stream;
+pub use crate::checkpoint::SearchCheckpoint;
+pub use crate::decompressor::DecompressingReader;
+use crate::decompressor::{decompress_stream, find_decompressor, CompressionFormat, CountingReader, Decompressor};
+pub use crate::histogram::{HistogramKey, MatchHistogram};
+use crate::index::{
+    find_in_index, find_matching_ranges, get_index_file_name, index_file_exists, is_index_file, is_indexable,
+    open_dump_at_offset,
+};
+pub use crate::match_counts::MatchCounts;
+pub use crate::matcher::{check_pattern, Engine, PatternWarning};
+use crate::matcher::{get_required_literal, Matcher};
+pub use crate::output::OutputLimit;
+use crate::output::{OutputCoordinator, PartOutput};
+pub use crate::page_list::PageList;
+use crate::path::native_path;
+use crate::plaintext::strip_markup;
+pub use crate::plan::{plan_search, FilePlan, FileSplit, SearchPlan};
+pub use crate::progress::{Progress, SearchProgressTracker};
+pub use crate::result_set::{ResultRecorder, ResultSet};
+use crate::revision_sha1::TextHasher;
+pub use crate::revision_sha1::{Sha1Mismatch, Sha1Verifier};
+use crate::seekable::{is_seekable_zstd_dump, open_frame_range, split_into_frame_ranges};
+pub use crate::shortcuts::expand_shortcuts;
+pub use crate::title::{Namespace, Namespaces, Title};
+use crate::trigram::{find_candidate_ranges, get_trigram_index_file_name, trigram_index_exists};
+
+macro_rules! buffer_write {
+    ($dst:expr, $($arg:tt)*) => (
+        write!($dst, $($arg)*).unwrap();
+    )
+}
+
+macro_rules! buffer_writeln {
+    ($dst:expr, $($arg:tt)*) => (
+        writeln!($dst, $($arg)*).unwrap();
+    )
+}
+
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("I/O error {0}")]
+    Io(#[from] std::io::Error),
+    #[error("UTF8 format error: {0}")]
+    StdUtf8(#[from] std::str::Utf8Error),
+    #[error("XML format error: {0}")]
+    Utf8(#[from] simdutf8::basic::Utf8Error),
+    #[error("XML format error: {0}")]
+    Xml(quick_xml::Error),
+    #[error("Regex error: {0}")]
+    Regex(#[from] regex::Error),
+    #[cfg(feature = "pcre2")]
+    #[error("PCRE2 error: {0}")]
+    Pcre2(#[from] pcre2::Error),
+    #[error("Only text expected in {0}")]
+    OnlyTextExpectedInTag(String),
+    #[error("Unexpected empty tag found: {0}")]
+    UnexpectedEmptyTag(String),
+    #[error("Could not get current directory: {0}")]
+    CouldNotGetCurrentDir(std::io::Error),
+    #[error("Dump file (or prefix) is invalid")]
+    DumpFileOrPrefixInvalid(),
+    #[error("No dump files found")]
+    NoDumpFilesFound(),
+    #[error("Subcommand could not be started: {0}")]
+    SubCommandCouldNotBeStarted(std::io::Error),
+    #[error("Subcommand terminated unsuccessfully. {0} Error output: '{1}'")]
+    SubCommandTerminatedUnsuccessfully(std::process::ExitStatus, String),
+    #[error("Page {0} not found")]
+    PageNotFound(String),
+    #[error("No decompressor for {0} files found on the PATH, looked for: {1}")]
+    DecompressorNotFound(String, String),
+    #[error("Invalid number in {0}: '{1}'")]
+    InvalidNumberInTag(String, String),
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+    #[error("Full-text index error: {0}")]
+    Tantivy(#[from] tantivy::TantivyError),
+    #[error("Invalid full-text query: {0}")]
+    TantivyQuery(#[from] tantivy::query::QueryParserError),
+    #[error("JSON format error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("bzip2 format error: {0}")]
+    Bzip2(#[from] bzip2::Error),
+    #[error("Dump file ends in the middle of a bzip2 stream")]
+    TruncatedBzip2Stream(),
+    #[error("Dump file ends in the middle of a zstd frame")]
+    TruncatedZstdFrame(),
+    #[error("Invalid line in index file: '{0}'")]
+    InvalidIndexLine(String),
+    #[error("Index file {0} already exists")]
+    IndexFileExists(String),
+    #[error("Invalid line in result set file: '{0}'")]
+    InvalidResultSetLine(String),
+    #[error("Invalid line in checkpoint file: '{0}'")]
+    InvalidCheckpointLine(String),
+    #[error("Checkpoint {0} was written by a different search")]
+    CheckpointOfDifferentSearch(String),
+    #[error("Searching page {0} took longer than the time limit of {1:?}")]
+    PageTimeLimitExceeded(String, Duration),
+    #[error("Invalid trigram index file: {0}")]
+    InvalidTrigramIndex(String),
+    #[error("No siteinfo found at the start of the dump")]
+    SiteInfoNotFound(),
+    #[error("{0} files cannot be searched while streaming")]
+    StreamDecompressionNotSupported(String),
+    #[error("Pattern is not a list of plain literals: '{0}'")]
+    PatternNotLiteral(String),
+    #[error("Unclosed pattern shortcut: '{0}'")]
+    UnclosedPatternShortcut(String),
+    #[error("Replacements can only be used with the regex engine")]
+    CapturesNeedRegexEngine(),
+}
+
+// unnest some XML parsing errors
+impl From<quick_xml::Error> for Error {
+    #[inline]
+    fn from(error: quick_xml::Error) -> Self {
+        match error {
+            quick_xml::Error::Utf8(e) => Self::StdUtf8(e),
+            quick_xml::Error::Io(e) => Self::Io(e),
+            error => Self::Xml(error),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[inline(always)]
+fn read_str_and_then<T: BufRead, ResT, F>(
+    reader: &mut Reader<T>,
+    buf: &mut Vec<u8>,
+    tag: &str,
+    mut f: F,
+) -> Result<ResT>
+where
+    F: FnMut(&str) -> Result<ResT>,
+{
+    if let Event::Text(escaped_text) = reader.read_event(buf)? {
+        let unescaped_text = escaped_text.unescaped()?;
+        let text = from_utf8(&unescaped_text)?;
+        f(text)
+    } else {
+        Err(Error::OnlyTextExpectedInTag(tag.to_owned()))
+    }
+}
+
+#[inline(always)]
+fn read_bytes_and_then<T: BufRead, ResT, F>(
+    reader: &mut Reader<T>,
+    buf: &mut Vec<u8>,
+    tag: &str,
+    mut f: F,
+) -> Result<ResT>
+where
+    F: FnMut(&[u8]) -> Result<ResT>,
+{
+    if let Event::Text(escaped_text) = reader.read_event(buf)? {
+        let unescaped_text = escaped_text.unescaped()?;
+        f(&unescaped_text)
+    } else {
+        Err(Error::OnlyTextExpectedInTag(tag.to_owned()))
+    }
+}
+
+#[inline(always)]
+fn read_escaped_bytes_and_then<T: BufRead, ResT, F>(
+    reader: &mut Reader<T>,
+    buf: &mut Vec<u8>,
+    tag: &str,
+    mut f: F,
+) -> Result<ResT>
+where
+    F: FnMut(&[u8]) -> Result<ResT>,
+{
+    if let Event::Text(escaped_text) = reader.read_event(buf)? {
+        f(&escaped_text)
+    } else {
+        Err(Error::OnlyTextExpectedInTag(tag.to_owned()))
+    }
+}
+
+enum SkipToStartTagOrEofResult {
+    /// With the length of the start tag in bytes
+    StartTagFound(u64),
+    Eof,
+}
+
+#[inline(always)]
+fn skip_to_start_tag_or_eof<T: BufRead>(
+    reader: &mut Reader<T>,
+    buf: &mut Vec<u8>,
+    tag_name: &[u8],
+) -> Result<SkipToStartTagOrEofResult> {
+    loop {
+        match reader.read_event(buf)? {
+            Event::Start(ref e) if e.local_name() == tag_name => {
+                // the event does not include the angle brackets
+                return Ok(SkipToStartTagOrEofResult::StartTagFound(e.len() as u64 + 2));
+            }
+            Event::Empty(ref e) if e.local_name() == tag_name => {
+                return Err(Error::UnexpectedEmptyTag(from_utf8(tag_name)?.to_owned()));
+            }
+            Event::Eof => {
+                return Ok(SkipToStartTagOrEofResult::Eof);
+            }
+            _other_event => {}
+        }
+        buf.clear();
+    }
+}
+
+#[inline(always)]
+fn skip_to_start_tag<T: BufRead>(reader: &mut Reader<T>, buf: &mut Vec<u8>, tag_name: &[u8]) -> Result<()> {
+    if let SkipToStartTagOrEofResult::Eof = skip_to_start_tag_or_eof(reader, buf, tag_name)? {
+        return Err(Error::Xml(quick_xml::Error::UnexpectedEof(
+            from_utf8(tag_name)?.to_owned(),
+        )));
+    }
+    Ok(())
+}
+
+/// Skips to the end tag matching an already read start tag. Unlike `Reader::read_to_end()` the tags are matched by
+/// their local name, so that dumps whose elements have a namespace prefix like `<mw:text>` can be read.
+#[inline(always)]
+fn skip_to_end_tag<T: BufRead>(reader: &mut Reader<T>, buf: &mut Vec<u8>, tag_name: &[u8]) -> Result<()> {
+    let mut depth = 0;
+    loop {
+        match reader.read_event(buf)? {
+            Event::Start(ref e) if e.local_name() == tag_name => depth += 1,
+            Event::End(ref e) if e.local_name() == tag_name => {
+                if depth == 0 {
+                    return Ok(());
+                }
+                depth -= 1;
+            }
+            Event::Eof => {
+                return Err(Error::Xml(quick_xml::Error::UnexpectedEof(
+                    from_utf8(tag_name)?.to_owned(),
+                )))
+            }
+            _other_event => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Elements of a revision preceding its text.
+struct RevisionHeader {
+    model: String,
+    format: String,
+    minor: bool,
+    contributor_id: String,
+    contributor_ip: String,
+    contributor_deleted: bool,
+    /// Only read if `read_comment` is set
+    comment: String,
+    read_comment: bool,
+}
+
+impl RevisionHeader {
+    fn new() -> RevisionHeader {
+        RevisionHeader {
+            model: String::with_capacity(50),
+            format: String::with_capacity(50),
+            minor: false,
+            contributor_id: String::with_capacity(50),
+            contributor_ip: String::with_capacity(50),
+            contributor_deleted: false,
+            comment: String::with_capacity(200),
+            read_comment: false,
+        }
+    }
+
+    fn clear(&mut self) {
+        // dumps from before content models were introduced only contain wikitext
+        self.model.clear();
+        self.model.push_str("wikitext");
+        self.format.clear();
+        self.format.push_str("text/x-wiki");
+        self.minor = false;
+        self.contributor_id.clear();
+        self.contributor_ip.clear();
+        self.contributor_deleted = false;
+        self.comment.clear();
+    }
+
+    fn revision_info<'a>(
+        &'a self,
+        page_id: &'a str,
+        namespace: &'a str,
+        title: &'a str,
+        revision_id: &'a str,
+        text_len: Option<u64>,
+    ) -> RevisionInfo<'a> {
+        RevisionInfo {
+            page_id,
+            namespace,
+            title,
+            revision_id,
+            model: self.model.as_str(),
+            format: self.format.as_str(),
+            minor: self.minor,
+            contributor_id: Some(self.contributor_id.as_str()).filter(|id| !id.is_empty()),
+            contributor_ip: Some(self.contributor_ip.as_str()).filter(|ip| !ip.is_empty()),
+            text_len,
+        }
+    }
+}
+
+/// Returns true for elements with a `deleted` attribute, which replace the text, contributor or comment of revisions
+/// deleted or suppressed by administrators.
+fn is_deleted(element: &BytesStart) -> bool {
+    element
+        .attributes()
+        .any(|attribute| attribute.is_ok_and(|attribute| attribute.key == b"deleted"))
+}
+
+enum RevisionText {
+    /// With the size of the escaped text in bytes if known
+    Present(Option<u64>),
+    Empty,
+    Deleted,
+}
+
+#[inline(always)]
+fn read_contributor<T: BufRead>(reader: &mut Reader<T>, buf: &mut Vec<u8>, header: &mut RevisionHeader) -> Result<()> {
+    loop {
+        match reader.read_event(buf)? {
+            Event::Start(ref e) => match e.local_name() {
+                b"id" => read_str_and_then(reader, buf, "id", |text| {
+                    header.contributor_id.push_str(text);
+                    Ok(())
+                })?,
+                b"ip" => read_str_and_then(reader, buf, "ip", |text| {
+                    header.contributor_ip.push_str(text);
+                    Ok(())
+                })?,
+                _other_tag => {}
+            },
+            Event::End(ref e) if e.local_name() == b"contributor" => return Ok(()),
+            Event::Eof => return Err(Error::Xml(quick_xml::Error::UnexpectedEof("contributor".to_owned()))),
+            _other_event => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Reads the elements of a revision up to its text and returns whether the revision has text.
+#[inline(always)]
+fn read_revision_up_to_text<T: BufRead>(
+    reader: &mut Reader<T>,
+    buf: &mut Vec<u8>,
+    header: &mut RevisionHeader,
+) -> Result<RevisionText> {
+    header.clear();
+    loop {
+        match reader.read_event(buf)? {
+            Event::Start(ref e) => match e.local_name() {
+                b"text" if is_deleted(e) => {
+                    skip_to_end_tag(reader, buf, b"text")?;
+                    return Ok(RevisionText::Deleted);
+                }
+                b"text" => {
+                    let text_len = e
+                        .attributes()
+                        .filter_map(|attribute| attribute.ok())
+                        .find(|attribute| attribute.key == b"bytes")
+                        .and_then(|attribute| from_utf8(&attribute.value).ok()?.parse().ok());
+                    return Ok(RevisionText::Present(text_len));
+                }
+                b"model" => read_str_and_then(reader, buf, "model", |text| {
+                    header.model.clear();
+                    header.model.push_str(text);
+                    Ok(())
+                })?,
+                b"format" => read_str_and_then(reader, buf, "format", |text| {
+                    header.format.clear();
+                    header.format.push_str(text);
+                    Ok(())
+                })?,
+                b"contributor" => read_contributor(reader, buf, header)?,
+                b"comment" if header.read_comment => read_str_and_then(reader, buf, "comment", |text| {
+                    header.comment.push_str(text);
+                    Ok(())
+                })?,
+                _other_tag => {}
+            },
+            Event::Empty(ref e) => match e.local_name() {
+                b"text" if is_deleted(e) => return Ok(RevisionText::Deleted),
+                b"text" => return Ok(RevisionText::Empty),
+                b"minor" => header.minor = true,
+                b"contributor" => header.contributor_deleted = is_deleted(e),
+                _other_tag => {}
+            },
+            Event::Eof => return Err(Error::Xml(quick_xml::Error::UnexpectedEof("text".to_owned()))),
+            _other_event => {}
+        }
+        buf.clear();
+    }
+}
+
+#[inline(always)]
+fn set_color(buffer: &mut Buffer, c: Color) {
+    buffer.set_color(ColorSpec::new().set_fg(Some(c))).unwrap();
+}
+
+#[inline(always)]
+fn set_plain(buffer: &mut Buffer) {
+    buffer.set_color(ColorSpec::new().set_fg(None)).unwrap();
+}
+
+const fn ceiling_div(x: u64, y: u64) -> u64 {
+    (x + y - 1) / y
+}
+
+pub struct MatchedRevision<'a> {
+    pub page_id: u64,
+    pub namespace: i32,
+    pub title: &'a str,
+    pub revision_id: u64,
+    pub match_count: usize,
+    /// Positions of the matches in the text
+    pub positions: &'a [MatchPosition],
+    pub text: &'a [u8],
+}
+
+/// Position of a match in the text of a revision.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+pub struct MatchPosition {
+    /// Line of the start of the match, starting at 1
+    pub line: usize,
+    /// Column of the start of the match in characters, starting at 1
+    pub column: usize,
+    /// Byte offset of the start of the match
+    pub start: usize,
+    /// Byte offset of the end of the match
+    pub end: usize,
+}
+
+/// Receives matching revisions instead of them being printed to stdout.
+pub trait MatchSink: Sync {
+    fn add_match(&self, matched_revision: &MatchedRevision) -> Result<()>;
+    fn finish(&self) -> Result<()>;
+}
+
+#[non_exhaustive]
+pub struct SearchDumpResult {
+    pub bytes_processed: u64,
+    pub compressed_files_found: bool,
+    /// The search was stopped at the deadline before all pages were searched.
+    pub truncated: bool,
+    /// Revisions whose text or contributor was deleted or suppressed, only counted if enabled with
+    /// `SearchOptions::count_deleted()`
+    pub deleted_revisions: DeletedRevisionCounts,
+    /// All dump files are on rotational storage (only detected on Linux), so by default only one uncompressed file per
+    /// disk is read at a time.
+    pub rotational_storage: bool,
+    /// Dump files which could not be searched completely with their errors, only set if enabled with
+    /// `SearchOptions::keep_going()`
+    pub failed_files: Vec<(String, Error)>,
+    /// Start of each bzip2 stream of the .bz2 files which were decompressed in-process and read completely, only
+    /// recorded by `process_dump()`
+    pub bzip2_streams: Vec<(String, Vec<Bzip2Stream>)>,
+    /// Numbers of searched and matching pages and revisions, only counted by `search_dump()` and
+    /// `search_dump_streams()`
+    pub match_counts: MatchCounts,
+}
+
+/// Numbers of revisions with deleted or suppressed parts. Revisions with deleted text are skipped, revisions with a
+/// suppressed contributor are searched like IP edits.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DeletedRevisionCounts {
+    pub deleted_texts: u64,
+    pub deleted_contributors: u64,
+}
+
+impl DeletedRevisionCounts {
+    fn add(&mut self, other: DeletedRevisionCounts) {
+        self.deleted_texts += other.deleted_texts;
+        self.deleted_contributors += other.deleted_contributors;
+    }
+
+    const fn is_empty(&self) -> bool {
+        self.deleted_texts == 0 && self.deleted_contributors == 0
+    }
+}
+
+/// Progress of reading a compressed dump file or stream, sent after every 16 MiB of decompressed data and once the
+/// end has been reached. The number of compressed bytes read is unknown if the file name is passed to an external
+/// decompressor (7z or a user-supplied binary).
+#[derive(Debug)]
+pub enum SearchProgress {
+    /// File name, compressed bytes read so far, decompressed bytes read so far
+    BytesRead(String, Option<u64>, u64),
+    /// File name, compressed size, decompressed size
+    Finished(String, Option<u64>, u64),
+}
+
+const PROGRESS_INTERVAL: u64 = 16 * 1024 * 1024;
+
+/// Called with the progress by the worker threads, see `SearchOptions::with_progress_callback()`.
+pub type ProgressCallback = dyn Fn(&SearchProgress) + Sync;
+
+/// Counts the decompressed bytes read and sends the progress.
+struct ProgressReader<'a, R> {
+    reader: R,
+    file_name: &'a str,
+    compressed_bytes_read: Option<Arc<AtomicU64>>,
+    bytes_read: u64,
+    next_report: u64,
+    progress_send: Option<&'a Sender<SearchProgress>>,
+    progress_callback: Option<&'a ProgressCallback>,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    fn new(
+        reader: R,
+        file_name: &'a str,
+        compressed_bytes_read: Option<Arc<AtomicU64>>,
+        search_options: &SearchOptions<'a>,
+    ) -> ProgressReader<'a, R> {
+        ProgressReader {
+            reader,
+            file_name,
+            compressed_bytes_read,
+            bytes_read: 0,
+            next_report: PROGRESS_INTERVAL,
+            progress_send: search_options.progress_send,
+            progress_callback: search_options.progress_callback,
+        }
+    }
+
+    fn send(&self, progress: fn(String, Option<u64>, u64) -> SearchProgress) {
+        if self.progress_send.is_none() && self.progress_callback.is_none() {
+            return;
+        }
+        let compressed_bytes_read = self
+            .compressed_bytes_read
+            .as_ref()
+            .map(|count| count.load(Ordering::Relaxed));
+        let progress = progress(self.file_name.to_owned(), compressed_bytes_read, self.bytes_read);
+        if let Some(progress_callback) = self.progress_callback {
+            progress_callback(&progress);
+        }
+        if let Some(progress_send) = self.progress_send {
+            // the receiver may stop listening at any time
+            let _ = progress_send.send(progress);
+        }
+    }
+
+    /// Sends the final progress after the end has been reached.
+    fn finish(&self) {
+        self.send(SearchProgress::Finished);
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.bytes_read += len as u64;
+        if self.bytes_read >= self.next_report {
+            self.next_report = self.bytes_read + PROGRESS_INTERVAL;
+            self.send(SearchProgress::BytesRead);
+        }
+        Ok(len)
+    }
+}
+
+/// The part of the revisions of XML dumps to search.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SearchField {
+    Text,
+    /// The edit summary, matching revisions are output as if the comment was their text
+    Comment,
+}
+
+/// The field of CirrusSearch dump documents to search.
+#[derive(Clone, Copy)]
+pub enum CirrusSearchField {
+    /// Rendered plain text
+    Text,
+    /// Wikitext source
+    SourceText,
+}
+
+/// What is done with a page which takes longer to search than the limit set with
+/// `SearchOptions::with_page_time_limit()`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PageTimeLimitAction {
+    /// The remaining revisions of the page are skipped and the page is reported on stderr
+    Skip,
+    /// The search fails with `Error::PageTimeLimitExceeded`
+    Abort,
+}
+
+pub struct SearchOptions<'a> {
+    restrict_namespaces: Option<&'a [&'a str]>,
+    only_print_title: bool,
+    thread_count: Option<NonZeroUsize>,
+    io_parallelism: Option<NonZeroUsize>,
+    chunk_size: Option<u64>,
+    chunk_size_bounds: (u64, u64),
+    binary_7z: Option<&'a str>,
+    options_7z: Option<&'a [&'a str]>,
+    binary_bzcat: Option<&'a str>,
+    options_bzcat: Option<&'a [&'a str]>,
+    color_choice: ColorChoice,
+    match_sink: Option<&'a dyn MatchSink>,
+    cirrussearch_field: CirrusSearchField,
+    search_field: SearchField,
+    deduplicate: bool,
+    keep_going: bool,
+    replacement: Option<&'a str>,
+    print_title_prefix: bool,
+    count_unique: bool,
+    restrict_models: Option<&'a [&'a str]>,
+    restrict_formats: Option<&'a [&'a str]>,
+    skip_minor: bool,
+    only_ip_edits: bool,
+    group_by_page: bool,
+    deadline: Option<Instant>,
+    page_time_limit: Option<(Duration, PageTimeLimitAction)>,
+    ordered_output: bool,
+    compress_output: bool,
+    output_limit: Option<OutputLimit>,
+    split_output_directory: Option<&'a Path>,
+    title_filter: Option<&'a regex::Regex>,
+    page_list: Option<&'a PageList>,
+    restrict_page_ids: Option<&'a HashSet<u64>>,
+    exclude_page_ids: Option<&'a HashSet<u64>>,
+    page_id_range: Option<RangeInclusive<u64>>,
+    result_recorder: Option<&'a ResultRecorder>,
+    checkpoint: Option<&'a SearchCheckpoint>,
+    match_histogram: Option<&'a MatchHistogram>,
+    sha1_verifier: Option<&'a Sha1Verifier>,
+    strip_markup: bool,
+    engine: Engine,
+    prefilter: bool,
+    progress_send: Option<&'a Sender<SearchProgress>>,
+    progress_callback: Option<&'a ProgressCallback>,
+    count_deleted: bool,
+    report_deleted: bool,
+    text_window_size: Option<usize>,
+    memory_budget: Option<MemoryBudget>,
+}
+
+impl<'a> SearchOptions<'a> {
+    #[must_use]
+    pub const fn new() -> SearchOptions<'a> {
+        SearchOptions {
+            restrict_namespaces: None,
+            only_print_title: false,
+            thread_count: None,
+            io_parallelism: None,
+            chunk_size: None,
+            chunk_size_bounds: DEFAULT_CHUNK_SIZE_BOUNDS,
+            binary_7z: None,
+            options_7z: None,
+            binary_bzcat: None,
+            options_bzcat: None,
+            color_choice: ColorChoice::Never,
+            match_sink: None,
+            cirrussearch_field: CirrusSearchField::Text,
+            search_field: SearchField::Text,
+            deduplicate: false,
+            keep_going: false,
+            replacement: None,
+            print_title_prefix: false,
+            count_unique: false,
+            restrict_models: None,
+            restrict_formats: None,
+            skip_minor: false,
+            only_ip_edits: false,
+            group_by_page: false,
+            deadline: None,
+            page_time_limit: None,
+            ordered_output: false,
+            compress_output: false,
+            output_limit: None,
+            split_output_directory: None,
+            title_filter: None,
+            page_list: None,
+            restrict_page_ids: None,
+            exclude_page_ids: None,
+            page_id_range: None,
+            result_recorder: None,
+            checkpoint: None,
+            match_histogram: None,
+            sha1_verifier: None,
+            strip_markup: false,
+            engine: Engine::Auto,
+            prefilter: true,
+            progress_send: None,
+            progress_callback: None,
+            count_deleted: false,
+            report_deleted: false,
+            text_window_size: None,
+            memory_budget: None,
+        }
+    }
+    pub fn restrict_namespaces(&mut self, restrict_namespaces: &'a [&'a str]) -> &mut SearchOptions<'a> {
+        self.restrict_namespaces = Some(restrict_namespaces);
+        self
+    }
+    pub fn only_print_title(&mut self, only_print_title: bool) -> &mut SearchOptions<'a> {
+        self.only_print_title = only_print_title;
+        self
+    }
+    pub fn with_thread_count(&mut self, thread_count: NonZeroUsize) -> &mut SearchOptions<'a> {
+        self.thread_count = Some(thread_count);
+        self
+    }
+    /// Limit the number of dump files read at once, the parts of each file are still searched by all threads.
+    /// Defaults to one uncompressed file per device if all files are on spinning disks, compressed files are
+    /// decompressed much slower than they are read.
+    pub fn with_io_parallelism(&mut self, io_parallelism: NonZeroUsize) -> &mut SearchOptions<'a> {
+        self.io_parallelism = Some(io_parallelism);
+        self
+    }
+    /// Split plain dump files into slices of this size instead of choosing the size from the total size of the files.
+    pub fn with_chunk_size(&mut self, chunk_size: u64) -> &mut SearchOptions<'a> {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+    /// Bounds of the size of the slices plain dump files are split into by default: the total size of the files
+    /// divided by the number of threads, so that small dumps are still searched by all threads while giant dumps are
+    /// not split into lots of small slices. Defaults to 16 MiB and 500 MiB, the minimum wins if it is larger.
+    pub fn with_chunk_size_bounds(&mut self, min: u64, max: u64) -> &mut SearchOptions<'a> {
+        self.chunk_size_bounds = (min, max);
+        self
+    }
+    pub fn with_binary_7z(&mut self, binary_7z: &'a str) -> &mut SearchOptions<'a> {
+        self.binary_7z = Some(binary_7z);
+        self
+    }
+    pub fn with_options_7z(&mut self, options_7z: &'a [&'a str]) -> &mut SearchOptions<'a> {
+        self.options_7z = Some(options_7z);
+        self
+    }
+    pub fn with_binary_bzcat(&mut self, binary_bzcat: &'a str) -> &mut SearchOptions<'a> {
+        self.binary_bzcat = Some(binary_bzcat);
+        self
+    }
+    pub fn with_options_bzcat(&mut self, options_bzcat: &'a [&'a str]) -> &mut SearchOptions<'a> {
+        self.options_bzcat = Some(options_bzcat);
+        self
+    }
+    pub fn with_color_choice(&mut self, color_choice: ColorChoice) -> &mut SearchOptions<'a> {
+        self.color_choice = color_choice;
+        self
+    }
+    pub fn with_match_sink(&mut self, match_sink: &'a dyn MatchSink) -> &mut SearchOptions<'a> {
+        self.match_sink = Some(match_sink);
+        self
+    }
+    pub fn with_cirrussearch_field(&mut self, cirrussearch_field: CirrusSearchField) -> &mut SearchOptions<'a> {
+        self.cirrussearch_field = cirrussearch_field;
+        self
+    }
+    /// Search the text or the comment of the revisions of XML dumps.
+    pub fn with_search_field(&mut self, search_field: SearchField) -> &mut SearchOptions<'a> {
+        self.search_field = search_field;
+        self
+    }
+    pub fn deduplicate(&mut self, deduplicate: bool) -> &mut SearchOptions<'a> {
+        self.deduplicate = deduplicate;
+        self
+    }
+    /// Print each match replaced by the template, in which capture groups can be referenced (`$1`, `${name}`).
+    pub fn with_replacement(&mut self, replacement: &'a str) -> &mut SearchOptions<'a> {
+        self.replacement = Some(replacement);
+        self
+    }
+    pub fn print_title_prefix(&mut self, print_title_prefix: bool) -> &mut SearchOptions<'a> {
+        self.print_title_prefix = print_title_prefix;
+        self
+    }
+    /// Count how often each match (or each replaced match) occurs and print a table sorted by count at the end
+    /// instead of the matches.
+    pub fn count_unique(&mut self, count_unique: bool) -> &mut SearchOptions<'a> {
+        self.count_unique = count_unique;
+        self
+    }
+    /// Only process revisions with one of these content models (e.g. `wikitext`), the text of other revisions is
+    /// skipped without being unescaped.
+    pub fn restrict_models(&mut self, restrict_models: &'a [&'a str]) -> &mut SearchOptions<'a> {
+        self.restrict_models = Some(restrict_models);
+        self
+    }
+    /// Only process revisions with one of these content formats (e.g. `text/x-wiki`), not applicable to
+    /// CirrusSearch dumps.
+    pub fn restrict_formats(&mut self, restrict_formats: &'a [&'a str]) -> &mut SearchOptions<'a> {
+        self.restrict_formats = Some(restrict_formats);
+        self
+    }
+
+    /// Skip revisions marked as minor edits.
+    pub fn skip_minor(&mut self, skip_minor: bool) -> &mut SearchOptions<'a> {
+        self.skip_minor = skip_minor;
+        self
+    }
+    /// Only process revisions made by unregistered users, CirrusSearch dumps contain no such information.
+    pub fn only_ip_edits(&mut self, only_ip_edits: bool) -> &mut SearchOptions<'a> {
+        self.only_ip_edits = only_ip_edits;
+        self
+    }
+
+    /// Print one line per page with the range and number of matching revisions instead of the matches.
+    pub fn group_by_page(&mut self, group_by_page: bool) -> &mut SearchOptions<'a> {
+        self.group_by_page = group_by_page;
+        self
+    }
+
+    /// Stop searching once the deadline has passed, pages already being searched are finished. The result is
+    /// marked as truncated then.
+    pub fn with_deadline(&mut self, deadline: Instant) -> &mut SearchOptions<'a> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Limit the time spent on a single page of an XML dump, e.g. with a pathological pattern. The limit is checked
+    /// before each revision and each text window, so a single revision text which is not read in windows (see
+    /// `with_text_window_size()`) is always searched completely.
+    pub fn with_page_time_limit(&mut self, limit: Duration, action: PageTimeLimitAction) -> &mut SearchOptions<'a> {
+        self.page_time_limit = Some((limit, action));
+        self
+    }
+
+    /// Continue with the other dump files if searching one of them fails, e.g. because its decompressor fails. The
+    /// errors are returned in `SearchDumpResult::failed_files` instead of failing the search.
+    pub fn keep_going(&mut self, keep_going: bool) -> &mut SearchOptions<'a> {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Print the output in the order of the dump files. The output of parts searched in parallel is kept in memory
+    /// until all previous parts have been printed.
+    pub fn ordered_output(&mut self, ordered_output: bool) -> &mut SearchOptions<'a> {
+        self.ordered_output = ordered_output;
+        self
+    }
+
+    /// Compress the output printed to stdout with gzip, the output is not colored. Value counts are printed
+    /// uncompressed after the compressed output.
+    pub fn compress_output(&mut self, compress_output: bool) -> &mut SearchOptions<'a> {
+        self.compress_output = compress_output;
+        self
+    }
+
+    /// Print only the first or last results, the output of each matching revision or, if the output is grouped by
+    /// page, of each page. The dump is still searched completely, so the match counts include all matches.
+    pub fn limit_output(&mut self, limit: OutputLimit) -> &mut SearchOptions<'a> {
+        self.output_limit = Some(limit);
+        self
+    }
+
+    /// Write the output of the pages of each namespace to a file in the directory (`0.txt`, `1.txt`, ...) instead
+    /// of printing it to stdout. Value counts are still printed to stdout.
+    pub fn split_output_by_namespace(&mut self, directory: &'a Path) -> &mut SearchOptions<'a> {
+        self.split_output_directory = Some(directory);
+        self
+    }
+
+    /// Only process pages with titles matching the filter. Indexed dumps are not scanned completely, only the
+    /// ranges (bzip2 streams for multistream dumps) containing matching titles found in the index are read.
+    /// The titles in CirrusSearch dumps lack the namespace prefix.
+    pub fn with_title_filter(&mut self, title_filter: &'a regex::Regex) -> &mut SearchOptions<'a> {
+        self.title_filter = Some(title_filter);
+        self
+    }
+
+    /// Only process the pages in the list. Like with the title filter only the ranges of indexed dumps containing
+    /// these pages are read.
+    pub fn with_page_list(&mut self, page_list: &'a PageList) -> &mut SearchOptions<'a> {
+        self.page_list = Some(page_list);
+        self
+    }
+
+    /// Only process the pages with these ids, e.g. the pages of a result set. Like with the title filter only the
+    /// ranges of indexed dumps containing these pages are read.
+    pub fn with_restrict_page_ids(&mut self, page_ids: &'a HashSet<u64>) -> &mut SearchOptions<'a> {
+        self.restrict_page_ids = Some(page_ids);
+        self
+    }
+
+    /// Skip the pages with these ids, e.g. the pages of a result set.
+    pub fn with_exclude_page_ids(&mut self, page_ids: &'a HashSet<u64>) -> &mut SearchOptions<'a> {
+        self.exclude_page_ids = Some(page_ids);
+        self
+    }
+
+    /// Only process the pages whose id is in the range, e.g. to split a search into parts. Dump files named by page id
+    /// ranges like the parts of history dumps (`...-p1p844.bz2`) are skipped if they don't overlap it and only the
+    /// ranges of indexed dumps containing these pages are read.
+    pub fn with_page_id_range(&mut self, page_id_range: RangeInclusive<u64>) -> &mut SearchOptions<'a> {
+        self.page_id_range = Some(page_id_range);
+        self
+    }
+
+    /// Record the page and revision ids of the matching revisions, which can be saved as a result set afterwards.
+    pub fn with_result_recorder(&mut self, result_recorder: &'a ResultRecorder) -> &mut SearchOptions<'a> {
+        self.result_recorder = Some(result_recorder);
+        self
+    }
+
+    /// Record the parts of the dump files whose output has been printed in the checkpoint and skip the parts recorded
+    /// by an earlier search, so an interrupted search can be resumed. Compressed files which are not indexed or
+    /// seekable are only recorded as a whole.
+    pub fn with_checkpoint(&mut self, checkpoint: &'a SearchCheckpoint) -> &mut SearchOptions<'a> {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Count the matching revisions by the size of their text or by namespace.
+    pub fn with_match_histogram(&mut self, match_histogram: &'a MatchHistogram) -> &mut SearchOptions<'a> {
+        self.match_histogram = Some(match_histogram);
+        self
+    }
+
+    /// Verify the text of the searched revisions against their SHA1, revisions without a SHA1 are not verified.
+    /// Disables the prefilter so that pages are not skipped.
+    pub fn with_sha1_verifier(&mut self, sha1_verifier: &'a Sha1Verifier) -> &mut SearchOptions<'a> {
+        self.sha1_verifier = Some(sha1_verifier);
+        self
+    }
+
+    /// Search an approximate plain text version of the wikitext: templates, tables, comments and HTML tags are
+    /// removed and links are reduced to their label.
+    pub fn strip_markup(&mut self, strip_markup: bool) -> &mut SearchOptions<'a> {
+        self.strip_markup = strip_markup;
+        self
+    }
+
+    /// Selects the engine used to match the search pattern, by default Aho-Corasick is used for plain literals.
+    pub fn with_engine(&mut self, engine: Engine) -> &mut SearchOptions<'a> {
+        self.engine = engine;
+        self
+    }
+
+    /// Enables skipping pages of plain dump files which do not contain a literal required by the search pattern
+    /// without parsing them and using trigram indexes to skip parts of indexed dumps, enabled by default.
+    pub fn prefilter(&mut self, prefilter: bool) -> &mut SearchOptions<'a> {
+        self.prefilter = prefilter;
+        self
+    }
+
+    /// Send the progress of reading compressed dump files and streams to the channel.
+    pub fn with_progress_sender(&mut self, progress_send: &'a Sender<SearchProgress>) -> &mut SearchOptions<'a> {
+        self.progress_send = Some(progress_send);
+        self
+    }
+
+    /// Call the callback with the progress of reading compressed dump files and streams, e.g. to update a
+    /// `SearchProgressTracker`. It is called by the worker threads, so it should return quickly.
+    pub fn with_progress_callback(&mut self, progress_callback: &'a ProgressCallback) -> &mut SearchOptions<'a> {
+        self.progress_callback = Some(progress_callback);
+        self
+    }
+
+    /// Count the revisions whose text or contributor was deleted or suppressed. All pages need to be read for this, so
+    /// the prefilter is not used.
+    pub fn count_deleted(&mut self, count_deleted: bool) -> &mut SearchOptions<'a> {
+        self.count_deleted = count_deleted;
+        self
+    }
+
+    /// Print the title of each page with revisions whose text or contributor was deleted or suppressed to stderr,
+    /// with the numbers of such revisions. Implies `count_deleted()`.
+    pub fn report_deleted(&mut self, report_deleted: bool) -> &mut SearchOptions<'a> {
+        self.report_deleted = report_deleted;
+        self
+    }
+
+    /// Read and search revision texts larger than the window size in overlapping windows instead of as a whole,
+    /// which bounds the memory used by each thread for huge pages. This is only done for patterns whose matches have
+    /// a bounded length which is smaller than the window size and not if markup is stripped or revisions are
+    /// deduplicated. The lines of matches are cut at the window boundaries and the matches of each window are
+    /// printed as a separate block.
+    pub fn with_text_window_size(&mut self, text_window_size: usize) -> &mut SearchOptions<'a> {
+        self.text_window_size = Some(text_window_size);
+        self
+    }
+
+    /// Limit the memory used for revision texts of at least 1 MiB read by all threads together. Threads wait before
+    /// reading such a text while the texts read by other threads would exceed the limit together with it, a text
+    /// larger than the limit is read once no other large texts are being read.
+    pub fn with_memory_budget(&mut self, memory_budget: u64) -> &mut SearchOptions<'a> {
+        self.memory_budget = Some(MemoryBudget::new(memory_budget));
+        self
+    }
+
+    fn is_prefilter_enabled(&self) -> bool {
+        self.prefilter
+            && !self.strip_markup
+            && !self.count_deleted
+            && !self.report_deleted
+            && self.sha1_verifier.is_none()
+    }
+
+    fn create_output_coordinator(&self, ordered: bool) -> Result<OutputCoordinator> {
+        let mut output_coordinator = match self.split_output_directory {
+            Some(directory) => OutputCoordinator::split_by_namespace(directory, ordered)?,
+            None if self.compress_output => OutputCoordinator::compressed_stdout(ordered),
+            None => OutputCoordinator::stdout(self.color_choice, ordered),
+        };
+        output_coordinator.limit_results(self.output_limit);
+        Ok(output_coordinator)
+    }
+
+    fn is_past_deadline(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn is_title_included(&self, title: &str) -> bool {
+        self.title_filter
+            .is_none_or(|title_filter| title_filter.is_match(title))
+            && self.page_list.is_none_or(|page_list| page_list.contains_title(title))
+    }
+
+    fn is_page_id_included(&self, page_id: &str) -> bool {
+        if self.page_list.is_none()
+            && self.restrict_page_ids.is_none()
+            && self.exclude_page_ids.is_none()
+            && self.page_id_range.is_none()
+        {
+            return true;
+        }
+        page_id
+            .parse()
+            .is_ok_and(|page_id| self.is_parsed_page_id_included(page_id))
+    }
+
+    fn is_parsed_page_id_included(&self, page_id: u64) -> bool {
+        self.page_list
+            .is_none_or(|page_list| page_list.contains_page_id(page_id))
+            && self
+                .restrict_page_ids
+                .is_none_or(|page_ids| page_ids.contains(&page_id))
+            && self
+                .exclude_page_ids
+                .is_none_or(|page_ids| !page_ids.contains(&page_id))
+            && self.page_id_range.as_ref().is_none_or(|range| range.contains(&page_id))
+    }
+
+    /// Whether the dump file may contain pages in the page id range, judging by the page id range in its name.
+    fn may_contain_page_ids(&self, dump_file: &str) -> bool {
+        match (&self.page_id_range, get_page_id_range_of_file(dump_file)) {
+            (Some(range), Some((first, last))) => first <= *range.end() && *range.start() <= last,
+            _ => true,
+        }
+    }
+
+    /// Number of threads searching the parts of the dump files.
+    fn effective_thread_count(&self) -> usize {
+        self.thread_count
+            .map_or_else(rayon::current_num_threads, NonZeroUsize::get)
+    }
+
+    /// Size of the slices plain dump files are split into, the total size of these files divided by the number of
+    /// threads within the bounds unless set explicitly.
+    fn chunk_size(&self, sliced_size: u64) -> u64 {
+        let (min, max) = self.chunk_size_bounds;
+        self.chunk_size
+            .unwrap_or_else(|| (sliced_size / self.effective_thread_count() as u64).min(max).max(min))
+            .max(1)
+    }
+
+    /// Whether only some pages are processed, which are looked up in the index of indexed dumps.
+    fn is_page_selection_restricted(&self) -> bool {
+        self.title_filter.is_some()
+            || self.page_list.is_some()
+            || self.restrict_page_ids.is_some()
+            || self.page_id_range.is_some()
+    }
+
+    fn is_model_included(&self, model: &str) -> bool {
+        self.restrict_models.is_none_or(|models| models.contains(&model))
+    }
+
+    fn is_revision_included(&self, header: &RevisionHeader) -> bool {
+        self.is_model_included(&header.model)
+            && self
+                .restrict_formats
+                .is_none_or(|formats| formats.contains(&header.format.as_str()))
+            && !(self.skip_minor && header.minor)
+            && !(self.only_ip_edits && header.contributor_ip.is_empty())
+    }
+}
+
+impl<'a> Default for SearchOptions<'a> {
+    fn default() -> Self {
+        SearchOptions::new()
+    }
+}
+
+pub fn is_compressed(file: &str) -> bool {
+    CompressionFormat::of_file(file).is_some()
+}
+
+fn strip_compression_suffix(file: &str) -> &str {
+    file.strip_suffix(".7z")
+        .or_else(|| file.strip_suffix(".bz2"))
+        .or_else(|| file.strip_suffix(".gz"))
+        .or_else(|| file.strip_suffix(".zst"))
+        .unwrap_or(file)
+}
+
+/// Returns the page id range in the name of dump files split by page ids, e.g. `(1, 844)` for
+/// `enwiki-20240101-pages-meta-history1.xml-p1p844.bz2`.
+fn get_page_id_range_of_file(dump_file: &str) -> Option<(u64, u64)> {
+    let file_name = Path::new(dump_file).file_name()?.to_str()?;
+    file_name.rsplit('-').find_map(|part| {
+        let (first, last) = part.strip_prefix('p')?.split_once('p')?;
+        let last_len = last.bytes().take_while(u8::is_ascii_digit).count();
+        Some((first.parse().ok()?, last[..last_len].parse().ok()?))
+    })
+}
+
+/// CirrusSearch dumps (e.g. `enwiki-20240101-cirrussearch-content.json.gz`) are newline-delimited JSON
+/// instead of XML.
+pub fn is_cirrussearch_dump(file: &str) -> bool {
+    strip_compression_suffix(file).ends_with(".json")
+}
+
+/// Metadata of the revision currently being processed, as found in the dump.
+pub struct RevisionInfo<'a> {
+    pub page_id: &'a str,
+    pub namespace: &'a str,
+    pub title: &'a str,
+    pub revision_id: &'a str,
+    /// Content model, e.g. `wikitext` or `wikibase-item`
+    pub model: &'a str,
+    /// Content format, e.g. `text/x-wiki` or `application/json`, empty for CirrusSearch dumps
+    pub format: &'a str,
+    pub minor: bool,
+    /// User id of the contributor, not set for IP edits, deleted contributors and CirrusSearch dumps
+    pub contributor_id: Option<&'a str>,
+    /// IP address of the contributor if the edit was not made by a registered user
+    pub contributor_ip: Option<&'a str>,
+    /// Size of the text in bytes as given in the dump
+    pub text_len: Option<u64>,
+}
+
+/// A window of a revision text read in overlapping windows.
+pub struct TextWindow<'t> {
+    /// The unescaped text of the window, starting with the end of the previous window
+    pub text: &'t [u8],
+    /// The length of the start of the text which was part of the previous window, matches ending there have already
+    /// been processed
+    pub overlap: usize,
+    /// Whether the processor returned true for a previous window of the same revision
+    pub matched_before: bool,
+}
+
+/// Processes the text of each revision read from the dump, output written to the buffer is printed to stdout
+/// after each revision.
+pub trait RevisionProcessor: Sync {
+    /// Returns true if the revision matches, which is counted in `SearchDumpResult::match_counts`. Processors which
+    /// don't search for anything return false.
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], output: &mut Buffer) -> Result<bool>;
+
+    /// Returns true if the revision matches, called instead of `process_revision` if matches are grouped by page.
+    fn is_match(&self, _revision: &RevisionInfo, _text: &[u8]) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Returns a literal contained in the text of all revisions the processor has output for or which match, pages
+    /// of plain dump files without it are skipped without parsing them.
+    fn required_literal(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Returns the maximum length of the text matched by the processor if it can process revision texts in
+    /// overlapping windows, see `SearchOptions::with_text_window_size()`.
+    fn max_match_len(&self) -> Option<usize> {
+        None
+    }
+
+    /// Called instead of `process_revision` for each window of a revision text read in overlapping windows. Returns
+    /// true if the processor matched or output something for the window.
+    fn process_text_window(
+        &self,
+        _revision: &RevisionInfo,
+        _window: &TextWindow,
+        _output: &mut Buffer,
+    ) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Called at the end of each page of an XML dump, after all its revisions have been processed by the same thread.
+    /// The page id is empty for pages skipped before their id was read.
+    fn page_finished(&self, _page_id: &str, _output: &mut Buffer) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Matching revisions of the page currently being read.
+struct PageMatches {
+    first_revision_id: String,
+    last_revision_id: String,
+    count: u64,
+}
+
+impl PageMatches {
+    fn new() -> PageMatches {
+        PageMatches {
+            first_revision_id: String::with_capacity(50),
+            last_revision_id: String::with_capacity(50),
+            count: 0,
+        }
+    }
+
+    fn add(&mut self, revision_id: &str) {
+        if self.count == 0 {
+            self.first_revision_id.clear();
+            self.first_revision_id.push_str(revision_id);
+        }
+        self.last_revision_id.clear();
+        self.last_revision_id.push_str(revision_id);
+        self.count += 1;
+    }
+
+    /// Writes the title with the range and number of matching revisions and resets the count for the next page.
+    fn write_and_clear(&mut self, title: &str, stdout_buffer: &mut Buffer) {
+        if self.count == 0 {
+            return;
+        }
+        set_color(stdout_buffer, Color::Cyan);
+        buffer_write!(stdout_buffer, "{}", title);
+        set_plain(stdout_buffer);
+        buffer_write!(stdout_buffer, "@");
+        set_color(stdout_buffer, Color::Yellow);
+        if self.count == 1 {
+            buffer_write!(stdout_buffer, "{}", self.first_revision_id);
+        } else {
+            buffer_write!(stdout_buffer, "{}..{}", self.first_revision_id, self.last_revision_id);
+        }
+        set_plain(stdout_buffer);
+        let unit = if self.count == 1 { "revision" } else { "revisions" };
+        buffer_writeln!(stdout_buffer, " ({} {})", self.count, unit);
+        self.count = 0;
+    }
+}
+
+struct RegexSearch<'a> {
+    matcher: Matcher,
+    required_literal: Option<Vec<u8>>,
+    only_print_title: bool,
+    match_sink: Option<&'a dyn MatchSink>,
+    replacement: Option<&'a str>,
+    print_title_prefix: bool,
+    value_counts: Option<Mutex<HashMap<Vec<u8>, u64>>>,
+    result_recorder: Option<&'a ResultRecorder>,
+    match_histogram: Option<&'a MatchHistogram>,
+}
+
+impl<'a> RegexSearch<'a> {
+    fn new(regex: &str, search_options: &SearchOptions<'a>) -> Result<RegexSearch<'a>> {
+        let matcher = Matcher::new(regex, search_options.engine, search_options.replacement.is_some())?;
+        // PCRE2 syntax is not parsed
+        let required_literal = matches!(matcher, Matcher::Regex(_) | Matcher::Literals(_))
+            .then(|| get_required_literal(regex))
+            .flatten();
+        Ok(RegexSearch {
+            matcher,
+            required_literal,
+            only_print_title: search_options.only_print_title,
+            match_sink: search_options.match_sink,
+            replacement: search_options.replacement,
+            print_title_prefix: search_options.print_title_prefix,
+            value_counts: search_options.count_unique.then(|| Mutex::new(HashMap::new())),
+            result_recorder: search_options.result_recorder,
+            match_histogram: search_options.match_histogram,
+        })
+    }
+
+    /// Only matches ending after `min_match_end` are counted. Returns true if matches were found.
+    fn count_values(
+        &self,
+        value_counts: &Mutex<HashMap<Vec<u8>, u64>>,
+        text: &[u8],
+        min_match_end: usize,
+    ) -> Result<bool> {
+        let mut values = Vec::new();
+        match (self.replacement, self.matcher.regex()) {
+            (Some(replacement), Some(re)) => {
+                // UNWRAP: the whole match is always captured
+                for captures in re
+                    .captures_iter(text)
+                    .filter(|captures| is_new_match(captures.get(0).unwrap().end(), min_match_end))
+                {
+                    let mut value = Vec::new();
+                    captures.expand(replacement.as_bytes(), &mut value);
+                    values.push(value);
+                }
+            }
+            _ => {
+                for m in self.matcher.find_iter(text) {
+                    let m = m?;
+                    if is_new_match(m.end, min_match_end) {
+                        values.push(text[m].to_owned());
+                    }
+                }
+            }
+        }
+        if values.is_empty() {
+            return Ok(false);
+        }
+        // lock once per revision
+        let mut value_counts = value_counts.lock().unwrap();
+        for value in values {
+            *value_counts.entry(value).or_insert(0) += 1;
+        }
+        Ok(true)
+    }
+
+    fn print_value_counts(self, stdout_writer: &BufferWriter) {
+        if let Some(value_counts) = self.value_counts {
+            let mut value_counts: Vec<_> = value_counts.into_inner().unwrap().into_iter().collect();
+            value_counts.sort_unstable_by(|(value1, count1), (value2, count2)| {
+                count2.cmp(count1).then_with(|| value1.cmp(value2))
+            });
+            let mut buffer = stdout_writer.buffer();
+            for (value, count) in value_counts {
+                set_color(&mut buffer, Color::Yellow);
+                buffer_write!(buffer, "{}", count);
+                set_plain(&mut buffer);
+                buffer_write!(buffer, "\t");
+                buffer.write_all(&value).unwrap();
+                buffer_writeln!(buffer, "");
+            }
+            stdout_writer.print(&buffer).unwrap();
+        }
+    }
+
+    /// Only matches ending after `min_match_end` are printed. Returns true if matches were found.
+    fn print_replaced_matches(
+        &self,
+        revision: &RevisionInfo,
+        text: &[u8],
+        min_match_end: usize,
+        replacement: &str,
+        output: &mut Buffer,
+    ) -> bool {
+        // UNWRAP: the regex engine is always used with replacements
+        let re = self.matcher.regex().unwrap();
+        let mut value = Vec::with_capacity(100);
+        let mut matched = false;
+        // UNWRAP: the whole match is always captured
+        for captures in re
+            .captures_iter(text)
+            .filter(|captures| is_new_match(captures.get(0).unwrap().end(), min_match_end))
+        {
+            value.clear();
+            captures.expand(replacement.as_bytes(), &mut value);
+            if self.print_title_prefix {
+                set_color(output, Color::Cyan);
+                buffer_write!(output, "{}", revision.title);
+                set_plain(output);
+                buffer_write!(output, "@");
+                set_color(output, Color::Yellow);
+                buffer_write!(output, "{}", revision.revision_id);
+                set_plain(output);
+                buffer_write!(output, "\t");
+            }
+            output.write_all(&value).unwrap();
+            buffer_writeln!(output, "");
+            matched = true;
+        }
+        matched
+    }
+
+    /// Records a matching revision, `text` is only used for its length if the size of the text is not given in the
+    /// dump.
+    fn record(&self, revision: &RevisionInfo, text: &[u8]) -> Result<()> {
+        if let Some(result_recorder) = self.result_recorder {
+            result_recorder.add(
+                parse_number_in_tag("id", revision.page_id)?,
+                parse_number_in_tag("id", revision.revision_id)?,
+            );
+        }
+        if let Some(match_histogram) = self.match_histogram {
+            match_histogram.add(revision.namespace, revision.text_len.unwrap_or(text.len() as u64))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> RevisionProcessor for RegexSearch<'a> {
+    #[inline(always)]
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], output: &mut Buffer) -> Result<bool> {
+        let matched = if let Some(match_sink) = self.match_sink {
+            let positions = find_match_positions(text, &self.matcher)?;
+            if !positions.is_empty() {
+                match_sink.add_match(&MatchedRevision {
+                    page_id: parse_number_in_tag("id", revision.page_id)?,
+                    namespace: parse_number_in_tag("ns", revision.namespace)?,
+                    title: revision.title,
+                    revision_id: parse_number_in_tag("id", revision.revision_id)?,
+                    match_count: positions.len(),
+                    positions: &positions,
+                    text,
+                })?;
+            }
+            !positions.is_empty()
+        } else if let Some(value_counts) = &self.value_counts {
+            self.count_values(value_counts, text, 0)?
+        } else if let Some(replacement) = self.replacement {
+            self.print_replaced_matches(revision, text, 0, replacement, output)
+        } else if self.only_print_title {
+            let is_match = self.matcher.is_match(text)?;
+            if is_match {
+                print_title(revision, output);
+            }
+            is_match
+        } else {
+            find_in_text(
+                output,
+                revision.title,
+                revision.revision_id,
+                text,
+                &self.matcher,
+                0,
+                true,
+            )?
+        };
+        if matched {
+            self.record(revision, text)?;
+        }
+        Ok(matched)
+    }
+
+    fn is_match(&self, revision: &RevisionInfo, text: &[u8]) -> Result<bool> {
+        let is_match = self.matcher.is_match(text)?;
+        if is_match {
+            self.record(revision, text)?;
+        }
+        Ok(is_match)
+    }
+
+    fn max_match_len(&self) -> Option<usize> {
+        // matching revisions are stored with their whole text
+        if self.match_sink.is_some() {
+            return None;
+        }
+        self.matcher.max_match_len()
+    }
+
+    fn process_text_window(&self, revision: &RevisionInfo, window: &TextWindow, output: &mut Buffer) -> Result<bool> {
+        let matched = if let Some(value_counts) = &self.value_counts {
+            self.count_values(value_counts, window.text, window.overlap)?
+        } else if let Some(replacement) = self.replacement {
+            self.print_replaced_matches(revision, window.text, window.overlap, replacement, output)
+        } else if self.only_print_title {
+            if window.matched_before {
+                return Ok(true);
+            }
+            let is_match = self.matcher.is_match(window.text)?;
+            if is_match {
+                print_title(revision, output);
+            }
+            is_match
+        } else {
+            find_in_text(
+                output,
+                revision.title,
+                revision.revision_id,
+                window.text,
+                &self.matcher,
+                window.overlap,
+                !window.matched_before,
+            )?
+        };
+        if matched && !window.matched_before {
+            self.record(revision, window.text)?;
+        }
+        Ok(matched)
+    }
+
+    fn required_literal(&self) -> Option<&[u8]> {
+        self.required_literal.as_deref()
+    }
+}
+
+/// Matches ending in the part of a text window which was part of the previous window have already been processed.
+fn is_new_match(match_end: usize, min_match_end: usize) -> bool {
+    min_match_end == 0 || match_end > min_match_end
+}
+
+fn print_title(revision: &RevisionInfo, output: &mut Buffer) {
+    set_color(output, Color::Cyan);
+    buffer_write!(output, "{}", revision.title);
+    set_plain(output);
+    buffer_write!(output, "@");
+    set_color(output, Color::Yellow);
+    buffer_write!(output, "{}", revision.revision_id);
+    set_plain(output);
+}
+
+/// Skips revisions already processed, e.g. if the same pages are found in several dump files.
+struct DeduplicatingProcessor<'a> {
+    processor: &'a dyn RevisionProcessor,
+    seen_revisions: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl<'a> RevisionProcessor for DeduplicatingProcessor<'a> {
+    fn process_revision(&self, revision: &RevisionInfo, text: &[u8], output: &mut Buffer) -> Result<bool> {
+        let key = (
+            parse_number_in_tag("id", revision.page_id)?,
+            parse_number_in_tag("id", revision.revision_id)?,
+        );
+        if self.seen_revisions.lock().unwrap().insert(key) {
+            self.processor.process_revision(revision, text, output)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn is_match(&self, revision: &RevisionInfo, text: &[u8]) -> Result<bool> {
+        let key = (
+            parse_number_in_tag("id", revision.page_id)?,
+            parse_number_in_tag("id", revision.revision_id)?,
+        );
+        if self.seen_revisions.lock().unwrap().insert(key) {
+            self.processor.is_match(revision, text)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn required_literal(&self) -> Option<&[u8]> {
+        self.processor.required_literal()
+    }
+
+    fn page_finished(&self, page_id: &str, output: &mut Buffer) -> Result<()> {
+        self.processor.page_finished(page_id, output)
+    }
+}
+
+pub fn search_dump(regex: &str, dump_files: &[String], search_options: &SearchOptions) -> Result<SearchDumpResult> {
+    let regex_search = RegexSearch::new(regex, search_options)?;
+    let search_dump_result = process_dump(dump_files, &regex_search, search_options)?;
+    regex_search.print_value_counts(&BufferWriter::stdout(search_options.color_choice));
+    Ok(search_dump_result)
+}
+
+/// Searches dumps read from streams one after another, e.g. while they are downloaded. Each stream is given with
+/// the name of its dump file which determines the format. Streams are opened lazily by the iterator.
+pub fn search_dump_streams<R, I>(regex: &str, streams: I, search_options: &SearchOptions) -> Result<SearchDumpResult>
+where
+    R: Read,
+    I: IntoIterator<Item = Result<(String, R)>>,
+{
+    let regex_search = RegexSearch::new(regex, search_options)?;
+    let search_dump_result = process_dump_streams(streams, &regex_search, search_options)?;
+    regex_search.print_value_counts(&BufferWriter::stdout(search_options.color_choice));
+    Ok(search_dump_result)
+}
+
+/// Processes dumps read from streams one after another, see `search_dump_streams`. Compressed streams are
+/// decompressed in-process, .7z files are not supported.
+pub fn process_dump_streams<R, I>(
+    streams: I,
+    processor: &dyn RevisionProcessor,
+    search_options: &SearchOptions,
+) -> Result<SearchDumpResult>
+where
+    R: Read,
+    I: IntoIterator<Item = Result<(String, R)>>,
+{
+    let deduplicating_processor;
+    let processor = if search_options.deduplicate {
+        deduplicating_processor = DeduplicatingProcessor {
+            processor,
+            seen_revisions: Mutex::new(HashSet::new()),
+        };
+        &deduplicating_processor
+    } else {
+        processor
+    };
+    // streams are searched one after another, so the output is always ordered
+    let output_coordinator = search_options.create_output_coordinator(false)?;
+    let mut output = output_coordinator.part_output(0);
+    let mut scratch = ScratchBuffers::new();
+    let mut bytes_processed = 0;
+    let mut compressed_files_found = false;
+    let buf_size = 2 * 1024 * 1024;
+    for stream in streams {
+        // streams are opened lazily, so the remaining ones are not even requested
+        if search_options.is_past_deadline() {
+            break;
+        }
+        let (file_name, reader) = stream?;
+        bytes_processed += match CompressionFormat::of_file(&file_name) {
+            Some(format) => {
+                compressed_files_found = true;
+                let reader = CountingReader::new(reader);
+                let compressed_bytes_read = Some(reader.counter());
+                let mut progress_reader = ProgressReader::new(
+                    decompress_stream(format, reader)?,
+                    &file_name,
+                    compressed_bytes_read,
+                    search_options,
+                );
+                let mut buf_reader = BufReader::with_capacity(buf_size, &mut progress_reader);
+                let bytes_processed_0 = search_stream(
+                    &mut output,
+                    processor,
+                    &file_name,
+                    &mut buf_reader,
+                    search_options,
+                    &mut scratch,
+                )?;
+                progress_reader.finish();
+                Ok(bytes_processed_0)
+            }
+            None => {
+                let mut buf_reader = BufReader::with_capacity(buf_size, reader);
+                search_stream(
+                    &mut output,
+                    processor,
+                    &file_name,
+                    &mut buf_reader,
+                    search_options,
+                    &mut scratch,
+                )
+            }
+        }?;
+    }
+    output.finish();
+    output_coordinator.finish()?;
+    Ok(SearchDumpResult {
+        bytes_processed,
+        compressed_files_found,
+        truncated: search_options.is_past_deadline(),
+        deleted_revisions: scratch.take_deleted_revisions(),
+        rotational_storage: false,
+        failed_files: Vec::new(),
+        bzip2_streams: Vec::new(),
+        match_counts: scratch.take_match_counts(),
+    })
+}
+
+fn search_stream<B: BufRead>(
+    output: &mut PartOutput,
+    processor: &dyn RevisionProcessor,
+    file_name: &str,
+    buf_reader: &mut B,
+    search_options: &SearchOptions,
+    scratch: &mut ScratchBuffers,
+) -> Result<u64> {
+    if is_cirrussearch_dump(file_name) {
+        search_cirrussearch_reader(output, processor, buf_reader, search_options, scratch)
+    } else {
+        search_dump_reader(output, processor, buf_reader, 0, u64::MAX, search_options, scratch)
+    }
+}
+
+/// Opens a dump file for reading it from start to end, compressed files are decompressed with the decompressor
+/// `search_dump()` would use for them.
+pub fn open_dump_file(dump_file: &str, search_options: &SearchOptions) -> Result<DecompressingReader> {
+    match find_decompressors(&[dump_file.to_owned()], search_options)?.pop() {
+        Some((_, decompressor)) => decompressor.open(dump_file),
+        None => Ok(DecompressingReader::uncompressed(File::open(dump_file)?)),
+    }
+}
+
+/// Whether .bz2 dump files among the dump files would be decompressed by a single thread, by bzcat or internal
+/// decompression, since neither lbzip2 nor pbzip2 was found or chosen.
+pub fn is_bzip2_decompression_slow(dump_files: &[String], search_options: &SearchOptions) -> bool {
+    dump_files.iter().any(|dump_file| dump_file.ends_with(".bz2"))
+        && find_decompressor(
+            CompressionFormat::Bzip2,
+            search_options.binary_bzcat,
+            search_options.options_bzcat,
+            search_options.thread_count,
+        )
+        .is_ok_and(|decompressor| !decompressor.is_parallel_bzip2())
+}
+
+fn find_decompressors(
+    dump_files: &[String],
+    search_options: &SearchOptions,
+) -> Result<Vec<(CompressionFormat, Decompressor)>> {
+    let mut decompressors: Vec<(CompressionFormat, Decompressor)> = Vec::new();
+    for format in dump_files
+        .iter()
+        .filter_map(|dump_file| CompressionFormat::of_file(dump_file))
+    {
+        if !decompressors.iter().any(|(f, _)| *f == format) {
+            let decompressor = match format {
+                CompressionFormat::SevenZip => find_decompressor(
+                    format,
+                    search_options.binary_7z,
+                    search_options.options_7z,
+                    search_options.thread_count,
+                )?,
+                CompressionFormat::Bzip2 => find_decompressor(
+                    format,
+                    search_options.binary_bzcat,
+                    search_options.options_bzcat,
+                    search_options.thread_count,
+                )?,
+                CompressionFormat::Gzip | CompressionFormat::Zstd => {
+                    find_decompressor(format, None, None, search_options.thread_count)?
+                }
+            };
+            decompressors.push((format, decompressor));
+        }
+    }
+    Ok(decompressors)
+}
+
+pub fn process_dump(
+    dump_files: &[String],
+    processor: &dyn RevisionProcessor,
+    search_options: &SearchOptions,
+) -> Result<SearchDumpResult> {
+    // parts of dumps split by page ids outside of the page id range are not read at all
+    let dump_files = dump_files
+        .iter()
+        .filter(|dump_file| search_options.may_contain_page_ids(dump_file))
+        .cloned()
+        .collect::<Vec<_>>();
+    let dump_files = dump_files.as_slice();
+    let deduplicating_processor;
+    let processor = if search_options.deduplicate {
+        deduplicating_processor = DeduplicatingProcessor {
+            processor,
+            seen_revisions: Mutex::new(HashSet::new()),
+        };
+        &deduplicating_processor
+    } else {
+        processor
+    };
+    let single_threaded = search_options.thread_count.filter(|t| t.get() == 1).is_some();
+    let rotational_devices = count_rotational_devices(dump_files);
+    // find decompressors upfront instead of failing in the middle of the search
+    let decompressors = find_decompressors(dump_files, search_options)?;
+    let required_literal = processor
+        .required_literal()
+        .filter(|_| search_options.is_prefilter_enabled());
+    let indexed_ranges = dump_files
+        .iter()
+        .map(|dump_file| get_search_ranges(dump_file, search_options, required_literal))
+        .collect::<Result<Vec<_>>>()?;
+
+    let output_coordinator = search_options.create_output_coordinator(search_options.ordered_output)?;
+    let bytes_processed = AtomicU64::new(0);
+    let deleted_revisions = Mutex::new(DeletedRevisionCounts::default());
+    let match_counts = Mutex::new(MatchCounts::default());
+    let compressed_file_found = AtomicBool::new(false);
+    let failed_files = Mutex::new(Vec::new());
+    let bzip2_streams = Mutex::new(Vec::new());
+    let record_file_error = |dump_file: &str, err: Error| -> Result<()> {
+        if !search_options.keep_going {
+            return Err(err);
+        }
+        failed_files.lock().unwrap().push((dump_file.to_owned(), err));
+        Ok(())
+    };
+
+    if single_threaded
+        && !dump_files.as_ref().iter().map(String::as_ref).any(is_compressed)
+        && !search_options.is_page_selection_restricted()
+        && search_options.exclude_page_ids.is_none()
+        && search_options.checkpoint.is_none()
+        && indexed_ranges.iter().all(Option::is_none)
+    {
+        // don't use rayon when single-threaded and reading plain files
+        let mut scratch = ScratchBuffers::new();
+        for (i, dump_file) in dump_files.iter().enumerate() {
+            let mut output = output_coordinator.part_output(i);
+            match search_dump_part(
+                &mut output,
+                processor,
+                dump_file,
+                0,
+                u64::MAX,
+                search_options,
+                &mut scratch,
+            ) {
+                Ok(bytes_processed_0) => {
+                    bytes_processed.fetch_add(bytes_processed_0, Ordering::Relaxed);
+                    deleted_revisions.lock().unwrap().add(scratch.take_deleted_revisions());
+                    match_counts.lock().unwrap().add(scratch.take_match_counts());
+                }
+                Err(err) => record_file_error(dump_file, err)?,
+            }
+            output.finish();
+        }
+        output_coordinator.finish()?;
+    } else {
+        // parts are numbered across all files for ordered output, compressed files are searched as a single part
+        // unless they are seekable
+        let mut files = Vec::with_capacity(dump_files.len());
+        let mut part_count = 0;
+        let chunk_size = search_options.chunk_size(sliced_size(dump_files, &indexed_ranges)?);
+        // file and byte range of each part for the checkpoint
+        let mut part_ranges = Vec::new();
+        for (dump_file, indexed_ranges) in dump_files.iter().zip(indexed_ranges) {
+            let (parts, slice_size) = match &indexed_ranges {
+                Some(indexed_ranges) => (indexed_ranges.len() as u64, 0),
+                None if is_compressed(dump_file) => (1, u64::MAX),
+                None => split_into_parts(dump_file, chunk_size)?,
+            };
+            if search_options.checkpoint.is_some() {
+                match &indexed_ranges {
+                    Some(indexed_ranges) => part_ranges.extend(
+                        indexed_ranges
+                            .iter()
+                            .map(|&(start, end)| (dump_file.as_str(), start, end)),
+                    ),
+                    None if is_compressed(dump_file) => part_ranges.push((dump_file.as_str(), 0, u64::MAX)),
+                    None => part_ranges
+                        .extend((0..parts).map(|i| (dump_file.as_str(), i * slice_size, (i + 1) * slice_size))),
+                }
+            }
+            files.push(DumpFileParts {
+                dump_file,
+                first_part: part_count,
+                parts,
+                slice_size,
+                indexed_ranges,
+            });
+            part_count += parts as usize;
+        }
+
+        // parts of a file are searched by the cpu pool, files by the io pool if the number of files read at once
+        // is limited: always if set explicitly, by default only for uncompressed files on spinning disks, compressed
+        // files are decompressed much slower than they are read
+        let cpu_pool = ThreadPoolBuilder::new()
+            .num_threads(search_options.thread_count.map_or(0, NonZeroUsize::get))
+            .build()
+            .expect("Could not initialize thread pool");
+        let io_pool = search_options
+            .io_parallelism
+            .or(rotational_devices)
+            .map(|io_parallelism| {
+                ThreadPoolBuilder::new()
+                    .num_threads(io_parallelism.get())
+                    .build()
+                    .expect("Could not initialize thread pool")
+            });
+
+        let checkpoint = search_options.checkpoint;
+        let is_part_completed = |dump_file: &str, start: u64, end: u64| {
+            checkpoint.is_some_and(|checkpoint| checkpoint.is_completed(dump_file, start, end))
+        };
+        // parts finished after the deadline may not have been searched completely
+        let record_printed_parts = || -> Result<()> {
+            match checkpoint {
+                Some(checkpoint) if !search_options.is_past_deadline() => {
+                    let printed_parts = output_coordinator.take_printed_parts()?;
+                    checkpoint.add(printed_parts.iter().map(|&part| &part_ranges[part]))
+                }
+                _ => Ok(()),
+            }
+        };
+
+        let search_file = |scratch: &mut ScratchBuffers, file_parts: &DumpFileParts| -> Result<()> {
+            let dump_file = file_parts.dump_file;
+            if search_options.is_past_deadline() {
+                return Ok(());
+            }
+            if let Some(indexed_ranges) = &file_parts.indexed_ranges {
+                if is_compressed(dump_file) {
+                    compressed_file_found.fetch_or(true, Ordering::Relaxed);
+                }
+                cpu_pool.install(|| {
+                    indexed_ranges.par_iter().enumerate().try_for_each_init(
+                        ScratchBuffers::new,
+                        |scratch, (i, &(start, end))| {
+                            let mut output = output_coordinator.part_output(file_parts.first_part + i);
+                            if is_part_completed(dump_file, start, end) {
+                                output.finish();
+                                return Ok(());
+                            }
+                            let bytes_processed_0 = search_indexed_range(
+                                &mut output,
+                                processor,
+                                dump_file,
+                                start,
+                                end,
+                                search_options,
+                                scratch,
+                            )?;
+                            output.finish();
+                            bytes_processed.fetch_add(bytes_processed_0, Ordering::Relaxed);
+                            deleted_revisions.lock().unwrap().add(scratch.take_deleted_revisions());
+                            match_counts.lock().unwrap().add(scratch.take_match_counts());
+                            record_printed_parts()
+                        },
+                    )
+                })
+            } else if let Some(format) = CompressionFormat::of_file(dump_file) {
+                if is_part_completed(dump_file, 0, u64::MAX) {
+                    output_coordinator.part_output(file_parts.first_part).finish();
+                    return Ok(());
+                }
+                // UNWRAP: decompressors were found for all formats above
+                let (_, decompressor) = decompressors.iter().find(|(f, _)| *f == format).unwrap();
+                let mut decompressed = decompressor.open(dump_file)?;
+                let compressed_bytes_read = decompressed.compressed_bytes_read();
+                let mut progress_reader =
+                    ProgressReader::new(&mut decompressed, dump_file, compressed_bytes_read, search_options);
+                let buf_size = 2 * 1024 * 1024;
+                let mut buf_reader = BufReader::with_capacity(buf_size, &mut progress_reader);
+                let mut output = output_coordinator.part_output(file_parts.first_part);
+                let search_res = if is_cirrussearch_dump(dump_file) {
+                    search_cirrussearch_reader(&mut output, processor, &mut buf_reader, search_options, scratch)
+                } else {
+                    search_dump_reader(
+                        &mut output,
+                        processor,
+                        &mut buf_reader,
+                        0,
+                        u64::MAX,
+                        search_options,
+                        scratch,
+                    )
+                };
+                let bytes_processed_0 = match search_res {
+                    Ok(bytes_processed_0) => bytes_processed_0,
+                    Err(err) => {
+                        eprintln!("Error searching {dump_file}");
+                        drop(buf_reader);
+                        drop(progress_reader);
+                        // the other files are still searched with keep-going
+                        decompressed.abort();
+                        return Err(err);
+                    }
+                };
+                output.finish();
+                progress_reader.finish();
+                compressed_file_found.fetch_or(true, Ordering::Relaxed);
+                bytes_processed.fetch_add(bytes_processed_0, Ordering::Relaxed);
+                deleted_revisions.lock().unwrap().add(scratch.take_deleted_revisions());
+                match_counts.lock().unwrap().add(scratch.take_match_counts());
+                if search_options.is_past_deadline() {
+                    // the decompressor would otherwise block writing the rest of the file
+                    decompressed.abort();
+                    Ok(())
+                } else {
+                    if let Some(streams) = decompressed.bzip2_streams() {
+                        bzip2_streams.lock().unwrap().push((dump_file.to_owned(), streams));
+                    }
+                    decompressed.finish()?;
+                    record_printed_parts()
+                }
+            } else {
+                let search_part = |scratch: &mut ScratchBuffers, i: u64| -> Result<()> {
+                    let mut output = output_coordinator.part_output(file_parts.first_part + i as usize);
+                    let (start, end) = (i * file_parts.slice_size, (i + 1) * file_parts.slice_size);
+                    if is_part_completed(dump_file, start, end) {
+                        output.finish();
+                        return Ok(());
+                    }
+                    let bytes_processed_0 =
+                        search_dump_part(&mut output, processor, dump_file, start, end, search_options, scratch)?;
+                    output.finish();
+                    bytes_processed.fetch_add(bytes_processed_0, Ordering::Relaxed);
+                    deleted_revisions.lock().unwrap().add(scratch.take_deleted_revisions());
+                    match_counts.lock().unwrap().add(scratch.take_match_counts());
+                    record_printed_parts()
+                };
+                cpu_pool.install(|| {
+                    (0..file_parts.parts)
+                        .into_par_iter()
+                        .try_for_each_init(ScratchBuffers::new, search_part)
+                })
+            }
+        };
+        // the files searched in the other pool are skipped after an error
+        let failed = AtomicBool::new(false);
+        let search_files = |files: &[&DumpFileParts]| {
+            files
+                .par_iter()
+                .try_for_each_init(ScratchBuffers::new, |scratch, file_parts| {
+                    if failed.load(Ordering::Relaxed) {
+                        return Ok(());
+                    }
+                    search_file(scratch, file_parts)
+                        .or_else(|err| record_file_error(file_parts.dump_file, err))
+                        .inspect_err(|_| failed.store(true, Ordering::Relaxed))
+                })
+        };
+        let search_res = match &io_pool {
+            Some(io_pool) => {
+                let (limited_files, other_files): (Vec<_>, Vec<_>) = files.iter().partition(|file_parts| {
+                    search_options.io_parallelism.is_some() || !is_compressed(file_parts.dump_file)
+                });
+                thread::scope(|scope| {
+                    let limited_search = scope.spawn(|| io_pool.install(|| search_files(&limited_files)));
+                    let other_res = cpu_pool.install(|| search_files(&other_files));
+                    // UNWRAP: a panic is propagated anyway when the scope ends
+                    limited_search.join().unwrap().and(other_res)
+                })
+            }
+            None => cpu_pool.install(|| search_files(&files.iter().collect::<Vec<_>>())),
+        };
+        let finish_res = output_coordinator.finish();
+        // the parts completed so far are saved even if the search failed
+        let checkpoint_res = checkpoint.map_or(Ok(()), |checkpoint| {
+            record_printed_parts()?;
+            checkpoint.write()
+        });
+        search_res?;
+        finish_res?;
+        checkpoint_res?;
+    }
+
+    Ok(SearchDumpResult {
+        bytes_processed: bytes_processed.load(Ordering::Relaxed),
+        compressed_files_found: compressed_file_found.load(Ordering::Relaxed),
+        truncated: search_options.is_past_deadline(),
+        deleted_revisions: deleted_revisions.into_inner().unwrap(),
+        rotational_storage: rotational_devices.is_some(),
+        failed_files: failed_files.into_inner().unwrap(),
+        bzip2_streams: bzip2_streams.into_inner().unwrap(),
+        match_counts: match_counts.into_inner().unwrap(),
+    })
+}
+
+/// A dump file with the parts it is searched in, parts are numbered consecutively across all dump files.
+struct DumpFileParts<'a> {
+    dump_file: &'a str,
+    first_part: usize,
+    parts: u64,
+    slice_size: u64,
+    /// Ranges found in the index of the dump file to read instead of the whole file
+    indexed_ranges: Option<Vec<(u64, u64)>>,
+}
+
+const DEFAULT_CHUNK_SIZE_BOUNDS: (u64, u64) = (16 * 1024 * 1024, 500 * 1024 * 1024);
+
+/// Returns whether a dump file is split into slices unless it is searched in indexed ranges.
+fn is_sliced(dump_file: &str) -> bool {
+    // JSON documents are preceded by their index line, so don't split CirrusSearch dumps
+    !is_compressed(dump_file) && !is_cirrussearch_dump(dump_file)
+}
+
+/// Returns the total size of the dump files split into slices, see `SearchOptions::chunk_size()`.
+fn sliced_size(dump_files: &[String], search_ranges: &[Option<Vec<(u64, u64)>>]) -> Result<u64> {
+    let mut size = 0;
+    for (dump_file, ranges) in dump_files.iter().zip(search_ranges) {
+        if ranges.is_none() && is_sliced(dump_file) {
+            size += metadata(dump_file)?.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Returns the number of parts a plain dump file is split into to search it in parallel and the size of the parts.
+fn split_into_parts(dump_file: &str, chunk_size: u64) -> Result<(u64, u64)> {
+    let len = metadata(dump_file)?.len();
+    let parts = if is_sliced(dump_file) {
+        ceiling_div(len, chunk_size).max(1)
+    } else {
+        1
+    };
+    let slice_size = ceiling_div(len, parts); // make sure to read to end
+    Ok((parts, slice_size))
+}
+
+/// Returns the number of rotational devices the dump files are on if all of them are on rotational storage.
+/// Concurrent reads slow spinning disks down, so by default only one file per rotational device is read at a time.
+#[cfg(target_os = "linux")]
+fn count_rotational_devices(dump_files: &[String]) -> Option<NonZeroUsize> {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut devices = HashSet::new();
+    for dump_file in dump_files {
+        let device = metadata(dump_file).ok()?.dev();
+        if !is_rotational_device(device) {
+            return None;
+        }
+        devices.insert(device);
+    }
+    NonZeroUsize::new(devices.len())
+}
+
+#[cfg(target_os = "linux")]
+fn is_rotational_device(device: u64) -> bool {
+    let major = ((device >> 8) & 0xfff) | ((device >> 32) & !0xfff);
+    let minor = (device & 0xff) | ((device >> 12) & !0xff);
+    // partitions have no queue directory, it is found in the directory of the whole disk instead
+    ["queue/rotational", "../queue/rotational"]
+        .iter()
+        .any(|path| fs::read_to_string(format!("/sys/dev/block/{major}:{minor}/{path}")).is_ok_and(|s| s.trim() == "1"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn count_rotational_devices(_dump_files: &[String]) -> Option<NonZeroUsize> {
+    None
+}
+
+/// Buffers reused for all parts searched by a worker thread instead of being allocated for each part.
+struct ScratchBuffers {
+    buf: Vec<u8>,
+    escaped_title: Vec<u8>,
+    title: String,
+    namespace: String,
+    page_id: String,
+    revision_id: String,
+    revision_header: RevisionHeader,
+    page_matches: PageMatches,
+    plaintext: Vec<u8>,
+    // counted since the last call of take_deleted_revisions
+    deleted_revisions: DeletedRevisionCounts,
+    // counted since the last call of take_match_counts
+    match_counts: MatchCounts,
+    escaped_text_window: Vec<u8>,
+    text_window: Vec<u8>,
+}
+
+impl ScratchBuffers {
+    fn new() -> ScratchBuffers {
+        ScratchBuffers {
+            buf: Vec::with_capacity(1000 * 1024),
+            escaped_title: Vec::with_capacity(10000),
+            title: String::with_capacity(10000),
+            namespace: String::with_capacity(10),
+            page_id: String::with_capacity(50),
+            revision_id: String::with_capacity(50),
+            revision_header: RevisionHeader::new(),
+            page_matches: PageMatches::new(),
+            plaintext: Vec::new(),
+            deleted_revisions: DeletedRevisionCounts::default(),
+            match_counts: MatchCounts::default(),
+            escaped_text_window: Vec::new(),
+            text_window: Vec::new(),
+        }
+    }
+
+    fn take_deleted_revisions(&mut self) -> DeletedRevisionCounts {
+        std::mem::take(&mut self.deleted_revisions)
+    }
+
+    fn take_match_counts(&mut self) -> MatchCounts {
+        std::mem::take(&mut self.match_counts)
+    }
+}
+
+fn search_dump_part(
+    output: &mut PartOutput,
+    processor: &dyn RevisionProcessor,
+    dump_file: &str,
+    start: u64,
+    end: u64,
+    search_options: &SearchOptions,
+    scratch: &mut ScratchBuffers,
+) -> Result<u64> {
+    let required_literal = processor
+        .required_literal()
+        .filter(|_| search_options.is_prefilter_enabled() && !is_cirrussearch_dump(dump_file));
+    if let Some(literal) = required_literal {
+        let page_tag = get_page_start_tag(dump_file)?;
+        let (page_starts, page_count, bytes_scanned) = find_pages_containing(
+            dump_file,
+            start,
+            end,
+            &page_tag,
+            literal,
+            search_options,
+            &mut scratch.buf,
+        )?;
+        // the pages without the literal have been searched as well
+        scratch.match_counts.pages_scanned += page_count - page_starts.len() as u64;
+        let mut file = File::open(dump_file)?;
+        for page_start in page_starts {
+            file.seek(SeekFrom::Start(page_start))?;
+            let mut buf_reader = BufReader::with_capacity(64 * 1024, &mut file);
+            search_dump_reader(
+                output,
+                processor,
+                &mut buf_reader,
+                page_start,
+                page_start + 1,
+                search_options,
+                scratch,
+            )?;
+        }
+        return Ok(bytes_scanned);
+    }
+    let mut file = File::open(dump_file)?;
+    file.seek(SeekFrom::Start(start))?;
+    let buf_size = 2 * 1024 * 1024;
+    let mut buf_reader = BufReader::with_capacity(buf_size, file);
+    if is_cirrussearch_dump(dump_file) {
+        search_cirrussearch_reader(output, processor, &mut buf_reader, search_options, scratch)
+    } else {
+        search_dump_reader(output, processor, &mut buf_reader, start, end, search_options, scratch)
+    }
+}
+
+/// Returns the start tag of the pages of a plain dump file, `<page>` unless the root element has a namespace prefix
+/// like `<mw:mediawiki>`, which then also applies to the pages.
+fn get_page_start_tag(dump_file: &str) -> Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(4096);
+    File::open(dump_file)?.take(4096).read_to_end(&mut head)?;
+    let mut reader = Reader::from_reader(head.as_slice());
+    let mut buf = Vec::with_capacity(1024);
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                let prefix = &e.name()[..e.name().len() - e.local_name().len()];
+                return Ok([b"<", prefix, b"page>"].concat());
+            }
+            // not a dump, which is reported when it is parsed
+            Ok(Event::Eof) | Err(_) => return Ok(b"<page>".to_vec()),
+            Ok(_other_event) => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Scans the pages starting in a range of a plain dump file for a literal without parsing the XML. Returns the
+/// offsets of the pages containing the literal, the number of pages starting in the range and the number of bytes
+/// scanned, which includes the rest of the last page after the end of the range.
+fn find_pages_containing(
+    dump_file: &str,
+    start: u64,
+    end: u64,
+    page_tag: &[u8],
+    literal: &[u8],
+    search_options: &SearchOptions,
+    block: &mut Vec<u8>,
+) -> Result<(Vec<u64>, u64, u64)> {
+    const BLOCK_SIZE: usize = 16 * 1024 * 1024;
+    let page_finder = memmem::Finder::new(page_tag);
+    let literal_finder = memmem::Finder::new(literal);
+    // the end of the previous block is kept to find tags and literals crossing the block boundary
+    let overlap = literal.len().max(page_tag.len()) - 1;
+
+    let mut file = File::open(dump_file)?;
+    file.seek(SeekFrom::Start(start))?;
+    block.clear();
+    let mut block_start = start;
+    let mut kept = 0;
+    // the last page start seen, hits before the first page start belong to the previous range
+    let mut current_page = None;
+    let mut pages = Vec::new();
+    let mut page_count = 0;
+    loop {
+        if search_options.is_past_deadline() {
+            break;
+        }
+        (&mut file).take((BLOCK_SIZE - block.len()) as u64).read_to_end(block)?;
+        let page_starts: Vec<u64> = page_finder
+            .find_iter(block)
+            .filter(|&pos| pos + page_tag.len() > kept)
+            .map(|pos| block_start + pos as u64)
+            .collect();
+        // pages starting at or after the end are searched with the next range
+        let own_page_count = page_starts.partition_point(|&page_start| page_start < end);
+        let scan_end = page_starts
+            .get(own_page_count)
+            .map_or(block.len(), |&page_start| (page_start - block_start) as usize);
+        for pos in literal_finder.find_iter(&block[..scan_end]) {
+            if pos + literal.len() <= kept {
+                continue;
+            }
+            let hit = block_start + pos as u64;
+            let preceding_page_count = page_starts[..own_page_count].partition_point(|&page_start| page_start < hit);
+            let page = match preceding_page_count {
+                0 => current_page,
+                n => Some(page_starts[n - 1]),
+            };
+            if let Some(page) = page.filter(|&page| pages.last() != Some(&page)) {
+                pages.push(page);
+            }
+        }
+        if own_page_count > 0 {
+            current_page = Some(page_starts[own_page_count - 1]);
+        }
+        page_count += own_page_count as u64;
+        if own_page_count < page_starts.len() || block.len() < BLOCK_SIZE {
+            break;
+        }
+        let discarded = block.len() - overlap;
+        block.drain(..discarded);
+        block_start += discarded as u64;
+        kept = overlap;
+    }
+    Ok((pages, page_count, block_start + block.len() as u64 - start))
+}
+
+/// Returns the ranges of an indexed or seekable dump file which are searched in parallel, `None` if the file is
+/// searched as a whole or split into slices.
+fn get_search_ranges(
+    dump_file: &str,
+    search_options: &SearchOptions,
+    required_literal: Option<&[u8]>,
+) -> Result<Option<Vec<(u64, u64)>>> {
+    match get_indexed_ranges(dump_file, search_options, required_literal)? {
+        // seekable zstd dumps are split into ranges of frames instead of being decompressed as a whole
+        None if is_seekable_zstd_dump(dump_file) => split_into_frame_ranges(dump_file).map(Some),
+        indexed_ranges => Ok(indexed_ranges),
+    }
+}
+
+/// Returns the ranges of an indexed dump which need to be searched: those with pages matching the title filter and
+/// the page list and, if the dump has a trigram index, those which may contain the required literal. Returns `None`
+/// if the whole dump needs to be searched.
+fn get_indexed_ranges(
+    dump_file: &str,
+    search_options: &SearchOptions,
+    required_literal: Option<&[u8]>,
+) -> Result<Option<Vec<(u64, u64)>>> {
+    if !is_indexable(dump_file) {
+        return Ok(None);
+    }
+    let title_ranges = if search_options.is_page_selection_restricted() && index_file_exists(dump_file) {
+        Some(find_matching_ranges(
+            &get_index_file_name(dump_file),
+            |page_id, title| {
+                search_options.is_title_included(title) && search_options.is_parsed_page_id_included(page_id)
+            },
+        )?)
+    } else {
+        None
+    };
+    let literal_ranges = match required_literal {
+        Some(literal) if trigram_index_exists(dump_file) => {
+            find_candidate_ranges(&get_trigram_index_file_name(dump_file), literal)?
+        }
+        _ => None,
+    };
+    Ok(match (title_ranges, literal_ranges) {
+        (Some(title_ranges), Some(literal_ranges)) => {
+            let literal_range_starts: HashSet<u64> = literal_ranges.iter().map(|&(start, _)| start).collect();
+            Some(
+                title_ranges
+                    .into_iter()
+                    .filter(|(start, _)| literal_range_starts.contains(start))
+                    .collect(),
+            )
+        }
+        (title_ranges, literal_ranges) => title_ranges.or(literal_ranges),
+    })
+}
+
+/// Searches a range of an indexed dump, for multistream dumps only the bzip2 stream at the start of the range is
+/// decompressed.
+fn search_indexed_range(
+    output: &mut PartOutput,
+    processor: &dyn RevisionProcessor,
+    dump_file: &str,
+    start: u64,
+    end: u64,
+    search_options: &SearchOptions,
+    scratch: &mut ScratchBuffers,
+) -> Result<u64> {
+    let buf_size = 2 * 1024 * 1024;
+    let reader = if is_seekable_zstd_dump(dump_file) {
+        open_frame_range(dump_file, start, end)?
+    } else {
+        open_dump_at_offset(dump_file, start)?
+    };
+    let mut buf_reader = BufReader::with_capacity(buf_size, reader);
+    if is_compressed(dump_file) {
+        search_dump_reader(output, processor, &mut buf_reader, 0, u64::MAX, search_options, scratch)
+    } else {
+        search_dump_reader(output, processor, &mut buf_reader, start, end, search_options, scratch)
+    }
+}
+
+#[derive(Deserialize)]
+struct CirrusSearchIndexLine {
+    index: CirrusSearchIndex,
+}
+
+#[derive(Deserialize)]
+struct CirrusSearchIndex {
+    #[serde(rename = "_id")]
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct CirrusSearchDocument {
+    namespace: i32,
+    title: String,
+    version: u64,
+    content_model: Option<String>,
+    text: Option<String>,
+    source_text: Option<String>,
+}
+
+fn search_cirrussearch_reader<B: BufRead>(
+    output: &mut PartOutput,
+    processor: &dyn RevisionProcessor,
+    buf_reader: &mut B,
+    search_options: &SearchOptions,
+    scratch: &mut ScratchBuffers,
+) -> Result<u64> {
+    let line = &mut scratch.buf;
+    let page_id = &mut scratch.page_id;
+    let mut bytes_read = 0_u64;
+
+    loop {
+        if search_options.is_past_deadline() {
+            break;
+        }
+        line.clear();
+        let len = buf_reader.read_until(b'\n', line)?;
+        if len == 0 {
+            break;
+        }
+        bytes_read += len as u64;
+        if line.starts_with(b"{\"index\"") {
+            let index_line: CirrusSearchIndexLine = serde_json::from_slice(line)?;
+            page_id.clear();
+            page_id.push_str(&index_line.index.id);
+            continue;
+        }
+        let document: CirrusSearchDocument = serde_json::from_slice(line)?;
+        let namespace = document.namespace.to_string();
+        if search_options
+            .restrict_namespaces
+            .is_some_and(|restrict_namespaces| !restrict_namespaces.contains(&namespace.as_str()))
+        {
+            continue;
+        }
+        if !search_options.is_title_included(&document.title) || !search_options.is_page_id_included(page_id) {
+            continue;
+        }
+        let model = document.content_model.as_deref().unwrap_or("wikitext");
+        if !search_options.is_model_included(model) || search_options.only_ip_edits {
+            continue;
+        }
+        let text = match search_options.cirrussearch_field {
+            CirrusSearchField::Text => document.text,
+            CirrusSearchField::SourceText => document.source_text,
+        };
+        let mut matched = false;
+        if let Some(text) = text {
+            let text_len = text.len() as u64;
+            let text = if search_options.strip_markup {
+                scratch.plaintext.clear();
+                strip_markup(text.as_bytes(), &mut scratch.plaintext);
+                scratch.plaintext.as_slice()
+            } else {
+                text.as_bytes()
+            };
+            output.set_namespace(&namespace);
+            // titles in CirrusSearch dumps lack the namespace prefix
+            let revision = RevisionInfo {
+                page_id: page_id.as_str(),
+                namespace: namespace.as_str(),
+                title: document.title.as_str(),
+                revision_id: &document.version.to_string(),
+                model,
+                format: "",
+                minor: false,
+                contributor_id: None,
+                contributor_ip: None,
+                text_len: Some(text_len),
+            };
+            matched = if search_options.group_by_page {
+                let matched = processor.is_match(&revision, text)?;
+                if matched {
+                    scratch.page_matches.add(revision.revision_id);
+                    scratch.page_matches.write_and_clear(&document.title, output.buffer());
+                }
+                matched
+            } else {
+                processor.process_revision(&revision, text, output.buffer())?
+            };
+            // each document holds a single revision of the page
+            output.page_finished();
+        }
+        scratch.match_counts.add_page(true, matched.into());
+    }
+    Ok(bytes_read)
+}
+
+fn search_dump_reader<B: BufRead>(
+    output: &mut PartOutput,
+    processor: &dyn RevisionProcessor,
+    buf_reader: &mut B,
+    start: u64,
+    end: u64,
+    search_options: &SearchOptions,
+    scratch: &mut ScratchBuffers,
+) -> Result<u64> {
+    let mut reader = Reader::from_reader(buf_reader);
+    reader.check_end_names(false);
+
+    let ScratchBuffers {
+        buf,
+        escaped_title,
+        title,
+        namespace,
+        page_id,
+        revision_id,
+        revision_header,
+        page_matches,
+        plaintext,
+        deleted_revisions,
+        match_counts,
+        escaped_text_window,
+        text_window: text_window_buf,
+    } = scratch;
+    buf.clear();
+    let mut title_unescaped = false;
+    // (window size, overlap) if large texts are read in windows
+    let text_windows = search_options
+        .text_window_size
+        .filter(|_| !search_options.strip_markup && !search_options.deduplicate)
+        .and_then(|window_size| {
+            processor
+                .max_match_len()
+                .filter(|&max_match_len| max_match_len < window_size)
+                .map(|max_match_len| (window_size, max_match_len.saturating_sub(1)))
+        });
+    // read directly from the underlying reader, not counted in the position of the XML reader
+    let mut text_window_bytes_read = 0;
+    let search_comments = search_options.search_field == SearchField::Comment;
+    revision_header.read_comment = search_comments;
+    // SHA1 of the text of the current revision if it is verified
+    let mut text_sha1 = None;
+
+    loop {
+        if search_options.is_past_deadline() {
+            break;
+        }
+        let page_tag_len = match skip_to_start_tag_or_eof(&mut reader, buf, b"page")? {
+            SkipToStartTagOrEofResult::StartTagFound(tag_len) => tag_len,
+            SkipToStartTagOrEofResult::Eof => break,
+        };
+        let page_tag_start_pos = reader.buffer_position() as u64 + text_window_bytes_read + start - page_tag_len;
+        if page_tag_start_pos >= end {
+            break;
+        }
+        // empty for pages skipped before their id
+        page_id.clear();
+        // dumps before export-0.6 have no <ns> element
+        namespace.clear();
+        let mut page_deleted_revisions = DeletedRevisionCounts::default();
+        let mut page_searched = false;
+        let mut page_revisions_matched = 0;
+        let page_start = search_options.page_time_limit.map(|_| Instant::now());
+        let is_page_time_limit_exceeded = || {
+            page_start
+                .zip(search_options.page_time_limit)
+                .is_some_and(|(page_start, (limit, _))| page_start.elapsed() > limit)
+        };
+        // revisions skipped after the time limit was exceeded
+        let mut skipped_revisions = None;
+        loop {
+            match reader.read_event(buf)? {
+                Event::Start(ref e) => match e.local_name() {
+                    b"title" => {
+                        // only unescaped and validated if a revision of the page is processed
+                        read_escaped_bytes_and_then(&mut reader, buf, "title", |text| {
+                            escaped_title.clear();
+                            escaped_title.extend_from_slice(text);
+                            Ok(())
+                        })?;
+                        title_unescaped = false;
+                        if search_options.title_filter.is_some() || search_options.page_list.is_some() {
+                            unescape_title(escaped_title, title)?;
+                            title_unescaped = true;
+                            if !search_options.is_title_included(title) {
+                                break;
+                            }
+                        }
+                    }
+                    b"ns" => {
+                        let skip = read_escaped_bytes_and_then(&mut reader, buf, "ns", |text| {
+                            if search_options.restrict_namespaces.is_some_and(|restrict_namespaces| {
+                                !restrict_namespaces.iter().any(|ns| ns.as_bytes() == text)
+                            }) {
+                                return Ok(true);
+                            }
+                            namespace.clear();
+                            namespace.push_str(from_utf8(text)?);
+                            Ok(false)
+                        })?;
+                        if skip {
+                            break;
+                        }
+                        output.set_namespace(namespace);
+                    }
+                    // ids of contributors of uploads following the revisions are ignored
+                    b"id" if page_id.is_empty() => {
+                        // revision and contributor ids are consumed in the revision branch
+                        read_str_and_then(&mut reader, buf, "id", |text| {
+                            page_id.clear();
+                            page_id.push_str(text);
+                            Ok(())
+                        })?;
+                        if !search_options.is_page_id_included(page_id) {
+                            // skipped pages are not passed to the processor
+                            page_id.clear();
+                            break;
+                        }
+                    }
+                    b"revision" => {
+                        if namespace.is_empty() {
+                            // pages of dumps without <ns> elements are treated as being in the main namespace
+                            if search_options
+                                .restrict_namespaces
+                                .is_some_and(|restrict_namespaces| !restrict_namespaces.contains(&"0"))
+                            {
+                                break;
+                            }
+                            namespace.push('0');
+                            output.set_namespace(namespace);
+                        }
+                        // not excluded by the namespace, title or page filters
+                        page_searched = true;
+                        skip_to_start_tag(&mut reader, buf, b"id")?;
+                        read_str_and_then(&mut reader, buf, "id", |text| {
+                            revision_id.clear();
+                            revision_id.push_str(text);
+                            Ok(())
+                        })?;
+                        let revision_text = read_revision_up_to_text(&mut reader, buf, revision_header)?;
+                        if revision_header.contributor_deleted {
+                            page_deleted_revisions.deleted_contributors += 1;
+                        }
+                        text_sha1 = None;
+                        let (has_text, text_len) = match revision_text {
+                            RevisionText::Present(text_len) => (true, text_len),
+                            RevisionText::Empty => {
+                                if search_options.sha1_verifier.is_some() && !search_comments {
+                                    text_sha1 = Some(TextHasher::default().finish());
+                                }
+                                (false, None)
+                            }
+                            RevisionText::Deleted => {
+                                page_deleted_revisions.deleted_texts += 1;
+                                (false, None)
+                            }
+                        };
+                        if search_comments {
+                            if !revision_header.comment.is_empty()
+                                && search_options.is_revision_included(revision_header)
+                            {
+                                if !title_unescaped {
+                                    unescape_title(escaped_title, title)?;
+                                    title_unescaped = true;
+                                }
+                                let comment = revision_header.comment.as_bytes();
+                                let revision = revision_header.revision_info(
+                                    page_id,
+                                    namespace,
+                                    title,
+                                    revision_id,
+                                    Some(comment.len() as u64),
+                                );
+                                let matched = if search_options.group_by_page {
+                                    let matched = processor.is_match(&revision, comment)?;
+                                    if matched {
+                                        page_matches.add(revision_id);
+                                    }
+                                    matched
+                                } else {
+                                    let matched = processor.process_revision(&revision, comment, output.buffer())?;
+                                    output.revision_finished();
+                                    matched
+                                };
+                                page_revisions_matched += u64::from(matched);
+                            }
+                        } else if has_text && skipped_revisions.is_none() && is_page_time_limit_exceeded() {
+                            if !title_unescaped {
+                                unescape_title(escaped_title, title)?;
+                                title_unescaped = true;
+                            }
+                            check_page_time_limit(title, search_options)?;
+                            skipped_revisions = Some(0);
+                        }
+                        if has_text && search_comments {
+                            skip_to_end_tag(&mut reader, buf, b"text")?;
+                        } else if has_text && skipped_revisions.is_some() {
+                            skipped_revisions = skipped_revisions.map(|count| count + 1);
+                            skip_to_end_tag(&mut reader, buf, b"text")?;
+                        } else if has_text && !search_options.is_revision_included(revision_header) {
+                            // skip without unescaping
+                            skip_to_end_tag(&mut reader, buf, b"text")?;
+                        } else if has_text {
+                            if !title_unescaped {
+                                unescape_title(escaped_title, title)?;
+                                title_unescaped = true;
+                            }
+                            let revision =
+                                revision_header.revision_info(page_id, namespace, title, revision_id, text_len);
+                            let text_window = text_windows
+                                .filter(|&(window_size, _)| text_len.is_some_and(|len| len > window_size as u64));
+                            // for the escaped text and its unescaped copy
+                            let reservation = search_options.memory_budget.as_ref().and_then(|memory_budget| {
+                                let size = match text_window {
+                                    Some((window_size, _)) => 2 * window_size as u64,
+                                    None => 2 * text_len?,
+                                };
+                                (size >= MIN_RESERVED_SIZE).then(|| memory_budget.reserve(size))
+                            });
+                            if let Some((window_size, overlap)) = text_window {
+                                let mut matched = false;
+                                let mut window_time_limit_exceeded = false;
+                                let mut text_hasher = search_options.sha1_verifier.map(|_| TextHasher::default());
+                                text_window_bytes_read += read_text_windows(
+                                    reader.get_mut(),
+                                    escaped_text_window,
+                                    text_window_buf,
+                                    window_size,
+                                    overlap,
+                                    |text, overlap| {
+                                        if let Some(text_hasher) = &mut text_hasher {
+                                            text_hasher.update(&text[overlap..]);
+                                        }
+                                        if window_time_limit_exceeded {
+                                            // the rest of the text is only read
+                                            return Ok(());
+                                        }
+                                        if is_page_time_limit_exceeded() {
+                                            check_page_time_limit(title, search_options)?;
+                                            window_time_limit_exceeded = true;
+                                            return Ok(());
+                                        }
+                                        if search_options.group_by_page {
+                                            matched = matched || processor.is_match(&revision, text)?;
+                                        } else {
+                                            let window = TextWindow {
+                                                text,
+                                                overlap,
+                                                matched_before: matched,
+                                            };
+                                            matched |=
+                                                processor.process_text_window(&revision, &window, output.buffer())?;
+                                        }
+                                        Ok(())
+                                    },
+                                )?;
+                                if search_options.group_by_page && matched {
+                                    page_matches.add(revision_id);
+                                }
+                                page_revisions_matched += u64::from(matched);
+                                if window_time_limit_exceeded {
+                                    // the revision is counted as skipped although it was partly searched
+                                    skipped_revisions = Some(0);
+                                }
+                                text_sha1 = text_hasher.map(TextHasher::finish);
+                                skip_to_end_tag(&mut reader, buf, b"text")?;
+                            } else {
+                                read_bytes_and_then(&mut reader, buf, "text", |text| {
+                                    if search_options.sha1_verifier.is_some() {
+                                        let mut text_hasher = TextHasher::default();
+                                        text_hasher.update(text);
+                                        text_sha1 = Some(text_hasher.finish());
+                                    }
+                                    let text = if search_options.strip_markup {
+                                        plaintext.clear();
+                                        strip_markup(text, plaintext);
+                                        plaintext.as_slice()
+                                    } else {
+                                        text
+                                    };
+                                    let matched = if search_options.group_by_page {
+                                        let matched = processor.is_match(&revision, text)?;
+                                        if matched {
+                                            page_matches.add(revision_id);
+                                        }
+                                        matched
+                                    } else {
+                                        processor.process_revision(&revision, text, output.buffer())?
+                                    };
+                                    page_revisions_matched += u64::from(matched);
+                                    Ok(())
+                                })?;
+                            }
+                            if reservation.is_some() {
+                                // the memory is only returned to the budget if the buffers shrink
+                                for large_buf in [
+                                    &mut *buf,
+                                    &mut *plaintext,
+                                    &mut *escaped_text_window,
+                                    &mut *text_window_buf,
+                                ] {
+                                    large_buf.clear();
+                                    large_buf.shrink_to(MIN_RESERVED_SIZE as usize);
+                                }
+                                // released before waiting for the output lock, which might be held by a thread waiting
+                                // for the budget
+                                drop(reservation);
+                            }
+                            if !search_options.group_by_page {
+                                output.revision_finished();
+                            }
+                        }
+                    }
+                    // follows the text
+                    b"sha1" if text_sha1.is_some() => {
+                        // UNWRAP: checked by the guard
+                        let actual = text_sha1.take().unwrap();
+                        let expected = read_str_and_then(&mut reader, buf, "sha1", |expected| {
+                            Ok((expected != actual).then(|| expected.to_owned()))
+                        })?;
+                        // UNWRAP: the hash is only computed if a verifier is set
+                        let sha1_verifier = search_options.sha1_verifier.unwrap();
+                        sha1_verifier.add_verified();
+                        if let Some(expected) = expected {
+                            if !title_unescaped {
+                                unescape_title(escaped_title, title)?;
+                                title_unescaped = true;
+                            }
+                            sha1_verifier.add_mismatch(Sha1Mismatch {
+                                title: title.clone(),
+                                revision_id: revision_id.clone(),
+                                expected,
+                                actual,
+                            });
+                        }
+                    }
+                    _other_tag => { /* ignore */ }
+                },
+                Event::End(bytes_end) if bytes_end.local_name() == b"page" => {
+                    break;
+                }
+                Event::Eof => return Err(Error::Xml(quick_xml::Error::UnexpectedEof("page".to_owned()))),
+                _other_event => (),
+            }
+            buf.clear();
+        }
+        if search_options.report_deleted && !page_deleted_revisions.is_empty() {
+            if !title_unescaped {
+                unescape_title(escaped_title, title)?;
+            }
+            eprintln!(
+                "{}: {} revisions with deleted text, {} revisions with deleted contributor",
+                title, page_deleted_revisions.deleted_texts, page_deleted_revisions.deleted_contributors
+            );
+        }
+        deleted_revisions.add(page_deleted_revisions);
+        match_counts.add_page(page_searched, page_revisions_matched);
+        if skipped_revisions.is_some() || is_page_time_limit_exceeded() {
+            if !title_unescaped {
+                unescape_title(escaped_title, title)?;
+            }
+            check_page_time_limit(title, search_options)?;
+            // UNWRAP: only exceeded if a limit is set
+            let (limit, _) = search_options.page_time_limit.unwrap();
+            eprintln!(
+                "{}: time limit of {:?} exceeded, {} revisions skipped",
+                title,
+                limit,
+                skipped_revisions.unwrap_or(0)
+            );
+        }
+        processor.page_finished(page_id, output.buffer())?;
+        page_matches.write_and_clear(title, output.buffer());
+        output.page_finished();
+    }
+    Ok(reader.buffer_position() as u64 + text_window_bytes_read)
+}
+
+/// Fails if pages exceeding the time limit abort the search.
+fn check_page_time_limit(title: &str, search_options: &SearchOptions) -> Result<()> {
+    match search_options.page_time_limit {
+        Some((limit, PageTimeLimitAction::Abort)) => Err(Error::PageTimeLimitExceeded(title.to_owned(), limit)),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the position up to which escaped text can be unescaped without splitting an entity or a character.
+fn get_text_window_end(escaped: &[u8]) -> usize {
+    // entities are at most 10 bytes long, e.g. `&#x10FFFF;`
+    let tail_start = escaped.len().saturating_sub(10);
+    let end = match memrchr(b'&', &escaped[tail_start..]) {
+        Some(pos) if memchr(b';', &escaped[tail_start + pos..]).is_none() => tail_start + pos,
+        _ => escaped.len(),
+    };
+    // the last character might be incomplete
+    let tail_start = end.saturating_sub(4);
+    match escaped[tail_start..end].iter().rposition(|&b| b & 0xC0 != 0x80) {
+        Some(pos) => {
+            let char_start = tail_start + pos;
+            let char_len = match escaped[char_start] {
+                0x00..=0x7F => 1,
+                0xC0..=0xDF => 2,
+                0xE0..=0xEF => 3,
+                _ => 4,
+            };
+            if char_start + char_len > end {
+                char_start
+            } else {
+                end
+            }
+        }
+        None => end,
+    }
+}
+
+/// Reads the escaped text of a revision directly from the underlying reader in windows of at most `window_size`
+/// bytes, so that the text is never held in memory as a whole. Each window is unescaped and passed to `f` with the
+/// length of its start which was part of the previous window, at least `overlap` bytes except for the first window.
+/// Stops before the end tag of the text and returns the number of bytes read.
+fn read_text_windows<B: BufRead>(
+    reader: &mut B,
+    escaped: &mut Vec<u8>,
+    window: &mut Vec<u8>,
+    window_size: usize,
+    overlap: usize,
+    mut f: impl FnMut(&[u8], usize) -> Result<()>,
+) -> Result<u64> {
+    escaped.clear();
+    window.clear();
+    let mut bytes_read = 0;
+    let mut window_overlap = 0;
+    loop {
+        let available = reader.fill_buf()?;
+        if available.is_empty() {
+            return Err(Error::Xml(quick_xml::Error::UnexpectedEof("text".to_owned())));
+        }
+        // the text is escaped, so the first < starts the end tag
+        let text_end = memchr(b'<', available);
+        let len = text_end.unwrap_or(available.len()).min(window_size - escaped.len());
+        let finished = text_end == Some(len);
+        escaped.extend_from_slice(&available[..len]);
+        reader.consume(len);
+        bytes_read += len as u64;
+        if !finished && escaped.len() < window_size {
+            continue;
+        }
+        let end = if finished {
+            escaped.len()
+        } else {
+            get_text_window_end(escaped)
+        };
+        let unescaped = quick_xml::escape::unescape(&escaped[..end]).map_err(quick_xml::Error::EscapeError)?;
+        window.extend_from_slice(&unescaped);
+        f(window, window_overlap)?;
+        if finished {
+            return Ok(bytes_read);
+        }
+        escaped.drain(..end);
+        let mut overlap_start = window.len().saturating_sub(overlap);
+        while overlap_start > 0 && window[overlap_start] & 0xC0 == 0x80 {
+            overlap_start -= 1;
+        }
+        window.drain(..overlap_start);
+        window_overlap = window.len();
+    }
+}
+
+fn unescape_title(escaped_title: &[u8], title: &mut String) -> Result<()> {
+    let unescaped_title = quick_xml::escape::unescape(escaped_title).map_err(quick_xml::Error::EscapeError)?;
+    title.clear();
+    title.push_str(from_utf8(&unescaped_title)?);
+    Ok(())
+}
+
+fn parse_number_in_tag<T: std::str::FromStr>(tag: &str, text: &str) -> Result<T> {
+    text.parse()
+        .map_err(|_| Error::InvalidNumberInTag(tag.to_owned(), text.to_owned()))
+}
+
+/// Prints the lines of the matches ending after `min_match_end`, preceded by the title if `print_title` is set.
+/// Returns true if matches were found.
+#[inline(always)]
+fn find_in_text(
+    buffer: &mut Buffer,
+    title: &str,
+    revision_id: &str,
+    text: &[u8],
+    matcher: &Matcher,
+    min_match_end: usize,
+    print_title: bool,
+) -> Result<bool> {
+    let mut last_match_end: usize = 0;
+    let mut first_match = true;
+    for m in matcher.find_iter(text) {
+        let m = m?;
+        if !is_new_match(m.end, min_match_end) {
+            continue;
+        }
+        if first_match && print_title {
+            // print title once
+            set_color(buffer, Color::Cyan);
+            buffer_write!(buffer, "{}", title);
+            set_plain(buffer);
+            buffer_write!(buffer, "@");
+            set_color(buffer, Color::Yellow);
+            buffer_writeln!(buffer, "{}", revision_id);
+            set_plain(buffer);
+        }
+
+        match memrchr(b'\n', &text[last_match_end..m.start]) {
+            None => {
+                // match starting on same line that the last match ended
+
+                // print text between matches
+                buffer_write!(buffer, "{}", from_utf8(&text[last_match_end..m.start])?);
+            }
+            Some(pos) => {
+                // match starting on a new line
+
+                // finish line from previous match
+                if !first_match {
+                    match memchr(b'\n', &text[last_match_end..m.start]) {
+                        None => {
+                            panic!("Memchr/Memrchr inconsistency");
+                        }
+                        Some(pos) => {
+                            buffer_writeln!(buffer, "{}", from_utf8(&text[last_match_end..last_match_end + pos])?);
+                        }
+                    }
+                }
+                // print text in line preceding match
+                buffer_write!(buffer, "{}", from_utf8(&text[last_match_end + pos + 1..m.start])?);
+            }
+        };
+        // print matched text
+
+        // don't print extra newline and the following line if match end with \n
+        let actual_match_end = if m.start < m.end && text[m.end - 1] == b'\n' {
+            m.end - 1
+        } else {
+            m.end
+        };
+        set_color(buffer, Color::Red);
+        buffer_write!(buffer, "{}", from_utf8(&text[m.start..actual_match_end])?);
+        set_plain(buffer);
+        last_match_end = actual_match_end;
+        if first_match {
+            first_match = false;
+        }
+    }
+    let matches_found = !first_match;
+    if matches_found {
+        // print rest of last matching line
+        match memchr(b'\n', &text[last_match_end..]) {
+            None => {
+                buffer_writeln!(buffer, "{}", from_utf8(&text[last_match_end..])?);
+            }
+            Some(pos) => {
+                buffer_writeln!(buffer, "{}", from_utf8(&text[last_match_end..last_match_end + pos])?);
+            }
+        }
+        // separate from next match
+        writeln!(buffer).unwrap();
+    }
+    Ok(matches_found)
+}
+
+/// Returns the positions of all matches in the text. Lines and columns are counted incrementally from the previous
+/// match, so the text is scanned only once.
+fn find_match_positions(text: &[u8], matcher: &Matcher) -> Result<Vec<MatchPosition>> {
+    let mut positions = Vec::new();
+    let mut line = 1;
+    let mut column = 1;
+    // lines and columns are counted up to this offset
+    let mut counted_to = 0;
+    for m in matcher.find_iter(text) {
+        let m = m?;
+        let mut column_start = counted_to;
+        if let Some(last_newline) = memrchr(b'\n', &text[counted_to..m.start]) {
+            line += memchr_iter(b'\n', &text[counted_to..m.start]).count();
+            column = 1;
+            column_start = counted_to + last_newline + 1;
+        }
+        // UTF-8 continuation bytes don't start a character
+        column += text[column_start..m.start]
+            .iter()
+            .filter(|&&byte| byte & 0xC0 != 0x80)
+            .count();
+        counted_to = m.start;
+        positions.push(MatchPosition {
+            line,
+            column,
+            start: m.start,
+            end: m.end,
+        });
+    }
+    Ok(positions)
+}
+
+/// A single page extracted from the dump.
+pub struct PageXml {
+    /// The raw XML of the `<page>` element.
+    pub xml: String,
+    /// The wikitext of the last revision.
+    pub text: Option<String>,
+}
+
+/// Finds a page by its title by scanning the dump files one after another. Dump files with an index (see
+/// `wdgrep make-index`) are not scanned, the page is read directly from the indexed offset instead.
+pub fn extract_page(dump_files: &[String], title: &str, search_options: &SearchOptions) -> Result<PageXml> {
+    let decompressors = find_decompressors(dump_files, search_options)?;
+    let buf_size = 2 * 1024 * 1024;
+    for dump_file in dump_files.iter().filter(|dump_file| !is_cirrussearch_dump(dump_file)) {
+        if is_indexable(dump_file) && index_file_exists(dump_file) {
+            if let Some((offset, _)) = find_in_index(&get_index_file_name(dump_file), title)? {
+                if let Some(page) = extract_page_at(dump_file, offset, title)? {
+                    return Ok(page);
+                }
+            }
+            continue;
+        }
+        let page = match CompressionFormat::of_file(dump_file) {
+            Some(format) => {
+                // UNWRAP: decompressors were found for all formats above
+                let (_, decompressor) = decompressors.iter().find(|(f, _)| *f == format).unwrap();
+                let mut decompressed = decompressor.open(dump_file)?;
+                let page = extract_page_from_reader(&mut BufReader::with_capacity(buf_size, &mut decompressed), title)?;
+                if page.is_none() {
+                    // if the page was found the decompressor is still running, so only check its result
+                    // after reading everything
+                    decompressed.finish()?;
+                }
+                page
+            }
+            None => extract_page_from_reader(&mut BufReader::with_capacity(buf_size, File::open(dump_file)?), title)?,
+        };
+        if let Some(page) = page {
+            return Ok(page);
+        }
+    }
+    Err(Error::PageNotFound(title.to_owned()))
+}
+
+/// Reads a page from an uncompressed or multistream dump at an offset found in its index.
+pub fn extract_page_at(dump_file: &str, offset: u64, title: &str) -> Result<Option<PageXml>> {
+    let mut reader = BufReader::with_capacity(2 * 1024 * 1024, open_dump_at_offset(dump_file, offset)?);
+    extract_page_from_reader(&mut reader, title)
+}
+
+// Pages in dumps are always formatted the same way, so a line-based scan suffices and avoids parsing the XML of all
+// other pages.
+fn extract_page_from_reader<B: BufRead>(buf_reader: &mut B, title: &str) -> Result<Option<PageXml>> {
+    let mut line: Vec<u8> = Vec::with_capacity(1024);
+    let mut page: Vec<u8> = Vec::with_capacity(1024 * 1024);
+    let mut in_page = false;
+    loop {
+        line.clear();
+        if buf_reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        let trimmed_line = line.trim_ascii();
+        if trimmed_line == b"<page>" {
+            in_page = true;
+            page.clear();
+        }
+        if !in_page {
+            continue;
+        }
+        page.extend_from_slice(&line);
+        if let Some(escaped_title) = trimmed_line
+            .strip_prefix(b"<title>")
+            .and_then(|t| t.strip_suffix(b"</title>"))
+        {
+            if quick_xml::escape::unescape(escaped_title)
+                .map_err(quick_xml::Error::EscapeError)?
+                .as_ref()
+                != title.as_bytes()
+            {
+                in_page = false;
+            }
+        } else if trimmed_line == b"</page>" {
+            return Ok(Some(parse_page_xml(page)?));
+        }
+    }
+}
+
+fn parse_page_xml(page: Vec<u8>) -> Result<PageXml> {
+    let mut text = None;
+    {
+        let mut reader = Reader::from_reader(page.as_slice());
+        let mut buf: Vec<u8> = Vec::with_capacity(1024);
+        loop {
+            match reader.read_event(&mut buf)? {
+                Event::Start(ref e) if e.local_name() == b"text" => {
+                    text = Some(read_str_and_then(&mut reader, &mut buf, "text", |text| {
+                        Ok(text.to_owned())
+                    })?);
+                }
+                Event::Eof => break,
+                _other_event => (),
+            }
+            buf.clear();
+        }
+    }
+    Ok(PageXml {
+        xml: String::from_utf8(page).map_err(|e| e.utf8_error())?,
+        text,
+    })
+}
+
+/// Returns the dump file or the dump files starting with the prefix and their total size. On Windows paths longer
+/// than `MAX_PATH`, e.g. on network shares, are returned in the extended-length form (`\\?\UNC\server\...`).
+pub fn get_dump_files(dump_file_or_prefix: &str) -> Result<(Vec<String>, u64)> {
+    let dump_file_or_prefix = &*native_path(dump_file_or_prefix);
+    let mut dump_files = Vec::new();
+    let mut total_size = 0;
+    let metadata = fs::metadata(dump_file_or_prefix);
+    match metadata {
+        Ok(metadata) => {
+            if !metadata.is_file() {
+                return Err(Error::DumpFileOrPrefixInvalid());
+            }
+            total_size += metadata.len();
+            dump_files.push(dump_file_or_prefix.to_owned());
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            // check if prefix
+            let dump_file_or_prefix_path = Path::new(dump_file_or_prefix);
+            let parent_dir = dump_file_or_prefix_path
+                .parent()
+                .filter(|path| !path.as_os_str().is_empty())
+                .map_or_else(
+                    || std::env::current_dir().map_err(Error::CouldNotGetCurrentDir),
+                    |path| Result::Ok(path.to_owned()),
+                )?;
+            if !parent_dir.is_dir() {
+                return Err(Error::DumpFileOrPrefixInvalid());
+            }
+            let prefix = dump_file_or_prefix_path
+                .file_name()
+                .ok_or(Error::DumpFileOrPrefixInvalid())?
+                .to_str()
+                .unwrap(); // UNWRAP: must be UTF-8 because split from UTF-8 path
+            for entry in fs::read_dir(parent_dir)? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                if metadata.is_file() {
+                    match (entry.file_name().to_str(), entry.path().to_str()) {
+                        (Some(utf8_file_name), Some(path))
+                            if utf8_file_name.starts_with(prefix) && !is_index_file(utf8_file_name) =>
+                        {
+                            dump_files.push(path.to_owned());
+                            total_size += metadata.len();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            // if there are multiple versions of the same file prefer plain to .zst to .7z to .bz2 to .gz
+            dump_files.sort_unstable_by(|file1, file2| {
+                let rank = |file: &str| {
+                    [".zst", ".7z", ".bz2", ".gz"]
+                        .iter()
+                        .position(|ext| file.ends_with(ext))
+                };
+                strip_compression_suffix(file1)
+                    .cmp(strip_compression_suffix(file2))
+                    .then_with(|| rank(file1).cmp(&rank(file2)))
+            });
+            let mut i = 0;
+            while i + 1 < dump_files.len() {
+                if strip_compression_suffix(dump_files[i].as_str())
+                    == strip_compression_suffix(dump_files[i + 1].as_str())
+                {
+                    let skipped_file = dump_files.remove(i + 1);
+                    total_size -= fs::metadata(skipped_file)?.len();
+                    continue;
+                }
+                i += 1;
+            }
+        }
+        Err(e) => {
+            return Err(Error::Io(e));
+        }
+    }
+    if dump_files.is_empty() {
+        return Err(Error::NoDumpFilesFound());
+    }
+    Ok((dump_files, total_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_find_in_text_ansi_result(text: &str, pattern: &str) -> String {
+        let stdout_writer = BufferWriter::stdout(ColorChoice::AlwaysAnsi);
+        let mut stdout_buffer = stdout_writer.buffer();
+        find_in_text(
+            &mut stdout_buffer,
+            "title",
+            "revision_id",
+            text.as_bytes(),
+            &Matcher::new(pattern, Engine::Auto, false).unwrap(),
+            0,
+            true,
+        )
+        .unwrap();
+        // stdout_writer.print(&stdout_buffer).unwrap();
+        std::str::from_utf8(stdout_buffer.as_slice())
+            .expect("Output is not UTF-8")
+            .to_owned()
+    }
+
+    #[test]
+    #[allow(clippy::trivial_regex)]
+    fn test_print() {
+        let text = "Abc Xyz Abc Xyz\n123 456\nAbc Xyz Abc Xyz\n";
+        assert_eq!(get_find_in_text_ansi_result(text, "Abc"),
+            "\u{1b}[0m\u{1b}[36mtitle\u{1b}[0m@\u{1b}[0m\u{1b}[33mrevision_id\n\u{1b}[0m\u{1b}[0m\u{1b}[31mAbc\u{1b}[0m Xyz \u{1b}[0m\u{1b}[31mAbc\u{1b}[0m Xyz\n\u{1b}[0m\u{1b}[31mAbc\u{1b}[0m Xyz \u{1b}[0m\u{1b}[31mAbc\u{1b}[0m Xyz\n\n"
+        );
+        assert_eq!(get_find_in_text_ansi_result(text, "^"),
+            "\u{1b}[0m\u{1b}[36mtitle\u{1b}[0m@\u{1b}[0m\u{1b}[33mrevision_id\n\u{1b}[0m\u{1b}[0m\u{1b}[31m\u{1b}[0mAbc Xyz Abc Xyz\n\n"
+        );
+        assert_eq!(get_find_in_text_ansi_result(text, "Xyz\\n"),
+            "\u{1b}[0m\u{1b}[36mtitle\u{1b}[0m@\u{1b}[0m\u{1b}[33mrevision_id\n\u{1b}[0mAbc Xyz Abc \u{1b}[0m\u{1b}[31mXyz\u{1b}[0m\nAbc Xyz Abc \u{1b}[0m\u{1b}[31mXyz\u{1b}[0m\n\n"
+        );
+        assert_eq!(
+            get_find_in_text_ansi_result(text, "\\n"),
+            "\u{1b}[0m\u{1b}[36mtitle\u{1b}[0m@\u{1b}[0m\u{1b}[33mrevision_id\n\u{1b}[0mAbc Xyz Abc Xyz\u{1b}[0m\u{1b}[31m\u{1b}[0m\n123 456\u{1b}[0m\u{1b}[31m\u{1b}[0m\nAbc Xyz Abc Xyz\u{1b}[0m\u{1b}[31m\u{1b}[0m\n\n"
+        );
+        assert_eq!(
+            get_find_in_text_ansi_result(text, "123"),
+            "\u{1b}[0m\u{1b}[36mtitle\u{1b}[0m@\u{1b}[0m\u{1b}[33mrevision_id\n\u{1b}[0m\u{1b}[0m\u{1b}[31m123\u{1b}[0m 456\n\n"
+        );
+        assert_eq!(
+            get_find_in_text_ansi_result(text, "."),
+            "\u{1b}[0m\u{1b}[36mtitle\u{1b}[0m@\u{1b}[0m\u{1b}[33mrevision_id\n\u{1b}[0m\u{1b}[0m\u{1b}[31mA\u{1b}[0m\u{1b}[0m\u{1b}[31mb\u{1b}[0m\u{1b}[0m\u{1b}[31mc\u{1b}[0m\u{1b}[0m\u{1b}[31m \u{1b}[0m\u{1b}[0m\u{1b}[31mX\u{1b}[0m\u{1b}[0m\u{1b}[31my\u{1b}[0m\u{1b}[0m\u{1b}[31mz\u{1b}[0m\u{1b}[0m\u{1b}[31m \u{1b}[0m\u{1b}[0m\u{1b}[31mA\u{1b}[0m\u{1b}[0m\u{1b}[31mb\u{1b}[0m\u{1b}[0m\u{1b}[31mc\u{1b}[0m\u{1b}[0m\u{1b}[31m \u{1b}[0m\u{1b}[0m\u{1b}[31mX\u{1b}[0m\u{1b}[0m\u{1b}[31my\u{1b}[0m\u{1b}[0m\u{1b}[31mz\u{1b}[0m\n\u{1b}[0m\u{1b}[31m1\u{1b}[0m\u{1b}[0m\u{1b}[31m2\u{1b}[0m\u{1b}[0m\u{1b}[31m3\u{1b}[0m\u{1b}[0m\u{1b}[31m \u{1b}[0m\u{1b}[0m\u{1b}[31m4\u{1b}[0m\u{1b}[0m\u{1b}[31m5\u{1b}[0m\u{1b}[0m\u{1b}[31m6\u{1b}[0m\n\u{1b}[0m\u{1b}[31mA\u{1b}[0m\u{1b}[0m\u{1b}[31mb\u{1b}[0m\u{1b}[0m\u{1b}[31mc\u{1b}[0m\u{1b}[0m\u{1b}[31m \u{1b}[0m\u{1b}[0m\u{1b}[31mX\u{1b}[0m\u{1b}[0m\u{1b}[31my\u{1b}[0m\u{1b}[0m\u{1b}[31mz\u{1b}[0m\u{1b}[0m\u{1b}[31m \u{1b}[0m\u{1b}[0m\u{1b}[31mA\u{1b}[0m\u{1b}[0m\u{1b}[31mb\u{1b}[0m\u{1b}[0m\u{1b}[31mc\u{1b}[0m\u{1b}[0m\u{1b}[31m \u{1b}[0m\u{1b}[0m\u{1b}[31mX\u{1b}[0m\u{1b}[0m\u{1b}[31my\u{1b}[0m\u{1b}[0m\u{1b}[31mz\u{1b}[0m\n\n"
+        );
+        assert_eq!(
+            get_find_in_text_ansi_result(text, ".*"),
+            "\u{1b}[0m\u{1b}[36mtitle\u{1b}[0m@\u{1b}[0m\u{1b}[33mrevision_id\n\u{1b}[0m\u{1b}[0m\u{1b}[31mAbc Xyz Abc Xyz\u{1b}[0m\n\u{1b}[0m\u{1b}[31m123 456\u{1b}[0m\n\u{1b}[0m\u{1b}[31mAbc Xyz Abc Xyz\u{1b}[0m\n\u{1b}[0m\u{1b}[31m\u{1b}[0m\n\n"
+        );
+        assert_eq!(
+            get_find_in_text_ansi_result(text, "(.|\\n)*"),
+            "\u{1b}[0m\u{1b}[36mtitle\u{1b}[0m@\u{1b}[0m\u{1b}[33mrevision_id\n\u{1b}[0m\u{1b}[0m\u{1b}[31mAbc Xyz Abc Xyz\n123 456\nAbc Xyz Abc Xyz\u{1b}[0m\n\n"
+        );
+        assert_eq!(get_find_in_text_ansi_result(text, "no_match"), "");
+    }
+
+    #[test]
+    fn test_find_match_positions() {
+        let text = "Abc äbc\nxyz\n\näbc Abc Abc\n".as_bytes();
+        let matcher = Matcher::new("Abc|\\n\\n", Engine::Regex, false).unwrap();
+        let positions: Vec<_> = find_match_positions(text, &matcher)
+            .unwrap()
+            .iter()
+            .map(|position| (position.line, position.column, &text[position.start..position.end]))
+            .collect();
+        assert_eq!(
+            positions,
+            [
+                (1, 1, "Abc".as_bytes()),
+                (2, 4, b"\n\n"),
+                (4, 5, b"Abc"),
+                (4, 9, b"Abc")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_text_windows() {
+        let escaped = "a &amp; b &#x65E5;本語 needle &lt;ref&gt; é</text>".as_bytes();
+        let mut reader = escaped;
+        let (mut escaped_window, mut window) = (Vec::new(), Vec::new());
+        let mut windows = Vec::new();
+        let bytes_read = read_text_windows(&mut reader, &mut escaped_window, &mut window, 8, 3, |text, overlap| {
+            windows.push((from_utf8(text)?.to_owned(), overlap));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(bytes_read as usize, escaped.len() - b"</text>".len());
+        assert_eq!(reader, b"</text>");
+        // entities and characters are not split, each window starts with at least three bytes of the previous one
+        let text: String = windows.iter().map(|(text, overlap)| &text[*overlap..]).collect();
+        assert_eq!(text, "a & b 日本語 needle <ref> é");
+        assert!(windows[1..].iter().all(|(_, overlap)| *overlap >= 3));
+    }
+
+    #[test]
+    fn test_chunk_size() {
+        const MIB: u64 = 1024 * 1024;
+        let mut search_options = SearchOptions::new();
+        search_options.with_thread_count(NonZeroUsize::new(4).unwrap());
+        // the total size divided by the number of threads within the default bounds of 16 MiB and 500 MiB
+        assert_eq!(search_options.chunk_size(0), 16 * MIB);
+        assert_eq!(search_options.chunk_size(10), 16 * MIB);
+        assert_eq!(search_options.chunk_size(400 * MIB), 100 * MIB);
+        assert_eq!(search_options.chunk_size(100_000 * MIB), 500 * MIB);
+        // the minimum wins if it is larger and the size is at least one byte
+        search_options.with_chunk_size_bounds(8 * MIB, 4 * MIB);
+        assert_eq!(search_options.chunk_size(400 * MIB), 8 * MIB);
+        search_options.with_chunk_size_bounds(0, 0);
+        assert_eq!(search_options.chunk_size(400 * MIB), 1);
+        // an explicit size ignores the total size
+        search_options.with_chunk_size(3 * MIB);
+        assert_eq!(search_options.chunk_size(0), 3 * MIB);
+        assert_eq!(search_options.chunk_size(100_000 * MIB), 3 * MIB);
+        search_options.with_chunk_size(0);
+        assert_eq!(search_options.chunk_size(400 * MIB), 1);
+    }
+
+    #[test]
+    fn test_split_into_parts() {
+        let directory = std::env::temp_dir().join(format!("wdgreplib-split-into-parts-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let create_file = |name: &str, len: usize| {
+            let file_name = directory.join(name).to_str().unwrap().to_owned();
+            fs::write(&file_name, vec![b'x'; len]).unwrap();
+            file_name
+        };
+        let empty = create_file("testwiki-20240101-pages-articles1.xml", 0);
+        let tiny = create_file("testwiki-20240101-pages-articles2.xml", 10);
+        let plain = create_file("testwiki-20240101-pages-articles3.xml", 1000);
+        let compressed = create_file("testwiki-20240101-pages-articles4.xml.bz2", 1000);
+        let cirrussearch = create_file("testwiki-20240101-cirrussearch-content.json", 1000);
+        let indexed = create_file("testwiki-20240101-pages-articles5.xml", 1000);
+
+        // empty and tiny files are a single part which is read to the end
+        assert_eq!(split_into_parts(&empty, 16).unwrap(), (1, 0));
+        assert_eq!(split_into_parts(&tiny, 16).unwrap(), (1, 10));
+        assert_eq!(split_into_parts(&plain, 1000).unwrap(), (1, 1000));
+        assert_eq!(split_into_parts(&plain, 999).unwrap(), (2, 500));
+        assert_eq!(split_into_parts(&plain, 300).unwrap(), (4, 250));
+        assert_eq!(split_into_parts(&plain, 1).unwrap(), (1000, 1));
+        assert_eq!(split_into_parts(&cirrussearch, 300).unwrap(), (1, 1000));
+
+        // files searched in indexed ranges, compressed files and CirrusSearch dumps are not sliced
+        let dump_files = [empty, tiny, plain, compressed, cirrussearch, indexed];
+        let mut search_ranges = vec![None; dump_files.len()];
+        search_ranges[5] = Some(vec![(0, 1000)]);
+        assert_eq!(sliced_size(&dump_files, &search_ranges).unwrap(), 1010);
+        search_ranges[5] = None;
+        assert_eq!(sliced_size(&dump_files, &search_ranges).unwrap(), 2010);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,40 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Numbers of searched and matching pages and revisions. Each worker counts the pages it reads in its scratch
+//! buffers, all revisions of a page are read by the same worker, and the counts are added up after each part.
+
+/// Numbers of searched and matching pages and revisions, see `SearchDumpResult::match_counts`.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MatchCounts {
+    /// Pages not excluded by the namespace, title or page filters, including those without revision texts. Pages
+    /// of uncompressed dumps skipped by the prefilter because they lack a literal required by the pattern are counted
+    /// as well, even if a filter would have excluded them.
+    pub pages_scanned: u64,
+    pub pages_matched: u64,
+    pub revisions_matched: u64,
+}
+
+impl MatchCounts {
+    pub(crate) fn add(&mut self, other: MatchCounts) {
+        self.pages_scanned += other.pages_scanned;
+        self.pages_matched += other.pages_matched;
+        self.revisions_matched += other.revisions_matched;
+    }
+
+    /// Counts a page read by the worker with the number of its matching revisions, `searched` is false if it was
+    /// excluded by a filter.
+    pub(crate) fn add_page(&mut self, searched: bool, revisions_matched: u64) {
+        if searched {
+            self.pages_scanned += 1;
+        }
+        if revisions_matched > 0 {
+            self.pages_matched += 1;
+            self.revisions_matched += revisions_matched;
+        }
+    }
+}
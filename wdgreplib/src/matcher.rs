@@ -0,0 +1,361 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Matching of the search pattern in revision texts. Patterns which are just a list of alternative plain literals
+//! (e.g. `foo|bar|baz`) are matched with Aho-Corasick, which is much faster than the regex engine for long lists.
+//! With the `pcre2` feature PCRE2 can be selected for patterns with lookaround or backreferences.
+
+use std::fmt;
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use regex::bytes::{Regex, RegexBuilder};
+use regex_syntax::hir::{Class, Hir, HirKind, Literal, RepetitionKind, RepetitionRange};
+
+use crate::{Error, Result};
+
+/// The engine used to match the search pattern.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// Aho-Corasick if the pattern consists only of plain literals and no capture groups are needed, otherwise regex
+    Auto,
+    Regex,
+    AhoCorasick,
+    /// Supports lookaround and backreferences, but not replacements
+    #[cfg(feature = "pcre2")]
+    Pcre2,
+}
+
+pub(crate) enum Matcher {
+    Regex(Regex),
+    Literals(Box<AhoCorasick>),
+    #[cfg(feature = "pcre2")]
+    Pcre2(pcre2::bytes::Regex),
+}
+
+pub(crate) enum Matches<'m, 't> {
+    Regex(regex::bytes::Matches<'m, 't>),
+    Literals(aho_corasick::FindIter<'m, 't, usize>),
+    #[cfg(feature = "pcre2")]
+    Pcre2(pcre2::bytes::Matches<'m, 't>),
+}
+
+impl<'m, 't> Iterator for Matches<'m, 't> {
+    type Item = Result<Range<usize>>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Result<Range<usize>>> {
+        match self {
+            Matches::Regex(matches) => matches.next().map(|m| Ok(m.start()..m.end())),
+            Matches::Literals(matches) => matches.next().map(|m| Ok(m.start()..m.end())),
+            // PCRE2 may fail while matching, e.g. if the backtracking limit is exceeded
+            #[cfg(feature = "pcre2")]
+            Matches::Pcre2(matches) => matches.next().map(|m| Ok(m.map(|m| m.start()..m.end())?)),
+        }
+    }
+}
+
+impl Matcher {
+    /// Replacements referencing capture groups need the regex engine.
+    pub(crate) fn new(pattern: &str, engine: Engine, needs_captures: bool) -> Result<Matcher> {
+        let use_literals = match engine {
+            Engine::Regex => false,
+            Engine::AhoCorasick if needs_captures => return Err(Error::CapturesNeedRegexEngine()),
+            Engine::AhoCorasick => true,
+            #[cfg(feature = "pcre2")]
+            Engine::Pcre2 if needs_captures => return Err(Error::CapturesNeedRegexEngine()),
+            #[cfg(feature = "pcre2")]
+            Engine::Pcre2 => {
+                return Ok(Matcher::Pcre2(
+                    pcre2::bytes::RegexBuilder::new()
+                        .utf(true)
+                        .ucp(true)
+                        .jit_if_available(true)
+                        .build(pattern)?,
+                ))
+            }
+            Engine::Auto => !needs_captures,
+        };
+        if use_literals {
+            match get_literals(pattern) {
+                // leftmost-first matches the semantics of an alternation in a regex
+                Some(literals) => {
+                    return Ok(Matcher::Literals(Box::new(
+                        AhoCorasickBuilder::new()
+                            .match_kind(MatchKind::LeftmostFirst)
+                            .build(literals),
+                    )))
+                }
+                None if engine == Engine::AhoCorasick => return Err(Error::PatternNotLiteral(pattern.to_owned())),
+                None => {}
+            }
+        }
+        Ok(Matcher::Regex(RegexBuilder::new(pattern).build()?))
+    }
+
+    /// Returns the regex if the regex engine is used.
+    pub(crate) fn regex(&self) -> Option<&Regex> {
+        match self {
+            Matcher::Regex(re) => Some(re),
+            Matcher::Literals(_) => None,
+            #[cfg(feature = "pcre2")]
+            Matcher::Pcre2(_) => None,
+        }
+    }
+
+    /// Returns the maximum length of the matches in bytes or None if it is unbounded or unknown.
+    pub(crate) fn max_match_len(&self) -> Option<usize> {
+        match self {
+            Matcher::Regex(re) => max_match_len(&regex_syntax::Parser::new().parse(re.as_str()).ok()?),
+            Matcher::Literals(ac) => Some(ac.max_pattern_len()),
+            #[cfg(feature = "pcre2")]
+            Matcher::Pcre2(_) => None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_match(&self, text: &[u8]) -> Result<bool> {
+        match self {
+            Matcher::Regex(re) => Ok(re.is_match(text)),
+            Matcher::Literals(ac) => Ok(ac.is_match(text)),
+            #[cfg(feature = "pcre2")]
+            Matcher::Pcre2(re) => Ok(re.is_match(text)?),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn find_iter<'m, 't>(&'m self, text: &'t [u8]) -> Matches<'m, 't> {
+        match self {
+            Matcher::Regex(re) => Matches::Regex(re.find_iter(text)),
+            Matcher::Literals(ac) => Matches::Literals(ac.find_iter(text)),
+            #[cfg(feature = "pcre2")]
+            Matcher::Pcre2(re) => Matches::Pcre2(re.find_iter(text)),
+        }
+    }
+}
+
+/// Returns the maximum length in bytes of the text matched by the expression or None if it is unbounded.
+fn max_match_len(hir: &Hir) -> Option<usize> {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Anchor(_) | HirKind::WordBoundary(_) => Some(0),
+        HirKind::Literal(Literal::Unicode(c)) => Some(c.len_utf8()),
+        HirKind::Literal(Literal::Byte(_)) => Some(1),
+        // the ranges are sorted, so the last one contains the longest characters
+        HirKind::Class(Class::Unicode(class)) => Some(class.iter().last().map_or(0, |range| range.end().len_utf8())),
+        HirKind::Class(Class::Bytes(_)) => Some(1),
+        HirKind::Repetition(repetition) => {
+            let max_count = match &repetition.kind {
+                RepetitionKind::ZeroOrOne => 1,
+                RepetitionKind::Range(RepetitionRange::Exactly(n) | RepetitionRange::Bounded(_, n)) => *n,
+                RepetitionKind::ZeroOrMore | RepetitionKind::OneOrMore | RepetitionKind::Range(_) => return None,
+            };
+            max_match_len(&repetition.hir)?.checked_mul(max_count as usize)
+        }
+        HirKind::Group(group) => max_match_len(&group.hir),
+        HirKind::Concat(hirs) => hirs
+            .iter()
+            .try_fold(0_usize, |len, hir| len.checked_add(max_match_len(hir)?)),
+        HirKind::Alternation(hirs) => hirs.iter().try_fold(0, |len, hir| Some(len.max(max_match_len(hir)?))),
+    }
+}
+
+/// Appends the bytes of a literal, returns false if the expression is not a plain literal.
+fn append_literal(hir: &Hir, literal: &mut Vec<u8>) -> bool {
+    match hir.kind() {
+        HirKind::Literal(Literal::Unicode(c)) => {
+            literal.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            true
+        }
+        HirKind::Literal(Literal::Byte(b)) => {
+            literal.push(*b);
+            true
+        }
+        HirKind::Concat(hirs) => hirs.iter().all(|hir| append_literal(hir, literal)),
+        // captures are not needed here
+        HirKind::Group(group) => append_literal(&group.hir, literal),
+        _ => false,
+    }
+}
+
+/// Returns the alternatives of the pattern if all of them are non-empty plain literals.
+fn get_literals(pattern: &str) -> Option<Vec<Vec<u8>>> {
+    let hir = regex_syntax::Parser::new().parse(pattern).ok()?;
+    let alternatives = match hir.kind() {
+        HirKind::Alternation(hirs) => hirs.iter().collect(),
+        _ => vec![&hir],
+    };
+    alternatives
+        .into_iter()
+        .map(|hir| {
+            let mut literal = Vec::new();
+            (append_literal(hir, &mut literal) && !literal.is_empty()).then_some(literal)
+        })
+        .collect()
+}
+
+fn longest_required_literal(hir: &Hir) -> Option<Vec<u8>> {
+    match hir.kind() {
+        HirKind::Group(group) => longest_required_literal(&group.hir),
+        HirKind::Concat(hirs) => {
+            let mut longest = Vec::new();
+            let mut current = Vec::new();
+            for hir in hirs {
+                let mut literal = Vec::new();
+                if append_literal(hir, &mut literal) {
+                    current.extend_from_slice(&literal);
+                } else {
+                    current.clear();
+                    // a literal required inside of the subexpression cannot be extended by its neighbours
+                    if let Some(required) = longest_required_literal(hir).filter(|r| r.len() > longest.len()) {
+                        longest = required;
+                    }
+                }
+                if current.len() > longest.len() {
+                    longest.clone_from(&current);
+                }
+            }
+            Some(longest)
+        }
+        _ => {
+            let mut literal = Vec::new();
+            append_literal(hir, &mut literal).then_some(literal)
+        }
+    }
+}
+
+/// Returns the longest plain literal contained in every match of the pattern, e.g. `foo` for `\bfoo\d+`. Literals
+/// with characters escaped in XML are not returned since they are not found verbatim in the dump.
+pub(crate) fn get_required_literal(pattern: &str) -> Option<Vec<u8>> {
+    let hir = regex_syntax::Parser::new().parse(pattern).ok()?;
+    longest_required_literal(&hir).filter(|literal| {
+        !literal.is_empty() && !literal.iter().any(|c| matches!(c, b'&' | b'<' | b'>' | b'"' | b'\''))
+    })
+}
+
+/// A property of a search pattern which likely keeps it from finding what was intended.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum PatternWarning {
+    /// Every revision matches
+    MatchesEmpty,
+    /// The pattern starts or ends with an unanchored `.*`, which only extends the matches to the whole line
+    UnanchoredAnyRepetition,
+    /// The pattern contains an XML entity and its unescaped character, the text is matched after unescaping
+    XmlEntity(String, char),
+}
+
+impl fmt::Display for PatternWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternWarning::MatchesEmpty => write!(
+                f,
+                "The search term matches the empty string, so every revision matches."
+            ),
+            PatternWarning::UnanchoredAnyRepetition => write!(
+                f,
+                "The search term starts or ends with an unanchored .*, which slows down the search and only extends \
+                 the matches to the whole line."
+            ),
+            PatternWarning::XmlEntity(entity, c) => write!(
+                f,
+                "The search term contains the XML entity {entity}, but the text is searched unescaped, use {c} instead."
+            ),
+        }
+    }
+}
+
+/// Returns true for `.` and other classes matching (almost) any character.
+fn is_any_class(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Class(Class::Unicode(class)) => {
+            let len: u32 = class
+                .iter()
+                .map(|range| range.end() as u32 - range.start() as u32 + 1)
+                .sum();
+            // all scalar values except the surrogates and the newline
+            len >= 0x110000 - 0x800 - 1
+        }
+        HirKind::Class(Class::Bytes(class)) => {
+            let len: u32 = class
+                .iter()
+                .map(|range| range.end() as u32 - range.start() as u32 + 1)
+                .sum();
+            len >= 255
+        }
+        _ => false,
+    }
+}
+
+fn is_any_repetition(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Repetition(repetition) => repetition.is_match_empty() && is_any_class(&repetition.hir),
+        HirKind::Group(group) => is_any_repetition(&group.hir),
+        _ => false,
+    }
+}
+
+/// Checks the pattern for common mistakes. Patterns not supported by the regex engine are only checked for XML
+/// entities.
+pub fn check_pattern(pattern: &str) -> Vec<PatternWarning> {
+    let mut warnings = Vec::new();
+    if let Ok(hir) = regex_syntax::Parser::new().parse(pattern) {
+        if hir.is_match_empty() {
+            warnings.push(PatternWarning::MatchesEmpty);
+        } else if let HirKind::Concat(hirs) = hir.kind() {
+            if hirs.first().is_some_and(is_any_repetition) || hirs.last().is_some_and(is_any_repetition) {
+                warnings.push(PatternWarning::UnanchoredAnyRepetition);
+            }
+        }
+    }
+    // UNWRAP: constant
+    let entity_re = regex::Regex::new(r"&(lt|gt|amp|quot|apos|#[0-9]+|#x[0-9a-fA-F]+);").unwrap();
+    for cap in entity_re.captures_iter(pattern) {
+        let c = match &cap[1] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            name => match name.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => name[1..].parse().ok(),
+            }
+            .and_then(char::from_u32),
+        };
+        if let Some(c) = c {
+            let warning = PatternWarning::XmlEntity(cap[0].to_owned(), c);
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_required_literal() {
+        let literal = |pattern| get_required_literal(pattern).map(|literal| String::from_utf8(literal).unwrap());
+        assert_eq!(literal(r"\bfoo\d+"), Some("foo".to_owned()));
+        assert_eq!(literal(r"ab\d+needle(s|es)"), Some("needle".to_owned()));
+        // escaped metacharacters are part of the literal
+        assert_eq!(literal(r"foo\.bar\(\d"), Some("foo.bar(".to_owned()));
+        assert_eq!(literal(r"\[\[Category:"), Some("[[Category:".to_owned()));
+        // not every match contains any of the alternatives
+        assert_eq!(literal("foo|bar"), None);
+        assert_eq!(literal("(?:needle|haystack)"), None);
+        // case-insensitive letters are not literals, other characters are
+        assert_eq!(literal("(?i)needle"), None);
+        assert_eq!(literal("(?i)needle 2024"), Some(" 2024".to_owned()));
+        assert_eq!(literal("(?i:needle)haystack"), Some("haystack".to_owned()));
+        // escaped in the dump
+        assert_eq!(literal("a<ref>"), None);
+        assert_eq!(literal(".*"), None);
+        assert_eq!(literal("(unclosed"), None);
+    }
+}
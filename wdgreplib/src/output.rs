@@ -0,0 +1,346 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Coordination of the output of the worker threads. The output of a page is never interleaved with the output of
+//! other pages. Optionally parts are printed in the order of the dump files instead of as soon as possible, and the
+//! output is split into one file per namespace instead of being printed to stdout or compressed with gzip. The
+//! number of printed results can be limited to the first or last ones.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use termcolor::{Buffer, BufferWriter, ColorChoice};
+
+/// Output of a single page larger than this is printed while the page is still being processed, the output of
+/// other threads waits until the page is finished.
+const PAGE_OUTPUT_FLUSH_THRESHOLD: usize = 4 * 1024 * 1024;
+
+/// Output of a part as the namespaces of its pages and their output with the end of the output of each revision, a
+/// single one unless the output is split by namespace.
+type NamespaceBuffers = Vec<(String, Buffer, Vec<usize>)>;
+
+/// Limits the printed results, the output of each revision or, if the output is grouped by page, of each page. The
+/// search is still completed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputLimit {
+    /// Only the first results are printed
+    Head(u64),
+    /// Only the last results are printed when the search is finished
+    Tail(u64),
+}
+
+/// Results printed so far or kept until the end.
+#[derive(Default)]
+struct LimitedOutput {
+    printed: u64,
+    // namespace and output of the last results
+    tail: VecDeque<(String, Vec<u8>)>,
+}
+
+/// Output of parts finished before all previous parts, only used for ordered output.
+struct PendingParts {
+    next_part: usize,
+    finished_parts: BTreeMap<usize, NamespaceBuffers>,
+    // parts whose output has been printed completely since they were last taken
+    printed_parts: Vec<usize>,
+}
+
+/// Files written to when splitting the output by namespace, opened when the first output for their namespace is
+/// printed.
+struct NamespaceFiles {
+    directory: PathBuf,
+    files: HashMap<String, BufWriter<File>>,
+    // the first write error, returned when finishing
+    error: Option<io::Error>,
+}
+
+impl NamespaceFiles {
+    fn write(&mut self, namespace: &str, buffer: &Buffer) {
+        if self.error.is_some() {
+            return;
+        }
+        let res = match self.files.get_mut(namespace) {
+            Some(file) => file.write_all(buffer.as_slice()),
+            None => File::create(self.directory.join(format!("{namespace}.txt"))).and_then(|file| {
+                let file = self.files.entry(namespace.to_owned()).or_insert(BufWriter::new(file));
+                file.write_all(buffer.as_slice())
+            }),
+        };
+        self.error = res.err();
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.files.values_mut().try_for_each(Write::flush)
+    }
+}
+
+pub(crate) struct OutputCoordinator {
+    stdout_writer: BufferWriter,
+    ordered: bool,
+    namespace_files: Option<Mutex<NamespaceFiles>>,
+    compressed_stdout: Option<Mutex<GzEncoder<BufWriter<io::Stdout>>>>,
+    limit: Option<OutputLimit>,
+    limited_output: Mutex<LimitedOutput>,
+    // held while printing, so it also serializes the output of pages printed in several steps
+    pending_parts: Mutex<PendingParts>,
+}
+
+impl OutputCoordinator {
+    pub(crate) fn stdout(color_choice: ColorChoice, ordered: bool) -> OutputCoordinator {
+        OutputCoordinator {
+            stdout_writer: BufferWriter::stdout(color_choice),
+            ordered,
+            namespace_files: None,
+            compressed_stdout: None,
+            limit: None,
+            limited_output: Mutex::new(LimitedOutput::default()),
+            pending_parts: Mutex::new(PendingParts {
+                next_part: 0,
+                finished_parts: BTreeMap::new(),
+                printed_parts: Vec::new(),
+            }),
+        }
+    }
+
+    /// Writes the output of the pages of each namespace to `<namespace>.txt` in the directory, which is created if
+    /// it does not exist. The output is not colored.
+    pub(crate) fn split_by_namespace(directory: &Path, ordered: bool) -> io::Result<OutputCoordinator> {
+        fs::create_dir_all(directory)?;
+        Ok(OutputCoordinator {
+            namespace_files: Some(Mutex::new(NamespaceFiles {
+                directory: directory.to_owned(),
+                files: HashMap::new(),
+                error: None,
+            })),
+            ..OutputCoordinator::stdout(ColorChoice::Never, ordered)
+        })
+    }
+
+    /// Prints the output compressed with gzip, the output is not colored.
+    pub(crate) fn compressed_stdout(ordered: bool) -> OutputCoordinator {
+        OutputCoordinator {
+            compressed_stdout: Some(Mutex::new(GzEncoder::new(
+                BufWriter::new(io::stdout()),
+                Compression::default(),
+            ))),
+            ..OutputCoordinator::stdout(ColorChoice::Never, ordered)
+        }
+    }
+
+    pub(crate) fn limit_results(&mut self, limit: Option<OutputLimit>) {
+        self.limit = limit;
+    }
+
+    /// Returns the output for a part of a dump, parts are numbered consecutively in the order of the dump files.
+    pub(crate) fn part_output(&self, part: usize) -> PartOutput<'_> {
+        PartOutput {
+            coordinator: self,
+            part,
+            namespace: String::new(),
+            buffer: self.stdout_writer.buffer(),
+            result_ends: Vec::new(),
+            previous_buffers: Vec::new(),
+            guard: None,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PendingParts> {
+        // UNWRAP: printing does not panic while holding the lock
+        self.pending_parts.lock().unwrap()
+    }
+
+    fn print(&self, namespace: &str, buffer: &Buffer) {
+        match (&self.namespace_files, &self.compressed_stdout) {
+            (Some(namespace_files), _) => namespace_files.lock().unwrap().write(namespace, buffer),
+            (None, Some(compressed_stdout)) => compressed_stdout.lock().unwrap().write_all(buffer.as_slice()).unwrap(),
+            (None, None) => self.stdout_writer.print(buffer).unwrap(),
+        }
+    }
+
+    fn print_bytes(&self, namespace: &str, bytes: &[u8]) {
+        let mut buffer = self.stdout_writer.buffer();
+        // UNWRAP: writing to a buffer does not fail
+        buffer.write_all(bytes).unwrap();
+        self.print(namespace, &buffer);
+    }
+
+    /// Prints the results in the buffer, which end at the given positions and at the end of the buffer, unless the
+    /// limit has been reached.
+    fn print_results(&self, namespace: &str, buffer: &Buffer, result_ends: &[usize]) {
+        let Some(limit) = self.limit else {
+            return self.print(namespace, buffer);
+        };
+        let output = buffer.as_slice();
+        let mut limited_output = self.limited_output.lock().unwrap();
+        let mut start = 0;
+        for end in result_ends.iter().copied().chain([output.len()]) {
+            let result = &output[start..end];
+            start = end;
+            if result.is_empty() {
+                continue;
+            }
+            match limit {
+                OutputLimit::Head(count) if limited_output.printed < count => {
+                    limited_output.printed += 1;
+                    self.print_bytes(namespace, result);
+                }
+                OutputLimit::Head(_) => {}
+                OutputLimit::Tail(count) => {
+                    limited_output.tail.push_back((namespace.to_owned(), result.to_vec()));
+                    if limited_output.tail.len() as u64 > count {
+                        limited_output.tail.pop_front();
+                    }
+                }
+            }
+        }
+    }
+
+    fn print_all(&self, buffers: &NamespaceBuffers) {
+        for (namespace, buffer, result_ends) in buffers {
+            self.print_results(namespace, buffer, result_ends);
+        }
+    }
+
+    /// Returns the parts whose output has been printed completely since the last call and flushes the output, e.g. to
+    /// record them in a checkpoint.
+    pub(crate) fn take_printed_parts(&self) -> io::Result<Vec<usize>> {
+        let printed_parts = std::mem::take(&mut self.lock().printed_parts);
+        match (&self.namespace_files, &self.compressed_stdout) {
+            (Some(namespace_files), _) => namespace_files.lock().unwrap().flush()?,
+            // the compressed output so far can be decompressed completely
+            (None, Some(compressed_stdout)) => compressed_stdout.lock().unwrap().flush()?,
+            (None, None) => io::stdout().flush()?,
+        }
+        Ok(printed_parts)
+    }
+
+    /// Prints the output of all parts which have not been printed yet, e.g. of parts skipped after an error, and the
+    /// last results if only those are printed, then flushes the namespace files or finishes the compressed output.
+    pub(crate) fn finish(&self) -> io::Result<()> {
+        let mut pending_parts = self.lock();
+        for buffers in std::mem::take(&mut pending_parts.finished_parts).into_values() {
+            self.print_all(&buffers);
+        }
+        let tail = std::mem::take(&mut self.limited_output.lock().unwrap().tail);
+        for (namespace, result) in tail {
+            self.print_bytes(&namespace, &result);
+        }
+        match (&self.namespace_files, &self.compressed_stdout) {
+            (Some(namespace_files), _) => namespace_files.lock().unwrap().flush(),
+            (None, Some(compressed_stdout)) => {
+                let mut compressed_stdout = compressed_stdout.lock().unwrap();
+                compressed_stdout.try_finish()?;
+                compressed_stdout.get_mut().flush()
+            }
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+pub(crate) struct PartOutput<'a> {
+    coordinator: &'a OutputCoordinator,
+    part: usize,
+    // namespace of the current page, only set when splitting the output by namespace
+    namespace: String,
+    buffer: Buffer,
+    // end of the output of each revision in the buffer
+    result_ends: Vec<usize>,
+    // output of previous pages in other namespaces not printed yet
+    previous_buffers: NamespaceBuffers,
+    // kept after printing the beginning of a large page until the page is finished
+    guard: Option<MutexGuard<'a, PendingParts>>,
+}
+
+impl<'a> PartOutput<'a> {
+    pub(crate) fn buffer(&mut self) -> &mut Buffer {
+        &mut self.buffer
+    }
+
+    /// Sets the namespace of the current page, called before any output for the page is written.
+    pub(crate) fn set_namespace(&mut self, namespace: &str) {
+        if self.coordinator.namespace_files.is_none() || self.namespace == namespace {
+            return;
+        }
+        if !self.buffer.is_empty() {
+            let buffer = std::mem::replace(&mut self.buffer, self.coordinator.stdout_writer.buffer());
+            self.previous_buffers.push((
+                std::mem::take(&mut self.namespace),
+                buffer,
+                std::mem::take(&mut self.result_ends),
+            ));
+        }
+        namespace.clone_into(&mut self.namespace);
+    }
+
+    /// Prints the output of the current page so far if it got too large to keep it in memory.
+    pub(crate) fn revision_finished(&mut self) {
+        if self.buffer.len() > self.result_ends.last().copied().unwrap_or(0) {
+            self.result_ends.push(self.buffer.len());
+        }
+        if !self.coordinator.ordered && self.buffer.len() >= PAGE_OUTPUT_FLUSH_THRESHOLD {
+            self.print();
+        }
+    }
+
+    pub(crate) fn page_finished(&mut self) {
+        if self.coordinator.ordered {
+            return;
+        }
+        if !self.buffer.is_empty() || !self.previous_buffers.is_empty() {
+            self.print();
+        }
+        self.guard = None;
+    }
+
+    fn print(&mut self) {
+        let coordinator = self.coordinator;
+        self.guard.get_or_insert_with(|| coordinator.lock());
+        coordinator.print_all(&self.previous_buffers);
+        self.previous_buffers.clear();
+        coordinator.print_results(&self.namespace, &self.buffer, &self.result_ends);
+        self.buffer.clear();
+        self.result_ends.clear();
+    }
+
+    /// Prints the remaining output, with ordered output also the output of following parts already finished.
+    pub(crate) fn finish(mut self) {
+        if !self.coordinator.ordered {
+            self.page_finished();
+            self.coordinator.lock().printed_parts.push(self.part);
+            return;
+        }
+        let coordinator = self.coordinator;
+        let mut buffers = self.previous_buffers;
+        buffers.push((self.namespace, self.buffer, self.result_ends));
+        let mut pending_parts = coordinator.lock();
+        if self.part != pending_parts.next_part {
+            pending_parts.finished_parts.insert(self.part, buffers);
+            return;
+        }
+        coordinator.print_all(&buffers);
+        pending_parts.printed_parts.push(self.part);
+        pending_parts.next_part += 1;
+        loop {
+            let next_part = pending_parts.next_part;
+            match pending_parts.finished_parts.remove(&next_part) {
+                Some(buffers) => {
+                    coordinator.print_all(&buffers);
+                    pending_parts.printed_parts.push(next_part);
+                    pending_parts.next_part += 1;
+                }
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Lists of pages to restrict the search to, e.g. the pages found by an earlier search.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::title::Namespaces;
+use crate::Result;
+
+/// Pages given by their normalized titles or by their page ids.
+pub enum PageList {
+    Titles(HashSet<String>),
+    PageIds(HashSet<u64>),
+}
+
+impl PageList {
+    /// Reads a list with one title or page id per line, empty lines are ignored. The list contains page ids if all
+    /// lines are numbers, otherwise titles, which are normalized with the namespaces of the wiki to match the titles
+    /// in the dump.
+    pub fn from_file(file: &Path, namespaces: &Namespaces) -> Result<PageList> {
+        Ok(PageList::parse(&fs::read_to_string(file)?, namespaces))
+    }
+
+    pub fn parse(list: &str, namespaces: &Namespaces) -> PageList {
+        let lines = list.lines().map(str::trim).filter(|line| !line.is_empty());
+        let page_ids: Option<HashSet<u64>> = lines.clone().map(|line| line.parse().ok()).collect();
+        match page_ids {
+            Some(page_ids) if !page_ids.is_empty() => PageList::PageIds(page_ids),
+            _ => PageList::Titles(
+                lines
+                    .map(|title| namespaces.normalize_title(title).as_str().to_owned())
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            PageList::Titles(titles) => titles.len(),
+            PageList::PageIds(page_ids) => page_ids.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns false if the page is not in a list of titles.
+    pub(crate) fn contains_title(&self, title: &str) -> bool {
+        match self {
+            PageList::Titles(titles) => titles.contains(title),
+            PageList::PageIds(_) => true,
+        }
+    }
+
+    /// Returns false if the page is not in a list of page ids.
+    pub(crate) fn contains_page_id(&self, page_id: u64) -> bool {
+        match self {
+            PageList::Titles(_) => true,
+            PageList::PageIds(page_ids) => page_ids.contains(&page_id),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Windows paths longer than `MAX_PATH` are only accepted by decompressors and other programs in the extended-length
+//! form starting with `\\?\`. This also applies to dumps on network shares (`\\server\dumps\...`), whose UNC paths
+//! get long quickly.
+
+use std::borrow::Cow;
+
+/// Including the terminating null character.
+const MAX_PATH: usize = 260;
+const EXTENDED_LENGTH_PREFIX: &str = r"\\?\";
+const UNC_EXTENDED_LENGTH_PREFIX: &str = r"\\?\UNC\";
+
+fn is_separator(c: char) -> bool {
+    c == '\\' || c == '/'
+}
+
+/// Returns the extended-length form of an absolute Windows path: `\\?\C:\dumps\...` for paths with a drive letter and
+/// `\\?\UNC\server\share\...` for UNC paths. Forward slashes are replaced with backslashes since they are not
+/// separators in this form. Relative paths, paths with `.` or `..` components, which are not resolved in this form,
+/// and device paths are returned unchanged.
+pub(crate) fn to_extended_length_path(path: &str) -> Cow<'_, str> {
+    if path.starts_with(EXTENDED_LENGTH_PREFIX) || path.starts_with(r"\\.\") {
+        return Cow::Borrowed(path);
+    }
+    if path
+        .split(is_separator)
+        .any(|component| component == "." || component == "..")
+    {
+        return Cow::Borrowed(path);
+    }
+    let mut chars = path.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(drive), Some(':'), Some(separator)) if drive.is_ascii_alphabetic() && is_separator(separator) => {
+            Cow::Owned(format!("{EXTENDED_LENGTH_PREFIX}{}", path.replace('/', "\\")))
+        }
+        (Some(first), Some(second), Some(_)) if is_separator(first) && is_separator(second) => {
+            Cow::Owned(format!("{UNC_EXTENDED_LENGTH_PREFIX}{}", path[2..].replace('/', "\\")))
+        }
+        _ => Cow::Borrowed(path),
+    }
+}
+
+/// Returns the path to open and to pass to subprocesses, in the extended-length form on Windows if it is too long
+/// for the regular form.
+pub(crate) fn native_path(path: &str) -> Cow<'_, str> {
+    if cfg!(windows) && path.len() >= MAX_PATH {
+        to_extended_length_path(path)
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_extended_length_path() {
+        assert_eq!(
+            to_extended_length_path(r"C:\dumps\enwiki-20240101-pages-articles.xml.bz2"),
+            r"\\?\C:\dumps\enwiki-20240101-pages-articles.xml.bz2"
+        );
+        assert_eq!(
+            to_extended_length_path("d:/dumps/enwiki-20240101-pages-articles.xml"),
+            r"\\?\d:\dumps\enwiki-20240101-pages-articles.xml"
+        );
+        assert_eq!(
+            to_extended_length_path(r"\\server\dumps\enwiki-20240101-pages-articles.xml.bz2"),
+            r"\\?\UNC\server\dumps\enwiki-20240101-pages-articles.xml.bz2"
+        );
+        assert_eq!(
+            to_extended_length_path("//server/dumps/enwiki-"),
+            r"\\?\UNC\server\dumps\enwiki-"
+        );
+        for unchanged in [
+            r"\\?\C:\dumps\enwiki-",
+            r"\\?\UNC\server\dumps\enwiki-",
+            r"\\.\pipe\dump",
+            r"dumps\enwiki-",
+            r"C:dumps\enwiki-",
+            r"C:\dumps\..\enwiki-",
+            r"\\server\dumps\.\enwiki-",
+            "/srv/dumps/enwiki-",
+        ] {
+            assert_eq!(to_extended_length_path(unchanged), unchanged);
+        }
+    }
+
+    #[test]
+    fn test_native_path() {
+        let short_path = r"\\server\dumps\enwiki-20240101-pages-articles.xml.bz2";
+        assert_eq!(native_path(short_path), short_path);
+        let long_path = format!(
+            r"\\server\dumps\{}\enwiki-20240101-pages-articles.xml.bz2",
+            "d".repeat(250)
+        );
+        if cfg!(windows) {
+            assert_eq!(native_path(&long_path), format!(r"\\?\UNC{}", &long_path[1..]));
+        } else {
+            assert_eq!(native_path(&long_path), long_path);
+        }
+    }
+}
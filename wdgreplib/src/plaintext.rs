@@ -0,0 +1,160 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Approximate conversion of wikitext to plain text in a single pass, so that regular expressions can be written
+//! against the text readers see instead of the markup.
+
+use memchr::{memchr, memmem};
+
+/// Returns the length of the construct starting with `open` at the start of the text up to and including the
+/// matching `close`, or the length of the text if it is not closed.
+fn find_closing(text: &[u8], open: &[u8], close: &[u8]) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        if rest.starts_with(open) {
+            depth += 1;
+            i += open.len();
+        } else if rest.starts_with(close) {
+            depth -= 1;
+            i += close.len();
+            if depth == 0 {
+                return i;
+            }
+        } else if open != b"{{" && rest.starts_with(b"{{") {
+            // templates in tables may end with |}}
+            i += find_closing(rest, b"{{", b"}}");
+        } else {
+            i += 1;
+        }
+    }
+    text.len()
+}
+
+/// Returns the label of the inner part of an internal link: the part after the last `|` outside of nested links
+/// (e.g. in image captions) or the link target.
+fn get_link_label(link: &[u8]) -> &[u8] {
+    let mut depth = 0;
+    let mut label_start = 0;
+    let mut i = 0;
+    while i < link.len() {
+        if link[i..].starts_with(b"[[") {
+            depth += 1;
+            i += 2;
+        } else if link[i..].starts_with(b"]]") {
+            depth -= 1;
+            i += 2;
+        } else {
+            if link[i] == b'|' && depth == 0 {
+                label_start = i + 1;
+            }
+            i += 1;
+        }
+    }
+    let label = &link[label_start..];
+    label.strip_prefix(b":").unwrap_or(label)
+}
+
+fn is_external_link(text: &[u8]) -> bool {
+    let url = &text[1..];
+    [&b"http://"[..], b"https://", b"//", b"ftp://", b"mailto:"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+/// Appends a plain text version of the wikitext: templates, tables, comments and HTML tags (not their content) are
+/// removed, internal links are reduced to their label, external links to their description and bold and italic
+/// quotes are dropped.
+pub(crate) fn strip_markup(text: &[u8], out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        match rest[0] {
+            b'{' if rest.starts_with(b"{{") => i += find_closing(rest, b"{{", b"}}"),
+            b'{' if rest.starts_with(b"{|") => i += find_closing(rest, b"{|", b"|}"),
+            b'[' if rest.starts_with(b"[[") => {
+                let len = find_closing(rest, b"[[", b"]]");
+                let inner = &rest[2..len];
+                let inner = inner.strip_suffix(b"]]").unwrap_or(inner);
+                strip_markup(get_link_label(inner), out);
+                i += len;
+            }
+            b'[' if is_external_link(rest) => {
+                let len = memchr(b']', rest).map_or(rest.len(), |pos| pos + 1);
+                let link = rest[1..len].strip_suffix(b"]").unwrap_or(&rest[1..len]);
+                if let Some(pos) = memchr(b' ', link) {
+                    strip_markup(&link[pos + 1..], out);
+                }
+                i += len;
+            }
+            b'\'' if rest.starts_with(b"''") => {
+                i += rest.iter().take_while(|&&c| c == b'\'').count();
+            }
+            b'<' if rest.starts_with(b"<!--") => {
+                i += memmem::find(rest, b"-->").map_or(rest.len(), |pos| pos + 3);
+            }
+            b'<' if rest.get(1).is_some_and(|&c| c.is_ascii_alphabetic() || c == b'/') => {
+                i += memchr(b'>', rest).map_or(rest.len(), |pos| pos + 1);
+            }
+            _ => {
+                // copy everything up to the next character which may start markup
+                let len = rest[1..]
+                    .iter()
+                    .position(|c| matches!(c, b'{' | b'[' | b'\'' | b'<'))
+                    .map_or(rest.len(), |pos| pos + 1);
+                out.extend_from_slice(&rest[..len]);
+                i += len;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(text: &str) -> String {
+        let mut out = Vec::new();
+        strip_markup(text.as_bytes(), &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_strip_markup() {
+        // templates
+        assert_eq!(strip("a {{Infobox|x={{nested|{{deeper}}}}|y=1}} b"), "a  b");
+        // links
+        assert_eq!(strip("[[Target]] and [[Target|label]]"), "Target and label");
+        assert_eq!(strip("[[:Category:Example]]"), "Category:Example");
+        assert_eq!(
+            strip("[[File:X.jpg|thumb|A [[Target|caption]] with '''bold''']]"),
+            "A caption with bold"
+        );
+        assert_eq!(
+            strip("[https://example.org the description] [//example.org]"),
+            "the description "
+        );
+        // tables, including templates ending with |}}
+        assert_eq!(
+            strip("before\n{| class=\"wikitable\"\n|-\n| cell {{t|}} || [[Target]]\n|}\nafter"),
+            "before\n\nafter"
+        );
+        // comments and tags, the content of tags is kept
+        assert_eq!(
+            strip("a<!-- hidden [[Target]] -->b<ref name=\"x\">cite</ref>"),
+            "abcite"
+        );
+        assert_eq!(strip("''italic'' 3 < 4"), "italic 3 < 4");
+        // unterminated constructs extend to the end of the text
+        assert_eq!(strip("text {{unclosed [[Target]]"), "text ");
+        assert_eq!(strip("text [[Target|label"), "text label");
+        assert_eq!(strip("text [["), "text ");
+        assert_eq!(strip("text {|\n| cell"), "text ");
+        assert_eq!(strip("text <!-- unclosed"), "text ");
+        assert_eq!(strip("text }} ]] |}"), "text }} ]] |}");
+    }
+}
@@ -0,0 +1,124 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! The plan of a search: how each dump file is split into parts, which decompressors are used and how many threads
+//! search the parts, determined the same way as by `search_dump()` but without searching.
+
+use std::fs::metadata;
+use std::num::NonZeroUsize;
+
+use crate::{
+    count_rotational_devices, find_decompressors, get_search_ranges, is_compressed, sliced_size, split_into_parts,
+    RegexSearch, Result, RevisionProcessor, SearchOptions,
+};
+
+/// How a dump file is read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileSplit {
+    /// Not read since it cannot contain pages in the page id range
+    Skipped,
+    /// Decompressed and searched as a whole by a single thread
+    Whole,
+    /// Split into slices of the plain file searched in parallel
+    Slices { parts: u64, slice_size: u64 },
+    /// Only the streams or frames found with the multistream or trigram index, or all frames of a seekable zstd
+    /// file, are searched in parallel
+    Ranges { parts: u64 },
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct FilePlan {
+    pub dump_file: String,
+    pub size: u64,
+    pub split: FileSplit,
+}
+
+/// How the dump files would be searched, see `plan_search()`.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SearchPlan {
+    pub files: Vec<FilePlan>,
+    /// Extension and description of the decompressor of each compression format
+    pub decompressors: Vec<(String, String)>,
+    /// Number of threads searching the parts of the files
+    pub thread_count: usize,
+    /// Size of the slices plain files are split into
+    pub chunk_size: u64,
+    /// Total size of the plain files split into slices
+    pub sliced_size: u64,
+    /// Bounds of the chunk size derived from the total size, not set if the chunk size was set explicitly
+    pub chunk_size_bounds: Option<(u64, u64)>,
+    /// Maximum number of files read at the same time, unlimited if not set
+    pub io_parallelism: Option<usize>,
+    /// The number of files read at the same time is only limited for uncompressed files since it was not set
+    /// explicitly, compressed files are decompressed much slower than they are read
+    pub io_parallelism_uncompressed_only: bool,
+    /// All dump files are on rotational storage (only detected on Linux)
+    pub rotational_storage: bool,
+}
+
+/// Returns how `search_dump()` would search the dump files for the regex with the given options.
+pub fn plan_search(regex: &str, dump_files: &[String], search_options: &SearchOptions) -> Result<SearchPlan> {
+    let regex_search = RegexSearch::new(regex, search_options)?;
+    let required_literal = regex_search
+        .required_literal()
+        .filter(|_| search_options.is_prefilter_enabled());
+    let searched_files = dump_files
+        .iter()
+        .filter(|dump_file| search_options.may_contain_page_ids(dump_file))
+        .cloned()
+        .collect::<Vec<_>>();
+    let search_ranges = searched_files
+        .iter()
+        .map(|dump_file| get_search_ranges(dump_file, search_options, required_literal))
+        .collect::<Result<Vec<_>>>()?;
+    let sliced_size = sliced_size(&searched_files, &search_ranges)?;
+    let chunk_size = search_options.chunk_size(sliced_size);
+    let mut search_ranges = searched_files.iter().zip(search_ranges);
+    let mut files = Vec::with_capacity(dump_files.len());
+    for dump_file in dump_files {
+        let split = if !search_options.may_contain_page_ids(dump_file) {
+            FileSplit::Skipped
+        } else if let Some((_, Some(ranges))) = search_ranges.next() {
+            FileSplit::Ranges {
+                parts: ranges.len() as u64,
+            }
+        } else if is_compressed(dump_file) {
+            FileSplit::Whole
+        } else {
+            let (parts, slice_size) = split_into_parts(dump_file, chunk_size)?;
+            FileSplit::Slices { parts, slice_size }
+        };
+        files.push(FilePlan {
+            dump_file: dump_file.clone(),
+            size: metadata(dump_file)?.len(),
+            split,
+        });
+    }
+    let decompressors = find_decompressors(&searched_files, search_options)?
+        .into_iter()
+        .map(|(format, decompressor)| (format.extension().to_owned(), decompressor.to_string()))
+        .collect();
+    let rotational_devices = count_rotational_devices(&searched_files);
+    Ok(SearchPlan {
+        files,
+        decompressors,
+        thread_count: search_options.effective_thread_count(),
+        chunk_size,
+        sliced_size,
+        chunk_size_bounds: search_options
+            .chunk_size
+            .is_none()
+            .then_some(search_options.chunk_size_bounds),
+        io_parallelism: search_options
+            .io_parallelism
+            .or(rotational_devices)
+            .map(NonZeroUsize::get),
+        io_parallelism_uncompressed_only: search_options.io_parallelism.is_none(),
+        rotational_storage: rotational_devices.is_some(),
+    })
+}
@@ -0,0 +1,90 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Aggregation of the progress of a search for frontends, which read it through the `Progress` trait shared with the
+//! downloads of wdgetlib.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub use wdprogress::Progress;
+use wdprogress::ProgressCounters;
+
+use crate::SearchProgress;
+
+/// Progress of a search in bytes of the dump files read, updated with the progress passed to the callback given to
+/// `SearchOptions::with_progress_callback()`. Only compressed files report their progress, files whose compressed
+/// bytes read are unknown (read by 7z or an external decompressor) are counted when they are finished.
+pub struct SearchProgressTracker {
+    counters: ProgressCounters,
+    file_sizes: HashMap<String, u64>,
+    /// Bytes of each file counted so far
+    counted_bytes: Mutex<HashMap<String, u64>>,
+}
+
+impl SearchProgressTracker {
+    /// Creates the tracker for the dump files with their sizes, the totals are unknown if no sizes are given, e.g.
+    /// for streams.
+    pub fn new(file_sizes: HashMap<String, u64>) -> SearchProgressTracker {
+        let counters = ProgressCounters::new();
+        if !file_sizes.is_empty() {
+            counters.add_bytes_total(file_sizes.values().sum());
+            counters.add_files_total(file_sizes.len() as u64);
+        }
+        SearchProgressTracker {
+            counters,
+            file_sizes,
+            counted_bytes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn update(&self, progress: &SearchProgress) {
+        let (file_name, compressed_bytes_read, finished) = match progress {
+            SearchProgress::BytesRead(file_name, compressed_bytes_read, _) => {
+                (file_name, *compressed_bytes_read, false)
+            }
+            SearchProgress::Finished(file_name, compressed_size, _) => (
+                file_name,
+                self.file_sizes.get(file_name).copied().or(*compressed_size),
+                true,
+            ),
+        };
+        if let Some(bytes_read) = compressed_bytes_read {
+            let mut counted_bytes = self.counted_bytes.lock().unwrap();
+            let counted_bytes = counted_bytes.entry(file_name.clone()).or_insert(0);
+            if bytes_read > *counted_bytes {
+                self.counters.add_bytes_done(bytes_read - *counted_bytes);
+                *counted_bytes = bytes_read;
+            }
+        }
+        if finished {
+            self.counters.add_files_done(1);
+        }
+    }
+}
+
+impl Progress for SearchProgressTracker {
+    fn bytes_done(&self) -> u64 {
+        self.counters.bytes_done()
+    }
+
+    fn bytes_total(&self) -> Option<u64> {
+        self.counters.bytes_total()
+    }
+
+    fn files_done(&self) -> u64 {
+        self.counters.files_done()
+    }
+
+    fn files_total(&self) -> Option<u64> {
+        self.counters.files_total()
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.counters.elapsed()
+    }
+}
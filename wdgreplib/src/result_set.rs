@@ -0,0 +1,85 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Result sets: the page and revision ids of the revisions matched by a search, saved as a tab-separated file. Later
+//! searches can be restricted to the pages of a result set or exclude them without searching their text again.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::{Error, Result};
+
+const HEADER: &str = "page_id\trevision_id";
+
+#[derive(Default)]
+pub struct ResultSet {
+    revisions: BTreeSet<(u64, u64)>,
+}
+
+impl ResultSet {
+    pub fn read(file: &Path) -> Result<ResultSet> {
+        let content = fs::read_to_string(file)?;
+        let mut revisions = BTreeSet::new();
+        for line in content.lines().filter(|line| !line.is_empty() && *line != HEADER) {
+            let revision = line
+                .split_once('\t')
+                .and_then(|(page_id, revision_id)| Some((page_id.parse().ok()?, revision_id.parse().ok()?)))
+                .ok_or_else(|| Error::InvalidResultSetLine(line.to_owned()))?;
+            revisions.insert(revision);
+        }
+        Ok(ResultSet { revisions })
+    }
+
+    /// Writes the revisions sorted by page id and revision id.
+    pub fn write(&self, file: &Path) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(file)?);
+        writeln!(writer, "{HEADER}")?;
+        for (page_id, revision_id) in &self.revisions {
+            writeln!(writer, "{page_id}\t{revision_id}")?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns the number of revisions.
+    pub fn len(&self) -> usize {
+        self.revisions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.revisions.is_empty()
+    }
+
+    pub fn page_ids(&self) -> HashSet<u64> {
+        self.revisions.iter().map(|&(page_id, _)| page_id).collect()
+    }
+}
+
+/// Collects the matching revisions while searching, see `SearchOptions::with_result_recorder()`.
+#[derive(Default)]
+pub struct ResultRecorder {
+    revisions: Mutex<BTreeSet<(u64, u64)>>,
+}
+
+impl ResultRecorder {
+    pub fn new() -> ResultRecorder {
+        ResultRecorder::default()
+    }
+
+    pub(crate) fn add(&self, page_id: u64, revision_id: u64) {
+        self.revisions.lock().unwrap().insert((page_id, revision_id));
+    }
+
+    /// Returns the revisions recorded so far.
+    pub fn result_set(&self) -> ResultSet {
+        ResultSet {
+            revisions: self.revisions.lock().unwrap().clone(),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Verification of the `<sha1>` of revisions, the SHA1 of the revision text encoded in base 36 by MediaWiki. Detects
+//! corrupted texts, e.g. caused by faulty recompression, more precisely than the checksums of the dump files.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use sha1::{Digest, Sha1};
+
+/// Length of the SHA1 in base 36, shorter hashes are padded with zeros.
+const BASE36_SHA1_LEN: usize = 31;
+const BASE36_DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// Computes the SHA1 of a revision text as it is stored in `<sha1>`, the text may be hashed in parts.
+#[derive(Default)]
+pub(crate) struct TextHasher(Sha1);
+
+impl TextHasher {
+    pub(crate) fn update(&mut self, text: &[u8]) {
+        self.0.update(text);
+    }
+
+    pub(crate) fn finish(self) -> String {
+        base36(&self.0.finalize())
+    }
+}
+
+/// Encodes the big-endian number in base 36 with at least 31 digits.
+fn base36(number: &[u8]) -> String {
+    let mut number = number.to_vec();
+    let mut digits = Vec::with_capacity(BASE36_SHA1_LEN);
+    while number.iter().any(|&byte| byte != 0) {
+        let mut remainder = 0;
+        for byte in &mut number {
+            let value = (remainder << 8) | u32::from(*byte);
+            *byte = (value / 36) as u8;
+            remainder = value % 36;
+        }
+        digits.push(BASE36_DIGITS[remainder as usize]);
+    }
+    digits.resize(digits.len().max(BASE36_SHA1_LEN), b'0');
+    digits.reverse();
+    // UNWRAP: only ASCII digits
+    String::from_utf8(digits).unwrap()
+}
+
+/// A revision whose text does not match its SHA1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sha1Mismatch {
+    pub title: String,
+    pub revision_id: String,
+    /// SHA1 given in the dump
+    pub expected: String,
+    /// SHA1 of the text read
+    pub actual: String,
+}
+
+/// Collects the revisions whose text does not match their SHA1 while searching, see
+/// `SearchOptions::with_sha1_verifier()`. Only the texts of revisions which are searched are verified, revisions
+/// skipped e.g. because of their namespace or model are not.
+#[derive(Default)]
+pub struct Sha1Verifier {
+    verified: AtomicU64,
+    mismatches: Mutex<Vec<Sha1Mismatch>>,
+}
+
+impl Sha1Verifier {
+    pub fn new() -> Sha1Verifier {
+        Sha1Verifier::default()
+    }
+
+    pub(crate) fn add_verified(&self) {
+        self.verified.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_mismatch(&self, mismatch: Sha1Mismatch) {
+        self.mismatches.lock().unwrap().push(mismatch);
+    }
+
+    /// Number of revisions verified, including the mismatches
+    pub fn verified(&self) -> u64 {
+        self.verified.load(Ordering::Relaxed)
+    }
+
+    /// Returns the mismatches in the order in which they were found.
+    pub fn mismatches(&self) -> Vec<Sha1Mismatch> {
+        self.mismatches.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_hasher() {
+        assert_eq!(TextHasher::default().finish(), "phoiac9h4m842xq45sp7s6u21eteeq1");
+        let mut hasher = TextHasher::default();
+        hasher.update(b"Where is ");
+        hasher.update(b"the needle?");
+        assert_eq!(hasher.finish(), "tmphezscd9g3d4985p493n7uwi096py");
+        assert_eq!(base36(&[0, 36]), format!("{:0>31}", "10"));
+    }
+}
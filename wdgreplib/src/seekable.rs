@@ -0,0 +1,93 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Seekable zstd dumps as written by `wdump recompress`: the siteinfo header and each group of pages are compressed
+//! into their own zstd frame and the page index lists the offset of the frame containing each page. Consecutive
+//! frames are grouped into parts which are searched in parallel like the slices of uncompressed dumps.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::index::{get_index_file_name, index_file_exists, load_offsets};
+use crate::Result;
+
+// compressed size, about 500 MiB of XML at the compression ratios of wikitext
+const PART_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Only `.xml.zst` files with a page index can be read starting at frame offsets.
+pub fn is_seekable_zstd_dump(dump_file: &str) -> bool {
+    dump_file.ends_with(".xml.zst") && index_file_exists(dump_file)
+}
+
+/// Returns the ranges of consecutive frames the dump is searched in, in the form returned by
+/// `index::find_matching_ranges`. The frame with the siteinfo header is not included.
+pub fn split_into_frame_ranges(dump_file: &str) -> Result<Vec<(u64, u64)>> {
+    let offsets = load_offsets(&get_index_file_name(dump_file))?;
+    let mut ranges = Vec::new();
+    let mut start = match offsets.first() {
+        Some(&start) => start,
+        None => return Ok(ranges),
+    };
+    for &offset in &offsets[1..] {
+        if offset - start >= PART_SIZE {
+            ranges.push((start, offset));
+            start = offset;
+        }
+    }
+    ranges.push((start, u64::MAX));
+    Ok(ranges)
+}
+
+/// Returns a reader decompressing the frames in the range.
+pub fn open_frame_range(dump_file: &str, start: u64, end: u64) -> Result<Box<dyn Read>> {
+    let mut file = File::open(dump_file)?;
+    file.seek(SeekFrom::Start(start))?;
+    Ok(Box::new(zstd::stream::read::Decoder::new(file.take(end - start))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+
+    use super::*;
+    use crate::index::make_index;
+
+    #[test]
+    fn test_frame_ranges() {
+        let directory = std::env::temp_dir().join(format!("wdgreplib-seekable-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let dump_file = directory.join("testwiki-20240101-pages-articles.xml.zst");
+        let dump_file = dump_file.to_str().unwrap();
+        let page = |id: u64| format!("  <page>\n    <title>Page {id}</title>\n    <id>{id}</id>\n  </page>\n");
+        let frames = [
+            "<mediawiki>\n  <siteinfo>\n  </siteinfo>\n".to_owned(),
+            page(1) + &page(2),
+            page(3) + "</mediawiki>\n",
+        ];
+        let compressed = frames
+            .iter()
+            .map(|frame| zstd::bulk::compress(frame.as_bytes(), 3).unwrap())
+            .collect::<Vec<_>>();
+        std::fs::write(dump_file, compressed.concat()).unwrap();
+        assert!(!is_seekable_zstd_dump(dump_file));
+        assert_eq!(make_index(dump_file, &get_index_file_name(dump_file)).unwrap(), 3);
+        assert!(is_seekable_zstd_dump(dump_file));
+
+        // all frames with pages form a single part of a small dump, each frame can be read on its own
+        let second_frame = compressed[0].len() as u64;
+        let third_frame = second_frame + compressed[1].len() as u64;
+        assert_eq!(split_into_frame_ranges(dump_file).unwrap(), [(second_frame, u64::MAX)]);
+        let mut lines = std::io::BufReader::new(open_frame_range(dump_file, third_frame, u64::MAX).unwrap()).lines();
+        assert_eq!(lines.nth(1).unwrap().unwrap(), "    <title>Page 3</title>");
+        let mut frame = String::new();
+        open_frame_range(dump_file, second_frame, third_frame)
+            .unwrap()
+            .read_to_string(&mut frame)
+            .unwrap();
+        assert_eq!(frame, frames[1]);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
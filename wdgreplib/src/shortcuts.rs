@@ -0,0 +1,144 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Shortcuts for common wikitext constructs in search patterns, e.g. `\template{Infobox}`, which are expanded into
+//! regexes tolerating the variations allowed by MediaWiki: whitespace around names and separators, spaces or
+//! underscores between words, a lowercase first letter and the namespace prefix of templates.
+
+use std::borrow::Cow;
+
+use crate::{Error, Result};
+
+const SHORTCUTS: [&str; 3] = ["template", "link", "category"];
+
+/// Returns a regex matching the title as written in wikitext: the first letter in either case and words separated
+/// by any number of spaces or underscores.
+fn title_regex(title: &str) -> String {
+    let mut regex = String::with_capacity(4 * title.len());
+    let mut chars = title.trim().chars();
+    if let Some(first) = chars.next() {
+        let (mut upper, mut lower) = (first.to_uppercase(), first.to_lowercase());
+        match (upper.next(), upper.next(), lower.next(), lower.next()) {
+            (Some(upper), None, Some(lower), None) if upper != lower => {
+                regex.push('[');
+                regex.push(upper);
+                regex.push(lower);
+                regex.push(']');
+            }
+            _ => regex.push_str(&regex::escape(first.encode_utf8(&mut [0; 4]))),
+        }
+    }
+    let mut in_separator = false;
+    for c in chars {
+        if c == ' ' || c == '_' {
+            if !in_separator {
+                regex.push_str("[ _]+");
+                in_separator = true;
+            }
+        } else {
+            regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4])));
+            in_separator = false;
+        }
+    }
+    regex
+}
+
+/// `{{Name|...}}` or `{{Template:Name}}`
+fn template_regex(name: &str) -> String {
+    format!(r"\{{\{{\s*(?:[Tt]emplate\s*:\s*)?{}\s*(?:\||\}}\}})", title_regex(name))
+}
+
+/// `[[Title]]`, `[[Title|label]]`, `[[Title#section]]` or `[[:Title]]`
+fn link_regex(title: &str) -> String {
+    format!(r"\[\[\s*:?\s*{}\s*(?:\||#|\]\])", title_regex(title))
+}
+
+/// `[[Category:Name]]` or `[[Category:Name|sort key]]`
+fn category_regex(name: &str) -> String {
+    format!(r"\[\[\s*[Cc]ategory\s*:\s*{}\s*(?:\||\]\])", title_regex(name))
+}
+
+fn shortcut_regex(shortcut: &str, argument: &str) -> String {
+    match shortcut {
+        "template" => template_regex(argument),
+        "link" => link_regex(argument),
+        _ => category_regex(argument),
+    }
+}
+
+/// Expands the shortcuts `\template{Name}`, `\link{Title}` and `\category{Name}` in the pattern. Other escape
+/// sequences, including `\\`, are kept unchanged.
+pub fn expand_shortcuts(pattern: &str) -> Result<Cow<'_, str>> {
+    if !pattern.contains('\\') {
+        return Ok(Cow::Borrowed(pattern));
+    }
+    let mut expanded = String::with_capacity(2 * pattern.len());
+    let mut rest = pattern;
+    while let Some(pos) = rest.find('\\') {
+        expanded.push_str(&rest[..pos]);
+        let escaped = &rest[pos + 1..];
+        let shortcut = SHORTCUTS
+            .iter()
+            .find(|&&name| escaped.strip_prefix(name).is_some_and(|rest| rest.starts_with('{')));
+        match shortcut {
+            Some(name) => {
+                let argument_and_rest = &escaped[name.len() + 1..];
+                let (argument, after) = argument_and_rest
+                    .split_once('}')
+                    .ok_or_else(|| Error::UnclosedPatternShortcut(rest[pos..].to_owned()))?;
+                expanded.push_str(&shortcut_regex(name, argument));
+                rest = after;
+            }
+            None => {
+                let escaped_len = escaped.chars().next().map_or(0, char::len_utf8);
+                expanded.push('\\');
+                expanded.push_str(&escaped[..escaped_len]);
+                rest = &escaped[escaped_len..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    Ok(Cow::Owned(expanded))
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    #[test]
+    fn test_expand_shortcuts() {
+        assert_eq!(expand_shortcuts(r"needle\d+").unwrap(), r"needle\d+");
+        // escaped backslashes are not shortcuts
+        assert_eq!(expand_shortcuts(r"\\link{x}").unwrap(), r"\\link{x}");
+        assert!(matches!(
+            expand_shortcuts(r"\template{Infobox"),
+            Err(Error::UnclosedPatternShortcut(_))
+        ));
+
+        let template = Regex::new(&expand_shortcuts(r"\template{Infobox settlement}").unwrap()).unwrap();
+        for text in [
+            "{{Infobox settlement|name=X}}",
+            "{{ infobox_settlement\n| name = X }}",
+            "{{Template:Infobox  settlement}}",
+        ] {
+            assert!(template.is_match(text), "{text}");
+        }
+        for text in ["{{Infobox settlements}}", "{{Infobox}}", "[[Infobox settlement]]"] {
+            assert!(!template.is_match(text), "{text}");
+        }
+
+        let link = Regex::new(&expand_shortcuts(r"see \link{C++ (language)}").unwrap()).unwrap();
+        assert!(link.is_match("see [[c++ (language)|C++]]"));
+        assert!(link.is_match("see [[:C++_(language)#History]]"));
+        assert!(!link.is_match("see [[C++ (languages)]]"));
+
+        let category = Regex::new(&expand_shortcuts(r"\category{Living people}").unwrap()).unwrap();
+        assert!(category.is_match("[[Category:Living_people|Doe, John]]"));
+        assert!(!category.is_match("[[Living people]]"));
+    }
+}
@@ -0,0 +1,118 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Metadata of the wiki found in the `<siteinfo>` block at the start of XML dumps.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde::Serialize;
+use simdutf8::basic::from_utf8;
+
+use crate::decompressor::{find_decompressor, CompressionFormat};
+use crate::{is_cirrussearch_dump, Error, Result};
+
+#[derive(Serialize, Default, Debug)]
+pub struct DumpHeader {
+    pub sitename: String,
+    pub dbname: String,
+    pub base: String,
+    pub generator: String,
+    pub case: String,
+    /// Namespace number to (localized) namespace name, the main namespace has an empty name
+    pub namespaces: BTreeMap<i32, String>,
+}
+
+fn get_namespace_key(e: &quick_xml::events::BytesStart) -> Result<i32> {
+    for attribute in e.attributes() {
+        let attribute = attribute.map_err(quick_xml::Error::from)?;
+        if attribute.key == b"key" {
+            let key = from_utf8(&attribute.value)?;
+            return key
+                .parse()
+                .map_err(|_| Error::InvalidNumberInTag("namespace".to_owned(), key.to_owned()));
+        }
+    }
+    Err(Error::InvalidNumberInTag("namespace".to_owned(), String::new()))
+}
+
+/// Parses the `<siteinfo>` block from the start of a decompressed dump.
+pub fn parse_dump_header_from_reader<B: BufRead>(buf_reader: B) -> Result<DumpHeader> {
+    let mut reader = Reader::from_reader(buf_reader);
+    reader.check_end_names(false);
+    let mut buf = Vec::with_capacity(1024);
+    let mut header = DumpHeader::default();
+    let mut in_siteinfo = false;
+    let mut current_element = Vec::new();
+    let mut namespace_key = None;
+    loop {
+        match reader.read_event(&mut buf)? {
+            Event::Start(ref e) => match e.local_name() {
+                b"siteinfo" => in_siteinfo = true,
+                b"page" => break,
+                b"namespace" if in_siteinfo => {
+                    let key = get_namespace_key(e)?;
+                    // the name is set by the text event if there is one
+                    header.namespaces.insert(key, String::new());
+                    namespace_key = Some(key);
+                }
+                name => {
+                    current_element.clear();
+                    current_element.extend_from_slice(name);
+                }
+            },
+            Event::Empty(ref e) if in_siteinfo && e.local_name() == b"namespace" => {
+                header.namespaces.insert(get_namespace_key(e)?, String::new());
+            }
+            Event::Text(ref e) if in_siteinfo => {
+                let text = e.unescaped()?;
+                let text = from_utf8(&text)?.to_owned();
+                match namespace_key {
+                    Some(key) => {
+                        header.namespaces.insert(key, text);
+                    }
+                    None => match current_element.as_slice() {
+                        b"sitename" => header.sitename = text,
+                        b"dbname" => header.dbname = text,
+                        b"base" => header.base = text,
+                        b"generator" => header.generator = text,
+                        b"case" => header.case = text,
+                        _other_element => {}
+                    },
+                }
+            }
+            Event::End(ref e) => match e.local_name() {
+                b"siteinfo" => return Ok(header),
+                b"namespace" => namespace_key = None,
+                _other_element => current_element.clear(),
+            },
+            Event::Eof => break,
+            _other_event => {}
+        }
+        buf.clear();
+    }
+    Err(Error::SiteInfoNotFound())
+}
+
+/// Parses the `<siteinfo>` block of a dump file, compressed files are only decompressed up to the first page.
+pub fn parse_dump_header(dump_file: &str) -> Result<DumpHeader> {
+    if is_cirrussearch_dump(dump_file) {
+        return Err(Error::SiteInfoNotFound());
+    }
+    match CompressionFormat::of_file(dump_file) {
+        Some(format) => {
+            let decompressor = find_decompressor(format, None, None, None)?;
+            let mut decompressed = decompressor.open(dump_file)?;
+            let header = parse_dump_header_from_reader(BufReader::new(&mut decompressed));
+            decompressed.abort();
+            header
+        }
+        None => parse_dump_header_from_reader(BufReader::new(File::open(dump_file)?)),
+    }
+}
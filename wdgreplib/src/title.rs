@@ -0,0 +1,237 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Namespaces and page titles normalized like MediaWiki does, so that titles given by users match the titles in the
+//! dumps regardless of underscores, surrounding whitespace, the case of the first letter and of namespace prefixes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::siteinfo::DumpHeader;
+
+/// A namespace by its number as in the `<ns>` element of the dump.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct Namespace(pub i32);
+
+impl Namespace {
+    pub const MAIN: Namespace = Namespace(0);
+
+    /// Talk namespaces have odd numbers.
+    pub const fn is_talk(self) -> bool {
+        self.0 >= 0 && self.0 % 2 == 1
+    }
+}
+
+impl fmt::Display for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Namespace {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Namespace, Self::Err> {
+        s.parse().map(Namespace)
+    }
+}
+
+/// A normalized page title with its namespace.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Title {
+    namespace: Namespace,
+    /// The title as in the dump, with the name of the namespace as prefix
+    prefixed: String,
+    text_start: usize,
+}
+
+impl Title {
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    /// Returns the title without the namespace prefix.
+    pub fn text(&self) -> &str {
+        &self.prefixed[self.text_start..]
+    }
+
+    /// Returns the title with the namespace prefix as in the dump.
+    pub fn as_str(&self) -> &str {
+        &self.prefixed
+    }
+}
+
+impl fmt::Display for Title {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.prefixed)
+    }
+}
+
+/// Namespaces of the MediaWiki core in English, used if the siteinfo of the dump is not available.
+const CANONICAL_NAMESPACES: [(i32, &str); 18] = [
+    (-2, "Media"),
+    (-1, "Special"),
+    (0, ""),
+    (1, "Talk"),
+    (2, "User"),
+    (3, "User talk"),
+    (4, "Project"),
+    (5, "Project talk"),
+    (6, "File"),
+    (7, "File talk"),
+    (8, "MediaWiki"),
+    (9, "MediaWiki talk"),
+    (10, "Template"),
+    (11, "Template talk"),
+    (12, "Help"),
+    (13, "Help talk"),
+    (14, "Category"),
+    (15, "Category talk"),
+];
+
+/// Replaces underscores with spaces, collapses runs of whitespace and trims the text.
+fn normalize_whitespace(text: &str) -> String {
+    text.split(|c: char| c == '_' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The namespaces of a wiki and how the case of titles is normalized.
+#[derive(Debug)]
+pub struct Namespaces {
+    /// Name of each namespace, empty for the main namespace
+    names: BTreeMap<Namespace, String>,
+    /// Namespace of each lowercase name
+    namespaces_by_name: HashMap<String, Namespace>,
+    /// The first letter of titles is uppercase, set unless the wiki is case-sensitive
+    first_letter_uppercase: bool,
+}
+
+impl Namespaces {
+    /// Creates the namespaces from their names and the case setting of the wiki (`first-letter` or
+    /// `case-sensitive`).
+    pub fn new<I: IntoIterator<Item = (Namespace, String)>>(names: I, case: &str) -> Namespaces {
+        let names: BTreeMap<Namespace, String> = names
+            .into_iter()
+            .map(|(namespace, name)| (namespace, normalize_whitespace(&name)))
+            .collect();
+        let namespaces_by_name = names
+            .iter()
+            .filter(|(_, name)| !name.is_empty())
+            .map(|(&namespace, name)| (name.to_lowercase(), namespace))
+            .collect();
+        Namespaces {
+            names,
+            namespaces_by_name,
+            first_letter_uppercase: case != "case-sensitive",
+        }
+    }
+
+    /// Returns the namespaces from the siteinfo of a dump.
+    pub fn from_dump_header(dump_header: &DumpHeader) -> Namespaces {
+        Namespaces::new(
+            dump_header
+                .namespaces
+                .iter()
+                .map(|(&key, name)| (Namespace(key), name.clone())),
+            &dump_header.case,
+        )
+    }
+
+    pub fn name(&self, namespace: Namespace) -> Option<&str> {
+        self.names.get(&namespace).map(String::as_str)
+    }
+
+    /// Returns the namespace given by its number or by its name in any case.
+    pub fn parse_namespace(&self, namespace: &str) -> Option<Namespace> {
+        match namespace.trim().parse() {
+            Ok(namespace) => self.names.contains_key(&namespace).then_some(namespace),
+            Err(_) => self
+                .namespaces_by_name
+                .get(&normalize_whitespace(namespace).to_lowercase())
+                .copied(),
+        }
+    }
+
+    /// Normalizes the title like MediaWiki: underscores are replaced with spaces, whitespace is collapsed and
+    /// trimmed, a known namespace prefix is replaced with the name of the namespace and the first letter of the
+    /// title without the prefix is uppercased unless the wiki is case-sensitive.
+    pub fn normalize_title(&self, title: &str) -> Title {
+        let title = normalize_whitespace(title);
+        // a leading colon forces the main namespace
+        let title = title.strip_prefix(':').map_or(title.as_str(), str::trim_start);
+        let (namespace, text) = title
+            .split_once(':')
+            .and_then(|(prefix, text)| Some((self.parse_namespace(prefix)?, text.trim_start())))
+            .filter(|&(namespace, _)| namespace != Namespace::MAIN)
+            .unwrap_or((Namespace::MAIN, title));
+        let mut prefixed = String::with_capacity(title.len() + 1);
+        if namespace != Namespace::MAIN {
+            // UNWRAP: only known namespaces are parsed
+            prefixed.push_str(self.name(namespace).unwrap());
+            prefixed.push(':');
+        }
+        let text_start = prefixed.len();
+        let mut chars = text.chars();
+        if let Some(first) = chars.next() {
+            if self.first_letter_uppercase {
+                prefixed.extend(first.to_uppercase());
+            } else {
+                prefixed.push(first);
+            }
+            prefixed.push_str(chars.as_str());
+        }
+        Title {
+            namespace,
+            prefixed,
+            text_start,
+        }
+    }
+}
+
+impl Default for Namespaces {
+    fn default() -> Namespaces {
+        Namespaces::new(
+            CANONICAL_NAMESPACES
+                .iter()
+                .map(|&(key, name)| (Namespace(key), name.to_owned())),
+            "first-letter",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_title() {
+        let namespaces = Namespaces::new(
+            [
+                (Namespace(0), String::new()),
+                (Namespace(1), "Diskussion".to_owned()),
+                (Namespace(3), "Benutzer_Diskussion".to_owned()),
+            ],
+            "first-letter",
+        );
+        let title = namespaces.normalize_title("  benutzer diskussion : example__user ");
+        assert_eq!(title.as_str(), "Benutzer Diskussion:Example user");
+        assert_eq!((title.namespace(), title.text()), (Namespace(3), "Example user"));
+        assert!(title.namespace().is_talk());
+        assert_eq!(namespaces.normalize_title("ärger").as_str(), "Ärger");
+        // unknown prefixes are part of the title in the main namespace
+        let title = namespaces.normalize_title("c++: a tour");
+        assert_eq!((title.namespace(), title.as_str()), (Namespace::MAIN, "C++: a tour"));
+        assert_eq!(namespaces.normalize_title(":diskussion").text(), "Diskussion");
+        assert_eq!(namespaces.parse_namespace("1"), Some(Namespace(1)));
+        assert_eq!(namespaces.parse_namespace("2"), None);
+
+        let case_sensitive = Namespaces::new([(Namespace(1), "Talk".to_owned())], "case-sensitive");
+        assert_eq!(case_sensitive.normalize_title("talk:iPhone").as_str(), "Talk:iPhone");
+    }
+}
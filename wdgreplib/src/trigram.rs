@@ -0,0 +1,231 @@
+// wikidumpgrep
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Trigram indexes of dumps with a page index. They map each trigram of the raw (XML-escaped) dump to the pages of
+//! uncompressed dumps, the bzip2 streams of multistream dumps or the zstd frames of seekable zstd dumps containing it,
+//! so searches for patterns requiring a literal only need to read the parts of the dump containing all trigrams of the
+//! literal.
+//!
+//! File format (integers little-endian): the magic bytes, the number of indexed parts and their offsets (u64 each),
+//! the number of trigrams and a table sorted by trigram of (trigram: u32, postings length: u32, postings offset: u64)
+//! entries followed by the postings, which are the delta-encoded part numbers as LEB128 varints.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::index::{load_offsets, open_dump_at_offset};
+use crate::{is_compressed, Error, Result};
+
+pub const TRIGRAM_INDEX_SUFFIX: &str = "-trigrams.idx";
+const MAGIC: &[u8; 8] = b"WDTRIGR1";
+const TABLE_ENTRY_LEN: u64 = 16;
+// number of parts read in parallel before their trigrams are added to the postings
+const BATCH_SIZE: usize = 256;
+
+/// Returns the name of the trigram index file belonging to a dump file, e.g.
+/// `enwiki-20240101-pages-articles-multistream-trigrams.idx` for
+/// `enwiki-20240101-pages-articles-multistream.xml.bz2`. Like the page index the one of a seekable zstd dump is named
+/// differently, e.g. `enwiki-20240101-pages-articles-zst-trigrams.idx`.
+pub fn get_trigram_index_file_name(dump_file: &str) -> String {
+    let stem = match dump_file.strip_suffix(".xml.zst") {
+        Some(stem) => format!("{stem}-zst"),
+        None => dump_file
+            .strip_suffix(".xml.bz2")
+            .or_else(|| dump_file.strip_suffix(".xml"))
+            .unwrap_or(dump_file)
+            .to_owned(),
+    };
+    format!("{stem}{TRIGRAM_INDEX_SUFFIX}")
+}
+
+pub fn trigram_index_exists(dump_file: &str) -> bool {
+    Path::new(&get_trigram_index_file_name(dump_file)).is_file()
+}
+
+fn get_trigrams(bytes: &[u8]) -> Vec<u32> {
+    let mut trigrams: Vec<u32> = bytes
+        .windows(3)
+        .map(|w| u32::from_be_bytes([0, w[0], w[1], w[2]]))
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+fn read_part(dump_file: &str, start: u64, end: u64) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    if is_compressed(dump_file) {
+        // only the bzip2 stream or zstd frame starting at the offset is decompressed
+        open_dump_at_offset(dump_file, start)?.read_to_end(&mut bytes)?;
+    } else {
+        open_dump_at_offset(dump_file, start)?
+            .take(end - start)
+            .read_to_end(&mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+struct Postings {
+    last_part: u32,
+    data: Vec<u8>,
+}
+
+fn write_varint(data: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        data.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    data.push(value as u8);
+}
+
+fn read_postings(data: &[u8]) -> Vec<u32> {
+    let mut parts = Vec::new();
+    let (mut part, mut value, mut shift) = (0_u32, 0_u32, 0);
+    for &b in data {
+        value |= u32::from(b & 0x7f) << shift;
+        shift += 7;
+        if b & 0x80 == 0 {
+            part += value;
+            parts.push(part);
+            (value, shift) = (0, 0);
+        }
+    }
+    parts
+}
+
+/// Creates the trigram index of a dump from its page index (see `index::make_index`) and returns the number of
+/// indexed parts. All postings are kept in memory until the index is written.
+pub fn make_trigram_index(dump_file: &str, index_file: &str, trigram_index_file: &str) -> Result<u64> {
+    let offsets = load_offsets(index_file)?;
+    let dump_len = std::fs::metadata(dump_file)?.len();
+    let mut postings: HashMap<u32, Postings> = HashMap::new();
+    for (batch_number, batch) in offsets.chunks(BATCH_SIZE).enumerate() {
+        let first_part = batch_number * BATCH_SIZE;
+        let batch_trigrams = (0..batch.len())
+            .into_par_iter()
+            .map(|i| {
+                let end = offsets.get(first_part + i + 1).copied().unwrap_or(dump_len);
+                Ok(get_trigrams(&read_part(dump_file, batch[i], end)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for (i, trigrams) in batch_trigrams.into_iter().enumerate() {
+            let part = (first_part + i) as u32;
+            for trigram in trigrams {
+                let postings = postings.entry(trigram).or_insert_with(|| Postings {
+                    last_part: 0,
+                    data: Vec::new(),
+                });
+                write_varint(&mut postings.data, part - postings.last_part);
+                postings.last_part = part;
+            }
+        }
+    }
+
+    let mut trigrams: Vec<_> = postings.into_iter().collect();
+    trigrams.sort_unstable_by_key(|(trigram, _)| *trigram);
+    let mut writer = BufWriter::new(File::create(trigram_index_file)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(offsets.len() as u64).to_le_bytes())?;
+    for offset in &offsets {
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+    writer.write_all(&(trigrams.len() as u64).to_le_bytes())?;
+    let mut postings_offset = (MAGIC.len() + 16 + 8 * offsets.len()) as u64 + TABLE_ENTRY_LEN * trigrams.len() as u64;
+    for (trigram, postings) in &trigrams {
+        writer.write_all(&trigram.to_le_bytes())?;
+        writer.write_all(&(postings.data.len() as u32).to_le_bytes())?;
+        writer.write_all(&postings_offset.to_le_bytes())?;
+        postings_offset += postings.data.len() as u64;
+    }
+    for (_, postings) in &trigrams {
+        writer.write_all(&postings.data)?;
+    }
+    writer.flush()?;
+    Ok(offsets.len() as u64)
+}
+
+fn read_u64(file: &mut File) -> Result<u64> {
+    let mut buf = [0; 8];
+    file.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Looks up the postings of a trigram by binary search in the table.
+fn find_postings(file: &mut File, table_start: u64, trigram_count: u64, trigram: u32) -> Result<Vec<u32>> {
+    let (mut low, mut high) = (0, trigram_count);
+    let mut entry = [0; TABLE_ENTRY_LEN as usize];
+    while low < high {
+        let mid = low + (high - low) / 2;
+        file.seek(SeekFrom::Start(table_start + mid * TABLE_ENTRY_LEN))?;
+        file.read_exact(&mut entry)?;
+        // UNWRAP: slices have the right length
+        let entry_trigram = u32::from_le_bytes(entry[..4].try_into().unwrap());
+        if entry_trigram < trigram {
+            low = mid + 1;
+        } else if entry_trigram > trigram {
+            high = mid;
+        } else {
+            let len = u32::from_le_bytes(entry[4..8].try_into().unwrap());
+            let offset = u64::from_le_bytes(entry[8..].try_into().unwrap());
+            let mut data = vec![0; len as usize];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut data)?;
+            return Ok(read_postings(&data));
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Returns the ranges of the dump which may contain the literal, sorted by offset, in the form returned by
+/// `index::find_matching_ranges`. Returns `None` if the literal is too short to be looked up.
+pub fn find_candidate_ranges(trigram_index_file: &str, literal: &[u8]) -> Result<Option<Vec<(u64, u64)>>> {
+    let trigrams = get_trigrams(literal);
+    if trigrams.is_empty() {
+        return Ok(None);
+    }
+    let mut file = File::open(trigram_index_file)?;
+    let mut magic = [0; MAGIC.len()];
+    file.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(Error::InvalidTrigramIndex(trigram_index_file.to_owned()));
+    }
+    let part_count = read_u64(&mut file)?;
+    let mut offsets = vec![0; part_count as usize * 8];
+    file.read_exact(&mut offsets)?;
+    let trigram_count = read_u64(&mut file)?;
+    let table_start = file.stream_position()?;
+
+    // intersect the postings starting with the shortest
+    let mut postings = trigrams
+        .into_iter()
+        .map(|trigram| find_postings(&mut file, table_start, trigram_count, trigram))
+        .collect::<Result<Vec<_>>>()?;
+    postings.sort_unstable_by_key(Vec::len);
+    let mut parts = postings.remove(0);
+    for other in &postings {
+        parts.retain(|part| other.binary_search(part).is_ok());
+    }
+
+    let get_offset = |part: usize| {
+        offsets
+            .get(part * 8..part * 8 + 8)
+            // UNWRAP: slice has the right length
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    parts
+        .into_iter()
+        .map(|part| {
+            let start =
+                get_offset(part as usize).ok_or_else(|| Error::InvalidTrigramIndex(trigram_index_file.to_owned()))?;
+            Ok((start, get_offset(part as usize + 1).unwrap_or(u64::MAX)))
+        })
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+}
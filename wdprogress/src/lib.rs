@@ -0,0 +1,146 @@
+// wikidumptools
+//
+// (C) 2020 Count Count
+//
+// Distributed under the terms of the MIT license.
+
+//! Progress of searches and downloads, independent of how it is displayed. The libraries aggregate the progress
+//! events of their workers into [`ProgressCounters`], which frontends can read from any thread through the
+//! [`Progress`] trait while the operation is running.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Aggregated progress of a search or download.
+pub trait Progress: Sync {
+    /// Bytes processed so far
+    fn bytes_done(&self) -> u64;
+    /// Total number of bytes to process, if known
+    fn bytes_total(&self) -> Option<u64>;
+    /// Files finished so far
+    fn files_done(&self) -> u64;
+    /// Total number of files to process, if known
+    fn files_total(&self) -> Option<u64>;
+    /// Time since the operation started
+    fn elapsed(&self) -> Duration;
+
+    /// Returns the estimated remaining time at the average rate so far, unknown if the total number of bytes is
+    /// unknown or nothing has been processed yet.
+    fn eta(&self) -> Option<Duration> {
+        let bytes_total = self.bytes_total()?;
+        let bytes_done = self.bytes_done();
+        if bytes_done == 0 {
+            return None;
+        }
+        let bytes_remaining = bytes_total.saturating_sub(bytes_done);
+        Some(self.elapsed().mul_f64(bytes_remaining as f64 / bytes_done as f64))
+    }
+}
+
+/// Totals which have not been reported yet.
+const UNKNOWN: u64 = u64::MAX;
+
+/// Progress counters which can be updated and read concurrently.
+pub struct ProgressCounters {
+    start: Instant,
+    bytes_done: AtomicU64,
+    bytes_total: AtomicU64,
+    files_done: AtomicU64,
+    files_total: AtomicU64,
+}
+
+impl ProgressCounters {
+    /// Creates the counters with unknown totals, the elapsed time starts now.
+    pub fn new() -> ProgressCounters {
+        ProgressCounters {
+            start: Instant::now(),
+            bytes_done: AtomicU64::new(0),
+            bytes_total: AtomicU64::new(UNKNOWN),
+            files_done: AtomicU64::new(0),
+            files_total: AtomicU64::new(UNKNOWN),
+        }
+    }
+
+    pub fn add_bytes_done(&self, bytes: u64) {
+        self.bytes_done.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Adds to the total number of bytes, which is known after the first call.
+    pub fn add_bytes_total(&self, bytes: u64) {
+        add_to_total(&self.bytes_total, bytes);
+    }
+
+    pub fn add_files_done(&self, files: u64) {
+        self.files_done.fetch_add(files, Ordering::Relaxed);
+    }
+
+    /// Adds to the total number of files, which is known after the first call.
+    pub fn add_files_total(&self, files: u64) {
+        add_to_total(&self.files_total, files);
+    }
+}
+
+impl Default for ProgressCounters {
+    fn default() -> ProgressCounters {
+        ProgressCounters::new()
+    }
+}
+
+fn add_to_total(total: &AtomicU64, count: u64) {
+    // UNWRAP: the closure always returns a new value
+    total
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+            Some(if total == UNKNOWN { count } else { total + count })
+        })
+        .unwrap();
+}
+
+fn known(total: &AtomicU64) -> Option<u64> {
+    Some(total.load(Ordering::Relaxed)).filter(|&total| total != UNKNOWN)
+}
+
+impl Progress for ProgressCounters {
+    fn bytes_done(&self) -> u64 {
+        self.bytes_done.load(Ordering::Relaxed)
+    }
+
+    fn bytes_total(&self) -> Option<u64> {
+        known(&self.bytes_total)
+    }
+
+    fn files_done(&self) -> u64 {
+        self.files_done.load(Ordering::Relaxed)
+    }
+
+    fn files_total(&self) -> Option<u64> {
+        known(&self.files_total)
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_counters() {
+        let counters = ProgressCounters::new();
+        assert_eq!(counters.bytes_total(), None);
+        assert_eq!(counters.eta(), None);
+        counters.add_bytes_total(0);
+        assert_eq!(counters.bytes_total(), Some(0));
+        counters.add_bytes_total(300);
+        counters.add_bytes_done(100);
+        counters.add_files_done(1);
+        assert_eq!(counters.bytes_total(), Some(300));
+        assert_eq!(counters.files_done(), 1);
+        assert_eq!(counters.files_total(), None);
+        // twice the elapsed time remains
+        let elapsed_before = counters.elapsed();
+        let eta = counters.eta().unwrap();
+        assert!(eta >= 2 * elapsed_before && eta <= 2 * counters.elapsed());
+    }
+}